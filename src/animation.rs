@@ -1,15 +1,207 @@
-use crate::{render::RenderSettings, scene::Scene};
-use image::RgbImage;
+use crate::{
+    deflection_lut::LutCache,
+    pass_recorder::PassRecorder,
+    progress::RenderProgress,
+    render::RenderSettings,
+    scene::Scene,
+    stereo::{eye_scenes, render_eyes, stack_eyes},
+};
+use egui::epaint::ecolor::{gamma_from_linear, linear_from_gamma};
+use image::{Pixel, Rgb32FImage};
 
+/// Frame number, scene and the scenes sampled while the shutter is open for motion blur, empty if
+/// the frame isn't blurred
 #[derive(Clone)]
-pub struct Frame(pub i32, pub Scene);
+pub struct Frame(pub i32, pub Scene, pub Vec<Scene>);
 
 impl Default for Frame {
     fn default() -> Self {
-        Frame(0, Default::default())
+        Frame(0, Default::default(), Vec::new())
     }
 }
 
+impl Frame {
+    /// renders the gamma encoded pixels in floating point like `Scene::render_region_float`,
+    /// averaging the light of the shutter samples if there are any, with both eyes stacked for
+    /// stereo renders
+    ///
+    /// returns None if the render was cancelled
+    pub fn render_float(
+        &self,
+        render_settings: RenderSettings,
+        progress: &RenderProgress,
+    ) -> Option<Rgb32FImage> {
+        self.render_float_and_hdr(render_settings, progress)
+            .map(|(image, _)| image)
+    }
+
+    /// renders the gamma encoded pixels like `render_float` along with the linear pixels they
+    /// were encoded from, before light brighter than white is clipped
+    ///
+    /// returns None if the render was cancelled
+    pub fn render_float_and_hdr(
+        &self,
+        render_settings: RenderSettings,
+        progress: &RenderProgress,
+    ) -> Option<(Rgb32FImage, Rgb32FImage)> {
+        if let Some(eye_separation) = render_settings.eye_separation() {
+            let render_settings = render_settings.one_eye();
+            let [left, right] = self
+                .eyes(eye_separation)
+                .map(|eye| eye.render_float_and_hdr(render_settings, progress));
+            let ((left, left_hdr), (right, right_hdr)) = (left?, right?);
+            return Some((
+                stack_eyes([left, right], render_settings),
+                stack_eyes([left_hdr, right_hdr], render_settings),
+            ));
+        }
+
+        let (offset, size) = render_settings.region();
+        let render = |scene: &Scene, progress: &RenderProgress| {
+            scene.render_region_float_and_hdr(render_settings, offset, size, progress)
+        };
+        if self.2.is_empty() {
+            return render(&self.1, progress);
+        }
+
+        let [mut average, hdr_average] =
+            average_samples(self.shutter_samples(progress, |scene, progress| {
+                let (mut image, hdr_image) = render(scene, progress)?;
+                image
+                    .pixels_mut()
+                    .for_each(|pixel| *pixel = pixel.map(linear_from_gamma));
+                Some([image, hdr_image])
+            }))?;
+        average
+            .pixels_mut()
+            .for_each(|pixel| *pixel = pixel.map(gamma_from_linear));
+        Some((average, hdr_average))
+    }
+
+    /// renders the linear pixels like `Scene::render_hdr_with_progress`, averaging the light of the
+    /// shutter samples if there are any
+    ///
+    /// returns None if the render was cancelled
+    pub fn render_hdr(
+        &self,
+        render_settings: RenderSettings,
+        progress: &RenderProgress,
+    ) -> Option<Rgb32FImage> {
+        if let Some(eye_separation) = render_settings.eye_separation() {
+            let render_settings = render_settings.one_eye();
+            return render_eyes(self.eyes(eye_separation), render_settings, |eye| {
+                eye.render_hdr(render_settings, progress)
+            });
+        }
+
+        if self.2.is_empty() {
+            return self.1.render_hdr_with_progress(render_settings, progress);
+        }
+
+        let [average] = average_samples(self.shutter_samples(progress, |scene, progress| {
+            Some([scene.render_hdr_with_progress(render_settings, progress)?])
+        }))?;
+        Some(average)
+    }
+
+    /// returns the frame with the tables of the lensing of its scenes taken from the cache if the
+    /// render settings ask for them
+    pub fn with_cached_deflection_luts(
+        &self,
+        render_settings: RenderSettings,
+        cache: &LutCache,
+    ) -> Frame {
+        let with_lut = |scene: &Scene| {
+            scene
+                .with_cached_deflection_lut(render_settings, cache)
+                .unwrap_or_else(|| scene.clone())
+        };
+        Frame(
+            self.0,
+            with_lut(&self.1),
+            self.2.iter().map(with_lut).collect(),
+        )
+    }
+
+    /// returns the frame with its scenes recording the passes of the render into the recorder
+    pub fn with_pass_recorder(&self, recorder: Option<PassRecorder>) -> Frame {
+        let with_recorder = |scene: &Scene| Scene {
+            pass_recorder: recorder.clone(),
+            ..scene.clone()
+        };
+        Frame(
+            self.0,
+            with_recorder(&self.1),
+            self.2.iter().map(with_recorder).collect(),
+        )
+    }
+
+    /// returns the frame seen by the left and right eye of a stereo render
+    fn eyes(&self, eye_separation: f64) -> [Frame; 2] {
+        let [left, right] = eye_scenes(&self.1, eye_separation);
+        let (left_samples, right_samples) = self
+            .2
+            .iter()
+            .map(|scene| {
+                let [left, right] = eye_scenes(scene, eye_separation);
+                (left, right)
+            })
+            .unzip();
+        [
+            Frame(self.0, left, left_samples),
+            Frame(self.0, right, right_samples),
+        ]
+    }
+
+    /// renders each shutter sample as a step of the progress
+    fn shutter_samples<'a, T>(
+        &'a self,
+        progress: &'a RenderProgress,
+        render: impl Fn(&Scene, &RenderProgress) -> Option<T> + 'a,
+    ) -> impl Iterator<Item = Option<T>> + 'a {
+        progress.add_total(self.2.len());
+        self.2.iter().map(move |scene| {
+            let image = render(scene, &progress.subtask());
+            progress.advance(1);
+            image
+        })
+    }
+}
+
+/// Returns whether the frame number is `offset` modulo `stride`
+pub fn is_in_stride(frame: i32, stride: usize, offset: usize) -> bool {
+    let stride = stride.max(1) as i32;
+    frame.rem_euclid(stride) == offset as i32 % stride
+}
+
+/// Returns the average of each of the linear images of the samples, None if any of them is None
+fn average_samples<const N: usize>(
+    samples: impl Iterator<Item = Option<[Rgb32FImage; N]>>,
+) -> Option<[Rgb32FImage; N]> {
+    let mut count = 0;
+    let mut sum: Option<[Rgb32FImage; N]> = None;
+    for images in samples {
+        let images = images?;
+        count += 1;
+        match &mut sum {
+            Some(sum) => sum.iter_mut().zip(&images).for_each(|(sum, image)| {
+                sum.pixels_mut()
+                    .zip(image.pixels())
+                    .for_each(|(total, pixel)| total.apply2(pixel, |a, b| a + b))
+            }),
+            None => sum = Some(images),
+        }
+    }
+
+    let mut average = sum?;
+    average.iter_mut().for_each(|image| {
+        image
+            .pixels_mut()
+            .for_each(|pixel| pixel.apply(|channel| channel / count as f32))
+    });
+    Some(average)
+}
+
 #[derive(Default)]
 pub struct Animation {
     frames: Vec<Frame>,
@@ -26,25 +218,101 @@ impl Animation {
                 .into_iter()
                 .map(|i| {
                     let mut new_scene = initial_scene.clone();
-                    new_scene.diver.set_time(
+                    new_scene.set_diver_time(
                         new_scene.diver.time() + duration * (i as f64 / (n_frames - 1) as f64),
                     );
-                    Frame(i as i32, new_scene)
+                    Frame(i as i32, new_scene, Vec::new())
+                })
+                .collect(),
+        )
+    }
+
+    /// sweeps the initial radius of the diver linearly from `start_radius` to `end_radius` keeping
+    /// the rest of the scene fixed
+    pub fn from_scene_radii(
+        initial_scene: Scene,
+        start_radius: f64,
+        end_radius: f64,
+        n_frames: usize,
+    ) -> Self {
+        Self::new(
+            (0..n_frames)
+                .map(|i| {
+                    let factor = i as f64 / (n_frames.max(2) - 1) as f64;
+                    let mut new_scene = initial_scene.clone();
+                    new_scene
+                        .diver
+                        .set_initial_radius(start_radius + (end_radius - start_radius) * factor);
+                    // the time can't be past the end of the fall from the new radius
+                    new_scene.set_diver_time(new_scene.diver.time());
+                    Frame(i as i32, new_scene, Vec::new())
                 })
                 .collect(),
         )
     }
 
+    /// keeps only the frames matching the predicate
+    pub fn filter(self, predicate: impl Fn(&Frame) -> bool) -> Self {
+        Self::new(
+            self.frames
+                .into_iter()
+                .filter(|frame| predicate(frame))
+                .collect(),
+        )
+    }
+
+    /// keeps only the frames whose frame number is `offset` modulo `stride`
+    ///
+    /// rendering with offsets `0..stride` splits the animation into disjoint subsets
+    /// that together cover every frame exactly once
+    pub fn stride(self, stride: usize, offset: usize) -> Self {
+        self.filter(|frame| is_in_stride(frame.0, stride, offset))
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
     pub fn n_frames(&self) -> usize {
         self.frames.len()
     }
+}
 
-    pub fn render_frames(
-        self,
-        render_settings: RenderSettings,
-    ) -> impl Iterator<Item = (i32, RgbImage)> {
-        self.frames
-            .into_iter()
-            .map(move |frame| (frame.0, frame.1.render(render_settings)))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strides_partition_frames() {
+        let frame_numbers: Vec<i32> = (-13..17).collect();
+        let scene = Scene::test_pattern();
+        let frames: Vec<Frame> = frame_numbers
+            .iter()
+            .map(|&frame| Frame(frame, scene.clone(), Vec::new()))
+            .collect();
+
+        for stride in 1..=5 {
+            let mut covered = Vec::new();
+            for offset in 0..stride {
+                let frames = Animation::new(frames.clone()).stride(stride, offset);
+                for frame in frames.frames() {
+                    assert_eq!(frame.0.rem_euclid(stride as i32), offset as i32);
+                    covered.push(frame.0);
+                }
+            }
+
+            covered.sort();
+            assert_eq!(covered, frame_numbers, "stride {stride}");
+        }
+    }
+
+    #[test]
+    fn negative_frames_in_stride() {
+        assert!(is_in_stride(-1, 3, 2));
+        assert!(is_in_stride(-3, 3, 0));
+        assert!(is_in_stride(-4, 3, 2));
+        assert!(!is_in_stride(-1, 3, 1));
+        // a stride of zero renders every frame
+        assert!(is_in_stride(-5, 0, 0));
     }
 }
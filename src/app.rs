@@ -1,14 +1,46 @@
 use crate::{
+    camera::Projection,
+    einstein_ring::EinsteinRing,
+    gpu_preview::GpuPreview,
     preview_manager::PreviewManager,
-    render::Renderer,
+    ray_inspector::RayInspector,
+    reference::{ComparisonMode, Reference},
+    render::{Crop, Renderer},
+    scene::{Scene, Spacetime},
     settings::Settings,
+    spherical_angle::RainAngle,
     timeline::Timeline,
-    windows::{ALL_WINDOWS, SETTINGS_WINDOW},
+    units::Units,
+    windows::{
+        ALL_WINDOWS, DIAGNOSTICS_WINDOW, EINSTEIN_RING_WINDOW, EMBEDDING_WINDOW,
+        GRAPH_EDITOR_WINDOW, KEYBOARD_SHORTCUTS_WINDOW, RAY_INSPECTOR_WINDOW, RENDER_VIEW_WINDOW,
+        SETTINGS_WINDOW, SPACETIME_DIAGRAM_WINDOW, WORLDLINE_PLOT_WINDOW,
+    },
 };
 use eframe::egui;
 use egui::{ColorImage, Sense, Vec2};
 use image::GenericImageView;
 use nalgebra::Vector2;
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Points drawn around outlines over the preview
+const OUTLINE_POINTS: usize = 256;
+
+/// How often to check whether a preview rendering in the background has finished
+const PREVIEW_POLL_INTERVAL: Duration = Duration::from_millis(15);
+
+/// How often to redraw the progress of a render
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long the preview stays at the resolution of interaction after the last drag or scroll
+const INTERACTION_HOLD: Duration = Duration::from_millis(250);
+
+const SHADOW_OUTLINE_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 80, 80);
+const EINSTEIN_RING_COLOR: egui::Color32 = egui::Color32::from_rgb(80, 220, 255);
 
 #[derive(Default)]
 pub struct BHDiver {
@@ -16,36 +48,184 @@ pub struct BHDiver {
     pub settings: Settings,
     pub preview_manager: PreviewManager,
     pub renderer: Renderer,
+    // pinned preview to compare the live preview against
+    pub reference: Reference,
+    // ray of the last ctrl-clicked preview pixel
+    pub ray_inspector: RayInspector,
+    // ring of light bent through a chosen angle to mark on the preview
+    pub einstein_ring: EinsteinRing,
+    // whether to ask before discarding unsaved changes for a new project
+    confirm_new_project: bool,
+    // shader preview, None if the GL context doesn't support it
+    gpu_preview: Option<Arc<Mutex<GpuPreview>>>,
+    // when the pointer last dragged or scrolled, the preview stays at the lower resolution of
+    // interaction until a moment after
+    last_interaction: Option<Instant>,
+    // texture of the CPU preview and what it was uploaded from
+    preview_texture: Option<(PreviewTextureKey, egui::TextureHandle)>,
+}
+
+/// Everything the texture of the CPU preview is made from, it is only uploaded again when this
+/// changes
+#[derive(PartialEq)]
+struct PreviewTextureKey {
+    generation: usize,
+    // column the flat space comparison is split at
+    split: Option<u32>,
+    // columns of the render that are shown
+    visible: Range<u32>,
+    reference: (usize, ComparisonMode, f32, f32),
 }
 
 impl BHDiver {
-    pub fn new(__cc: &eframe::CreationContext) -> Self {
+    pub fn new(cc: &eframe::CreationContext) -> Self {
         // Customize egui here with cc.egui_ctx.set_fonts and cc.egui_ctx.set_visuals.
         // Restore app state using cc.storage (requires the "persistence" feature).
         // Use the cc.gl (a glow::Context) to create graphics shaders and buffers that you can use
         // for e.g. egui::PaintCallback.
 
-        let mut app = Self::default();
+        let mut app = Self {
+            // fall back to the CPU preview if the shaders can't be used
+            gpu_preview: cc
+                .gl
+                .as_ref()
+                .and_then(|gl| GpuPreview::new(gl).ok())
+                .map(|gpu_preview| Arc::new(Mutex::new(gpu_preview))),
+            ..Default::default()
+        };
 
         // initialize first preview
-        app.preview_manager
-            .new_render(app.timeline.get_current_scene().clone(), Vector2::new(1, 1));
+        app.preview_manager.new_render(
+            app.timeline.get_current_scene().clone(),
+            app.renderer.preview_settings(Vector2::new(1, 1)),
+            None,
+            false,
+        );
 
         app
     }
+
+    /// inspects the ray seen at the pixel of the preview and opens the inspector
+    fn inspect_ray(
+        &mut self,
+        ctx: &egui::Context,
+        projection: Projection,
+        pixel: Vector2<u32>,
+        resolution: Vector2<u32>,
+    ) {
+        let scene = self.timeline.get_current_scene();
+        self.ray_inspector
+            .inspect(scene.pixel_to_view_angle(projection, pixel, resolution));
+        RAY_INSPECTOR_WINDOW.set_open(ctx, true);
+    }
+
+    pub fn is_gpu_preview_available(&self) -> bool {
+        self.gpu_preview.is_some()
+    }
+
+    /// replaces the timeline with one starting from the default scene
+    pub fn new_project(&mut self) {
+        self.timeline = Timeline::new(
+            1,
+            120,
+            self.timeline.fps,
+            self.settings.default_scene.clone(),
+        );
+    }
+
+    /// returns the scale of the resolution of the CPU preview, full resolution once the view has
+    /// stopped being dragged or zoomed if the resolution is adaptive
+    fn preview_scale(&mut self, ctx: &egui::Context) -> f32 {
+        if !self.settings.adaptive_resolution {
+            return self.settings.resolution_scale;
+        }
+
+        if ctx.input(|i| i.pointer.any_down() || i.scroll_delta != Vec2::ZERO) {
+            self.last_interaction = Some(Instant::now());
+        }
+        match self.last_interaction.map(|time| time.elapsed()) {
+            Some(elapsed) if elapsed < INTERACTION_HOLD => {
+                // come back to restore the full resolution
+                ctx.request_repaint_after(INTERACTION_HOLD - elapsed);
+                self.settings.resolution_scale
+            }
+            _ => 1_f32,
+        }
+    }
+
+    /// asks whether to discard unsaved changes before starting a new project
+    fn show_new_project_dialog(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Unsaved changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("The current project has unsaved changes.");
+                ui.horizontal(|ui| {
+                    if ui.button("Discard and start new project").clicked() {
+                        self.new_project();
+                        self.confirm_new_project = false;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_new_project = false;
+                    }
+                });
+            });
+    }
 }
 
 impl eframe::App for BHDiver {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // still frames rendered on the GPU have to be drawn from the UI thread, the CPU renders
+        // them instead if it can't
+        if let Some((scene, render_settings, passes, output_path)) = self.renderer.take_gpu_frame()
+        {
+            let image = frame
+                .gl()
+                .zip(self.gpu_preview.as_ref())
+                .and_then(|(gl, gpu_preview)| {
+                    gpu_preview
+                        .lock()
+                        .unwrap()
+                        .render_image(gl, &scene, render_settings)
+                        .ok()
+                });
+            self.renderer
+                .save_frame(scene, image, render_settings, passes, output_path);
+        }
+
         // Show all the windows
         ALL_WINDOWS.iter().for_each(|window| {
             window.show(ctx, self);
         });
 
+        if self.confirm_new_project {
+            self.show_new_project_dialog(ctx);
+        }
+
         // Menu bar
         egui::TopBottomPanel::top("menu_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("New").clicked() {
+                        if self.timeline.has_unsaved_changes() {
+                            self.confirm_new_project = true;
+                        } else {
+                            self.new_project();
+                        }
+                        ui.close_menu();
+                    }
+                });
                 SETTINGS_WINDOW.menu_button(ui);
+                GRAPH_EDITOR_WINDOW.menu_button(ui);
+                DIAGNOSTICS_WINDOW.menu_button(ui);
+                SPACETIME_DIAGRAM_WINDOW.menu_button(ui);
+                WORLDLINE_PLOT_WINDOW.menu_button(ui);
+                EMBEDDING_WINDOW.menu_button(ui);
+                RAY_INSPECTOR_WINDOW.menu_button(ui);
+                EINSTEIN_RING_WINDOW.menu_button(ui);
+                RENDER_VIEW_WINDOW.menu_button(ui);
+                KEYBOARD_SHORTCUTS_WINDOW.menu_button(ui);
             });
         });
 
@@ -53,7 +233,7 @@ impl eframe::App for BHDiver {
             .default_height(100_f32)
             .resizable(true)
             .show(ctx, |ui| {
-                self.timeline.show(ui);
+                self.timeline.show(ui, &self.settings.keymap);
             });
 
         egui::SidePanel::new(egui::panel::Side::Left, "scene panel")
@@ -62,14 +242,14 @@ impl eframe::App for BHDiver {
                 self.timeline.with_current_scene(|scene| {
                     ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
                         ui.heading("Scene");
-                        scene.show(ui);
+                        scene.show(ui, &self.settings.units);
                     });
                 });
             });
 
         egui::SidePanel::right("render panel").show(ctx, |ui| {
             ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
-                self.renderer.show(&self.timeline, ui);
+                self.renderer.show(&self.timeline, &self.settings.units, ui);
             });
         });
 
@@ -77,48 +257,182 @@ impl eframe::App for BHDiver {
             // get pixels per egui point
             let pixelsperpoint = frame.info().native_pixels_per_point.unwrap();
 
+            let lock_aspect = self.settings.lock_preview_aspect;
+
+            // the eyedropper picks from the pixels of the CPU preview
+            let gpu_preview = self.gpu_preview.clone().filter(|_| {
+                self.settings.gpu_preview
+                    && !self.renderer.render_settings().render_mode.is_diagnostic()
+                    && !self.settings.flat_comparison
+                    && !self.renderer.eyedropper_active()
+                    && GpuPreview::supports(
+                        &self.timeline.get_current_scene(),
+                        self.renderer.render_settings(),
+                    )
+            });
+
+            // reference comparison controls
+            if gpu_preview.is_none() {
+                ui.horizontal(|ui| {
+                    if self.reference.show(ui) {
+                        self.preview_manager
+                            .with_render(|render, _time| self.reference.pin(render));
+                    }
+                });
+            }
+
             // update the preview resolution
-            let space = ui.available_size();
-            let res = space * pixelsperpoint * self.settings.resolution_scale;
+            let space = if lock_aspect {
+                fit_aspect_ratio(ui.available_size(), self.renderer.aspect_ratio())
+            } else {
+                ui.available_size()
+            };
+            let res = space * pixelsperpoint * self.preview_scale(ctx);
             let preview_res = Vector2::new(res.x as u32, res.y as u32);
+            let native_res = space * pixelsperpoint;
+            let refine_res = Vector2::new(native_res.x as u32, native_res.y as u32);
+
+            let mut preview_settings = self.renderer.preview_settings(preview_res);
+            if lock_aspect {
+                preview_settings.projection = self.renderer.render_settings().projection;
+            }
+
+            // draw the preview with the shader at full resolution every frame
+            if let Some(gpu_preview) = gpu_preview {
+                if lock_aspect {
+                    ui.add_space((ui.available_height() - space.y) / 2_f32);
+                }
+                let response = ui
+                    .vertical_centered(|ui| {
+                        ui.allocate_exact_size(space, Sense::click_and_drag()).1
+                    })
+                    .inner;
 
+                GpuPreview::paint(
+                    &gpu_preview,
+                    ui,
+                    response.rect,
+                    self.timeline.get_current_scene(),
+                    preview_settings,
+                );
+
+                let scene = self.timeline.get_current_scene();
+                paint_outlines(
+                    ui,
+                    response.rect,
+                    &scene,
+                    &preview_outlines(&scene, &self.settings, &self.einstein_ring),
+                    preview_settings.projection,
+                    preview_res,
+                    0..preview_res.x,
+                );
+                if self.settings.physics_overlay {
+                    paint_physics_overlay(
+                        ui,
+                        response.rect,
+                        &self.timeline.get_current_scene(),
+                        &self.settings.units,
+                    );
+                }
+
+                if let Some(pixel) = ray_inspector_click(ctx, &response, preview_res) {
+                    self.inspect_ray(ctx, preview_settings.projection, pixel, preview_res);
+                }
+
+                handle_crop_or_camera(
+                    ctx,
+                    ui,
+                    &response,
+                    &mut self.renderer,
+                    &mut self.timeline,
+                    &self.settings,
+                );
+                return;
+            }
+
+            let generation = self.preview_manager.render_generation();
             self.preview_manager.with_render(|render, _time| {
                 // get the aspect ratio of the image
                 let aspect_ratio_img = render.width() as f32 / render.height() as f32;
 
-                // get the aspect ratio of the space to fill
-                let aspect_ratio_space = ui.available_width() / ui.available_height();
+                let (space, img_pixel_width) = if lock_aspect {
+                    // letterbox the whole image in the available space
+                    let space = fit_aspect_ratio(ui.available_size(), aspect_ratio_img);
+                    ui.add_space((ui.available_height() - space.y) / 2_f32);
 
-                // get the space we want to fill with the image
-                let space = Vec2::new(
-                    ui.available_width()
-                        .min(ui.available_height() * aspect_ratio_img),
-                    ui.available_height(),
-                );
+                    (space, render.width())
+                } else {
+                    // get the aspect ratio of the space to fill
+                    let aspect_ratio_space = ui.available_width() / ui.available_height();
 
-                // get the pixel width of the image to fit in the aspect ratio of the space
-                // keeping the height the same
-                let img_pixel_width = render
-                    .width()
-                    .min((render.height() as f32 * aspect_ratio_space) as u32);
-
-                // trim the image
-                let img = render.view(
-                    (render.width() - img_pixel_width) / 2,
-                    0,
-                    img_pixel_width,
-                    render.height(),
-                );
+                    // get the space we want to fill with the image
+                    let space = Vec2::new(
+                        ui.available_width()
+                            .min(ui.available_height() * aspect_ratio_img),
+                        ui.available_height(),
+                    );
+
+                    // get the pixel width of the image to fit in the aspect ratio of the space
+                    // keeping the height the same
+                    let img_pixel_width = render
+                        .width()
+                        .min((render.height() as f32 * aspect_ratio_space) as u32);
+
+                    (space, img_pixel_width)
+                };
+
+                let trim_start = (render.width() - img_pixel_width) / 2;
+
+                // show flat space left of the split
+                let split = self.settings.flat_comparison.then(|| {
+                    trim_start
+                        + (self.settings.flat_comparison_split * img_pixel_width as f32) as u32
+                });
+                let visible = trim_start..trim_start + img_pixel_width;
 
-                // get a new egui texture handle
-                let texture: &egui::TextureHandle = &ctx.load_texture(
-                    "render texture",
-                    ColorImage::from_rgb(
+                // only upload the image when it changed since the last frame
+                let key = PreviewTextureKey {
+                    generation,
+                    split,
+                    visible: visible.clone(),
+                    reference: self.reference.state(),
+                };
+                if self
+                    .preview_texture
+                    .as_ref()
+                    .map(|(previous_key, _)| previous_key)
+                    != Some(&key)
+                {
+                    let flat_split =
+                        split.and_then(|split| self.preview_manager.split_with_flat(render, split));
+                    let render = flat_split.as_ref().unwrap_or(render);
+
+                    // show the comparison with the reference if one is pinned
+                    let composite = self.reference.composite(render, visible.clone());
+                    let displayed = composite.as_ref().unwrap_or(render);
+
+                    // trim the image
+                    let img = displayed.view(trim_start, 0, img_pixel_width, render.height());
+                    let image = ColorImage::from_rgb(
                         [img.dimensions().0 as _, img.dimensions().1 as _],
                         img.to_image().as_flat_samples().as_slice(),
-                    ),
-                    Default::default(),
-                );
+                    );
+
+                    // reuse the texture so egui updates it in place
+                    match &mut self.preview_texture {
+                        Some((previous_key, texture)) => {
+                            texture.set(image, Default::default());
+                            *previous_key = key;
+                        }
+                        None => {
+                            let texture =
+                                ctx.load_texture("render texture", image, Default::default());
+                            self.preview_texture = Some((key, texture));
+                        }
+                    }
+                }
+                let texture = &self.preview_texture.as_ref().unwrap().1;
+
                 // show the image
 
                 let img_ui = ui
@@ -127,37 +441,403 @@ impl eframe::App for BHDiver {
                     })
                     .inner;
 
-                // Don't show the cursor when holding down on the image
-                if img_ui.is_pointer_button_down_on() {
-                    ctx.set_cursor_icon(egui::CursorIcon::None)
+                self.reference.paint_labels(ui, img_ui.rect);
+
+                if self.settings.flat_comparison {
+                    drag_flat_comparison_split(
+                        ui,
+                        img_ui.rect,
+                        &mut self.settings.flat_comparison_split,
+                    );
                 }
 
-                // handle input
-                // scrolling while hovered
-                self.timeline.with_current_scene(|current_scene| {
-                    if img_ui.hovered() {
-                        let scroll = ctx.input(|i| i.scroll_delta.y);
-                        if scroll != 0_f32 {
-                            current_scene
-                                .camera
-                                .zoom(scroll, self.settings.zoom_sensitivity)
-                        }
-                    }
-                    // mouse drag
-                    let drag_delta = img_ui.drag_delta();
-                    if drag_delta.length() != 0_f32 {
-                        current_scene
-                            .camera
-                            .drag_delta(drag_delta, self.settings.mouse_sensitivity)
+                let scene = self.timeline.get_current_scene();
+                paint_outlines(
+                    ui,
+                    img_ui.rect,
+                    &scene,
+                    &preview_outlines(&scene, &self.settings, &self.einstein_ring),
+                    preview_settings.projection,
+                    Vector2::new(render.width(), render.height()),
+                    trim_start..trim_start + img_pixel_width,
+                );
+                if self.settings.physics_overlay {
+                    paint_physics_overlay(
+                        ui,
+                        img_ui.rect,
+                        &self.timeline.get_current_scene(),
+                        &self.settings.units,
+                    );
+                }
+
+                // show the progress of the refinement over the corner of the preview
+                if let Some(progress) = self.preview_manager.refinement_progress() {
+                    ui.painter().text(
+                        img_ui.rect.left_bottom() + Vec2::new(4_f32, -4_f32),
+                        egui::Align2::LEFT_BOTTOM,
+                        format!("Refining… {:.0}%", progress * 100_f32),
+                        egui::TextStyle::Small.resolve(ui.style()),
+                        ui.visuals().weak_text_color(),
+                    );
+                }
+
+                // inspect the ray of the ctrl-clicked preview pixel
+                let resolution = Vector2::new(render.width(), render.height());
+                let trimmed = Vector2::new(img_pixel_width, render.height());
+                if let Some(pixel) = ray_inspector_click(ctx, &img_ui, trimmed) {
+                    let pixel = pixel + Vector2::new(trim_start, 0);
+                    let scene = self.timeline.get_current_scene();
+                    self.ray_inspector.inspect(scene.pixel_to_view_angle(
+                        preview_settings.projection,
+                        pixel,
+                        resolution,
+                    ));
+                    RAY_INSPECTOR_WINDOW.set_open(ctx, true);
+                }
+
+                // pick the grading from the clicked preview pixel
+                if self.renderer.eyedropper_active()
+                    && img_ui.clicked()
+                    && !ctx.input(|i| i.modifiers.command)
+                {
+                    if let Some(pos) = img_ui.interact_pointer_pos() {
+                        let uv = (pos - img_ui.rect.min) / img_ui.rect.size();
+                        let x = (render.width() - img_pixel_width) / 2
+                            + (uv.x * img_pixel_width as f32) as u32;
+                        let y = (uv.y * render.height() as f32) as u32;
+                        let pixel = *render
+                            .get_pixel(x.min(render.width() - 1), y.min(render.height() - 1));
+
+                        self.renderer
+                            .pick_grading(pixel, ctx.input(|i| i.modifiers.shift));
                     }
-                });
+                }
+
+                handle_crop_or_camera(
+                    ctx,
+                    ui,
+                    &img_ui,
+                    &mut self.renderer,
+                    &mut self.timeline,
+                    &self.settings,
+                );
             });
 
             // Start a new render
-            self.preview_manager
-                .new_render(self.timeline.get_current_scene(), preview_res);
+            let mut refine_settings = self.renderer.preview_settings(refine_res);
+            refine_settings.super_sampling = Some(2);
+            refine_settings.projection = preview_settings.projection;
+            self.preview_manager.new_render(
+                self.timeline.get_current_scene(),
+                preview_settings,
+                self.settings.refine_preview.then_some(refine_settings),
+                self.settings.flat_comparison,
+            );
         });
 
-        ctx.request_repaint();
+        // egui repaints on input by itself, work finishing in the background has to be polled for
+        if self.preview_manager.is_working() {
+            ctx.request_repaint_after(PREVIEW_POLL_INTERVAL);
+        } else if self.renderer.is_rendering() {
+            ctx.request_repaint_after(PROGRESS_POLL_INTERVAL);
+        }
+    }
+}
+
+/// draws the radius, speed, remaining proper time and hovering acceleration of the diver and the
+/// angular size of the shadow over the top left corner of the preview
+fn paint_physics_overlay(ui: &egui::Ui, rect: egui::Rect, scene: &Scene, units: &Units) {
+    let mut lines = Vec::new();
+
+    if scene.spacetime == Spacetime::Wormhole {
+        lines.push(format!(
+            "Distance  {}",
+            units.describe_length(scene.wormhole_distance())
+        ));
+    } else {
+        lines.push(format!(
+            "Radius  {}",
+            units.describe_length(scene.diver_radius())
+        ));
+        lines.push(match scene.diver_static_speed() {
+            Some(speed) => format!("Speed  {:.3}c relative to static observers", speed),
+            None => format!(
+                "Speed  {:.3}c relative to the rain",
+                scene.scaled_diver().velocity().norm()
+            ),
+        });
+        let remaining_time = scene.remaining_proper_time();
+        lines.push(if remaining_time.is_finite() {
+            format!("Proper time left  {}", units.describe_time(remaining_time))
+        } else {
+            "Proper time left  never falls in".to_owned()
+        });
+        if let Some(acceleration) = scene.diver.proper_acceleration_for_mass(scene.mass) {
+            lines.push(format!(
+                "Acceleration  {}",
+                units.describe_acceleration(acceleration)
+            ));
+        }
+    }
+
+    if scene.spacetime == Spacetime::Wormhole {
+        let throat = 2_f64 * scene.critical_rain_angle().to_degrees();
+        lines.push(format!("Throat diameter  {:.2}°", throat));
+    } else if let Some(radius) = scene.shadow_angular_radius() {
+        // the shadow of a binary has no single diameter
+        let shadow = 2_f64 * radius.to_degrees();
+        lines.push(format!("Shadow diameter  {:.2}°", shadow));
+    }
+
+    let painter = ui.painter_at(rect);
+    let galley = painter.layout_no_wrap(
+        lines.join("\n"),
+        egui::TextStyle::Monospace.resolve(ui.style()),
+        egui::Color32::WHITE,
+    );
+    let position = rect.left_top() + Vec2::new(8_f32, 8_f32);
+    painter.rect_filled(
+        egui::Rect::from_min_size(position, galley.size()).expand(4_f32),
+        4_f32,
+        egui::Color32::from_black_alpha(160),
+    );
+    painter.galley(position, galley);
+}
+
+/// returns the outlines to draw over the preview of the scene and their colors
+fn preview_outlines(
+    scene: &Scene,
+    settings: &Settings,
+    einstein_ring: &EinsteinRing,
+) -> Vec<(Vec<RainAngle>, egui::Color32)> {
+    let mut outlines = Vec::new();
+    if settings.shadow_outline {
+        if let Some(shadow) = scene.shadow_outline(OUTLINE_POINTS) {
+            outlines.push((shadow, SHADOW_OUTLINE_COLOR));
+        }
+    }
+    if let Some(ring) = einstein_ring.outline(scene, OUTLINE_POINTS) {
+        outlines.push((ring, EINSTEIN_RING_COLOR));
+    }
+    outlines
+}
+
+/// draws the outlines of view angles in their colors over the preview showing the columns of the image with this
+/// resolution and projection
+fn paint_outlines(
+    ui: &egui::Ui,
+    rect: egui::Rect,
+    scene: &Scene,
+    outlines: &[(Vec<RainAngle>, egui::Color32)],
+    projection: Projection,
+    resolution: Vector2<u32>,
+    columns: Range<u32>,
+) {
+    let to_screen = |pixel: Vector2<f64>| {
+        egui::pos2(
+            rect.left()
+                + ((pixel.x - columns.start as f64) / columns.len() as f64) as f32 * rect.width(),
+            rect.top() + (pixel.y / resolution.y as f64) as f32 * rect.height(),
+        )
+    };
+    let painter = ui.painter_at(rect);
+    for (outline, color) in outlines {
+        let points: Vec<_> = outline
+            .iter()
+            .map(|&angle| {
+                scene
+                    .view_angle_to_pixel(projection, angle, resolution)
+                    .map(to_screen)
+            })
+            .collect();
+
+        let stroke = egui::Stroke::new(1.5_f32, *color);
+        for (i, start) in points.iter().enumerate() {
+            let end = points[(i + 1) % points.len()];
+            // skip segments going behind the camera or wrapping around the 360° image
+            if let (Some(start), Some(end)) = (*start, end) {
+                if (end.x - start.x).abs() < rect.width() / 2_f32 {
+                    painter.line_segment([start, end], stroke);
+                }
+            }
+        }
+    }
+}
+
+/// draws the split between flat space and general relativity over the preview in `rect` and
+/// moves it when dragged, `split` is the fraction of the width it is at
+fn drag_flat_comparison_split(ui: &egui::Ui, rect: egui::Rect, split: &mut f32) {
+    // wide enough to grab, added after the preview so that it takes the drag instead
+    const HANDLE_WIDTH: f32 = 12_f32;
+
+    let x = rect.left() + *split * rect.width();
+    let handle = egui::Rect::from_x_y_ranges(
+        x - HANDLE_WIDTH / 2_f32..=x + HANDLE_WIDTH / 2_f32,
+        rect.y_range(),
+    );
+    let response = ui.interact(
+        handle,
+        egui::Id::new("flat comparison split"),
+        Sense::drag(),
+    );
+
+    if response.hovered() || response.dragged() {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeHorizontal);
+    }
+    if let Some(pos) = response
+        .interact_pointer_pos()
+        .filter(|_| response.dragged())
+    {
+        *split = ((pos.x - rect.left()) / rect.width()).clamp(0_f32, 1_f32);
+    }
+
+    let x = rect.left() + *split * rect.width();
+    let painter = ui.painter();
+    let font = egui::TextStyle::Body.resolve(ui.style());
+    let color = ui.visuals().strong_text_color();
+    let margin = Vec2::new(4_f32, 4_f32);
+
+    painter.vline(x, rect.y_range(), egui::Stroke::new(1_f32, color));
+    painter.circle_filled(
+        egui::pos2(x, rect.center().y),
+        HANDLE_WIDTH / 2_f32,
+        ui.visuals().widgets.inactive.bg_fill,
+    );
+    painter.text(
+        egui::pos2(x - margin.x, rect.bottom() - margin.y),
+        egui::Align2::RIGHT_BOTTOM,
+        "Flat space",
+        font.clone(),
+        color,
+    );
+    painter.text(
+        egui::pos2(x + margin.x, rect.bottom() - margin.y),
+        egui::Align2::LEFT_BOTTOM,
+        "General relativity",
+        font,
+        color,
+    );
+}
+
+/// returns the pixel of the image with the resolution shown in the response that was clicked
+/// holding ctrl, or command on macOS
+fn ray_inspector_click(
+    ctx: &egui::Context,
+    response: &egui::Response,
+    resolution: Vector2<u32>,
+) -> Option<Vector2<u32>> {
+    if !response.clicked() || !ctx.input(|i| i.modifiers.command) {
+        return None;
+    }
+
+    let uv = (response.interact_pointer_pos()? - response.rect.min) / response.rect.size();
+    Some(Vector2::new(
+        ((uv.x * resolution.x as f32) as u32).min(resolution.x.saturating_sub(1)),
+        ((uv.y * resolution.y as f32) as u32).min(resolution.y.saturating_sub(1)),
+    ))
+}
+
+/// selects the crop by dragging over the preview while the crop tool is on, otherwise moves the
+/// camera. The preview only lines up with the render when its aspect ratio is locked, so the crop
+/// is only shown and selected then
+fn handle_crop_or_camera(
+    ctx: &egui::Context,
+    ui: &egui::Ui,
+    response: &egui::Response,
+    renderer: &mut Renderer,
+    timeline: &mut Timeline,
+    settings: &Settings,
+) {
+    let lock_aspect = settings.lock_preview_aspect;
+    if lock_aspect && renderer.crop_tool_active() {
+        if let Some(crop) = drag_crop(ctx, response) {
+            renderer.set_crop(Some(crop));
+        }
+    } else {
+        handle_preview_input(ctx, response, timeline, settings);
+    }
+
+    if let Some(crop) = renderer.render_settings().crop.filter(|_| lock_aspect) {
+        paint_crop(ui, response.rect, crop);
+    }
+}
+
+/// returns the crop between where the drag over the preview started and the pointer
+fn drag_crop(ctx: &egui::Context, response: &egui::Response) -> Option<Crop> {
+    if !response.dragged() {
+        return None;
+    }
+    ctx.set_cursor_icon(egui::CursorIcon::Crosshair);
+
+    let (start, end) =
+        ctx.input(|i| Some((i.pointer.press_origin()?, i.pointer.interact_pos()?)))?;
+    let uv = |pos: egui::Pos2| {
+        let uv = (pos - response.rect.min) / response.rect.size();
+        Vector2::new(uv.x, uv.y)
+    };
+    Some(Crop::from_corners(uv(start), uv(end)))
+}
+
+/// shades the preview outside the crop and outlines it
+fn paint_crop(ui: &egui::Ui, rect: egui::Rect, crop: Crop) {
+    let to_screen = |corner: Vector2<f32>| {
+        rect.min + Vec2::new(corner.x * rect.width(), corner.y * rect.height())
+    };
+    let crop_rect = egui::Rect::from_min_max(to_screen(crop.min), to_screen(crop.max));
+
+    let painter = ui.painter();
+    let shade = egui::Color32::from_black_alpha(128);
+    for outside in [
+        egui::Rect::from_x_y_ranges(rect.x_range(), rect.top()..=crop_rect.top()),
+        egui::Rect::from_x_y_ranges(rect.x_range(), crop_rect.bottom()..=rect.bottom()),
+        egui::Rect::from_x_y_ranges(rect.left()..=crop_rect.left(), crop_rect.y_range()),
+        egui::Rect::from_x_y_ranges(crop_rect.right()..=rect.right(), crop_rect.y_range()),
+    ] {
+        painter.rect_filled(outside, 0_f32, shade);
+    }
+    painter.rect_stroke(
+        crop_rect,
+        0_f32,
+        egui::Stroke::new(1_f32, ui.visuals().strong_text_color()),
+    );
+}
+
+/// moves the camera of the current scene by dragging and scrolling on the preview
+fn handle_preview_input(
+    ctx: &egui::Context,
+    response: &egui::Response,
+    timeline: &mut Timeline,
+    settings: &Settings,
+) {
+    // Don't show the cursor when holding down on the image
+    if response.is_pointer_button_down_on() {
+        ctx.set_cursor_icon(egui::CursorIcon::None)
+    }
+
+    // handle input
+    // scrolling while hovered
+    timeline.with_current_scene(|current_scene| {
+        if response.hovered() {
+            let scroll = ctx.input(|i| i.scroll_delta.y);
+            if scroll != 0_f32 {
+                current_scene.camera.zoom(scroll, settings.zoom_sensitivity)
+            }
+        }
+        // mouse drag
+        let drag_delta = response.drag_delta();
+        if drag_delta.length() != 0_f32 {
+            current_scene
+                .camera
+                .drag_delta(drag_delta, settings.mouse_sensitivity)
+        }
+    });
+}
+
+/// returns the largest size with the aspect ratio that fits in the space
+fn fit_aspect_ratio(space: Vec2, aspect_ratio: f32) -> Vec2 {
+    if space.x / space.y > aspect_ratio {
+        Vec2::new(space.y * aspect_ratio, space.y)
+    } else {
+        Vec2::new(space.x, space.x / aspect_ratio)
     }
 }
@@ -0,0 +1,261 @@
+// Experimental pair of black holes lensing the sky together, by superposing their bending
+//
+// Around a single Schwarzschild black hole of mass M a photon drawn in flat coordinates obeys
+// x'' = -3 M h^2 x / r^5 exactly, where h = |x cross x'| is conserved and the speed approaches 1 at
+// infinity. Two holes are approximated by adding up this acceleration about each of them as if
+// the other weren't there, which ignores how the holes move and bend each other's spacetime. The
+// rays are stepped in 3D with the Runge-Kutta-Fehlberg integrator of the geodesic backend, with
+// steps no longer than a fraction of the distance to the nearest hole, until they fall through a
+// horizon or escape.
+//
+// The holes orbit their center of mass in the plane facing the diver, with the mass of the scene
+// split between them. The static observer at the diver sees a ray leaving at angle alpha from the
+// outward direction when dr/dlambda = cos(alpha) and r dphi/dlambda = sin(alpha) / sqrt(1 - 2M/r),
+// with the potential of both holes in place of 2M/r.
+
+use crate::{
+    geodesic::{next_step_size, rkf45_step},
+    traits::Interpolate,
+};
+use nalgebra::Vector3;
+use std::f64::consts::PI;
+
+/// Closest the centers of the holes may be in units of the total mass, their horizons touch at 2
+pub const MIN_SEPARATION: f64 = 2.5;
+
+/// Smallest ratio of the lighter to the heavier mass
+pub const MIN_MASS_RATIO: f64 = 0.01;
+
+/// Largest fraction of the distance to the nearest hole taken in a step
+const STEP_FRACTION: f64 = 0.1;
+
+/// Acceptable error in the position and velocity per step, relative to the distance to the
+/// nearest hole
+const TOLERANCE: f64 = 1e-9;
+
+/// Most steps a single ray may take before it is given up on
+const MAX_STEPS: usize = 100_000;
+
+/// Distance in units of the total mass beyond which outgoing rays have escaped, the remaining
+/// bending is around 2M over the distance
+const ESCAPE_RADIUS: f64 = 1000_f64;
+
+/// Position and velocity of the photon
+type State = [f64; 6];
+
+/// returns the position and velocity in the state
+fn split(state: &State) -> (Vector3<f64>, Vector3<f64>) {
+    (
+        Vector3::new(state[0], state[1], state[2]),
+        Vector3::new(state[3], state[4], state[5]),
+    )
+}
+
+/// returns the state with the position and velocity
+fn join(position: Vector3<f64>, velocity: Vector3<f64>) -> State {
+    [
+        position.x, position.y, position.z, velocity.x, velocity.y, velocity.z,
+    ]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binary {
+    // distance between the centers of the holes in units of the total mass
+    pub separation: f64,
+    // mass of the lighter hole over the heavier one, 0 to 1
+    pub mass_ratio: f64,
+    // angle of the line between the holes around the direction to the diver
+    pub phase: f64,
+}
+
+impl Default for Binary {
+    fn default() -> Self {
+        Self {
+            separation: 10_f64,
+            mass_ratio: 1_f64,
+            phase: 0_f64,
+        }
+    }
+}
+
+impl Interpolate for Binary {
+    fn interpolate(&self, other: &Self, factor: f32) -> Self {
+        Self {
+            separation: self.separation.interpolate(&other.separation, factor),
+            mass_ratio: self.mass_ratio.interpolate(&other.mass_ratio, factor),
+            phase: self.phase.interpolate(&other.phase, factor),
+        }
+    }
+}
+
+impl Binary {
+    /// returns the mass and position of each hole for a total mass of 1 with the center of mass at
+    /// the origin, the diver is along -z
+    pub fn holes(&self) -> [(f64, Vector3<f64>); 2] {
+        let ratio = self.mass_ratio.clamp(MIN_MASS_RATIO, 1_f64);
+        let heavy = 1_f64 / (1_f64 + ratio);
+        let light = ratio / (1_f64 + ratio);
+        let axis = Vector3::new(self.phase.cos(), self.phase.sin(), 0_f64);
+        let separation = self.separation.max(MIN_SEPARATION);
+
+        [
+            (heavy, -axis * separation * light),
+            (light, axis * separation * heavy),
+        ]
+    }
+
+    /// returns the derivative of the state of the photon bent by both holes
+    fn derivative(holes: &[(f64, Vector3<f64>); 2], state: State) -> State {
+        let (position, velocity) = split(&state);
+        let acceleration: Vector3<f64> = holes
+            .iter()
+            .map(|&(mass, center)| {
+                let offset = position - center;
+                let h_squared = offset.cross(&velocity).norm_squared();
+                -3_f64 * mass * h_squared * offset / offset.norm().powi(5)
+            })
+            .sum();
+        join(velocity, acceleration)
+    }
+
+    /// follows the ray that a static observer at distance r from the center of mass sees arriving
+    /// from the direction back to where it came from
+    ///
+    /// returns the direction of the sky it came from and the angle it swept around the center of
+    /// mass, None if it came from one of the holes or sweeps through more than `max_half_orbits`
+    /// half orbits
+    pub fn trace(
+        &self,
+        direction: Vector3<f64>,
+        r: f64,
+        max_half_orbits: u32,
+    ) -> Option<(Vector3<f64>, f64)> {
+        let holes = self.holes();
+        let horizon = |position: Vector3<f64>| {
+            holes
+                .iter()
+                .any(|&(mass, center)| (position - center).norm() <= 2_f64 * mass)
+        };
+
+        let position = -Vector3::z() * r;
+        if horizon(position) {
+            return None;
+        }
+
+        // there are no static observers where the potential of the holes is too deep
+        let potential: f64 = holes
+            .iter()
+            .map(|&(mass, center)| 2_f64 * mass / (position - center).norm())
+            .sum();
+        if potential >= 1_f64 {
+            return None;
+        }
+
+        let outward = -Vector3::z();
+        let direction = direction.normalize();
+        let radial = direction.dot(&outward);
+        let tangential = direction - outward * radial;
+        let velocity = outward * radial + tangential / (1_f64 - potential).sqrt();
+        let mut state = join(position, velocity);
+
+        let escape_radius = ESCAPE_RADIUS.max(10_f64 * r);
+        let max_angle = max_half_orbits as f64 * PI;
+        let mut swept = 0_f64;
+        let mut h = f64::INFINITY;
+
+        for _ in 0..MAX_STEPS {
+            let (position, velocity) = split(&state);
+            if position.norm() > escape_radius && position.dot(&velocity) > 0_f64 {
+                return Some((velocity.normalize(), swept));
+            }
+
+            let nearest = holes
+                .iter()
+                .map(|&(_, center)| (position - center).norm())
+                .fold(f64::INFINITY, f64::min);
+            h = h.min(STEP_FRACTION * nearest / velocity.norm());
+            let (next, error) = rkf45_step(state, h, |state| Self::derivative(&holes, state));
+
+            let tolerance = TOLERANCE * nearest;
+            if error > tolerance {
+                h = next_step_size(h, error, tolerance);
+                continue;
+            }
+
+            let (next_position, _) = split(&next);
+            swept += position.angle(&next_position);
+            if swept > max_angle || horizon(next_position) {
+                return None;
+            }
+            state = next;
+            h = next_step_size(h, error, tolerance);
+        }
+
+        None
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("binary_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Separation");
+                ui.add(
+                    egui::DragValue::new(&mut self.separation)
+                        .clamp_range(MIN_SEPARATION..=f64::MAX)
+                        .speed(0.05)
+                        .suffix(" M"),
+                )
+                .on_hover_text("Distance between the holes in units of their total mass");
+                ui.end_row();
+
+                ui.label("Mass ratio");
+                ui.add(
+                    egui::Slider::new(&mut self.mass_ratio, MIN_MASS_RATIO..=1_f64)
+                        .logarithmic(true),
+                )
+                .on_hover_text("Mass of the lighter hole over the heavier one");
+                ui.end_row();
+
+                ui.label("Phase");
+                let mut phase = self.phase.to_degrees();
+                if ui
+                    .add(egui::DragValue::new(&mut phase).speed(1_f64).suffix("°"))
+                    .on_hover_text(
+                        "Angle of the line between the holes around the view of the diver, \
+                        animate it to make them orbit",
+                    )
+                    .changed()
+                {
+                    self.phase = phase.to_radians();
+                }
+                ui.end_row();
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distant_rays_bend_like_a_single_hole() {
+        // far from a close pair the rays bend by 4M/b around their total mass
+        let binary = Binary {
+            separation: MIN_SEPARATION,
+            ..Default::default()
+        };
+        let r = 10_000_f64;
+        for b in [100_f64, 300_f64] {
+            let alpha = (b / r).asin();
+            let direction = Vector3::new(alpha.sin(), 0_f64, alpha.cos());
+            let (sky, _) = binary.trace(direction, r, 2).unwrap();
+
+            let deflection = sky.angle(&direction);
+            let expected = 4_f64 / b;
+            assert!(
+                (deflection / expected - 1_f64).abs() < 0.05,
+                "b {b}: deflection {deflection}, expected {expected}"
+            );
+        }
+    }
+}
@@ -1,15 +1,25 @@
 use crate::{
+    math::kerr_frame_twist,
     spherical_angle::{RainAngle, SphericalAngle},
     traits::Interpolate,
 };
 use nalgebra::{Rotation3, Vector2, Vector3};
 use std::f64::consts::PI;
 
+/// Direction to the black hole from the diver, the zero rain angle
+const BLACK_HOLE_DIRECTION: Vector3<f64> = Vector3::new(0_f64, 0_f64, 1_f64);
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum Projection {
     #[default]
     Perspective,
     Equirectangular,
+    // the six faces of a cube around the camera side by side in the order +x, -x, +y, -y, +z, -z
+    // with +z ahead, the layout game engines load skyboxes from
+    CubeMap,
+    // equidistant dome master, a circle as wide as the image is high with the view straight ahead
+    // at its center and the field of view as the aperture across it
+    Fisheye,
 }
 
 impl ToString for Projection {
@@ -17,6 +27,53 @@ impl ToString for Projection {
         match self {
             Projection::Perspective => "Perspective".to_owned(),
             Projection::Equirectangular => "360°".to_owned(),
+            Projection::CubeMap => "Cube map".to_owned(),
+            Projection::Fisheye => "Fisheye".to_owned(),
+        }
+    }
+}
+
+impl Projection {
+    pub const ALL: [Projection; 4] = [
+        Projection::Perspective,
+        Projection::Equirectangular,
+        Projection::CubeMap,
+        Projection::Fisheye,
+    ];
+
+    /// returns the width of images of the projection in heights, None if it can have any shape
+    pub fn aspect_ratio(&self) -> Option<u32> {
+        match self {
+            Projection::Perspective => None,
+            Projection::Equirectangular => Some(2),
+            Projection::CubeMap => Some(6),
+            Projection::Fisheye => Some(1),
+        }
+    }
+}
+
+/// Observer the camera sees the black hole as
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Observer {
+    // moving with the diver
+    #[default]
+    Diver,
+    // hovering at the radius of the diver, only possible outside the horizon
+    Shell,
+    // hovering with zero angular momentum, its frame turned against the dragging of the rain
+    Zamo,
+}
+
+impl Observer {
+    pub const ALL: [Observer; 3] = [Observer::Diver, Observer::Shell, Observer::Zamo];
+}
+
+impl ToString for Observer {
+    fn to_string(&self) -> String {
+        match self {
+            Observer::Diver => "Diver".to_owned(),
+            Observer::Shell => "Hovering shell".to_owned(),
+            Observer::Zamo => "Zero angular momentum".to_owned(),
         }
     }
 }
@@ -28,6 +85,10 @@ pub struct Camera {
     // view matrix for transforming from local space to world space
     // column vectors are right, up, facing in global space
     inverse_view_matrix: Rotation3<f64>,
+    // whether the camera keeps looking at the black hole
+    pub track_black_hole: bool,
+    // observer whose view the camera shows
+    pub observer: Observer,
 }
 
 impl Interpolate for Camera {
@@ -37,10 +98,14 @@ impl Interpolate for Camera {
             .inverse_view_matrix
             .slerp(&other.inverse_view_matrix, factor as f64);
 
-        Self {
+        let mut camera = Self {
             fov: self.fov.interpolate(&other.fov, factor),
             inverse_view_matrix,
-        }
+            track_black_hole: self.track_black_hole,
+            observer: self.observer,
+        };
+        camera.renormalize();
+        camera
     }
 }
 
@@ -60,9 +125,48 @@ impl Camera {
         Camera {
             fov,
             inverse_view_matrix,
+            track_black_hole: false,
+            observer: Observer::Diver,
+        }
+    }
+
+    /// returns the velocity of the observer through the rain frame given the infall speed of the
+    /// rain through static observers and the velocity of the diver, with z towards the black hole
+    ///
+    /// shell observers move outward through the rain, inside the horizon they can't exist so the
+    /// camera falls back to the diver
+    pub fn observer_velocity(&self, rain_speed: f64, diver_velocity: Vector3<f64>) -> Vector3<f64> {
+        match self.observer {
+            Observer::Shell | Observer::Zamo if rain_speed < 1_f64 => {
+                Vector3::new(0_f64, 0_f64, -rain_speed)
+            }
+            _ => diver_velocity,
+        }
+    }
+
+    /// returns the angle the frame of the observer is turned about the spin axis relative to the
+    /// rain frame at the radius around a black hole with spin a
+    ///
+    /// on the axis the zero angular momentum observer hovers like the shell, but its frame keeps
+    /// the azimuth of the distant stars while the rain frame is dragged around by the spin
+    pub fn frame_twist(&self, rain_speed: f64, r: f64, a: f64) -> f64 {
+        match self.observer {
+            Observer::Zamo if rain_speed < 1_f64 => kerr_frame_twist(r, a),
+            _ => 0_f64,
         }
     }
 
+    /// returns the direction in the rain frame of the view angle of the observer turned by the
+    /// twist of `frame_twist`, before it is aberrated
+    pub fn untwist(view_angle: RainAngle, twist: f64) -> RainAngle {
+        RainAngle::new(view_angle.theta(), view_angle.phi() + twist)
+    }
+
+    // removes accumulated floating point error so the view matrix stays orthonormal
+    pub fn renormalize(&mut self) {
+        self.inverse_view_matrix.renormalize();
+    }
+
     // adjusts the pitch of the camera by the angle
     pub fn pitch(&mut self, angle: f64) {
         self.inverse_view_matrix =
@@ -76,6 +180,11 @@ impl Camera {
         ) * self.inverse_view_matrix;
     }
 
+    // turns the camera by the rotation
+    pub fn rotate(&mut self, rotation: Rotation3<f64>) {
+        self.inverse_view_matrix = rotation * self.inverse_view_matrix;
+    }
+
     // makes the camera look at dir
     pub fn look_at(&mut self, dir: &Vector3<f64>, up: &Vector3<f64>) {
         // direction of z axis is -view direction
@@ -94,9 +203,25 @@ impl Camera {
         self.inverse_view_matrix = Rotation3::from_basis_unchecked(&[x, y, z]);
     }
 
+    // turns the camera to face the black hole keeping it as upright as possible
+    pub fn aim_at_black_hole(&mut self) {
+        let up = if self.up().cross(&BLACK_HOLE_DIRECTION).norm() > 1e-6 {
+            self.up()
+        } else {
+            // the current up vector is parallel to the view direction so use the old facing
+            self.facing()
+        };
+        self.look_at(&BLACK_HOLE_DIRECTION, &up);
+    }
+
     pub fn drag_delta(&mut self, delta: egui::Vec2, sensitivity: f64) {
         self.pitch(-delta.y as f64 * self.fov * 0.0005 * sensitivity);
-        self.yaw(-delta.x as f64 * self.fov * 0.0005 * sensitivity)
+        self.yaw(-delta.x as f64 * self.fov * 0.0005 * sensitivity);
+        self.renormalize();
+
+        if self.track_black_hole {
+            self.aim_at_black_hole();
+        }
     }
 
     pub fn zoom(&mut self, scroll: f32, sensitivity: f64) {
@@ -147,11 +272,132 @@ impl Camera {
                 // transform to global
                 let dir = self.inverse_view_matrix.transform_vector(&local_dir);
 
+                RainAngle::from_vector(dir)
+            }
+            Projection::CubeMap => {
+                // each face is as wide as the image is high
+                let size = resolution.y as f64;
+                let face = (pixel.x / resolution.y).min(5) as usize;
+                let across = 2_f64 * (pixel.x as f64 - face as f64 * size) / size - 1_f64;
+                let down = 2_f64 * pixel.y as f64 / size - 1_f64;
+                let vector = cube_face_to_vector(face, across, down);
+
+                // +z is ahead on the cube and behind the camera
+                let local_dir = Vector3::new(vector.x, vector.y, -vector.z);
+
+                // transform to global
+                let dir = self.inverse_view_matrix.transform_vector(&local_dir);
+
+                RainAngle::from_vector(dir)
+            }
+            Projection::Fisheye => {
+                // local coordinates in radii of the circle
+                let radius = resolution.y as f64 / 2_f64;
+                let x = (pixel.x as f64 - resolution.x as f64 / 2_f64) / radius;
+                let y = (radius - pixel.y as f64) / radius;
+
+                // the angle from the view direction grows evenly out from the center
+                let distance = x.hypot(y);
+                let angle = distance * self.fov / 2_f64;
+                let (sin, cos) = angle.sin_cos();
+                let local_dir = if distance > 0_f64 {
+                    Vector3::new(x / distance * sin, y / distance * sin, -cos)
+                } else {
+                    Vector3::new(0_f64, 0_f64, -1_f64)
+                };
+
+                // transform to global
+                let dir = self.inverse_view_matrix.transform_vector(&local_dir);
+
                 RainAngle::from_vector(dir)
             }
         }
     }
 
+    /// returns whether the pixel shows the sky, fisheye images only fill the circle of the dome
+    pub fn sees_pixel(
+        &self,
+        projection: Projection,
+        pixel: Vector2<u32>,
+        resolution: Vector2<u32>,
+    ) -> bool {
+        match projection {
+            Projection::Fisheye => {
+                let radius = resolution.y as f64 / 2_f64;
+                let x = pixel.x as f64 - resolution.x as f64 / 2_f64;
+                let y = radius - pixel.y as f64;
+                x.hypot(y) <= radius
+            }
+            _ => true,
+        }
+    }
+
+    /// returns the position of the rain angle on the image like the pixels of
+    /// `pixel_to_rain_angle`, None if it is behind a perspective camera
+    pub fn rain_angle_to_pixel(
+        &self,
+        projection: Projection,
+        rain_angle: RainAngle,
+        resolution: Vector2<u32>,
+    ) -> Option<Vector2<f64>> {
+        // transform to local
+        let local = self
+            .inverse_view_matrix
+            .inverse_transform_vector(&rain_angle.to_vector());
+
+        match projection {
+            Projection::Perspective => {
+                if local.z >= 0_f64 {
+                    return None;
+                }
+
+                let z = -(resolution.y as f64) / (2_f64 * (self.fov / 2_f64).tan());
+                let scale = z / local.z;
+                Some(Vector2::new(
+                    local.x * scale + resolution.x as f64 / 2_f64,
+                    resolution.y as f64 / 2_f64 - local.y * scale,
+                ))
+            }
+            Projection::Equirectangular => {
+                // undo making +z forward
+                let vector = Vector3::new(local.z, -local.x, -local.y);
+                let angle = RainAngle::from_vector(vector);
+
+                Some(Vector2::new(
+                    angle.phi().rem_euclid(2_f64 * PI) * resolution.y as f64 / PI,
+                    (1_f64 - angle.theta() / PI) * resolution.y as f64,
+                ))
+            }
+            Projection::CubeMap => {
+                let (face, across, down) =
+                    vector_to_cube_face(Vector3::new(local.x, local.y, -local.z));
+
+                let size = resolution.y as f64;
+                Some(Vector2::new(
+                    (face as f64 + (across + 1_f64) / 2_f64) * size,
+                    (down + 1_f64) / 2_f64 * size,
+                ))
+            }
+            Projection::Fisheye => {
+                let local = local.normalize();
+                let angle = (-local.z).clamp(-1_f64, 1_f64).acos();
+                let distance = angle / (self.fov / 2_f64);
+                let across = local.x.hypot(local.y);
+                let (x, y) = if across > 0_f64 {
+                    (local.x / across * distance, local.y / across * distance)
+                } else {
+                    (0_f64, distance)
+                };
+
+                let radius = resolution.y as f64 / 2_f64;
+                Some(Vector2::new(
+                    x * radius + resolution.x as f64 / 2_f64,
+                    radius - y * radius,
+                ))
+            }
+        }
+    }
+
     pub fn show(&mut self, ui: &mut egui::Ui) {
         egui::Grid::new("camera_grid")
             .num_columns(2)
@@ -164,11 +410,101 @@ impl Camera {
                             .clamp_range(0_f64..=180_f64)
                             .suffix("°"),
                     )
+                    .on_hover_text("The aperture of the dome for fisheye renders, 180° for a fulldome")
                     .changed()
                 {
                     self.fov = fov.to_radians();
                 };
                 ui.end_row();
+
+                ui.label("Observer");
+                egui::ComboBox::from_id_source("observer combo box")
+                    .selected_text(self.observer.to_string())
+                    .show_ui(ui, |ui| {
+                        for observer in Observer::ALL {
+                            ui.selectable_value(&mut self.observer, observer, observer.to_string());
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "A hovering shell observer stays at the radius of the diver instead of \
+                        falling, inside the horizon nothing can hover so the diver's view is shown. \
+                        The zero angular momentum observer hovers too but keeps its frame from being \
+                        dragged around by the spin, for comparing with the diver's twisted view",
+                    );
+                ui.end_row();
+
+                ui.label("Lock to black hole");
+                if ui
+                    .checkbox(&mut self.track_black_hole, "")
+                    .on_hover_text("Keep the black hole centered in view")
+                    .changed()
+                    && self.track_black_hole
+                {
+                    self.aim_at_black_hole();
+                }
+                ui.end_row();
             });
     }
 }
+
+/// Returns the direction on the cube through the point of the face, `across` and `down` go from
+/// -1 at the left and top of the face to 1 at the right and bottom
+///
+/// the faces are the +x, -x, +y, -y, +z and -z faces seen from inside the cube with +y up, turned
+/// like the faces of OpenGL cube maps
+fn cube_face_to_vector(face: usize, across: f64, down: f64) -> Vector3<f64> {
+    match face {
+        0 => Vector3::new(1_f64, -down, -across),
+        1 => Vector3::new(-1_f64, -down, across),
+        2 => Vector3::new(across, 1_f64, down),
+        3 => Vector3::new(across, -1_f64, -down),
+        4 => Vector3::new(across, -down, 1_f64),
+        _ => Vector3::new(-across, -down, -1_f64),
+    }
+}
+
+/// Returns the face of the cube the direction passes through and where like `cube_face_to_vector`
+fn vector_to_cube_face(vector: Vector3<f64>) -> (usize, f64, f64) {
+    let abs = vector.abs();
+    let (face, across, down, major) = if abs.x >= abs.y && abs.x >= abs.z {
+        if vector.x > 0_f64 {
+            (0, -vector.z, -vector.y, abs.x)
+        } else {
+            (1, vector.z, -vector.y, abs.x)
+        }
+    } else if abs.y >= abs.z {
+        if vector.y > 0_f64 {
+            (2, vector.x, vector.z, abs.y)
+        } else {
+            (3, vector.x, -vector.z, abs.y)
+        }
+    } else if vector.z > 0_f64 {
+        (4, vector.x, -vector.y, abs.z)
+    } else {
+        (5, -vector.x, -vector.y, abs.z)
+    };
+    (face, across / major, down / major)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Matrix3;
+
+    #[test]
+    fn drags_stay_orthonormal() {
+        let mut camera = Camera::default();
+        for i in 0..20_000 {
+            // small uneven drags in every direction like a hand on a mouse
+            let delta = egui::vec2((i as f32 * 0.37).sin() * 3_f32, (i as f32 * 0.23).cos());
+            camera.drag_delta(delta, 1_f64);
+        }
+
+        // without renormalizing the error grows by about 1e-17 with every drag
+        let matrix = camera.inverse_view_matrix.matrix();
+        let error = (matrix.transpose() * matrix - Matrix3::identity()).norm();
+        assert!(error < 1e-14, "orthonormality error {error}");
+        assert!((matrix.determinant() - 1_f64).abs() < 1e-14);
+    }
+}
@@ -0,0 +1,127 @@
+// A textured sphere such as a planet or star sitting next to the black hole
+//
+// Positions are in the frame of the map with the black hole at the origin, where the diver starts
+// on the -z axis looking towards +z at the black hole. The companion is static and shines by
+// itself, its texture is an environment wrapped around it by the direction from its center.
+
+use crate::{
+    environment::SharedEnvironment,
+    ray_path::RayPath,
+    spherical_angle::{MapAngle, SphericalAngle},
+    traits::Interpolate,
+    units::Units,
+};
+use image::Rgb;
+use nalgebra::Vector3;
+
+#[derive(Clone, PartialEq)]
+pub struct Companion {
+    // center relative to the black hole
+    pub position: Vector3<f64>,
+    pub radius: f64,
+    // texture wrapped around the sphere
+    pub surface: SharedEnvironment,
+}
+
+impl Default for Companion {
+    fn default() -> Self {
+        Self {
+            // behind the black hole as seen by the diver at the start
+            position: Vector3::new(3_f64, 0_f64, 20_f64),
+            radius: 2_f64,
+            surface: SharedEnvironment::default_grid(),
+        }
+    }
+}
+
+impl Interpolate for Companion {
+    fn interpolate(&self, other: &Self, factor: f32) -> Self {
+        Self {
+            position: Vector3::from_fn(|i, _| {
+                self.position[i].interpolate(&other.position[i], factor)
+            }),
+            radius: self.radius.interpolate(&other.radius, factor),
+            surface: self.surface.clone(),
+        }
+    }
+}
+
+/// Returns the smallest t >= 0 where start + t * direction is on the sphere, None if it misses
+pub fn sphere_intersection(
+    center: Vector3<f64>,
+    radius: f64,
+    start: Vector3<f64>,
+    direction: Vector3<f64>,
+) -> Option<f64> {
+    // solve |start + t * direction - center|^2 = radius^2
+    let offset = start - center;
+    let a = direction.norm_squared();
+    let half_b = offset.dot(&direction);
+    let c = offset.norm_squared() - radius.powi(2);
+
+    let discriminant = half_b.powi(2) - a * c;
+    if a == 0_f64 || discriminant < 0_f64 {
+        return None;
+    }
+
+    let root = discriminant.sqrt();
+    [(-half_b - root) / a, (-half_b + root) / a]
+        .into_iter()
+        .find(|t| *t >= 0_f64)
+}
+
+impl Companion {
+    /// returns the first point where the path hits the companion and the angle the ray swept
+    /// around the black hole before it
+    pub fn hit(&self, path: &RayPath) -> Option<(Vector3<f64>, f64)> {
+        path.segments(self.position.norm() + self.radius)
+            .find_map(|(start, end, swept)| {
+                self.intersect_segment(start, end).map(|hit| (hit, swept))
+            })
+    }
+
+    /// returns the first point on the straight segment between start and end where it hits the
+    /// companion
+    pub fn intersect_segment(
+        &self,
+        start: Vector3<f64>,
+        end: Vector3<f64>,
+    ) -> Option<Vector3<f64>> {
+        sphere_intersection(self.position, self.radius, start, end - start)
+            .filter(|t| *t <= 1_f64)
+            .map(|t| start + t * (end - start))
+    }
+
+    /// samples the texture at the point on the surface
+    pub fn get_pixel(&self, point: Vector3<f64>) -> Rgb<u8> {
+        self.surface
+            .get_pixel(MapAngle::from_vector(point - self.position))
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, units: &Units) {
+        egui::Grid::new("companion_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Position");
+                ui.horizontal(|ui| {
+                    for i in 0..3 {
+                        ui.add(units.length_drag_value(&mut self.position[i], 0.1));
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Center of the companion relative to the black hole, the diver starts on the \
+                    -z axis",
+                );
+                ui.end_row();
+
+                ui.label("Radius");
+                ui.add(
+                    units
+                        .length_drag_value(&mut self.radius, 0.05)
+                        .clamp_range(0.01..=f64::MAX),
+                );
+                ui.end_row();
+            });
+    }
+}
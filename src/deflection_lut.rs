@@ -0,0 +1,215 @@
+// Table of the lensing at the radius of the diver to interpolate instead of lensing every ray
+//
+// Around a single black hole the map angle of a ray only depends on the theta of its rain angle,
+// phi just turns with it, so a table over theta at the radius of the frame holds the whole lensing.
+// Building it lenses a few thousand rays instead of one per pixel. The table starts at the edge of
+// the shadow since nothing inside it reaches the sky, and it is least accurate just outside the
+// edge where the higher order images are squeezed together.
+//
+// Turning the camera doesn't change the table, so the frames of an animation share the tables of
+// the radii they were built at through a cache, and orbits and pans at one radius only lens once.
+
+use crate::{
+    geodesic::LensingBackend,
+    metric::{InteriorSchwarzschild, SharedMetric},
+    render::RenderSettings,
+    scene::{Scene, Spacetime},
+    spherical_angle::{MapAngle, RainAngle, SphericalAngle},
+};
+use egui::mutex::Mutex;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use std::{collections::VecDeque, f64::consts::PI, sync::Arc};
+
+/// Default number of rain angles in the table
+pub const DEFAULT_LUT_SIZE: usize = 4096;
+
+/// Fewest rain angles a table may have
+pub const MIN_LUT_SIZE: usize = 16;
+
+/// Map angles of rays arriving at evenly spaced rain thetas from the edge of the shadow to pi
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeflectionLut {
+    // rain theta of the first entry, the last entry is at pi
+    pub start: f64,
+    // map theta, change in phi and image order of each entry, None where the ray is captured
+    pub entries: Vec<Option<(f64, f64, u32)>>,
+}
+
+impl DeflectionLut {
+    /// returns whether the lensing of the scene can be put in a table, the binary breaks the
+    /// symmetry and the wormhole is traced without lensing through the black hole
+    pub fn supports(scene: &Scene) -> bool {
+        scene.spacetime == Spacetime::BlackHole && !(scene.lensing && scene.binary.is_some())
+    }
+
+    /// lenses the rays of the table with `size` entries at the radius of the diver
+    pub fn build(scene: &Scene, render_settings: RenderSettings, size: usize) -> Self {
+        let size = size.max(MIN_LUT_SIZE);
+
+        // rain angles inside the shadow never reach the environment
+        let start = if scene.lensing {
+            scene.critical_rain_angle()
+        } else {
+            0_f64
+        };
+
+        let mut entries: Vec<Option<(f64, f64, u32)>> = (0..size)
+            .into_par_iter()
+            .map(|i| {
+                let theta_rain = start + (PI - start) * i as f64 / (size - 1) as f64;
+                scene
+                    .lens(RainAngle::new(theta_rain, 0_f64), render_settings)
+                    .map(|(map_angle, order)| (map_angle.theta(), map_angle.phi(), order))
+            })
+            .collect();
+
+        // the map phi of photons with a rain phi of zero is the change in phi, remove the jumps of
+        // 2 pi from wrapping so that it can be interpolated
+        let mut previous_phi: Option<f64> = None;
+        for (_, phi, _) in entries.iter_mut().flatten() {
+            if let Some(previous_phi) = previous_phi {
+                *phi += 2_f64 * PI * ((previous_phi - *phi) / (2_f64 * PI)).round();
+            }
+            previous_phi = Some(*phi);
+        }
+
+        Self { start, entries }
+    }
+
+    /// returns the interpolated map angle and image order of the ray arriving from the rain angle
+    /// like `Scene::lens`, None if either neighbouring entry is captured
+    pub fn lookup(&self, rain_angle: RainAngle) -> Option<(MapAngle, u32)> {
+        let size = self.entries.len();
+        let position = (rain_angle.theta() - self.start) / (PI - self.start) * (size - 1) as f64;
+        if position < 0_f64 || !position.is_finite() {
+            return None;
+        }
+
+        let index = (position as usize).min(size - 2);
+        let factor = (position - index as f64).clamp(0_f64, 1_f64);
+        let (left, right) = (self.entries[index]?, self.entries[index + 1]?);
+        let nearest = if factor < 0.5 { left } else { right };
+
+        // phi jumps by pi where the photons pass over the poles, take the nearest entry there
+        let (theta, phi) = if (right.1 - left.1).abs() > PI / 2_f64 {
+            (nearest.0, nearest.1)
+        } else {
+            (
+                left.0 + (right.0 - left.0) * factor,
+                left.1 + (right.1 - left.1) * factor,
+            )
+        };
+
+        Some((MapAngle::new(theta, rain_angle.phi() + phi), nearest.2))
+    }
+}
+
+/// Everything the table of a scene depends on, scenes with the same key have the same table
+#[derive(Clone, PartialEq)]
+struct LutKey {
+    // radius of the diver in units of the mass of the black hole
+    radius: f64,
+    spin: f64,
+    charge: f64,
+    custom_metric: Option<SharedMetric>,
+    transparent_star: Option<InteriorSchwarzschild>,
+    lensing: bool,
+    aberration: bool,
+    max_half_orbits: u32,
+    lensing_backend: LensingBackend,
+    size: usize,
+}
+
+impl LutKey {
+    fn new(scene: &Scene, render_settings: RenderSettings, size: usize) -> Self {
+        Self {
+            radius: scene.scaled_diver().position(),
+            spin: scene.active_spin(),
+            charge: scene.active_charge(),
+            custom_metric: scene.active_custom_metric().cloned(),
+            transparent_star: scene.active_transparent_star(),
+            lensing: scene.lensing,
+            aberration: scene.has_aberration(),
+            max_half_orbits: render_settings.max_half_orbits,
+            lensing_backend: render_settings.lensing_backend,
+            size,
+        }
+    }
+}
+
+/// Table built for a key
+type CachedLut = (LutKey, Arc<DeflectionLut>);
+
+/// Tables of the latest radii rendered, shared between the frames of an animation
+#[derive(Clone, Default)]
+pub struct LutCache {
+    // most recently built last
+    tables: Arc<Mutex<VecDeque<CachedLut>>>,
+}
+
+impl LutCache {
+    // frames in flight and shutter samples render a few radii at once
+    const CAPACITY: usize = 64;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// returns the table of the scene with `size` entries, only building it if none of the
+    /// cached tables has the same lensing
+    pub fn get(
+        &self,
+        scene: &Scene,
+        render_settings: RenderSettings,
+        size: usize,
+    ) -> Arc<DeflectionLut> {
+        let key = LutKey::new(scene, render_settings, size);
+        if let Some((_, table)) = self.tables.lock().iter().find(|(cached, _)| *cached == key) {
+            return table.clone();
+        }
+
+        // build without holding the lock so that frames at other radii don't wait, two frames at
+        // the same new radius may both build it
+        let table = Arc::new(DeflectionLut::build(scene, render_settings, size));
+        let mut tables = self.tables.lock();
+        tables.push_back((key, table.clone()));
+        if tables.len() > Self::CAPACITY {
+            tables.pop_front();
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diver::Diver;
+
+    #[test]
+    fn lookup_matches_lensing() {
+        let render_settings = RenderSettings::default();
+        for r in [3_f64, 10_f64, 100_f64] {
+            let scene = Scene {
+                diver: Diver::new(r, 0_f64),
+                ..Scene::test_pattern()
+            };
+            let lut = DeflectionLut::build(&scene, render_settings, DEFAULT_LUT_SIZE);
+
+            // just outside the edge of the shadow the images are squeezed together faster than the
+            // table can follow
+            let start = lut.start + 0.05_f64;
+            for i in 0..=100 {
+                let rain_angle = RainAngle::new(start + (PI - start) * i as f64 / 100_f64, 1_f64);
+                let (expected, expected_order) = scene.lens(rain_angle, render_settings).unwrap();
+                let (map_angle, order) = lut.lookup(rain_angle).unwrap();
+
+                let error = map_angle.to_vector().angle(&expected.to_vector());
+                assert!(
+                    error < 1e-4,
+                    "r {r} rain angle {rain_angle:?}: error {error}"
+                );
+                assert_eq!(order, expected_order);
+            }
+        }
+    }
+}
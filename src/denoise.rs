@@ -0,0 +1,100 @@
+// Smooths the noise of renders with few samples per pixel, like quick drafts without super sampling
+//
+// A bilateral filter: every pixel becomes the average of its neighbours within RADIUS pixels,
+// weighted by how close they are and how similar their colors are, so sparkling stars and aliased
+// grid lines are smoothed while the sharp edge of the shadow and the photon ring stay sharp. The
+// strength is how far apart in gamma encoded value colors can be and still be averaged together.
+//
+// The filter runs on the gamma encoded image after grading, regions of the image are rendered with
+// a margin of RADIUS pixels so that tiles and strips are filtered across their edges. OpenEXR
+// renders keep the raw samples for compositors to denoise with their own tools.
+
+use image::{Rgb, Rgb32FImage};
+use rayon::prelude::{ParallelBridge, ParallelIterator};
+
+/// Distance in pixels of the furthest neighbours averaged into a pixel
+pub const RADIUS: u32 = 2;
+
+/// Strength new denoised renders start with
+pub const DEFAULT_STRENGTH: f32 = 0.1;
+
+/// Standard deviation of the falloff of the weights with distance in pixels
+const SPATIAL_SIGMA: f32 = 1.5;
+
+/// Returns the image with its noise smoothed by colors up to about `strength` apart
+pub fn denoise(image: &Rgb32FImage, strength: f32) -> Rgb32FImage {
+    let (width, height) = image.dimensions();
+    let spatial = -1_f32 / (2_f32 * SPATIAL_SIGMA.powi(2));
+    let range = -1_f32 / (2_f32 * strength.max(1e-4).powi(2));
+
+    let mut buf = Rgb32FImage::new(width, height);
+    buf.enumerate_pixels_mut()
+        .par_bridge()
+        .for_each(|(x, y, pixel)| {
+            let center = image.get_pixel(x, y);
+            let mut sum = [0_f32; 3];
+            let mut total_weight = 0_f32;
+
+            // neighbours past the edge of the image are left out
+            for ny in y.saturating_sub(RADIUS)..(y + RADIUS + 1).min(height) {
+                for nx in x.saturating_sub(RADIUS)..(x + RADIUS + 1).min(width) {
+                    let neighbour = image.get_pixel(nx, ny);
+                    let distance = (nx as f32 - x as f32).powi(2) + (ny as f32 - y as f32).powi(2);
+                    let difference: f32 = (0..3).map(|i| (neighbour[i] - center[i]).powi(2)).sum();
+
+                    let weight = (distance * spatial + difference * range).exp();
+                    (0..3).for_each(|i| sum[i] += neighbour[i] * weight);
+                    total_weight += weight;
+                }
+            }
+
+            // the pixel itself always has a weight of one
+            *pixel = Rgb(sum.map(|channel| channel / total_weight));
+        });
+
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smooths_noise_and_keeps_edges() {
+        // dark and light halves with the same noise in both
+        let noise = |x: u32, y: u32| ((x * 7 + y * 13) % 11) as f32 / 10_f32 - 0.5_f32;
+        let image = Rgb32FImage::from_fn(32, 32, |x, y| {
+            let value = if x < 16 { 0.2_f32 } else { 0.8_f32 };
+            Rgb([value + 0.05_f32 * noise(x, y); 3])
+        });
+        let denoised = denoise(&image, DEFAULT_STRENGTH);
+
+        // spread of the pixels of a half away from the edge and the image border
+        let spread = |image: &Rgb32FImage, columns: std::ops::Range<u32>| {
+            let values: Vec<f32> = columns
+                .flat_map(|x| (RADIUS..32 - RADIUS).map(move |y| (x, y)))
+                .map(|(x, y)| image.get_pixel(x, y)[0])
+                .collect();
+            let mean = values.iter().sum::<f32>() / values.len() as f32;
+            values
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f32>()
+                / values.len() as f32
+        };
+        for half in [RADIUS..16 - RADIUS, 16 + RADIUS..32 - RADIUS] {
+            let (before, after) = (spread(&image, half.clone()), spread(&denoised, half));
+            assert!(after < 0.5_f32 * before, "spread {after} from {before}");
+        }
+
+        // the pixels on either side of the edge stay on their side
+        for y in 0..32 {
+            let (dark, light) = (denoised.get_pixel(15, y)[0], denoised.get_pixel(16, y)[0]);
+            assert!((dark - 0.2_f32).abs() < 0.05_f32, "dark {dark} at row {y}");
+            assert!(
+                (light - 0.8_f32).abs() < 0.05_f32,
+                "light {light} at row {y}"
+            );
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::{
+    app::BHDiver,
+    math::{total_deflection, weak_field_deflection},
+};
+
+/// Impact parameters the deflection is compared at, from the weak field down to the photon sphere
+const DEFLECTION_IMPACT_PARAMETERS: [f64; 8] = [
+    10000_f64, 1000_f64, 300_f64, 100_f64, 30_f64, 10_f64, 7_f64, 5.5,
+];
+
+pub struct Diagnostics;
+
+impl Diagnostics {
+    pub fn build(ui: &mut egui::Ui, _app: &mut BHDiver) {
+        ui.heading("Deflection");
+        ui.label("Total deflection compared to the weak field limit 4M/b");
+
+        egui::Grid::new("deflection_grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("b");
+                ui.label("Deflection");
+                ui.label("4M/b");
+                ui.label("Relative difference");
+                ui.end_row();
+
+                for b in DEFLECTION_IMPACT_PARAMETERS {
+                    let weak_field = weak_field_deflection(b);
+
+                    ui.label(format!("{} M", b));
+                    if let Some(deflection) = total_deflection(b) {
+                        ui.label(format!("{:.3e}", deflection));
+                        ui.label(format!("{:.3e}", weak_field));
+                        ui.label(format!(
+                            "{:.2}%",
+                            100_f64 * (deflection / weak_field - 1_f64)
+                        ));
+                    } else {
+                        ui.label("Captured");
+                        ui.label(format!("{:.3e}", weak_field));
+                        ui.label("");
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+}
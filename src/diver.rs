@@ -1,17 +1,75 @@
-use crate::traits::Interpolate;
+use crate::{
+    math::{radial_fall_radius, radial_fall_time, rain_frame_velocity, tortoise},
+    traits::Interpolate,
+    units::Units,
+};
+use nalgebra::Vector3;
+use std::f64::consts::PI;
+
+/// Smallest radius of a circular orbit, closer orbits would be faster than light
+const MIN_ORBIT_RADIUS: f64 = 3.05;
+
+/// Smallest radius the diver can hover at, the acceleration needed grows without bound towards the
+/// horizon
+const MIN_HOVER_RADIUS: f64 = 2.05;
+
+/// Steps the advanced time is integrated over the proper time of the diver in
+const ADVANCED_TIME_STEPS: usize = 256;
+
+/// Fewest turns the ISCO spiral can make before reaching the singularity
+const MIN_SPIRAL_TURNS: f64 = 0.01;
+
+/// Path the diver takes around the black hole, the initial radius is used as the orbit radius
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Trajectory {
+    // falling straight in with the energy of the diver, from rest at infinity by default
+    #[default]
+    RadialFall,
+    // orbiting forever at a fixed radius
+    CircularOrbit,
+    // spiralling in from the innermost stable circular orbit at 6M
+    IscoSpiral,
+    // held at a fixed radius by a rocket, seeing what static observers see
+    Hover,
+}
+
+impl ToString for Trajectory {
+    fn to_string(&self) -> String {
+        match self {
+            Trajectory::RadialFall => "Radial fall".to_owned(),
+            Trajectory::CircularOrbit => "Circular orbit".to_owned(),
+            Trajectory::IscoSpiral => "Spiral from ISCO".to_owned(),
+            Trajectory::Hover => "Hover".to_owned(),
+        }
+    }
+}
 
 #[derive(Clone, Copy, PartialEq)]
 pub struct Diver {
     rinit: f64,
+    // proper time of the diver since the start of its trajectory, so interpolating it between
+    // keyframes plays back at the rate the diver experiences
     time: f64,
+    energy: f64,
+    // whether a radially falling diver starts moving away from the black hole
+    pub outward: bool,
+    pub trajectory: Trajectory,
+    // turns the ISCO spiral makes before reaching the singularity, more start closer to the ISCO
+    pub spiral_turns: f64,
 }
 
 impl Interpolate for Diver {
     fn interpolate(&self, other: &Self, factor: f32) -> Self {
-        Diver::new(
-            self.rinit.interpolate(&other.rinit, factor),
-            self.time.interpolate(&other.time, factor),
-        )
+        Diver {
+            energy: self.energy.interpolate(&other.energy, factor),
+            outward: self.outward,
+            trajectory: self.trajectory,
+            spiral_turns: self.spiral_turns,
+            ..Diver::new(
+                self.rinit.interpolate(&other.rinit, factor),
+                self.time.interpolate(&other.time, factor),
+            )
+        }
     }
 }
 
@@ -20,13 +78,42 @@ impl Default for Diver {
         Self {
             rinit: 10_f64,
             time: 0_f64,
+            energy: 1_f64,
+            outward: false,
+            trajectory: Trajectory::RadialFall,
+            spiral_turns: 3_f64,
         }
     }
 }
 
+/// Proper time along the ISCO spiral up to a constant, where the angle along it is negative and
+/// reaches the singularity at zero
+///
+/// along the spiral 1/r = 1/6 + 2/phi^2 and dtau/dphi = r^2 / sqrt(12)
+fn spiral_time(phi: f64) -> f64 {
+    let root = 12_f64.sqrt();
+    6_f64
+        * 3_f64.sqrt()
+        * (phi - 1.5 * root * (phi / root).atan() + 6_f64 * phi / (phi.powi(2) + 12_f64))
+}
+
 impl Diver {
     pub fn new(rinit: f64, time: f64) -> Self {
-        Diver { rinit, time }
+        Diver {
+            rinit,
+            time,
+            ..Default::default()
+        }
+    }
+
+    /// returns the diver in units of a black hole with this mass, the lengths and times of the
+    /// diver are in units of a black hole with unit mass
+    pub fn rescaled(&self, mass: f64) -> Self {
+        Diver {
+            rinit: self.rinit / mass,
+            time: self.time / mass,
+            ..*self
+        }
     }
 
     pub fn initial_radius(&self) -> f64 {
@@ -41,6 +128,43 @@ impl Diver {
         self.rinit = radius.max(0_f64);
     }
 
+    /// returns the energy per unit mass of a radially falling diver, one for a diver falling with
+    /// the rain
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    pub fn set_energy(&mut self, energy: f64) {
+        self.energy = energy.max(0_f64);
+    }
+
+    /// returns whether the diver falls with the rain, the frame the lensing is calculated in
+    pub fn falls_with_rain(&self) -> bool {
+        self.trajectory == Trajectory::RadialFall && self.energy == 1_f64 && !self.outward
+    }
+
+    /// returns the radius a radially falling diver with less energy than the rain is at rest at
+    fn rest_radius(&self) -> f64 {
+        if self.energy < 1_f64 {
+            2_f64 / (1_f64 - self.energy.powi(2))
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// returns the radius a radially falling diver starts from, bound divers can't start beyond
+    /// their radius of rest
+    fn start_radius(&self) -> f64 {
+        self.rinit.min(self.rest_radius())
+    }
+
+    /// returns whether a radially falling diver starts moving outward, which it can only do
+    /// outside the horizon
+    fn starts_outward(&self) -> bool {
+        self.outward && self.start_radius() > 2_f64
+    }
+
+    /// returns the proper time of the diver since the start of its trajectory
     pub fn time(&self) -> f64 {
         self.time
     }
@@ -57,43 +181,401 @@ impl Diver {
         self.time = time.min(self.final_time());
     }
 
+    /// sets the time like `set_time` around a black hole with this mass
+    pub fn set_time_for_mass(&mut self, time: f64, mass: f64) {
+        self.time = time.min(self.final_time_for_mass(mass));
+    }
+
+    /// returns the times bounding the part of the trajectory the diver is on, along which its
+    /// radius only rises or only falls, and whether it rises
+    fn monotonic_span(&self) -> (f64, f64, bool) {
+        if self.trajectory == Trajectory::RadialFall && self.starts_outward() && self.energy < 1_f64
+        {
+            // bound divers rise to their radius of rest and fall back
+            let rise_time = radial_fall_time(self.rest_radius(), self.energy)
+                - radial_fall_time(self.start_radius(), self.energy);
+            if self.time < rise_time {
+                (f64::NEG_INFINITY, rise_time, true)
+            } else {
+                (rise_time, self.final_time(), false)
+            }
+        } else {
+            (f64::NEG_INFINITY, self.final_time(), self.starts_outward())
+        }
+    }
+
+    /// sets the time to when the diver is at the radius like `set_position` around a black hole
+    /// with this mass
+    pub fn set_position_for_mass(&mut self, radius: f64, mass: f64) {
+        let mut scaled = self.rescaled(mass);
+        scaled.set_position(radius / mass);
+        self.rinit = scaled.rinit * mass;
+        self.time = scaled.time * mass;
+    }
+
+    /// sets the time to when the diver is at the radius, staying on the rising or falling part of
+    /// the trajectory it is on, circular orbits never change radius and hovering divers are moved
+    /// to it instead
+    pub fn set_position(&mut self, radius: f64) {
+        match self.trajectory {
+            Trajectory::CircularOrbit => return,
+            Trajectory::Hover => {
+                self.rinit = radius.max(MIN_HOVER_RADIUS);
+                return;
+            }
+            _ => (),
+        }
+
+        let (min_time, max_time, rising) = self.monotonic_span();
+        let radius_at = |time: f64| Diver { time, ..*self }.position();
+        let before = |time: f64| {
+            if rising {
+                radius_at(time) < radius
+            } else {
+                radius_at(time) > radius
+            }
+        };
+
+        // widen the bracket around the current time until it holds the radius
+        let (mut low, mut high) = (self.time.min(max_time), self.time.max(min_time));
+        let mut step = 1_f64;
+        for _ in 0..64 {
+            if before(low) || low <= min_time {
+                break;
+            }
+            low = (low - step).max(min_time);
+            step *= 2_f64;
+        }
+        step = 1_f64;
+        for _ in 0..64 {
+            if !before(high) || high >= max_time {
+                break;
+            }
+            high = (high + step).min(max_time);
+            step *= 2_f64;
+        }
+
+        for _ in 0..64 {
+            let mid = (low + high) / 2_f64;
+            if before(mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        self.set_time((low + high) / 2_f64);
+    }
+
     pub fn position(&self) -> f64 {
-        (self.rinit.powf(3_f64 / 2_f64) - 3_f64 * self.time / 2_f64.sqrt())
-            .powf(2_f64 / 3_f64)
-            .max(0_f64)
+        match self.trajectory {
+            Trajectory::RadialFall => {
+                let start_time = radial_fall_time(self.start_radius(), self.energy);
+                if !self.starts_outward() {
+                    radial_fall_radius(start_time - self.time, self.energy)
+                } else if self.energy >= 1_f64 {
+                    // unbound divers escape to infinity
+                    radial_fall_radius(start_time + self.time, self.energy)
+                } else {
+                    // bound divers rise to their radius of rest and fall back
+                    let rest_time = radial_fall_time(self.rest_radius(), self.energy);
+                    let remaining = 2_f64 * rest_time - start_time - self.time;
+                    if remaining > rest_time {
+                        radial_fall_radius(2_f64 * rest_time - remaining, self.energy)
+                    } else {
+                        radial_fall_radius(remaining, self.energy)
+                    }
+                }
+            }
+            Trajectory::CircularOrbit => self.rinit.max(MIN_ORBIT_RADIUS),
+            Trajectory::IscoSpiral => 1_f64 / (1_f64 / 6_f64 + 2_f64 / self.spiral_angle().powi(2)),
+            Trajectory::Hover => self.rinit.max(MIN_HOVER_RADIUS),
+        }
+    }
+
+    /// returns the angle along the ISCO spiral where the diver starts
+    fn spiral_start_angle(&self) -> f64 {
+        -2_f64 * PI * self.spiral_turns.max(MIN_SPIRAL_TURNS)
+    }
+
+    /// returns the angle along the ISCO spiral at the current time
+    fn spiral_angle(&self) -> f64 {
+        let start_angle = self.spiral_start_angle();
+        let target = spiral_time(start_angle) + self.time;
+
+        // the time increases with the angle so bisect for it
+        let mut low = start_angle;
+        while spiral_time(low) > target {
+            low *= 2_f64;
+        }
+        let mut high = 0_f64;
+        for _ in 0..64 {
+            let mid = (low + high) / 2_f64;
+            if spiral_time(mid) < target {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        (low + high) / 2_f64
     }
 
+    /// returns the angle the diver has travelled around the black hole since the start
+    pub fn orbit_angle(&self) -> f64 {
+        match self.trajectory {
+            Trajectory::RadialFall | Trajectory::Hover => 0_f64,
+            Trajectory::CircularOrbit => {
+                let r = self.position();
+                self.time / (r * (r - 3_f64).sqrt())
+            }
+            Trajectory::IscoSpiral => self.spiral_angle() - self.spiral_start_angle(),
+        }
+    }
+
+    /// returns the infall speed of the rain through the static frame at the position of the diver
     pub fn speed(&self) -> f64 {
         (2_f64 / self.position()).sqrt()
     }
 
+    /// returns the energy and angular momentum per unit mass of the diver and its radial velocity
+    /// dr/dtau
+    pub fn orbital_quantities(&self) -> (f64, f64, f64) {
+        let r = self.position();
+
+        match self.trajectory {
+            Trajectory::RadialFall => {
+                let speed = (self.energy.powi(2) - 1_f64 + 2_f64 / r).max(0_f64).sqrt();
+                let rising = self.starts_outward()
+                    && (self.energy >= 1_f64
+                        || self.time
+                            < radial_fall_time(self.rest_radius(), self.energy)
+                                - radial_fall_time(self.start_radius(), self.energy));
+                (self.energy, 0_f64, if rising { speed } else { -speed })
+            }
+            Trajectory::CircularOrbit => (
+                (1_f64 - 2_f64 / r) / (1_f64 - 3_f64 / r).sqrt(),
+                r.sqrt() / (1_f64 - 3_f64 / r).sqrt(),
+                0_f64,
+            ),
+            Trajectory::IscoSpiral => (
+                (8_f64 / 9_f64).sqrt(),
+                12_f64.sqrt(),
+                -24_f64.sqrt() * (1_f64 / r - 1_f64 / 6_f64).max(0_f64).powf(1.5),
+            ),
+            Trajectory::Hover => ((1_f64 - 2_f64 / r).sqrt(), 0_f64, 0_f64),
+        }
+    }
+
+    /// returns the rate dv/dtau the advanced time v = t + r* of the diver passes at, which stays
+    /// finite across the horizon
+    pub fn advanced_time_rate(&self) -> f64 {
+        let r = self.position();
+        let (energy, angular_momentum, radial_velocity) = self.orbital_quantities();
+        (1_f64 + (angular_momentum / r).powi(2)) / (energy - radial_velocity)
+    }
+
+    /// returns the advanced time v = t + r* of the diver, with the coordinate time t zero where
+    /// its trajectory starts
+    pub fn advanced_time(&self) -> f64 {
+        // the midpoint rule keeps off the singularity at the end of the trajectory
+        let step = self.time / ADVANCED_TIME_STEPS as f64;
+        let advance: f64 = (0..ADVANCED_TIME_STEPS)
+            .map(|i| {
+                let time = (i as f64 + 0.5) * step;
+                Diver { time, ..*self }.advanced_time_rate() * step
+            })
+            .sum();
+        let start = Diver {
+            time: 0_f64,
+            ..*self
+        };
+        tortoise(start.position()) + advance
+    }
+
+    /// returns the proper acceleration the rocket of a hovering diver has to give it, None if the
+    /// diver isn't hovering
+    ///
+    /// a static observer accelerates at M / (r^2 sqrt(1 - 2M/r)) away from the black hole
+    pub fn proper_acceleration(&self) -> Option<f64> {
+        (self.trajectory == Trajectory::Hover).then(|| {
+            let r = self.position();
+            1_f64 / (r.powi(2) * (1_f64 - 2_f64 / r).sqrt())
+        })
+    }
+
+    /// returns the proper acceleration like `proper_acceleration` around a black hole with this
+    /// mass
+    pub fn proper_acceleration_for_mass(&self, mass: f64) -> Option<f64> {
+        self.rescaled(mass)
+            .proper_acceleration()
+            .map(|acceleration| acceleration / mass)
+    }
+
+    /// returns the velocity of the diver through the rain frame at its position, with z towards
+    /// the black hole and x along the orbit
+    pub fn velocity(&self) -> Vector3<f64> {
+        if self.trajectory == Trajectory::RadialFall && self.falls_with_rain() {
+            return Vector3::zeros();
+        }
+
+        let r = self.position();
+        let (energy, angular_momentum, radial_velocity) = self.orbital_quantities();
+        let (along_orbit, inward) =
+            rain_frame_velocity(r, energy, angular_momentum, radial_velocity);
+        Vector3::new(along_orbit, 0_f64, inward)
+    }
+
+    /// returns the time the diver hits the singularity of a black hole with this mass
+    pub fn final_time_for_mass(&self, mass: f64) -> f64 {
+        mass * self.rescaled(mass).final_time()
+    }
+
     pub fn final_time(&self) -> f64 {
-        2_f64.sqrt() * self.rinit.powf(3_f64 / 2_f64) / 3_f64
+        match self.trajectory {
+            Trajectory::RadialFall => {
+                let start_time = radial_fall_time(self.start_radius(), self.energy);
+                if !self.starts_outward() {
+                    start_time
+                } else if self.energy >= 1_f64 {
+                    f64::INFINITY
+                } else {
+                    2_f64 * radial_fall_time(self.rest_radius(), self.energy) - start_time
+                }
+            }
+            Trajectory::CircularOrbit | Trajectory::Hover => f64::INFINITY,
+            Trajectory::IscoSpiral => spiral_time(0_f64) - spiral_time(self.spiral_start_angle()),
+        }
     }
 
     pub fn remaining_time(&self) -> f64 {
-        2_f64.sqrt() * self.position().powf(3_f64 / 2_f64) / 3_f64
+        self.final_time() - self.time
     }
 
-    pub fn show(&mut self, ui: &mut egui::Ui) {
+    /// shows the settings of the diver falling into a black hole with this mass
+    pub fn show(&mut self, ui: &mut egui::Ui, units: &Units, mass: f64) {
         egui::Grid::new("diver_grid").num_columns(2).show(ui, |ui| {
-            ui.label("Initial distance");
-            ui.add(
-                egui::DragValue::new(&mut self.rinit)
-                    .clamp_range(0_f64..=f64::MAX)
-                    .speed(0.1)
-                    .suffix(" M"),
-            );
+            ui.label("Trajectory");
+            egui::ComboBox::from_id_source("trajectory combo box")
+                .selected_text(self.trajectory.to_string())
+                .show_ui(ui, |ui| {
+                    for trajectory in [
+                        Trajectory::RadialFall,
+                        Trajectory::CircularOrbit,
+                        Trajectory::IscoSpiral,
+                        Trajectory::Hover,
+                    ] {
+                        ui.selectable_value(
+                            &mut self.trajectory,
+                            trajectory,
+                            trajectory.to_string(),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text("Orbits ignore the spin of the black hole");
+            ui.end_row();
+
+            match self.trajectory {
+                Trajectory::RadialFall => {
+                    ui.label("Initial distance");
+                    ui.add(
+                        units
+                            .length_drag_value(&mut self.rinit, 0.1)
+                            .clamp_range(0_f64..=f64::MAX),
+                    );
+                    ui.end_row();
+
+                    ui.label("Energy");
+                    ui.add(
+                        egui::DragValue::new(&mut self.energy)
+                            .speed(0.01)
+                            .clamp_range(0_f64..=f64::MAX),
+                    )
+                    .on_hover_text(
+                        "Energy per unit mass\n\
+                        1: falling from rest at infinity with the rain\n\
+                        Below 1: dropped from rest at 2M / (1 - E²)\n\
+                        Above 1: thrown inward from infinity",
+                    );
+                    ui.end_row();
+
+                    ui.label("Thrown outward");
+                    ui.checkbox(&mut self.outward, "").on_hover_text(
+                        "Start moving away from the black hole, outside the horizon\n\
+                        With an energy of 1 or more the diver escapes to infinity, an energy of 1 \
+                        plays the fall with the rain in reverse",
+                    );
+                    ui.end_row();
+                }
+                Trajectory::CircularOrbit => {
+                    ui.label("Orbit radius");
+                    ui.add(
+                        units
+                            .length_drag_value(&mut self.rinit, 0.1)
+                            .clamp_range(MIN_ORBIT_RADIUS..=f64::MAX),
+                    )
+                    .on_hover_text("Orbits inside 6M are unstable");
+                    ui.end_row();
+                }
+                Trajectory::IscoSpiral => {
+                    ui.label("Turns");
+                    ui.add(
+                        egui::DragValue::new(&mut self.spiral_turns)
+                            .speed(0.05)
+                            .clamp_range(MIN_SPIRAL_TURNS..=100_f64),
+                    )
+                    .on_hover_text(
+                        "Turns around the black hole before reaching the singularity, the spiral \
+                        starts closer to the ISCO with more turns",
+                    );
+                    ui.end_row();
+                }
+                Trajectory::Hover => {
+                    ui.label("Hover radius");
+                    ui.add(
+                        units
+                            .length_drag_value(&mut self.rinit, 0.1)
+                            .clamp_range(MIN_HOVER_RADIUS * mass..=f64::MAX),
+                    )
+                    .on_hover_text(
+                        "Animate the radius to lower the diver on its rocket instead of letting it \
+                        fall",
+                    );
+                    ui.end_row();
+
+                    if let Some(acceleration) = self.proper_acceleration_for_mass(mass) {
+                        ui.label("Proper acceleration");
+                        ui.label(units.describe_acceleration(acceleration))
+                            .on_hover_text("Acceleration the rocket has to keep up to hover");
+                        ui.end_row();
+                    }
+                }
+            }
+
+            ui.label("Radius");
+            ui.label(units.describe_length(mass * self.rescaled(mass).position()))
+                .on_hover_text("Current distance of the diver from the black hole");
+            ui.end_row();
 
+            ui.label("Fall duration");
+            let final_time = self.final_time_for_mass(mass);
+            if final_time.is_finite() {
+                ui.label(units.describe_time(final_time))
+                    .on_hover_text("Proper time from the start until the diver hits the singularity");
+            } else {
+                ui.label("Never falls in");
+            }
             ui.end_row();
 
-            let final_time = self.final_time();
-            ui.label("Time");
+            let final_time = units.time_to_display(final_time);
+            ui.label("Proper time");
             ui.add(
-                egui::DragValue::new(self.time_ref())
-                    .clamp_range(f64::MIN..=final_time)
-                    .speed(0.1)
-                    .suffix(" M"),
+                units
+                    .time_drag_value(self.time_ref(), 0.1)
+                    .clamp_range(f64::MIN..=final_time),
+            )
+            .on_hover_text(
+                "Time experienced by the diver since the start, keyframes driving the diver by time \
+                interpolate it so playback follows what the diver experiences",
             );
             ui.end_row();
         });
@@ -0,0 +1,104 @@
+// Finds the ring on the sky of the diver where light has been bent through a given angle
+//
+// A photon arriving at rain angle theta has swept an angle around the black hole that grows from
+// zero looking straight away from it to infinity at the edge of the shadow. The light from a
+// point on the sky sweeps pi minus its angle from the black hole, plus whole turns, so the light
+// of the point straight behind the black hole is spread over the rings sweeping pi, 3pi, ... and
+// that of the point straight behind the diver over the rings sweeping 2pi, 4pi, ...
+
+use crate::{app::BHDiver, scene::Scene, spherical_angle::RainAngle};
+use std::f64::consts::PI;
+
+/// Largest angle the light can be chosen to sweep, in turns it would take to bend it
+const MAX_TURNS: f64 = 5_f64;
+
+pub struct EinsteinRing {
+    // angle the light of the ring swept around the black hole
+    pub deflection: f64,
+    // whether to draw the ring over the preview
+    pub mark: bool,
+}
+
+impl Default for EinsteinRing {
+    fn default() -> Self {
+        Self {
+            // the direct Einstein ring of the point behind the black hole
+            deflection: PI,
+            mark: false,
+        }
+    }
+}
+
+impl EinsteinRing {
+    /// returns view angles around the ring in the scene if it is marked and can be found
+    pub fn outline(&self, scene: &Scene, n_points: usize) -> Option<Vec<RainAngle>> {
+        if !self.mark {
+            return None;
+        }
+        scene
+            .deflection_ring_angle(self.deflection)
+            .map(|angle| scene.ring_outline(angle, n_points))
+    }
+
+    pub fn build(ui: &mut egui::Ui, app: &mut BHDiver) {
+        let ring = &mut app.einstein_ring;
+
+        egui::Grid::new("einstein_ring_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Swept angle");
+                let mut degrees = ring.deflection.to_degrees();
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut degrees)
+                            .clamp_range(0_f64..=MAX_TURNS * 360_f64)
+                            .speed(1_f64)
+                            .suffix("°"),
+                    )
+                    .on_hover_text("Angle the light of the ring swept around the black hole")
+                    .changed()
+                {
+                    ring.deflection = degrees.to_radians();
+                }
+                ui.end_row();
+
+                ui.label("Images");
+                ui.horizontal(|ui| {
+                    for (order, label) in ["1st", "2nd", "3rd"].into_iter().enumerate() {
+                        let swept = (2 * order + 1) as f64 * PI;
+                        ui.selectable_value(&mut ring.deflection, swept, label)
+                            .on_hover_text(format!(
+                                "Ring of the point straight behind the black hole, the light \
+                                sweeps {:.0}°",
+                                swept.to_degrees()
+                            ));
+                    }
+                    ui.selectable_value(&mut ring.deflection, 2_f64 * PI, "Behind")
+                        .on_hover_text(
+                            "Ring of the point straight behind the diver, the light turns \
+                            around the black hole once",
+                        );
+                });
+                ui.end_row();
+
+                ui.label("Ring");
+                let scene = app.timeline.get_current_scene();
+                match scene.deflection_ring_angle(ring.deflection) {
+                    Some(angle) => {
+                        ui.label(format!("{:.3}° from the black hole", angle.to_degrees()))
+                            .on_hover_text(
+                                "Angular radius of the ring around the black hole as the diver \
+                                sees it",
+                            );
+                    }
+                    None => {
+                        ui.weak("Only found around a lensing black hole without spin or charge");
+                    }
+                }
+                ui.end_row();
+            });
+
+        ui.checkbox(&mut ring.mark, "Mark on preview")
+            .on_hover_text("Draw the ring over the preview");
+    }
+}
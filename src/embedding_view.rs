@@ -0,0 +1,246 @@
+// Draws the diver on Flamm's paraboloid, the embedding of the equatorial plane of the black hole
+//
+// The curved space outside the horizon at a fixed Schwarzschild time has the same distances as the
+// surface z = 2 sqrt(2M (r - 2M)) in flat space, so circles of circumference 2 pi r are further
+// apart the closer they are to the horizon. The plane drawn is the one the diver falls or orbits
+// in. The field of view of the camera is laid flat onto the surface as straight lines leaving the
+// diver, ignoring the bending of light, so it only shows which way the diver is looking.
+
+use crate::{
+    app::BHDiver,
+    scene::{Scene, Spacetime},
+    units::Units,
+};
+use nalgebra::{Vector2, Vector3};
+use std::f64::consts::PI;
+
+/// Circles and radial lines the surface is drawn with
+const CIRCLES: usize = 12;
+const SPOKES: usize = 24;
+
+/// Points along each circle and spoke
+const SEGMENTS: usize = 64;
+
+/// Smallest distance from the black hole shown in units of its mass
+const MIN_EXTENT: f64 = 10_f64;
+
+/// Fraction of the distance to the edge of the surface the field of view is drawn out to
+const FRUSTUM_LENGTH: f64 = 0.6;
+
+const MARKER_SIZE: f32 = 4_f32;
+
+/// Returns the height of the surface at the radius in units of the mass, inside the horizon the
+/// surface ends at its throat
+fn flamm_height(r: f64) -> f64 {
+    2_f64 * (2_f64 * (r - 2_f64).max(0_f64)).sqrt()
+}
+
+/// Returns the point on the surface above the point of the plane
+fn lift(point: Vector2<f64>) -> Vector3<f64> {
+    Vector3::new(point.x, point.y, flamm_height(point.norm()))
+}
+
+pub struct EmbeddingView;
+
+impl EmbeddingView {
+    pub fn build(ui: &mut egui::Ui, app: &mut BHDiver) {
+        // the direction the surface is seen from is kept in temporary ui memory
+        let view_id = egui::Id::new("embedding view angles");
+        let mut view: (f64, f64) =
+            ui.data_mut(|reader| reader.get_temp(view_id).unwrap_or((0_f64, 0.5)));
+
+        let scene = app.timeline.get_current_scene();
+        if scene.spacetime == Spacetime::Wormhole {
+            ui.label("The embedding is only drawn for black holes");
+            return;
+        }
+        ui.label("Drag to turn the surface");
+
+        let aspect = app.renderer.aspect_ratio() as f64;
+        Self::show_surface(ui, &scene, aspect, &app.settings.units, &mut view);
+
+        ui.data_mut(|reader| reader.insert_temp(view_id, view));
+    }
+
+    /// returns the corners of the field of view of the camera in the plane of the embedding as
+    /// unit vectors, None for any that lie perpendicular to it
+    fn frustum(scene: &Scene, aspect: f64) -> Vec<Option<Vector2<f64>>> {
+        let camera = &scene.camera;
+        let height = (camera.fov / 2_f64).tan();
+        let width = height * aspect;
+
+        [
+            (-1_f64, -1_f64),
+            (1_f64, -1_f64),
+            (1_f64, 1_f64),
+            (-1_f64, 1_f64),
+        ]
+        .into_iter()
+        .map(|(x, y)| {
+            // the camera looks along -facing in the rain frame where z points at the hole
+            let local = camera.right() * x * width + camera.up() * y * height - camera.facing();
+            let fixed = scene.to_fixed_frame(local);
+            let planar = Vector2::new(fixed.x, fixed.z);
+            (planar.norm() > 1e-9).then(|| planar.normalize())
+        })
+        .collect()
+    }
+
+    fn show_surface(
+        ui: &mut egui::Ui,
+        scene: &Scene,
+        aspect: f64,
+        units: &Units,
+        view: &mut (f64, f64),
+    ) {
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), ui.available_height().max(200_f32)),
+            egui::Sense::drag(),
+        );
+        let rect = response.rect;
+        let painter = painter.with_clip_rect(rect);
+        painter.rect_filled(rect, egui::Rounding::none(), ui.visuals().extreme_bg_color);
+
+        // dragging sideways turns the surface and up and down tilts it
+        let delta = response.drag_delta();
+        view.0 += delta.x as f64 * 0.01;
+        view.1 = (view.1 + delta.y as f64 * 0.01).clamp(0_f64, PI / 2_f64);
+        let (yaw, tilt) = *view;
+
+        // the diver in the plane it moves in, with the black hole at the origin
+        let r = scene.scaled_diver().position();
+        let diver = scene.to_fixed_frame(-Vector3::z() * r);
+        let diver = Vector2::new(diver.x, diver.z);
+        let extent = (1.5 * r).max(MIN_EXTENT);
+
+        // orthographic view of the surface turned by the yaw and seen from the tilt above it
+        let project = |point: Vector3<f64>| {
+            let (sin, cos) = yaw.sin_cos();
+            let across = point.x * cos - point.y * sin;
+            let depth = point.x * sin + point.y * cos;
+            Vector2::new(across, point.z * tilt.cos() + depth * tilt.sin())
+        };
+
+        // fit the rim and the throat into the rect
+        let bounds: Vec<_> = (0..SEGMENTS)
+            .flat_map(|i| {
+                let angle = 2_f64 * PI * i as f64 / SEGMENTS as f64;
+                let direction = Vector2::new(angle.cos(), angle.sin());
+                [
+                    project(lift(direction * extent)),
+                    project(lift(direction * 2_f64)),
+                ]
+            })
+            .collect();
+        let min = bounds
+            .iter()
+            .fold(Vector2::repeat(f64::INFINITY), |a, b| a.inf(b));
+        let max = bounds
+            .iter()
+            .fold(Vector2::repeat(f64::NEG_INFINITY), |a, b| a.sup(b));
+        let scale = (0.9 * rect.width() as f64 / (max.x - min.x))
+            .min(0.9 * rect.height() as f64 / (max.y - min.y));
+        let center = (min + max) / 2_f64;
+        let to_screen = |point: Vector3<f64>| {
+            let projected = (project(point) - center) * scale;
+            rect.center() + egui::vec2(projected.x as f32, -projected.y as f32)
+        };
+
+        let weak = ui.visuals().weak_text_color();
+        let grid = egui::Stroke::new(0.5_f32, weak);
+
+        // circles evenly spaced in radius, with the horizon and the photon sphere stronger
+        let circle = |radius: f64| {
+            (0..=SEGMENTS)
+                .map(|i| {
+                    let angle = 2_f64 * PI * i as f64 / SEGMENTS as f64;
+                    to_screen(lift(Vector2::new(angle.cos(), angle.sin()) * radius))
+                })
+                .collect::<Vec<_>>()
+        };
+        for i in 1..=CIRCLES {
+            let radius = 2_f64 + (extent - 2_f64) * i as f64 / CIRCLES as f64;
+            painter.add(egui::Shape::line(circle(radius), grid));
+        }
+        painter.add(egui::Shape::line(
+            circle(2_f64),
+            egui::Stroke::new(1.5_f32, ui.visuals().strong_text_color()),
+        ));
+        painter.add(egui::Shape::line(
+            circle(3_f64),
+            egui::Stroke::new(1_f32, weak),
+        ));
+
+        // spokes from the throat to the rim, denser near the throat where the surface is steep
+        for i in 0..SPOKES {
+            let angle = 2_f64 * PI * i as f64 / SPOKES as f64;
+            let direction = Vector2::new(angle.cos(), angle.sin());
+            let points = (0..=SEGMENTS)
+                .map(|j| {
+                    let fraction = (j as f64 / SEGMENTS as f64).powi(2);
+                    to_screen(lift(direction * (2_f64 + (extent - 2_f64) * fraction)))
+                })
+                .collect();
+            painter.add(egui::Shape::line(points, grid));
+        }
+
+        // the field of view drawn out along the surface from the diver, stopping at the horizon
+        let highlight = ui.visuals().selection.bg_fill;
+        let reach = FRUSTUM_LENGTH * extent;
+        let ray = |direction: Vector2<f64>| -> Vec<Vector2<f64>> {
+            (0..=SEGMENTS)
+                .map(|i| diver + direction * reach * i as f64 / SEGMENTS as f64)
+                .take_while(|point| point.norm() >= 2_f64)
+                .collect()
+        };
+        let corners = Self::frustum(scene, aspect);
+        let mut far_edge = Vec::new();
+        for direction in corners.iter().flatten() {
+            let points = ray(*direction);
+            if let Some(&end) = points.last() {
+                far_edge.push(to_screen(lift(end)));
+            }
+            painter.add(egui::Shape::line(
+                points
+                    .into_iter()
+                    .map(|point| to_screen(lift(point)))
+                    .collect(),
+                egui::Stroke::new(1_f32, highlight),
+            ));
+        }
+        if far_edge.len() == corners.len() {
+            far_edge.push(far_edge[0]);
+            painter.add(egui::Shape::line(
+                far_edge,
+                egui::Stroke::new(1_f32, highlight),
+            ));
+        }
+
+        // the diver, which leaves the surface once it crosses the horizon
+        let inside = r < 2_f64;
+        let marker = if inside {
+            diver.try_normalize(1e-12).unwrap_or(Vector2::x()) * 2_f64
+        } else {
+            diver
+        };
+        painter.circle_filled(to_screen(lift(marker)), MARKER_SIZE, highlight);
+
+        let font = egui::TextStyle::Small.resolve(ui.style());
+        painter.text(
+            rect.left_bottom(),
+            egui::Align2::LEFT_BOTTOM,
+            format!("Rim radius {}", units.describe_length(scene.mass * extent)),
+            font.clone(),
+            weak,
+        );
+        if inside {
+            painter.text(
+                rect.left_top(),
+                egui::Align2::LEFT_TOP,
+                "Inside the horizon, which the embedding doesn't reach",
+                font,
+                weak,
+            );
+        }
+    }
+}
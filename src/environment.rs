@@ -1,47 +1,642 @@
-use crate::spherical_angle::{MapAngle, SphericalAngle};
+use crate::{
+    spherical_angle::{MapAngle, SphericalAngle},
+    units::Units,
+};
 use image::{Rgb, RgbImage};
-use std::{f64::consts::PI, sync::Arc};
+use std::{
+    f64::consts::PI,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
+
+/// Radius an environment is brought in to from infinity
+const DEFAULT_RADIUS: f64 = 100_f64;
+
+/// Time each image of a loaded sequence is shown for until it is changed
+const DEFAULT_FRAME_DURATION: f64 = 1_f64;
+
+/// How image environments are filtered between their pixels
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SamplingMode {
+    // the color of the pixel the direction falls in
+    #[default]
+    Nearest,
+    // linear interpolation between the 2x2 nearest pixels
+    Bilinear,
+    // Catmull-Rom interpolation between the 4x4 nearest pixels, sharper than bilinear
+    Bicubic,
+}
+
+impl ToString for SamplingMode {
+    fn to_string(&self) -> String {
+        match self {
+            SamplingMode::Nearest => "Nearest".to_owned(),
+            SamplingMode::Bilinear => "Bilinear".to_owned(),
+            SamplingMode::Bicubic => "Bicubic".to_owned(),
+        }
+    }
+}
+
+impl SamplingMode {
+    pub const ALL: [SamplingMode; 3] = [
+        SamplingMode::Nearest,
+        SamplingMode::Bilinear,
+        SamplingMode::Bicubic,
+    ];
+}
+
+/// Background of the scene, implement this to supply procedural skies
+pub trait Environment: Send + Sync {
+    /// returns the color seen in the direction of the map angle far from the black hole
+    fn get_pixel(&self, angle: MapAngle) -> Rgb<u8>;
+
+    /// returns the color seen in the direction of the map angle when the environment is at the
+    /// time, the same at all times unless the environment is animated
+    fn get_pixel_at(&self, angle: MapAngle, _time: f64) -> Rgb<u8> {
+        self.get_pixel(angle)
+    }
+
+    /// samples the environment like `get_pixel_at` filtering between pixels with the sampling
+    /// mode and averaging over the footprint, the angle in radians across the patch of sky the
+    /// sample covers, procedural environments without pixels can leave this as is
+    fn sample_at(
+        &self,
+        angle: MapAngle,
+        time: f64,
+        _sampling: SamplingMode,
+        _footprint: f64,
+    ) -> Rgb<u8> {
+        self.get_pixel_at(angle, time)
+    }
+
+    /// returns whether the environment changes over time
+    fn is_animated(&self) -> bool {
+        false
+    }
+}
+
+/// Alignment of an environment to the map frame for sources with other conventions
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Orientation {
+    // mirror phi
+    pub flip_horizontal: bool,
+    // swap the poles
+    pub flip_vertical: bool,
+    // radians added to phi after flipping
+    pub phi_offset: f64,
+}
+
+impl Orientation {
+    /// returns the angle to sample the environment at for the map angle
+    pub fn apply(&self, angle: MapAngle) -> MapAngle {
+        let theta = if self.flip_vertical {
+            PI - angle.theta()
+        } else {
+            angle.theta()
+        };
+        let phi = if self.flip_horizontal {
+            2_f64 * PI - angle.phi()
+        } else {
+            angle.phi()
+        };
+
+        MapAngle::new(theta, phi + self.phi_offset)
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.flip_horizontal, "Flip horizontal");
+            ui.checkbox(&mut self.flip_vertical, "Flip vertical");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Rotation");
+            let mut phi_offset = self.phi_offset.to_degrees();
+            if ui
+                .add(
+                    egui::DragValue::new(&mut phi_offset)
+                        .clamp_range(-180_f64..=180_f64)
+                        .suffix("°"),
+                )
+                .changed()
+            {
+                self.phi_offset = phi_offset.to_radians();
+            }
+        });
+    }
+}
+
+/// Environment shared between scenes
+///
+/// scenes are equal when they share the same environment instance and orientation, so cloning a
+/// scene keeps it equal while loading another environment always counts as a change
+#[derive(Clone)]
+pub struct SharedEnvironment {
+    env: Arc<dyn Environment>,
+    pub orientation: Orientation,
+    // radius of the sphere around the black hole the environment is painted on, None at infinity
+    pub radius: Option<f64>,
+}
+
+impl SharedEnvironment {
+    pub fn new(env: impl Environment + 'static) -> Self {
+        Self {
+            env: Arc::new(env),
+            orientation: Default::default(),
+            radius: None,
+        }
+    }
+
+    pub fn show_radius(&mut self, ui: &mut egui::Ui, units: &Units) {
+        ui.horizontal(|ui| {
+            let mut finite = self.radius.is_some();
+            ui.checkbox(&mut finite, "Finite distance").on_hover_text(
+                "Paint the environment on a sphere around the black hole so that it shifts with \
+                parallax as the diver moves, only around black holes that aren't spinning",
+            );
+
+            if finite {
+                let mut radius = self.radius.unwrap_or(DEFAULT_RADIUS);
+                ui.add(
+                    units
+                        .length_drag_value(&mut radius, 0.5)
+                        .clamp_range(0.01..=f64::MAX),
+                );
+                self.radius = Some(radius);
+            } else {
+                self.radius = None;
+            }
+        });
+    }
+
+    /// shows a button replacing the environment with a sequence of images that loops, keeping its
+    /// orientation and radius
+    pub fn show_sequence_loader(&mut self, ui: &mut egui::Ui, units: &Units) {
+        // the frame duration and the last error are kept in temporary ui memory
+        let id = ui.id().with("image sequence");
+        let (mut frame_duration, mut error): (f64, Option<String>) = ui.data_mut(|reader| {
+            reader
+                .get_temp(id)
+                .unwrap_or((DEFAULT_FRAME_DURATION, None))
+        });
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("Load image sequence")
+                .on_hover_text(
+                    "Replace the environment with equirectangular images shown one after another \
+                    in the order of their names, the sequence loops",
+                )
+                .clicked()
+            {
+                if let Some(mut paths) = rfd::FileDialog::new()
+                    .add_filter("Images", &["png", "jpg", "jpeg", "tif", "tiff", "bmp"])
+                    .pick_files()
+                {
+                    paths.sort();
+                    match ImageSequenceEnvironment::open(&paths, frame_duration) {
+                        Ok(env) => {
+                            self.env = Arc::new(env);
+                            error = None;
+                        }
+                        Err(message) => error = Some(message),
+                    }
+                }
+            }
+            ui.label("Frame duration");
+            ui.add(
+                units
+                    .time_drag_value(&mut frame_duration, 0.1)
+                    .clamp_range(0.001..=f64::MAX),
+            )
+            .on_hover_text("Time each image of the next sequence loaded is shown for");
+        });
+        if let Some(error) = &error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        ui.data_mut(|reader| reader.insert_temp(id, (frame_duration, error)));
+    }
+
+    /// samples the environment in the direction of the map angle after orienting it
+    pub fn get_pixel(&self, angle: MapAngle) -> Rgb<u8> {
+        self.env.get_pixel(self.orientation.apply(angle))
+    }
+
+    /// samples the environment like `get_pixel` when it is at the time
+    pub fn get_pixel_at(&self, angle: MapAngle, time: f64) -> Rgb<u8> {
+        self.env.get_pixel_at(self.orientation.apply(angle), time)
+    }
+
+    /// samples the environment like `get_pixel_at` filtering with the sampling mode over the
+    /// footprint
+    pub fn sample_at(
+        &self,
+        angle: MapAngle,
+        time: f64,
+        sampling: SamplingMode,
+        footprint: f64,
+    ) -> Rgb<u8> {
+        self.env
+            .sample_at(self.orientation.apply(angle), time, sampling, footprint)
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.env.is_animated()
+    }
+}
+
+impl Default for SharedEnvironment {
+    fn default() -> Self {
+        // load the default sky once so that default scenes share it
+        static DEFAULT_ENVIRONMENT: OnceLock<SharedEnvironment> = OnceLock::new();
+
+        DEFAULT_ENVIRONMENT
+            .get_or_init(|| {
+                SharedEnvironment::new(
+                    ImageEnvironment::new(
+                        image::load_from_memory(include_bytes!("../sky.tif"))
+                            .unwrap()
+                            .into_rgb8(),
+                    )
+                    .unwrap(),
+                )
+            })
+            .clone()
+    }
+}
+
+impl SharedEnvironment {
+    /// returns the grid environment shared by all scenes that use it by default
+    pub fn default_grid() -> Self {
+        static GRID_ENVIRONMENT: OnceLock<SharedEnvironment> = OnceLock::new();
+
+        GRID_ENVIRONMENT
+            .get_or_init(|| SharedEnvironment::new(GridEnvironment::default()))
+            .clone()
+    }
+}
+
+impl PartialEq for SharedEnvironment {
+    fn eq(&self, other: &Self) -> bool {
+        // compare the data pointers only, vtables of the same type may differ between codegen units
+        Arc::as_ptr(&self.env) as *const () == Arc::as_ptr(&other.env) as *const ()
+            && self.orientation == other.orientation
+            && self.radius == other.radius
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 pub enum EnvironmentError {
     NotEquirectangularImage,
+    EmptySequence,
 }
 
+/// Environment from an equirectangular image
+///
+/// the image is kept along with mip levels each half the size of the last down to a single row,
+/// samples covering more of the sky than a pixel are taken from the level where a pixel is about
+/// as large as the footprint so that they average the sky instead of picking one star of it
 #[derive(Clone, PartialEq)]
-pub struct Environment {
-    image: Arc<RgbImage>,
+pub struct ImageEnvironment {
+    // the image followed by its mip levels
+    levels: Vec<RgbImage>,
 }
 
-impl Default for Environment {
-    fn default() -> Self {
-        Environment::new(
-            image::load_from_memory(include_bytes!("../sky.tif"))
-                .unwrap()
-                .into_rgb8(),
+impl ImageEnvironment {
+    pub fn new(image: impl Into<RgbImage>) -> Result<Self, EnvironmentError> {
+        let image = image.into();
+
+        if image.width() != 2 * image.height() {
+            return Err(EnvironmentError::NotEquirectangularImage);
+        }
+
+        let mut levels = vec![image];
+        while let Some(last) = levels.last().filter(|level| level.height() > 1) {
+            let height = last.height() / 2;
+            let level = image::imageops::resize(
+                last,
+                2 * height,
+                height,
+                image::imageops::FilterType::Triangle,
+            );
+            levels.push(level);
+        }
+
+        Ok(ImageEnvironment { levels })
+    }
+
+    /// returns the pixel of the mip level wrapping around in phi and clamped at the poles
+    fn texel(&self, level: usize, x: i64, y: i64) -> Rgb<u8> {
+        let image = &self.levels[level];
+        *image.get_pixel(
+            x.rem_euclid(image.width() as i64) as u32,
+            y.clamp(0, image.height() as i64 - 1) as u32,
         )
-        .unwrap()
+    }
+
+    /// returns the weighted sum of the `taps` by `taps` pixels of the mip level around the
+    /// continuous pixel coordinates, the weight of a pixel is the product of the kernel of its
+    /// distance along each axis
+    fn filter(
+        &self,
+        level: usize,
+        (x, y): (f64, f64),
+        taps: i64,
+        kernel: impl Fn(f64) -> f64,
+    ) -> [f64; 3] {
+        let (left, top) = (
+            x.floor() as i64 - taps / 2 + 1,
+            y.floor() as i64 - taps / 2 + 1,
+        );
+
+        let mut sum = [0_f64; 3];
+        for j in top..top + taps {
+            let weight_y = kernel(y - j as f64);
+            for i in left..left + taps {
+                let weight = kernel(x - i as f64) * weight_y;
+                let texel = self.texel(level, i, j);
+                (0..3).for_each(|channel| sum[channel] += texel[channel] as f64 * weight);
+            }
+        }
+        sum
+    }
+
+    /// samples the mip level at the angle with the sampling mode
+    fn sample_level(&self, level: usize, angle: &MapAngle, sampling: SamplingMode) -> [f64; 3] {
+        // continuous pixel coordinates with the centers of the pixels at whole numbers
+        let height = self.levels[level].height() as f64;
+        let position = (
+            height * angle.phi() / PI - 0.5,
+            height * angle.theta() / PI - 0.5,
+        );
+
+        match sampling {
+            SamplingMode::Nearest => {
+                let (x, y) = (position.0.round() as i64, position.1.round() as i64);
+                self.texel(level, x, y).0.map(|channel| channel as f64)
+            }
+            SamplingMode::Bilinear => self.filter(level, position, 2, |d| 1_f64 - d.abs()),
+            SamplingMode::Bicubic => self.filter(level, position, 4, |d| {
+                let d = d.abs();
+                if d < 1_f64 {
+                    1.5 * d.powi(3) - 2.5 * d.powi(2) + 1_f64
+                } else {
+                    -0.5 * d.powi(3) + 2.5 * d.powi(2) - 4_f64 * d + 2_f64
+                }
+            }),
+        }
     }
 }
 
-impl Environment {
-    pub fn new(image: impl Into<RgbImage>) -> Result<Self, EnvironmentError> {
-        let image = image.into();
+impl Environment for ImageEnvironment {
+    fn get_pixel(&self, angle: MapAngle) -> Rgb<u8> {
+        let image = &self.levels[0];
+        let x = (image.height() as f64 * angle.phi() / PI).floor() as u32;
+        let y = (image.height() as f64 * angle.theta() / PI).floor() as u32;
+        *image.get_pixel(x.min(image.width() - 1), y.min(image.height() - 1))
+    }
+
+    fn sample_at(
+        &self,
+        angle: MapAngle,
+        _time: f64,
+        sampling: SamplingMode,
+        footprint: f64,
+    ) -> Rgb<u8> {
+        // the level whose pixels are as wide as the footprint, blending the two nearest levels
+        let pixel_angle = PI / self.levels[0].height() as f64;
+        let level = (footprint / pixel_angle)
+            .log2()
+            .clamp(0_f64, (self.levels.len() - 1) as f64);
 
-        if image.width() == 2 * image.height() {
-            return Ok(Environment {
-                image: Arc::new(image),
-            });
+        let color = if level == 0_f64 {
+            if sampling == SamplingMode::Nearest {
+                return self.get_pixel(angle);
+            }
+            self.sample_level(0, &angle, sampling)
         } else {
-            return Err(EnvironmentError::NotEquirectangularImage);
+            let (lower, factor) = (level.floor() as usize, level.fract());
+            let upper = (lower + 1).min(self.levels.len() - 1);
+            let (lower, upper) = (
+                self.sample_level(lower, &angle, sampling),
+                self.sample_level(upper, &angle, sampling),
+            );
+            [0, 1, 2].map(|i| lower[i] + (upper[i] - lower[i]) * factor)
+        };
+
+        Rgb(color.map(|channel| channel.round().clamp(0_f64, 255_f64) as u8))
+    }
+}
+
+/// Environment looping through a sequence of equirectangular images
+#[derive(Clone, PartialEq)]
+pub struct ImageSequenceEnvironment {
+    frames: Vec<ImageEnvironment>,
+    // time each image is shown for in M
+    frame_duration: f64,
+}
+
+impl ImageSequenceEnvironment {
+    pub fn new(
+        images: impl IntoIterator<Item = RgbImage>,
+        frame_duration: f64,
+    ) -> Result<Self, EnvironmentError> {
+        let frames = images
+            .into_iter()
+            .map(ImageEnvironment::new)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if frames.is_empty() {
+            return Err(EnvironmentError::EmptySequence);
         }
+
+        Ok(ImageSequenceEnvironment {
+            frames,
+            frame_duration,
+        })
     }
 
-    pub fn get_pixel(&self, angle: MapAngle) -> Rgb<u8> {
-        let x = (self.image.height() as f64 * angle.phi() / PI).floor() as u32;
-        let y = (self.image.height() as f64 * angle.theta() / PI).floor() as u32;
-        *self.image.get_pixel(
-            x.min(self.image.width() - 1),
-            y.min(self.image.height() - 1),
-        )
+    /// loads the images at the paths in order, returns a message saying what went wrong if one
+    /// can't be used
+    pub fn open(paths: &[PathBuf], frame_duration: f64) -> Result<Self, String> {
+        let images = paths
+            .iter()
+            .map(|path| {
+                image::open(path)
+                    .map(|image| image.into_rgb8())
+                    .map_err(|error| format!("Couldn't open {}: {}", path.display(), error))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::new(images, frame_duration).map_err(|error| match error {
+            EnvironmentError::NotEquirectangularImage => {
+                "The images have to be equirectangular, twice as wide as they are tall".to_owned()
+            }
+            EnvironmentError::EmptySequence => "No images were picked".to_owned(),
+        })
+    }
+
+    /// returns the image shown at the time, the sequence repeats before and after itself
+    fn frame_at(&self, time: f64) -> &ImageEnvironment {
+        let index = (time / self.frame_duration).floor() as i64;
+        &self.frames[index.rem_euclid(self.frames.len() as i64) as usize]
+    }
+}
+
+impl Environment for ImageSequenceEnvironment {
+    fn get_pixel(&self, angle: MapAngle) -> Rgb<u8> {
+        self.frames[0].get_pixel(angle)
+    }
+
+    fn get_pixel_at(&self, angle: MapAngle, time: f64) -> Rgb<u8> {
+        self.frame_at(time).get_pixel(angle)
+    }
+
+    fn sample_at(
+        &self,
+        angle: MapAngle,
+        time: f64,
+        sampling: SamplingMode,
+        footprint: f64,
+    ) -> Rgb<u8> {
+        self.frame_at(time)
+            .sample_at(angle, time, sampling, footprint)
+    }
+
+    fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+}
+
+/// Environment of a single color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolidEnvironment(pub Rgb<u8>);
+
+impl Environment for SolidEnvironment {
+    fn get_pixel(&self, _angle: MapAngle) -> Rgb<u8> {
+        self.0
+    }
+}
+
+/// Procedural checkerboard with white lines along lines of constant theta and phi
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridEnvironment {
+    // degrees between lines
+    pub spacing: f64,
+    // width of the lines in degrees
+    pub line_width: f64,
+}
+
+impl Default for GridEnvironment {
+    fn default() -> Self {
+        Self {
+            spacing: 15_f64,
+            line_width: 0.5,
+        }
+    }
+}
+
+impl Environment for GridEnvironment {
+    fn get_pixel(&self, angle: MapAngle) -> Rgb<u8> {
+        let theta = angle.theta().to_degrees();
+        let phi = angle.phi().to_degrees();
+
+        let on_line = |angle: f64| {
+            let remainder = angle % self.spacing;
+            remainder.min(self.spacing - remainder) < self.line_width / 2_f64
+        };
+
+        if on_line(theta) || on_line(phi) {
+            Rgb([255, 255, 255])
+        } else if ((theta / self.spacing) as i32 + (phi / self.spacing) as i32) % 2 == 0 {
+            Rgb([40, 40, 80])
+        } else {
+            Rgb([80, 40, 40])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_angle(angle: MapAngle, theta: f64, phi: f64) {
+        assert!(
+            (angle.theta() - theta).abs() < 1e-12 && (angle.phi() - phi).abs() < 1e-12,
+            "({}, {}) != ({theta}, {phi})",
+            angle.theta(),
+            angle.phi()
+        );
+    }
+
+    #[test]
+    fn flip_horizontal_mirrors_phi() {
+        let orientation = Orientation {
+            flip_horizontal: true,
+            ..Default::default()
+        };
+        assert_angle(
+            orientation.apply(MapAngle::new(1_f64, 0.5)),
+            1_f64,
+            2_f64 * PI - 0.5,
+        );
+        assert_angle(
+            orientation.apply(MapAngle::new(1_f64, 4_f64)),
+            1_f64,
+            2_f64 * PI - 4_f64,
+        );
+        // phi = 0 mirrors onto itself instead of 2 pi
+        assert_angle(orientation.apply(MapAngle::new(2_f64, 0_f64)), 2_f64, 0_f64);
+    }
+
+    #[test]
+    fn flip_vertical_swaps_poles() {
+        let orientation = Orientation {
+            flip_vertical: true,
+            ..Default::default()
+        };
+        assert_angle(orientation.apply(MapAngle::new(0_f64, 1_f64)), PI, 1_f64);
+        assert_angle(orientation.apply(MapAngle::new(PI, 1_f64)), 0_f64, 1_f64);
+        assert_angle(
+            orientation.apply(MapAngle::new(0.3, 5_f64)),
+            PI - 0.3,
+            5_f64,
+        );
+    }
+
+    #[test]
+    fn phi_offset_wraps() {
+        let rotated = |phi_offset| Orientation {
+            phi_offset,
+            ..Default::default()
+        };
+        assert_angle(
+            rotated(1_f64).apply(MapAngle::new(1_f64, 6_f64)),
+            1_f64,
+            7_f64 - 2_f64 * PI,
+        );
+        assert_angle(
+            rotated(-1_f64).apply(MapAngle::new(1_f64, 0.5)),
+            1_f64,
+            2_f64 * PI - 0.5,
+        );
+        assert_angle(
+            rotated(-3_f64 * PI).apply(MapAngle::new(1_f64, 0.5)),
+            1_f64,
+            PI + 0.5,
+        );
+
+        // the offset is added after mirroring
+        let flipped = Orientation {
+            flip_horizontal: true,
+            phi_offset: 1_f64,
+            ..Default::default()
+        };
+        assert_angle(flipped.apply(MapAngle::new(1_f64, 0.5)), 1_f64, 0.5);
+        assert_angle(
+            flipped.apply(MapAngle::new(1_f64, 2_f64)),
+            1_f64,
+            2_f64 * PI - 1_f64,
+        );
     }
 }
@@ -0,0 +1,198 @@
+// Names the files of animation frames from a template set in the render panel
+//
+// Tokens in braces are replaced when a frame is saved: {name} is the file stem of the output path,
+// {frame} the number of the frame on the timeline so that strided renders share one sequence,
+// which {frame:04} pads with zeros to 4 digits (at most 10), {scene} the kind of spacetime
+// rendered (black-hole or wormhole) and {date} the day the render was started or queued as
+// YYYY-MM-DD in UTC. The extension of the output path is added to the end. The frames of an
+// animation need different names, so the template has to contain the frame number.
+
+use crate::scene::Spacetime;
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Template naming frames like `name.00001.png`, the naming used before templates existed
+pub const DEFAULT_TEMPLATE: &str = "{name}.{frame:05}";
+
+/// Most digits frame numbers can be padded to, every frame number fits in 10 digits
+pub const MAX_FRAME_WIDTH: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    UnclosedBrace,
+    UnknownToken(String),
+    MissingFrame,
+    FrameTooWide,
+    PathSeparator,
+}
+
+impl ToString for TemplateError {
+    fn to_string(&self) -> String {
+        match self {
+            TemplateError::UnclosedBrace => "A { is never closed".to_owned(),
+            TemplateError::UnknownToken(token) => format!("Unknown token {{{}}}", token),
+            TemplateError::MissingFrame => "Frames need a {frame} to tell them apart".to_owned(),
+            TemplateError::FrameTooWide => format!(
+                "Frame numbers can be padded to at most {} digits",
+                MAX_FRAME_WIDTH
+            ),
+            TemplateError::PathSeparator => "Frames can't be named into other folders".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Name,
+    Scene,
+    Date,
+    // zero padded to the width
+    Frame(usize),
+}
+
+/// Values of the tokens that are the same for every frame of a render
+#[derive(Debug, Clone)]
+pub struct TemplateValues {
+    name: String,
+    scene: String,
+    date: String,
+}
+
+impl TemplateValues {
+    /// returns the values for frames of the spacetime rendered to the output path today
+    pub fn new(output_path: &Path, spacetime: Spacetime) -> Self {
+        Self {
+            name: output_path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default()
+                .to_owned(),
+            scene: spacetime.to_string().to_lowercase().replace(' ', "-"),
+            date: today(),
+        }
+    }
+}
+
+/// Parsed filename template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilenameTemplate {
+    parts: Vec<Part>,
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_TEMPLATE).unwrap()
+    }
+}
+
+impl FilenameTemplate {
+    pub fn parse(template: &str) -> Result<Self, TemplateError> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            parts.push(Part::Text(rest[..start].to_owned()));
+            let length = rest[start..]
+                .find('}')
+                .ok_or(TemplateError::UnclosedBrace)?;
+            let token = &rest[start + 1..start + length];
+            parts.push(match token.split_once(':') {
+                None if token == "name" => Part::Name,
+                None if token == "scene" => Part::Scene,
+                None if token == "date" => Part::Date,
+                None if token == "frame" => Part::Frame(0),
+                Some(("frame", width)) => {
+                    let width = width
+                        .parse()
+                        .map_err(|_| TemplateError::UnknownToken(token.to_owned()))?;
+                    if width > MAX_FRAME_WIDTH {
+                        return Err(TemplateError::FrameTooWide);
+                    }
+                    Part::Frame(width)
+                }
+                _ => return Err(TemplateError::UnknownToken(token.to_owned())),
+            });
+            rest = &rest[start + length + 1..];
+        }
+        parts.push(Part::Text(rest.to_owned()));
+
+        if !parts.iter().any(|part| matches!(part, Part::Frame(_))) {
+            return Err(TemplateError::MissingFrame);
+        }
+        if parts
+            .iter()
+            .any(|part| matches!(part, Part::Text(text) if text.contains(['/', '\\'])))
+        {
+            return Err(TemplateError::PathSeparator);
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// returns the path of the frame next to the output path with its extension
+    pub fn frame_path(&self, output_path: &Path, values: &TemplateValues, frame: i32) -> PathBuf {
+        let mut file_name: String = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Name => values.name.clone(),
+                Part::Scene => values.scene.clone(),
+                Part::Date => values.date.clone(),
+                Part::Frame(width) => format!("{:0width$}", frame, width = width),
+            })
+            .collect();
+        if let Some(extension) = output_path.extension().and_then(|ext| ext.to_str()) {
+            file_name.push('.');
+            file_name.push_str(extension);
+        }
+
+        output_path.with_file_name(file_name)
+    }
+}
+
+/// returns the date in UTC as YYYY-MM-DD
+fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    // days since 1970-01-01 to the proleptic Gregorian calendar, counting years from March so the
+    // leap day is at the end
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_width_is_bounded() {
+        assert!(FilenameTemplate::parse("{name}.{frame:10}").is_ok());
+        assert_eq!(
+            FilenameTemplate::parse("{name}.{frame:11}"),
+            Err(TemplateError::FrameTooWide)
+        );
+        assert_eq!(
+            FilenameTemplate::parse("{frame:1000000}"),
+            Err(TemplateError::FrameTooWide)
+        );
+    }
+}
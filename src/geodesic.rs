@@ -0,0 +1,310 @@
+// Integrates null geodesics numerically as an alternative to the quadrature formulas in math.rs
+//
+// Photons around a spherically symmetric black hole stay in a plane where the inverse radius u
+// obeys u'' = -V'(u) / 2 as a function of the angle phi swept around the black hole, with the
+// potential V(u) of the orbit equation (du/dphi)^2 = 1/b^2 - V(u). The ray arriving at the diver
+// is followed backwards with an adaptive Runge-Kutta-Fehlberg 4(5) integrator until it escapes to
+// u = 0, falls through the photon sphere towards the horizon or loops around too many times.
+//
+// Inside matter the orbit equation is divided by the square of the radial stretch S, which gives
+// u'' = -V'(u) / (2 S^2) - (1/b^2 - V(u)) S'(u) / S^3.
+
+use crate::{
+    math::{image_order, metric_photon_constants},
+    metric::Metric,
+};
+use std::f64::consts::PI;
+
+/// Acceptable error in u and du/dphi per step
+const TOLERANCE: f64 = 1e-10;
+
+/// Most steps a single ray may take before it is given up on
+const MAX_STEPS: usize = 100_000;
+
+/// Which method the lensing is calculated with
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LensingBackend {
+    // closed form integrals evaluated by quadrature
+    #[default]
+    Quadrature,
+    // step by step integration of the geodesic equation
+    Geodesic,
+}
+
+impl ToString for LensingBackend {
+    fn to_string(&self) -> String {
+        match self {
+            LensingBackend::Quadrature => "Quadrature".to_owned(),
+            LensingBackend::Geodesic => "Geodesic".to_owned(),
+        }
+    }
+}
+
+/// Inverse radius and its derivative with respect to phi
+type State = [f64; 2];
+
+/// Returns the derivative of the state with respect to phi of the photon with impact parameter b
+/// around the black hole with this metric
+fn derivative(state: State, b: f64, metric: &dyn Metric) -> State {
+    let u = state[0];
+    let slope = metric.effective_potential_slope(u);
+    // trial steps may overshoot past infinity where there is no matter
+    let stretch = if u > 0_f64 {
+        metric.radial_stretch(1_f64 / u)
+    } else {
+        1_f64
+    };
+    if stretch == 1_f64 {
+        return [state[1], -slope / 2_f64];
+    }
+
+    let h = 1e-6 * u.max(1e-3);
+    let stretch_slope = (metric.radial_stretch(1_f64 / (u + h))
+        - metric.radial_stretch(1_f64 / (u - h)))
+        / (2_f64 * h);
+    [
+        state[1],
+        -slope / (2_f64 * stretch.powi(2))
+            - (1_f64 / b.powi(2) - metric.effective_potential(u)) * stretch_slope / stretch.powi(3),
+    ]
+}
+
+/// Takes a Runge-Kutta-Fehlberg step of size h of the state with the derivative and returns the
+/// fifth order state and the estimate of its error
+pub fn rkf45_step<const N: usize>(
+    state: [f64; N],
+    h: f64,
+    derivative: impl Fn([f64; N]) -> [f64; N],
+) -> ([f64; N], f64) {
+    let add = |terms: &[(f64, [f64; N])]| {
+        let mut sum = state;
+        for (weight, k) in terms {
+            for i in 0..N {
+                sum[i] += h * weight * k[i];
+            }
+        }
+        sum
+    };
+
+    let k1 = derivative(state);
+    let k2 = derivative(add(&[(1_f64 / 4_f64, k1)]));
+    let k3 = derivative(add(&[(3_f64 / 32_f64, k1), (9_f64 / 32_f64, k2)]));
+    let k4 = derivative(add(&[
+        (1932_f64 / 2197_f64, k1),
+        (-7200_f64 / 2197_f64, k2),
+        (7296_f64 / 2197_f64, k3),
+    ]));
+    let k5 = derivative(add(&[
+        (439_f64 / 216_f64, k1),
+        (-8_f64, k2),
+        (3680_f64 / 513_f64, k3),
+        (-845_f64 / 4104_f64, k4),
+    ]));
+    let k6 = derivative(add(&[
+        (-8_f64 / 27_f64, k1),
+        (2_f64, k2),
+        (-3544_f64 / 2565_f64, k3),
+        (1859_f64 / 4104_f64, k4),
+        (-11_f64 / 40_f64, k5),
+    ]));
+
+    let fourth = add(&[
+        (25_f64 / 216_f64, k1),
+        (1408_f64 / 2565_f64, k3),
+        (2197_f64 / 4104_f64, k4),
+        (-1_f64 / 5_f64, k5),
+    ]);
+    let fifth = add(&[
+        (16_f64 / 135_f64, k1),
+        (6656_f64 / 12825_f64, k3),
+        (28561_f64 / 56430_f64, k4),
+        (-9_f64 / 50_f64, k5),
+        (2_f64 / 55_f64, k6),
+    ]);
+
+    let error = fifth
+        .iter()
+        .zip(fourth)
+        .map(|(fifth, fourth)| (fifth - fourth).abs())
+        .fold(0_f64, f64::max);
+    (fifth, error)
+}
+
+/// Returns the size of the next step after a step of size h had the error, smaller to retry a
+/// step with too large an error and larger after an accurate one
+pub fn next_step_size(h: f64, error: f64, tolerance: f64) -> f64 {
+    h * (0.9 * (tolerance / error.max(1e-300)).powf(0.2)).clamp(0.1, 5_f64)
+}
+
+/// Follows the ray arriving at the rain angle back from the diver at radius r around the black
+/// hole with this metric, calling `visit` with the radius and swept angle after every step
+///
+/// returns the angle swept before escaping to infinity, None if the ray came from the black hole
+/// or sweeps through more than `max_half_orbits` half orbits
+///
+/// rays heading into the black hole are followed until they are inside both the photon sphere and
+/// `min_radius`
+fn follow_ray(
+    theta_rain: f64,
+    r: f64,
+    metric: &dyn Metric,
+    max_half_orbits: u32,
+    min_radius: f64,
+    mut visit: impl FnMut(f64, f64),
+) -> Option<f64> {
+    // past the inner horizon the rain frame breaks down
+    if r <= metric.inner_horizon_radius() {
+        return None;
+    }
+
+    let (b, outgoing) = metric_photon_constants(theta_rain, r, metric)?;
+    // without a horizon every ray comes back out
+    let has_horizon = metric.horizon_radius() > 0_f64;
+    let u_stop = if has_horizon {
+        (1_f64 / metric.photon_sphere_radius()).max(1_f64 / min_radius)
+    } else if min_radius.is_finite() {
+        1_f64 / min_radius
+    } else {
+        f64::INFINITY
+    };
+    let max_angle = max_half_orbits as f64 * PI;
+
+    // radial photons sweep no angle, or half a turn through the center where there is no horizon
+    if b.abs() < 1e-12 {
+        visit(r, 0_f64);
+        if outgoing {
+            if has_horizon || min_radius.is_finite() {
+                return None;
+            }
+            visit(0_f64, 0_f64);
+            visit(f64::INFINITY, PI);
+            return Some(PI);
+        }
+        visit(f64::INFINITY, 0_f64);
+        return Some(0_f64);
+    }
+
+    // going back in time outgoing photons move inwards
+    let speed = ((1_f64 / b.powi(2) - metric.effective_potential(1_f64 / r)).max(0_f64)).sqrt()
+        / metric.radial_stretch(r);
+    let mut state = [1_f64 / r, if outgoing { speed } else { -speed }];
+    let mut phi = 0_f64;
+    let mut h = (0.01 * b / r).min(0.01);
+    visit(r, phi);
+
+    for _ in 0..MAX_STEPS {
+        let (next, error) = rkf45_step(state, h, |state| derivative(state, b, metric));
+
+        if error > TOLERANCE {
+            h = next_step_size(h, error, TOLERANCE);
+            continue;
+        }
+
+        if next[0] <= 0_f64 {
+            // escaped, interpolate where it crossed infinity
+            let swept = phi + h * state[0] / (state[0] - next[0]);
+            visit(f64::INFINITY, swept);
+            return (swept <= max_angle).then_some(swept);
+        }
+
+        state = next;
+        phi += h;
+        visit(1_f64 / state[0], phi);
+
+        // moving inwards past the photon sphere nothing turns it back before the horizon
+        if (state[0] > u_stop && state[1] > 0_f64) || phi > max_angle {
+            return None;
+        }
+
+        h = next_step_size(h, error, TOLERANCE);
+    }
+
+    None
+}
+
+/// Returns the spherical map angle from the rain angle around the black hole with this metric and
+/// the order of the image it is part of by integrating the geodesic, like
+/// `metric_rain_angle_to_map_angle`
+pub fn geodesic_rain_angle_to_map_angle(
+    theta_rain: f64,
+    phi_rain: f64,
+    r: f64,
+    metric: &dyn Metric,
+    max_half_orbits: u32,
+) -> Option<(f64, f64, u32)> {
+    let swept_angle = follow_ray(
+        theta_rain,
+        r,
+        metric,
+        max_half_orbits,
+        f64::INFINITY,
+        |_, _| {},
+    )?;
+
+    let theta_map = PI - swept_angle;
+
+    let mut phi_map = phi_rain;
+
+    // flip phi if needed
+    if theta_map.sin() < 0_f64 {
+        phi_map += PI;
+    }
+
+    Some((theta_map.cos().acos(), phi_map, image_order(swept_angle)))
+}
+
+/// Returns the path of the ray arriving at the rain angle as the radius and the angle swept around
+/// the black hole going back from the diver, ending at an infinite radius if it came from the sky
+///
+/// rays falling into the black hole end once they are inside the photon sphere and `min_radius`
+pub fn geodesic_path(
+    theta_rain: f64,
+    r: f64,
+    metric: &dyn Metric,
+    max_half_orbits: u32,
+    min_radius: f64,
+) -> Vec<(f64, f64)> {
+    let mut path = Vec::new();
+    follow_ray(
+        theta_rain,
+        r,
+        metric,
+        max_half_orbits,
+        min_radius,
+        |r, phi| path.push((r, phi)),
+    );
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::{metric_critical_rain_angle, metric_rain_angle_to_map_angle},
+        metric::ReissnerNordstrom,
+    };
+
+    #[test]
+    fn matches_quadrature() {
+        // outside and inside the horizon of uncharged and charged black holes
+        for (charge, r) in [(0_f64, 10_f64), (0_f64, 3_f64), (0_f64, 1.5), (0.5, 6_f64)] {
+            let metric = ReissnerNordstrom::new(charge);
+            let critical = metric_critical_rain_angle(r, &metric);
+            for k in 1..20 {
+                let theta_rain = critical + (PI - critical) * k as f64 / 20_f64;
+                let (theta, phi, order) =
+                    metric_rain_angle_to_map_angle(theta_rain, 0.5, r, &metric, u32::MAX).unwrap();
+                let (geodesic_theta, geodesic_phi, geodesic_order) =
+                    geodesic_rain_angle_to_map_angle(theta_rain, 0.5, r, &metric, u32::MAX)
+                        .unwrap();
+
+                assert!(
+                    (geodesic_theta - theta).abs() < 1e-5 && (geodesic_phi - phi).abs() < 1e-12,
+                    "charge {charge} r {r} rain angle {theta_rain}: \
+                    ({geodesic_theta}, {geodesic_phi}) != ({theta}, {phi})"
+                );
+                assert_eq!(geodesic_order, order);
+            }
+        }
+    }
+}
@@ -0,0 +1,667 @@
+// Renders the preview on the GPU with a fragment shader
+//
+// The deflection only depends on the rain angle theta for a given radius so it is precomputed on
+// the CPU into a lookup table texture, and the environment is baked into an equirectangular
+// texture. The shader then only has to find the rain angle of each pixel, look up its map angle
+// and sample the environment. Still frames can also be rendered with it into an offscreen
+// framebuffer and read back, the CPU renderer stays the reference for everything else.
+
+use crate::{
+    camera::Projection,
+    deflection_lut::DeflectionLut,
+    environment::{SamplingMode, SharedEnvironment},
+    geodesic::LensingBackend,
+    metric::SharedMetric,
+    redshift::{frequency_ratio, Beaming, ColorShiftMode},
+    render::RenderSettings,
+    scene::{Scene, Spacetime, MIN_EXPOSURE},
+    spherical_angle::{MapAngle, SphericalAngle},
+};
+use eframe::{egui_glow, glow, glow::HasContext};
+use image::RgbImage;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use std::{
+    f64::consts::PI,
+    sync::{Arc, Mutex},
+};
+
+/// Number of rain angles in the deflection lookup table
+const LUT_SIZE: usize = 2048;
+
+/// Height of the baked environment texture, the width is twice this
+const ENVIRONMENT_HEIGHT: u32 = 1024;
+
+const VERTEX_SHADER: &str = r#"
+const vec2 VERTICES[3] = vec2[3](vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0));
+
+out vec2 v_uv;
+
+void main() {
+    vec2 position = VERTICES[gl_VertexID];
+    // uv with (0, 0) at the top left like image pixel coordinates
+    v_uv = vec2(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+const float PI = 3.14159265358979323846;
+
+uniform sampler2D u_environment;
+// map theta and the change in phi for each rain theta, negative theta for captured photons
+uniform sampler2D u_lut;
+uniform int u_lut_size;
+// rain theta of the first entry of the table, the last entry is at pi
+uniform float u_lut_start;
+
+// columns are the right, up and facing vectors of the camera
+uniform mat3 u_camera;
+// velocity of the diver through the rain frame
+uniform vec3 u_velocity;
+// rotation of the map by the orbit of the diver
+uniform mat3 u_orbit;
+uniform float u_fov;
+uniform vec2 u_resolution;
+// 0 for perspective and 1 for equirectangular
+uniform int u_projection;
+
+uniform float u_gain;
+uniform vec3 u_white_balance;
+
+// frequency ratio is pow(u_shift_scale / (1 - u_shift_speed * cos(theta_rain)), u_shift_strength)
+uniform float u_shift_speed;
+uniform float u_shift_scale;
+uniform float u_shift_strength;
+// wavelengths the red, green and blue channels sample the spectrum at
+const vec3 CHANNEL_WAVELENGTHS = vec3(610.0, 550.0, 465.0);
+
+in vec2 v_uv;
+out vec4 out_color;
+
+vec3 linear_from_gamma(vec3 gamma) {
+    return mix(gamma / 12.92, pow((gamma + 0.055) / 1.055, vec3(2.4)), step(0.04045, gamma));
+}
+
+vec3 gamma_from_linear(vec3 linear) {
+    return mix(linear * 12.92, 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, linear));
+}
+
+// same as the CPU color shift in redshift.rs
+float sample_spectrum(vec3 linear, float wavelength) {
+    if (wavelength >= CHANNEL_WAVELENGTHS.r) {
+        return linear.r;
+    }
+    if (wavelength <= CHANNEL_WAVELENGTHS.b) {
+        return linear.b;
+    }
+    if (wavelength >= CHANNEL_WAVELENGTHS.g) {
+        float factor = (CHANNEL_WAVELENGTHS.r - wavelength) / (CHANNEL_WAVELENGTHS.r - CHANNEL_WAVELENGTHS.g);
+        return mix(linear.r, linear.g, factor);
+    }
+    float factor = (CHANNEL_WAVELENGTHS.g - wavelength) / (CHANNEL_WAVELENGTHS.g - CHANNEL_WAVELENGTHS.b);
+    return mix(linear.g, linear.b, factor);
+}
+
+vec3 shift_color(vec3 linear, float ratio) {
+    float intensity = ratio * ratio * ratio;
+    return min(vec3(
+        sample_spectrum(linear, CHANNEL_WAVELENGTHS.r * ratio),
+        sample_spectrum(linear, CHANNEL_WAVELENGTHS.g * ratio),
+        sample_spectrum(linear, CHANNEL_WAVELENGTHS.b * ratio)
+    ) * intensity, 1.0);
+}
+
+vec3 local_direction() {
+    vec2 pixel = v_uv * u_resolution;
+
+    if (u_projection == 0) {
+        return vec3(
+            pixel.x - u_resolution.x / 2.0,
+            u_resolution.y / 2.0 - pixel.y,
+            -u_resolution.y / (2.0 * tan(u_fov / 2.0))
+        );
+    }
+
+    if (u_projection == 2) {
+        // faces side by side like cube_face_to_vector
+        float face = min(floor(pixel.x / u_resolution.y), 5.0);
+        float across = 2.0 * (pixel.x - face * u_resolution.y) / u_resolution.y - 1.0;
+        float down = 2.0 * v_uv.y - 1.0;
+        vec3 vector;
+        if (face == 0.0) {
+            vector = vec3(1.0, -down, -across);
+        } else if (face == 1.0) {
+            vector = vec3(-1.0, -down, across);
+        } else if (face == 2.0) {
+            vector = vec3(across, 1.0, down);
+        } else if (face == 3.0) {
+            vector = vec3(across, -1.0, -down);
+        } else if (face == 4.0) {
+            vector = vec3(across, -down, 1.0);
+        } else {
+            vector = vec3(-across, -down, -1.0);
+        }
+
+        // +z is ahead on the cube and behind the camera
+        return vec3(vector.x, vector.y, -vector.z);
+    }
+
+    if (u_projection == 3) {
+        // equidistant dome master like the fisheye in camera.rs
+        float radius = u_resolution.y / 2.0;
+        vec2 local = vec2(pixel.x - u_resolution.x / 2.0, radius - pixel.y) / radius;
+        float distance = length(local);
+        float angle = distance * u_fov / 2.0;
+        if (distance == 0.0) {
+            return vec3(0.0, 0.0, -1.0);
+        }
+        return vec3(local / distance * sin(angle), -cos(angle));
+    }
+
+    // traditional equirectangular coordinates with +z up
+    float theta = PI * (1.0 - v_uv.y);
+    float phi = PI * pixel.x / u_resolution.y;
+    vec3 vector = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+
+    // make +z forward on equirectangular projection
+    return vec3(-vector.y, -vector.z, vector.x);
+}
+
+void main() {
+    // the dome only fills the circle in the middle of fisheye images
+    vec2 from_center = (v_uv - 0.5) * u_resolution;
+    if (u_projection == 3 && length(from_center) > u_resolution.y / 2.0) {
+        out_color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec3 direction = normalize(u_camera * local_direction());
+
+    // same as RainAngle::from_moving_frame
+    float doppler = 1.0;
+    float speed = length(u_velocity);
+    if (speed > 0.0) {
+        float gamma = 1.0 / sqrt(1.0 - speed * speed);
+        vec3 velocity_direction = u_velocity / speed;
+        vec3 momentum = -direction
+            - (gamma - 1.0) * dot(direction, velocity_direction) * velocity_direction
+            + gamma * u_velocity;
+        float energy = gamma * (1.0 - dot(u_velocity, direction));
+        direction = normalize(-momentum / energy);
+        doppler = gamma * (1.0 + dot(u_velocity, direction));
+    }
+
+    float theta_rain = acos(clamp(direction.z, -1.0, 1.0));
+    float phi_rain = atan(direction.y, direction.x);
+
+    // interpolate the lookup table
+    float position = (theta_rain - u_lut_start) / (PI - u_lut_start) * float(u_lut_size - 1);
+    if (position < 0.0) {
+        out_color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    int index = min(int(position), u_lut_size - 2);
+    vec2 left = texelFetch(u_lut, ivec2(index, 0), 0).rg;
+    vec2 right = texelFetch(u_lut, ivec2(index + 1, 0), 0).rg;
+    if (left.r < 0.0 || right.r < 0.0) {
+        out_color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    vec2 map = mix(left, right, clamp(position - float(index), 0.0, 1.0));
+
+    // phi jumps by pi where the photons pass over the poles, take the nearest entry there
+    float phi_offset = abs(right.g - left.g) > PI / 2.0
+        ? (position - float(index) < 0.5 ? left.g : right.g)
+        : map.g;
+
+    float theta_map = map.r;
+    float phi_map = phi_rain + phi_offset;
+
+    vec3 map_direction = u_orbit * vec3(sin(theta_map) * cos(phi_map), sin(theta_map) * sin(phi_map), cos(theta_map));
+    theta_map = acos(clamp(map_direction.z, -1.0, 1.0));
+    phi_map = atan(map_direction.y, map_direction.x);
+
+    vec2 uv = vec2(fract(phi_map / (2.0 * PI)), theta_map / PI);
+    vec3 color = texture(u_environment, uv).rgb;
+
+    float ratio = pow(u_shift_scale / (1.0 - u_shift_speed * cos(theta_rain)) * doppler, u_shift_strength);
+    vec3 linear = shift_color(linear_from_gamma(color), ratio);
+
+    color = gamma_from_linear(clamp(linear * u_gain * u_white_balance, 0.0, 1.0));
+    out_color = vec4(color, 1.0);
+}
+"#;
+
+/// Inputs the lookup table was computed for
+#[derive(Clone, PartialEq)]
+struct LutKey {
+    lensing: bool,
+    aberration: bool,
+    spin: f64,
+    charge: f64,
+    custom_metric: Option<SharedMetric>,
+    radius: f64,
+    max_half_orbits: u32,
+    lensing_backend: LensingBackend,
+}
+
+pub struct GpuPreview {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    environment_texture: glow::Texture,
+    lut_texture: glow::Texture,
+    // inputs the textures were last filled for
+    environment: Option<SharedEnvironment>,
+    lut_key: Option<LutKey>,
+    lut_start: f32,
+}
+
+impl GpuPreview {
+    /// compiles the shaders, fails if the GL context is too old for them
+    pub fn new(gl: &glow::Context) -> Result<Self, String> {
+        let shader_version = egui_glow::ShaderVersion::get(gl);
+        if !shader_version.is_new_shader_interface() {
+            return Err(format!(
+                "GLSL {:?} is too old for the GPU preview",
+                shader_version
+            ));
+        }
+
+        let header = if shader_version.is_embedded() {
+            format!(
+                "{}\nprecision highp float;\n",
+                shader_version.version_declaration()
+            )
+        } else {
+            format!("{}\n", shader_version.version_declaration())
+        };
+
+        unsafe {
+            let program = gl.create_program()?;
+
+            let mut shaders = Vec::new();
+            for (shader_type, source) in [
+                (glow::VERTEX_SHADER, VERTEX_SHADER),
+                (glow::FRAGMENT_SHADER, FRAGMENT_SHADER),
+            ] {
+                let shader = gl.create_shader(shader_type)?;
+                gl.shader_source(shader, &format!("{}{}", header, source));
+                gl.compile_shader(shader);
+                if !gl.get_shader_compile_status(shader) {
+                    return Err(gl.get_shader_info_log(shader));
+                }
+                gl.attach_shader(program, shader);
+                shaders.push(shader);
+            }
+
+            gl.link_program(program);
+            if !gl.get_program_link_status(program) {
+                return Err(gl.get_program_info_log(program));
+            }
+
+            for shader in shaders {
+                gl.detach_shader(program, shader);
+                gl.delete_shader(shader);
+            }
+
+            let environment_texture = gl.create_texture()?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(environment_texture));
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::LINEAR as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                glow::LINEAR as i32,
+            );
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::REPEAT as i32);
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_WRAP_T,
+                glow::CLAMP_TO_EDGE as i32,
+            );
+
+            // the table is interpolated in the shader so no filtering is needed
+            let lut_texture = gl.create_texture()?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(lut_texture));
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                glow::NEAREST as i32,
+            );
+
+            gl.bind_texture(glow::TEXTURE_2D, None);
+
+            Ok(Self {
+                program,
+                vertex_array: gl.create_vertex_array()?,
+                environment_texture,
+                lut_texture,
+                environment: None,
+                lut_key: None,
+                lut_start: 0_f32,
+            })
+        }
+    }
+
+    /// returns whether the shader can draw the scene with the settings, wormholes, magnification,
+    /// blackbody color shifts, companions and jets fall back to the CPU preview, as do environments
+    /// sampled other than bilinearly like the texture or filtered with mipmaps, and denoised
+    /// renders
+    pub fn supports(scene: &Scene, render_settings: RenderSettings) -> bool {
+        render_settings.sampling == SamplingMode::Bilinear
+            && !render_settings.mipmaps
+            && render_settings.denoise.is_none()
+            && scene.spacetime == Spacetime::BlackHole
+            && !scene.magnification
+            && (scene.color_shift || scene.beaming == Beaming::Off)
+            && !(scene.color_shift && scene.color_shift_mode == ColorShiftMode::Blackbody)
+            && scene.companion.is_none()
+            && scene.jet.is_none()
+            && scene.neutron_star.is_none()
+            && scene.binary.is_none()
+            && scene.transparent_star.is_none()
+            && !scene.tidal_stretch
+            && scene.view_twist() == 0_f64
+            && scene.env.radius.is_none()
+            && !scene.env.is_animated()
+    }
+
+    /// adds a paint callback drawing the scene into the rect
+    pub fn paint(
+        preview: &Arc<Mutex<GpuPreview>>,
+        ui: &egui::Ui,
+        rect: egui::Rect,
+        scene: Scene,
+        render_settings: RenderSettings,
+    ) {
+        let preview = preview.clone();
+
+        let callback = egui::PaintCallback {
+            rect,
+            callback: Arc::new(egui_glow::CallbackFn::new(move |info, painter| {
+                let resolution = [
+                    info.viewport.width() * info.pixels_per_point,
+                    info.viewport.height() * info.pixels_per_point,
+                ];
+                preview
+                    .lock()
+                    .unwrap()
+                    .draw(painter.gl(), &scene, render_settings, resolution);
+            })),
+        };
+
+        ui.painter().add(callback);
+    }
+
+    fn draw(
+        &mut self,
+        gl: &glow::Context,
+        scene: &Scene,
+        render_settings: RenderSettings,
+        resolution: [f32; 2],
+    ) {
+        self.update_environment(gl, &scene.env);
+        self.update_lut(gl, scene, render_settings);
+
+        // same grading as the CPU render with the scene exposure fading to black at the minimum
+        let gain = if scene.exposure <= MIN_EXPOSURE {
+            0_f32
+        } else {
+            2_f32.powf(render_settings.exposure + scene.exposure as f32)
+        };
+
+        // at right angles to the fall the denominator is one leaving only the scale
+        let speed = if scene.has_aberration() {
+            scene.diver_speed()
+        } else {
+            0_f64
+        };
+        let shift_scale = frequency_ratio(
+            PI / 2_f64,
+            scene.diver_speed(),
+            scene.lensing,
+            scene.has_aberration(),
+        );
+
+        // columns of the rotation in Scene::trace_with_order
+        let (sin, cos) = (scene.scaled_diver().orbit_angle() as f32).sin_cos();
+        let orbit = [cos, 0_f32, sin, 0_f32, 1_f32, 0_f32, -sin, 0_f32, cos];
+
+        let camera: Vec<f32> = [
+            scene.camera.right(),
+            scene.camera.up(),
+            scene.camera.facing(),
+        ]
+        .iter()
+        .flat_map(|column| column.iter().map(|&value| value as f32))
+        .collect();
+
+        unsafe {
+            gl.use_program(Some(self.program));
+
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.environment_texture));
+            gl.active_texture(glow::TEXTURE1);
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.lut_texture));
+
+            let location = |name| gl.get_uniform_location(self.program, name);
+            gl.uniform_1_i32(location("u_environment").as_ref(), 0);
+            gl.uniform_1_i32(location("u_lut").as_ref(), 1);
+            gl.uniform_1_i32(location("u_lut_size").as_ref(), LUT_SIZE as i32);
+            gl.uniform_1_f32(location("u_lut_start").as_ref(), self.lut_start);
+            gl.uniform_matrix_3_f32_slice(location("u_camera").as_ref(), false, &camera);
+            let velocity = scene.view_velocity();
+            gl.uniform_3_f32(
+                location("u_velocity").as_ref(),
+                velocity.x as f32,
+                velocity.y as f32,
+                velocity.z as f32,
+            );
+            gl.uniform_matrix_3_f32_slice(location("u_orbit").as_ref(), false, &orbit);
+            gl.uniform_1_f32(location("u_fov").as_ref(), scene.camera.fov as f32);
+            gl.uniform_2_f32(
+                location("u_resolution").as_ref(),
+                resolution[0],
+                resolution[1],
+            );
+            gl.uniform_1_i32(
+                location("u_projection").as_ref(),
+                match render_settings.projection {
+                    Projection::Perspective => 0,
+                    Projection::Equirectangular => 1,
+                    Projection::CubeMap => 2,
+                    Projection::Fisheye => 3,
+                },
+            );
+            gl.uniform_1_f32(location("u_gain").as_ref(), gain);
+            let [r, g, b] = render_settings.white_balance;
+            gl.uniform_3_f32(location("u_white_balance").as_ref(), r, g, b);
+            gl.uniform_1_f32(location("u_shift_speed").as_ref(), speed as f32);
+            gl.uniform_1_f32(location("u_shift_scale").as_ref(), shift_scale as f32);
+            gl.uniform_1_f32(
+                location("u_shift_strength").as_ref(),
+                if scene.color_shift {
+                    scene.color_shift_strength as f32
+                } else {
+                    0_f32
+                },
+            );
+
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.draw_arrays(glow::TRIANGLES, 0, 3);
+
+            gl.bind_vertex_array(None);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            gl.active_texture(glow::TEXTURE0);
+            gl.use_program(None);
+        }
+    }
+
+    /// renders the scene at the resolution of the settings into an image, without super sampling
+    /// or dithering, fails if the framebuffer can't be created
+    pub fn render_image(
+        &mut self,
+        gl: &glow::Context,
+        scene: &Scene,
+        render_settings: RenderSettings,
+    ) -> Result<RgbImage, String> {
+        let (width, height) = (
+            render_settings.resolution.x.max(1),
+            render_settings.resolution.y.max(1),
+        );
+        let mut pixels = vec![0_u8; (width * height * 4) as usize];
+
+        unsafe {
+            let texture = gl.create_texture()?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA8 as i32,
+                width as i32,
+                height as i32,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                None,
+            );
+            gl.bind_texture(glow::TEXTURE_2D, None);
+
+            let framebuffer = gl.create_framebuffer()?;
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+            gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                Some(texture),
+                0,
+            );
+
+            let complete =
+                gl.check_framebuffer_status(glow::FRAMEBUFFER) == glow::FRAMEBUFFER_COMPLETE;
+            if complete {
+                gl.viewport(0, 0, width as i32, height as i32);
+                self.draw(gl, scene, render_settings, [width as f32, height as f32]);
+                gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+                gl.read_pixels(
+                    0,
+                    0,
+                    width as i32,
+                    height as i32,
+                    glow::RGBA,
+                    glow::UNSIGNED_BYTE,
+                    glow::PixelPackData::Slice(&mut pixels),
+                );
+            }
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.delete_framebuffer(framebuffer);
+            gl.delete_texture(texture);
+
+            if !complete {
+                return Err("the framebuffer for the GPU render is incomplete".to_owned());
+            }
+        }
+
+        // the rows are read from the bottom up
+        Ok(RgbImage::from_fn(width, height, |x, y| {
+            let i = (((height - 1 - y) * width + x) * 4) as usize;
+            image::Rgb([pixels[i], pixels[i + 1], pixels[i + 2]])
+        }))
+    }
+
+    /// bakes the environment into the texture if it changed
+    fn update_environment(&mut self, gl: &glow::Context, env: &SharedEnvironment) {
+        if self.environment.as_ref() == Some(env) {
+            return;
+        }
+
+        let width = 2 * ENVIRONMENT_HEIGHT;
+        let pixels: Vec<u8> = (0..width * ENVIRONMENT_HEIGHT)
+            .into_par_iter()
+            .flat_map_iter(|i| {
+                let (x, y) = (i % width, i / width);
+                let theta = PI * (y as f64 + 0.5) / ENVIRONMENT_HEIGHT as f64;
+                let phi = PI * (x as f64 + 0.5) / ENVIRONMENT_HEIGHT as f64;
+                let [r, g, b] = env.get_pixel(MapAngle::new(theta, phi)).0;
+                [r, g, b, 255]
+            })
+            .collect();
+
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.environment_texture));
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA8 as i32,
+                width as i32,
+                ENVIRONMENT_HEIGHT as i32,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                Some(&pixels),
+            );
+            gl.bind_texture(glow::TEXTURE_2D, None);
+        }
+
+        self.environment = Some(env.clone());
+    }
+
+    /// recomputes the deflection lookup table if the radius or model changed
+    fn update_lut(&mut self, gl: &glow::Context, scene: &Scene, render_settings: RenderSettings) {
+        let key = LutKey {
+            lensing: scene.lensing,
+            aberration: scene.has_aberration(),
+            spin: scene.active_spin(),
+            charge: scene.active_charge(),
+            custom_metric: scene.active_custom_metric().cloned(),
+            radius: scene.scaled_diver().position(),
+            max_half_orbits: render_settings.max_half_orbits,
+            lensing_backend: render_settings.lensing_backend,
+        };
+        if self.lut_key.as_ref() == Some(&key) {
+            return;
+        }
+
+        let lut = DeflectionLut::build(scene, render_settings, LUT_SIZE);
+        let table: Vec<f32> = lut
+            .entries
+            .iter()
+            .flat_map(|entry| match entry {
+                Some((theta, phi, _)) => [*theta as f32, *phi as f32],
+                None => [-1_f32, 0_f32],
+            })
+            .collect();
+
+        let bytes: Vec<u8> = table.iter().flat_map(|value| value.to_ne_bytes()).collect();
+
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.lut_texture));
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RG32F as i32,
+                LUT_SIZE as i32,
+                1,
+                0,
+                glow::RG,
+                glow::FLOAT,
+                Some(&bytes),
+            );
+            gl.bind_texture(glow::TEXTURE_2D, None);
+        }
+
+        self.lut_key = Some(key);
+        self.lut_start = lut.start as f32;
+    }
+}
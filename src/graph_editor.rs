@@ -0,0 +1,272 @@
+use crate::{
+    app::BHDiver,
+    keyframe::{segment_control_points, AnimatedField, FieldHandles, Handle},
+    timeline::Timeline,
+};
+
+const POINT_SIZE: f32 = 4_f32;
+
+/// Change to a keyframe made by dragging in the graph
+enum GraphEdit {
+    Value(i32, f64),
+    Incoming(i32, Handle),
+    Outgoing(i32, Handle),
+}
+
+/// Keyframe of the shown field with the positions of its handles as (frame, value) points
+struct GraphKeyframe {
+    frame: i32,
+    value: f64,
+    incoming: Option<(f32, f64)>,
+    outgoing: Option<(f32, f64)>,
+}
+
+pub struct GraphEditor;
+
+impl GraphEditor {
+    pub fn build(ui: &mut egui::Ui, app: &mut BHDiver) {
+        // the shown field is kept in temporary ui memory
+        let field_id = egui::Id::new("graph editor field");
+        let mut field_index: usize = ui.data_mut(|reader| reader.get_temp(field_id).unwrap_or(0));
+
+        let field = AnimatedField::ALL[field_index];
+
+        ui.horizontal(|ui| {
+            for (i, field) in AnimatedField::ALL.iter().enumerate() {
+                ui.selectable_value(&mut field_index, i, field.name());
+            }
+
+            ui.separator();
+
+            if ui
+                .button("Reset handles")
+                .on_hover_text("Interpolate this field linearly between all keyframes")
+                .clicked()
+            {
+                let frames: Vec<i32> = app.timeline.keyframes().keys().copied().collect();
+                for frame in frames {
+                    if let Some(keyframe) = app.timeline.keyframe_mut(frame) {
+                        *keyframe.handles_mut(field) = FieldHandles::default();
+                    }
+                }
+            }
+        });
+
+        ui.data_mut(|reader| reader.insert_temp(field_id, field_index));
+
+        Self::show_graph(ui, &mut app.timeline, field);
+    }
+
+    fn graph_keyframes(timeline: &Timeline, field: AnimatedField) -> Vec<GraphKeyframe> {
+        let keyframes: Vec<_> = timeline
+            .keyframes()
+            .iter()
+            .map(|(&frame, keyframe)| (frame, keyframe))
+            .collect();
+
+        keyframes
+            .iter()
+            .enumerate()
+            .map(|(i, &(frame, keyframe))| GraphKeyframe {
+                frame,
+                value: field.get(&keyframe.scene),
+                incoming: i
+                    .checked_sub(1)
+                    .map(|j| segment_control_points(field, keyframes[j], keyframes[i])[1]),
+                outgoing: keyframes
+                    .get(i + 1)
+                    .map(|&next| segment_control_points(field, keyframes[i], next)[0]),
+            })
+            .collect()
+    }
+
+    fn show_graph(ui: &mut egui::Ui, timeline: &mut Timeline, field: AnimatedField) {
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), ui.available_height().max(200_f32)),
+            egui::Sense::hover(),
+        );
+        let rect = response.rect;
+        painter.rect_filled(rect, egui::Rounding::none(), ui.visuals().extreme_bg_color);
+
+        let first_frame = timeline.left_most_frame();
+        let last_frame = timeline.right_most_frame();
+
+        // sample the curve at every frame
+        let samples: Vec<(i32, f64)> = (first_frame..=last_frame)
+            .map(|frame| (frame, field.get(&timeline.get_scene(frame))))
+            .collect();
+        let graph_keyframes = Self::graph_keyframes(timeline, field);
+
+        // value range containing the curve and all of the handles
+        let values =
+            samples
+                .iter()
+                .map(|&(_, value)| value)
+                .chain(graph_keyframes.iter().flat_map(|keyframe| {
+                    [keyframe.incoming, keyframe.outgoing]
+                        .into_iter()
+                        .flatten()
+                        .map(|(_, value)| value)
+                }));
+        let (mut min_value, mut max_value) = values
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+                (min.min(value), max.max(value))
+            });
+        let margin = ((max_value - min_value) * 0.1).max(1e-3);
+        min_value -= margin;
+        max_value += margin;
+
+        let frame_range = (last_frame - first_frame).max(1) as f32;
+
+        // transforms between (frame, value) and egui coordinates
+        let to_screen = |frame: f32, value: f64| {
+            egui::pos2(
+                rect.left() + rect.width() * (frame - first_frame as f32) / frame_range,
+                rect.bottom()
+                    - rect.height() * ((value - min_value) / (max_value - min_value)) as f32,
+            )
+        };
+        let from_screen = |pos: egui::Pos2| {
+            (
+                first_frame as f32 + frame_range * (pos.x - rect.left()) / rect.width(),
+                min_value
+                    + (max_value - min_value) * ((rect.bottom() - pos.y) / rect.height()) as f64,
+            )
+        };
+
+        // value labels
+        let font = egui::TextStyle::Small.resolve(ui.style());
+        painter.text(
+            rect.left_top(),
+            egui::Align2::LEFT_TOP,
+            format!("{:.3}", max_value),
+            font.clone(),
+            ui.visuals().weak_text_color(),
+        );
+        painter.text(
+            rect.left_bottom(),
+            egui::Align2::LEFT_BOTTOM,
+            format!("{:.3}", min_value),
+            font,
+            ui.visuals().weak_text_color(),
+        );
+
+        // curve
+        painter.add(egui::Shape::line(
+            samples
+                .iter()
+                .map(|&(frame, value)| to_screen(frame as f32, value))
+                .collect(),
+            ui.visuals().widgets.active.fg_stroke,
+        ));
+
+        // playhead
+        let mut play_head_stroke = ui.visuals().widgets.inactive.fg_stroke;
+        play_head_stroke.width = 1_f32;
+        painter.vline(
+            to_screen(timeline.current_frame as f32, 0_f64).x,
+            rect.y_range(),
+            play_head_stroke,
+        );
+
+        // keyframes and handles
+        let mut edits = Vec::new();
+        let selection = ui.visuals().selection;
+        for keyframe in graph_keyframes.iter() {
+            let key_pos = to_screen(keyframe.frame as f32, keyframe.value);
+
+            for (name, handle) in [
+                ("incoming", keyframe.incoming),
+                ("outgoing", keyframe.outgoing),
+            ] {
+                let Some((handle_frame, handle_value)) = handle else {
+                    continue;
+                };
+                let handle_pos = to_screen(handle_frame, handle_value);
+
+                painter.line_segment(
+                    [key_pos, handle_pos],
+                    ui.visuals().widgets.inactive.fg_stroke,
+                );
+                painter.rect_filled(
+                    egui::Rect::from_center_size(handle_pos, egui::Vec2::splat(POINT_SIZE * 1.5)),
+                    egui::Rounding::none(),
+                    ui.visuals().widgets.inactive.fg_stroke.color,
+                );
+
+                let handle_response = ui.interact(
+                    egui::Rect::from_center_size(handle_pos, egui::Vec2::splat(POINT_SIZE * 3_f32)),
+                    egui::Id::new("graph handle")
+                        .with(keyframe.frame)
+                        .with(name),
+                    egui::Sense::drag(),
+                );
+
+                if let Some(pos) = handle_response
+                    .dragged()
+                    .then(|| handle_response.interact_pointer_pos())
+                    .flatten()
+                {
+                    let (frame, value) = from_screen(pos);
+                    let frames = frame - keyframe.frame as f32;
+                    let value = value - keyframe.value;
+
+                    // handles can't cross to the other side of their keyframe
+                    edits.push(if name == "incoming" {
+                        GraphEdit::Incoming(
+                            keyframe.frame,
+                            Handle {
+                                frames: frames.min(0_f32),
+                                value,
+                            },
+                        )
+                    } else {
+                        GraphEdit::Outgoing(
+                            keyframe.frame,
+                            Handle {
+                                frames: frames.max(0_f32),
+                                value,
+                            },
+                        )
+                    });
+                }
+            }
+
+            painter.circle(key_pos, POINT_SIZE, selection.bg_fill, selection.stroke);
+
+            let key_response = ui.interact(
+                egui::Rect::from_center_size(key_pos, egui::Vec2::splat(POINT_SIZE * 3_f32)),
+                egui::Id::new("graph keyframe").with(keyframe.frame),
+                egui::Sense::drag(),
+            );
+            if let Some(pos) = key_response
+                .dragged()
+                .then(|| key_response.interact_pointer_pos())
+                .flatten()
+            {
+                edits.push(GraphEdit::Value(keyframe.frame, from_screen(pos).1));
+            }
+        }
+
+        // apply the edits
+        for edit in edits {
+            match edit {
+                GraphEdit::Value(frame, value) => {
+                    if let Some(keyframe) = timeline.keyframe_mut(frame) {
+                        field.set(&mut keyframe.scene, value);
+                    }
+                }
+                GraphEdit::Incoming(frame, handle) => {
+                    if let Some(keyframe) = timeline.keyframe_mut(frame) {
+                        keyframe.handles_mut(field).incoming = Some(handle);
+                    }
+                }
+                GraphEdit::Outgoing(frame, handle) => {
+                    if let Some(keyframe) = timeline.keyframe_mut(frame) {
+                        keyframe.handles_mut(field).outgoing = Some(handle);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+// A bipolar relativistic jet launched from near the black hole
+//
+// Each lobe is a cone around the axis that is optically thin and glows with an emissivity falling
+// off as a power of the distance from the black hole. The plasma flows out radially with a bulk
+// Lorentz factor, so the light it gives off towards the diver is Doppler boosted by delta^3 where
+// delta = 1 / (gamma (1 - beta cos psi)) and psi is the angle between the flow and the light. The
+// emission is ray marched along the path of the ray and added on top of what lies behind it.
+
+use crate::{ray_path::RayPath, traits::Interpolate, units::Units};
+use nalgebra::Vector3;
+
+/// Most samples taken along a single segment of a ray
+const MAX_SAMPLES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Jet {
+    // direction of one lobe in the frame of the map, the other lobe points the opposite way
+    pub axis: Vector3<f64>,
+    // half opening angle of the cones in radians
+    pub opening_angle: f64,
+    // distance from the black hole the jet starts at
+    pub base_radius: f64,
+    // distance from the black hole the jet ends at
+    pub length: f64,
+    // power of the distance the emissivity falls off with
+    pub falloff: f64,
+    // bulk Lorentz factor of the outflow
+    pub lorentz_factor: f64,
+    // emissivity at the base, linear intensity per unit length
+    pub brightness: f64,
+    // linear color of the emission
+    pub color: [f32; 3],
+}
+
+impl Default for Jet {
+    fn default() -> Self {
+        Self {
+            // across the path of the diver so it is seen from the side
+            axis: Vector3::new(0_f64, 1_f64, 0_f64),
+            opening_angle: 5_f64.to_radians(),
+            base_radius: 3_f64,
+            length: 60_f64,
+            falloff: 2_f64,
+            lorentz_factor: 2_f64,
+            brightness: 4_f64,
+            color: [0.6, 0.7, 1_f32],
+        }
+    }
+}
+
+impl Interpolate for Jet {
+    fn interpolate(&self, other: &Self, factor: f32) -> Self {
+        let axis = Vector3::from_fn(|i, _| self.axis[i].interpolate(&other.axis[i], factor));
+
+        Self {
+            axis: if axis.norm() > 0_f64 { axis } else { self.axis },
+            opening_angle: self.opening_angle.interpolate(&other.opening_angle, factor),
+            base_radius: self.base_radius.interpolate(&other.base_radius, factor),
+            length: self.length.interpolate(&other.length, factor),
+            falloff: self.falloff.interpolate(&other.falloff, factor),
+            lorentz_factor: self
+                .lorentz_factor
+                .interpolate(&other.lorentz_factor, factor),
+            brightness: self.brightness.interpolate(&other.brightness, factor),
+            color: [0, 1, 2].map(|i| self.color[i].interpolate(&other.color[i], factor)),
+        }
+    }
+}
+
+impl Jet {
+    /// returns the emissivity at the point, zero outside of the cones
+    fn emissivity(&self, point: Vector3<f64>) -> f64 {
+        let distance = point.norm();
+        if distance < self.base_radius || distance > self.length {
+            return 0_f64;
+        }
+
+        // angle from the axis of the nearest lobe
+        let angle = (point.dot(&self.axis.normalize()).abs() / distance)
+            .min(1_f64)
+            .acos();
+        if angle >= self.opening_angle {
+            return 0_f64;
+        }
+
+        // fade out towards the edge of the cone
+        let profile = 1_f64 - (angle / self.opening_angle).powi(2);
+        self.brightness * (distance / self.base_radius).powf(-self.falloff) * profile
+    }
+
+    /// returns the Doppler factor of light leaving the point in the direction
+    fn doppler_factor(&self, point: Vector3<f64>, direction: Vector3<f64>) -> f64 {
+        let gamma = self.lorentz_factor.max(1_f64);
+        let beta = (1_f64 - gamma.powi(-2)).sqrt();
+        let cos_psi = point.normalize().dot(&direction.normalize());
+        1_f64 / (gamma * (1_f64 - beta * cos_psi))
+    }
+
+    /// returns the linear intensity the jet adds to the ray along the path
+    pub fn intensity(&self, path: &RayPath) -> f64 {
+        let mut intensity = 0_f64;
+
+        for (start, end, _) in path.segments(self.length) {
+            let step = end - start;
+            let length = step.norm();
+
+            // skip segments that stay further out than the end of the jet
+            let t = (-start.dot(&step) / length.powi(2)).clamp(0_f64, 1_f64);
+            if length == 0_f64 || (start + t * step).norm() > self.length {
+                continue;
+            }
+
+            // sample finely enough to resolve the width of the cones
+            let spacing = start.norm().min(end.norm()).max(self.base_radius)
+                * self.opening_angle.max(0.01)
+                / 4_f64;
+            let samples = ((length / spacing).ceil() as usize).clamp(1, MAX_SAMPLES);
+            let ds = length / samples as f64;
+
+            // the ray is followed back so the light travels from the end to the start
+            let direction = -step;
+            for i in 0..samples {
+                let point = start + step * ((i as f64 + 0.5) / samples as f64);
+                let emissivity = self.emissivity(point);
+                if emissivity > 0_f64 {
+                    intensity += emissivity * self.doppler_factor(point, direction).powi(3) * ds;
+                }
+            }
+        }
+
+        intensity
+    }
+
+    /// returns the linear color with the light of the jet along the path added to it
+    pub fn shine(&self, linear: [f64; 3], path: &RayPath) -> [f64; 3] {
+        let intensity = self.intensity(path);
+        [0, 1, 2].map(|i| linear[i] + intensity * self.color[i] as f64)
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, units: &Units) {
+        egui::Grid::new("jet_grid").num_columns(2).show(ui, |ui| {
+            ui.label("Axis");
+            ui.horizontal(|ui| {
+                for i in 0..3 {
+                    ui.add(
+                        egui::DragValue::new(&mut self.axis[i])
+                            .clamp_range(-1_f64..=1_f64)
+                            .speed(0.01),
+                    );
+                }
+            })
+            .response
+            .on_hover_text("Direction of one lobe, the diver starts on the -z axis");
+            if self.axis.norm() == 0_f64 {
+                self.axis = Vector3::new(0_f64, 1_f64, 0_f64);
+            }
+            ui.end_row();
+
+            ui.label("Opening angle");
+            let mut opening_angle = self.opening_angle.to_degrees();
+            if ui
+                .add(
+                    egui::DragValue::new(&mut opening_angle)
+                        .clamp_range(0.1..=90_f64)
+                        .speed(0.1)
+                        .suffix("°"),
+                )
+                .on_hover_text("Half angle of the cone of each lobe")
+                .changed()
+            {
+                self.opening_angle = opening_angle.to_radians();
+            }
+            ui.end_row();
+
+            ui.label("Base radius");
+            ui.add(
+                units
+                    .length_drag_value(&mut self.base_radius, 0.05)
+                    .clamp_range(0.01..=self.length),
+            );
+            ui.end_row();
+
+            ui.label("Length");
+            ui.add(
+                units
+                    .length_drag_value(&mut self.length, 0.5)
+                    .clamp_range(self.base_radius..=f64::MAX),
+            );
+            ui.end_row();
+
+            ui.label("Falloff");
+            ui.add(
+                egui::DragValue::new(&mut self.falloff)
+                    .clamp_range(0_f64..=10_f64)
+                    .speed(0.01),
+            )
+            .on_hover_text(
+                "Power of the distance from the black hole the emissivity falls off with",
+            );
+            ui.end_row();
+
+            ui.label("Lorentz factor");
+            ui.add(
+                egui::DragValue::new(&mut self.lorentz_factor)
+                    .clamp_range(1_f64..=100_f64)
+                    .speed(0.01),
+            )
+            .on_hover_text(
+                "Bulk Lorentz factor of the outflow, the lobe coming towards the diver is boosted \
+                and the other is dimmed",
+            );
+            ui.end_row();
+
+            ui.label("Brightness");
+            ui.add(
+                egui::DragValue::new(&mut self.brightness)
+                    .clamp_range(0_f64..=f64::MAX)
+                    .speed(0.01),
+            );
+            ui.end_row();
+
+            ui.label("Color");
+            ui.color_edit_button_rgb(&mut self.color);
+            ui.end_row();
+        });
+    }
+}
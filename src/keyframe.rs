@@ -0,0 +1,199 @@
+use crate::scene::{Scene, MIN_EXPOSURE, MIN_MASS};
+
+/// Scalar scene parameters whose animation curves can be shaped with bezier handles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimatedField {
+    InitialRadius,
+    Time,
+    Radius,
+    Fov,
+    Exposure,
+    Spin,
+    Charge,
+    Mass,
+}
+
+impl AnimatedField {
+    pub const ALL: [AnimatedField; 8] = [
+        AnimatedField::InitialRadius,
+        AnimatedField::Time,
+        AnimatedField::Radius,
+        AnimatedField::Fov,
+        AnimatedField::Exposure,
+        AnimatedField::Spin,
+        AnimatedField::Charge,
+        AnimatedField::Mass,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnimatedField::InitialRadius => "Initial distance",
+            AnimatedField::Time => "Proper time",
+            AnimatedField::Radius => "Radius",
+            AnimatedField::Fov => "Vertical FOV",
+            AnimatedField::Exposure => "Exposure",
+            AnimatedField::Spin => "Spin",
+            AnimatedField::Charge => "Charge",
+            AnimatedField::Mass => "Mass",
+        }
+    }
+
+    pub fn get(&self, scene: &Scene) -> f64 {
+        match self {
+            AnimatedField::InitialRadius => scene.diver.initial_radius(),
+            AnimatedField::Time => scene.diver.time(),
+            AnimatedField::Radius => scene.diver_radius(),
+            AnimatedField::Fov => scene.camera.fov,
+            AnimatedField::Exposure => scene.exposure,
+            AnimatedField::Spin => scene.spin,
+            AnimatedField::Charge => scene.charge,
+            AnimatedField::Mass => scene.mass,
+        }
+    }
+
+    pub fn set(&self, scene: &mut Scene, value: f64) {
+        match self {
+            AnimatedField::InitialRadius => scene.diver.set_initial_radius(value),
+            AnimatedField::Time => scene.set_diver_time(value),
+            AnimatedField::Radius => scene.set_diver_radius(value),
+            AnimatedField::Fov => scene.camera.fov = value.clamp(0_f64, std::f64::consts::PI),
+            AnimatedField::Exposure => scene.exposure = value.max(MIN_EXPOSURE),
+            AnimatedField::Spin => scene.spin = value.clamp(0_f64, 1_f64),
+            AnimatedField::Charge => scene.charge = value.clamp(0_f64, 1_f64),
+            AnimatedField::Mass => scene.mass = value.max(MIN_MASS),
+        }
+    }
+
+    /// returns whether the field moves the diver between keyframes driving it this way, the
+    /// time and the radius of the diver can't both be followed
+    pub fn drives(&self, diver_drive: DiverDrive) -> bool {
+        match self {
+            AnimatedField::Time => diver_drive == DiverDrive::Time,
+            AnimatedField::Radius => diver_drive == DiverDrive::Radius,
+            _ => true,
+        }
+    }
+}
+
+/// What moves the diver along its trajectory from a keyframe to the next
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DiverDrive {
+    // the proper time is interpolated and the radius follows from it
+    #[default]
+    Time,
+    // the radius is interpolated and the time is solved for along the trajectory
+    Radius,
+}
+
+impl ToString for DiverDrive {
+    fn to_string(&self) -> String {
+        match self {
+            DiverDrive::Time => "Time".to_owned(),
+            DiverDrive::Radius => "Radius".to_owned(),
+        }
+    }
+}
+
+/// Bezier handle as an offset in frames and value from its keyframe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Handle {
+    pub frames: f32,
+    pub value: f64,
+}
+
+/// Handles of a keyframe for one field, handles that are not set give linear interpolation
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FieldHandles {
+    pub incoming: Option<Handle>,
+    pub outgoing: Option<Handle>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Keyframe {
+    pub scene: Scene,
+    handles: [FieldHandles; AnimatedField::ALL.len()],
+    // what moves the diver until the next keyframe
+    pub diver_drive: DiverDrive,
+}
+
+impl Keyframe {
+    pub fn new(scene: Scene) -> Self {
+        Self {
+            scene,
+            handles: Default::default(),
+            diver_drive: DiverDrive::Time,
+        }
+    }
+
+    pub fn handles(&self, field: AnimatedField) -> &FieldHandles {
+        &self.handles[field as usize]
+    }
+
+    pub fn handles_mut(&mut self, field: AnimatedField) -> &mut FieldHandles {
+        &mut self.handles[field as usize]
+    }
+}
+
+/// Returns the outgoing handle of the left keyframe and the incoming handle of the right keyframe
+/// of a segment as (frame, value) points, using the handles of a straight line where unset
+pub fn segment_control_points(
+    field: AnimatedField,
+    (left_frame, left): (i32, &Keyframe),
+    (right_frame, right): (i32, &Keyframe),
+) -> [(f32, f64); 2] {
+    let left_value = field.get(&left.scene);
+    let right_value = field.get(&right.scene);
+    let frames = (right_frame - left_frame) as f32;
+
+    let outgoing = left.handles(field).outgoing.unwrap_or(Handle {
+        frames: frames / 3_f32,
+        value: (right_value - left_value) / 3_f64,
+    });
+    let incoming = right.handles(field).incoming.unwrap_or(Handle {
+        frames: -frames / 3_f32,
+        value: (left_value - right_value) / 3_f64,
+    });
+
+    // keep the handles within the segment so the curve is a function of the frame
+    [
+        (
+            left_frame as f32 + outgoing.frames.clamp(0_f32, frames),
+            left_value + outgoing.value,
+        ),
+        (
+            right_frame as f32 + incoming.frames.clamp(-frames, 0_f32),
+            right_value + incoming.value,
+        ),
+    ]
+}
+
+/// Returns the value of a field at a frame between two keyframes following the bezier curve
+/// through their handles
+pub fn segment_value(
+    field: AnimatedField,
+    left: (i32, &Keyframe),
+    right: (i32, &Keyframe),
+    frame: f32,
+) -> f64 {
+    let [(x1, y1), (x2, y2)] = segment_control_points(field, left, right);
+    let (x0, y0) = (left.0 as f32, field.get(&left.1.scene));
+    let (x3, y3) = (right.0 as f32, field.get(&right.1.scene));
+
+    let bezier = |p0: f64, p1: f64, p2: f64, p3: f64, t: f64| {
+        let s = 1_f64 - t;
+        s * s * s * p0 + 3_f64 * s * s * t * p1 + 3_f64 * s * t * t * p2 + t * t * t * p3
+    };
+
+    // the frame coordinate is monotonic in t so solve for t by bisection
+    let (mut low, mut high) = (0_f64, 1_f64);
+    for _ in 0..40 {
+        let mid = (low + high) / 2_f64;
+        if bezier(x0 as f64, x1 as f64, x2 as f64, x3 as f64, mid) < frame as f64 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    bezier(y0, y1, y2, y3, (low + high) / 2_f64)
+}
@@ -0,0 +1,168 @@
+use crate::app::BHDiver;
+use egui::{Key, KeyboardShortcut, Modifiers};
+
+/// Actions that can be triggered from the keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    TogglePlayback,
+    NextFrame,
+    PreviousFrame,
+    JumpToEnd,
+    JumpToStart,
+    NextKeyframe,
+    PreviousKeyframe,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::TogglePlayback,
+        Action::NextFrame,
+        Action::PreviousFrame,
+        Action::JumpToEnd,
+        Action::JumpToStart,
+        Action::NextKeyframe,
+        Action::PreviousKeyframe,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::TogglePlayback => "Play / pause",
+            Action::NextFrame => "Next frame",
+            Action::PreviousFrame => "Previous frame",
+            Action::JumpToEnd => "Jump to end",
+            Action::JumpToStart => "Jump to start",
+            Action::NextKeyframe => "Next keyframe",
+            Action::PreviousKeyframe => "Previous keyframe",
+        }
+    }
+
+    pub fn default_shortcut(&self) -> KeyboardShortcut {
+        match self {
+            Action::TogglePlayback => KeyboardShortcut::new(Modifiers::NONE, Key::Space),
+            Action::NextFrame => KeyboardShortcut::new(Modifiers::NONE, Key::ArrowRight),
+            Action::PreviousFrame => KeyboardShortcut::new(Modifiers::NONE, Key::ArrowLeft),
+            Action::JumpToEnd => KeyboardShortcut::new(Modifiers::SHIFT, Key::ArrowRight),
+            Action::JumpToStart => KeyboardShortcut::new(Modifiers::SHIFT, Key::ArrowLeft),
+            Action::NextKeyframe => KeyboardShortcut::new(Modifiers::COMMAND, Key::ArrowRight),
+            Action::PreviousKeyframe => KeyboardShortcut::new(Modifiers::COMMAND, Key::ArrowLeft),
+        }
+    }
+}
+
+/// Remappable keyboard shortcuts of all actions
+pub struct Keymap {
+    shortcuts: [KeyboardShortcut; Action::ALL.len()],
+    // action waiting for the next key press to become its shortcut
+    recording: Option<Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            shortcuts: Action::ALL.map(|action| action.default_shortcut()),
+            recording: None,
+        }
+    }
+}
+
+impl Keymap {
+    pub fn shortcut(&self, action: Action) -> KeyboardShortcut {
+        self.shortcuts[action as usize]
+    }
+
+    pub fn set_shortcut(&mut self, action: Action, shortcut: KeyboardShortcut) {
+        self.shortcuts[action as usize] = shortcut;
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// returns whether the shortcut of the action was pressed, consuming the key press
+    ///
+    /// the shortcuts window is shown before the rest of the app so it consumes the key press when
+    /// recording a new shortcut
+    pub fn consume(&self, ui: &egui::Ui, action: Action) -> bool {
+        ui.input_mut(|input| input.consume_shortcut(&self.shortcut(action)))
+    }
+
+    /// returns the text describing the shortcut of the action for hover texts
+    pub fn format(&self, ctx: &egui::Context, action: Action) -> String {
+        ctx.format_shortcut(&self.shortcut(action))
+    }
+
+    /// records the next key press as the shortcut of the action being remapped
+    fn record(&mut self, ui: &egui::Ui) {
+        let Some(action) = self.recording else {
+            return;
+        };
+
+        let pressed = ui.input_mut(|input| {
+            let shortcut = input.events.iter().find_map(|event| match event {
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => Some(KeyboardShortcut::new(*modifiers, *key)),
+                _ => None,
+            })?;
+            input.consume_shortcut(&shortcut);
+            Some(shortcut)
+        });
+
+        match pressed {
+            // escape cancels the recording
+            Some(KeyboardShortcut {
+                key: Key::Escape, ..
+            }) => self.recording = None,
+            Some(shortcut) => {
+                self.set_shortcut(action, shortcut);
+                self.recording = None;
+            }
+            None => (),
+        }
+    }
+
+    pub fn build(ui: &mut egui::Ui, app: &mut BHDiver) {
+        let keymap = &mut app.settings.keymap;
+
+        keymap.record(ui);
+
+        egui::Grid::new("keymap_grid")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for action in Action::ALL {
+                    ui.label(action.name());
+
+                    let text = if keymap.recording == Some(action) {
+                        "Press a key…".to_owned()
+                    } else {
+                        keymap.format(ui.ctx(), action)
+                    };
+                    if ui
+                        .button(text)
+                        .on_hover_text("Click to change, escape to cancel")
+                        .clicked()
+                    {
+                        keymap.recording = Some(action);
+                    }
+
+                    // warn about shortcuts shared with other actions
+                    let shortcut = keymap.shortcut(action);
+                    if Action::ALL
+                        .iter()
+                        .any(|&other| other != action && keymap.shortcut(other) == shortcut)
+                    {
+                        ui.label("⚠").on_hover_text("Shared with another action");
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if ui.button("Reset to defaults").clicked() {
+            keymap.reset();
+        }
+    }
+}
@@ -1,16 +1,53 @@
 pub mod animation;
 /// Units are G = c = M = 1
 pub mod app;
+pub mod binary;
 pub mod camera;
+pub mod companion;
+pub mod deflection_lut;
+pub mod denoise;
+pub mod diagnostics;
 pub mod diver;
+pub mod einstein_ring;
+pub mod embedding_view;
 pub mod environment;
+pub mod filename_template;
+pub mod geodesic;
+pub mod gpu_preview;
+pub mod graph_editor;
+pub mod jet;
+pub mod keyframe;
+pub mod keymap;
+pub mod light_curve;
+pub mod manifest;
 pub mod math;
 pub mod math_utils;
+pub mod matte_pass;
+pub mod metric;
+pub mod neutron_star;
+pub mod notifications;
+pub mod pass_recorder;
 pub mod preview_manager;
+pub mod progress;
+pub mod ray_inspector;
+pub mod ray_path;
+pub mod redshift;
+pub mod redshift_pass;
+pub mod reference;
 pub mod render;
+pub mod render_view;
 pub mod scene;
 pub mod settings;
+pub mod spacetime_diagram;
 pub mod spherical_angle;
+pub mod stereo;
+pub mod telemetry;
+pub mod tiff_output;
+pub mod tiles;
 pub mod timeline;
 pub mod traits;
+pub mod units;
+pub mod uv_map;
+pub mod video_output;
 pub mod windows;
+pub mod worldline_plot;
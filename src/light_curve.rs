@@ -0,0 +1,67 @@
+// Writes the brightness of every frame of an animation render as a CSV light curve
+//
+// The brightness is the relative luminance of the linear pixel values, summed over the image and
+// averaged per pixel. The values are taken before light brighter than white is clipped, so bright
+// frames don't saturate.
+
+use crate::render::sidecar_path;
+use image::Rgb32FImage;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Weights of the linear red, green and blue channels in the luminance
+const LUMINANCE_WEIGHTS: [f64; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Returns the path of the light curve written alongside a frame sequence, `name.png` becomes
+/// `name.light_curve.csv`
+pub fn light_curve_path(output_path: &Path) -> PathBuf {
+    sidecar_path(output_path, "light_curve", "csv")
+}
+
+/// Returns the total linear luminance of the image and its mean per pixel
+pub fn image_brightness(image: &Rgb32FImage) -> (f64, f64) {
+    let total: f64 = image
+        .pixels()
+        .map(|pixel| {
+            (0..3)
+                .map(|i| LUMINANCE_WEIGHTS[i] * pixel[i] as f64)
+                .sum::<f64>()
+        })
+        .sum();
+    let n_pixels = (image.width() as u64 * image.height() as u64).max(1);
+    (total, total / n_pixels as f64)
+}
+
+/// Light curve being written while the frames render
+pub struct LightCurve {
+    writer: BufWriter<File>,
+}
+
+impl LightCurve {
+    /// creates the file and writes the header
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "frame,total_luminance,mean_luminance")?;
+        writer.flush()?;
+        Ok(Self { writer })
+    }
+
+    /// opens the file to add frames after the ones already in it, creating it if it doesn't exist
+    pub fn append(path: &Path) -> io::Result<Self> {
+        if !path.exists() {
+            return Self::create(path);
+        }
+        let writer = BufWriter::new(OpenOptions::new().append(true).open(path)?);
+        Ok(Self { writer })
+    }
+
+    /// appends the brightness of the frame, flushing so interrupted renders keep what they have
+    pub fn add_frame(&mut self, frame: i32, image: &Rgb32FImage) -> io::Result<()> {
+        let (total, mean) = image_brightness(image);
+        writeln!(self.writer, "{},{},{}", frame, total, mean)?;
+        self.writer.flush()
+    }
+}
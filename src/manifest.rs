@@ -0,0 +1,218 @@
+// Writes a JSON index of an animation render listing what every output frame contains
+
+use crate::{
+    animation::Animation,
+    binary::Binary,
+    camera::Camera,
+    companion::Companion,
+    jet::Jet,
+    neutron_star::NeutronStar,
+    render::{sidecar_path, RenderSettings},
+};
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Returns the path of the manifest written alongside a frame sequence, `name.png` becomes
+/// `name.manifest.json`
+///
+/// strided renders each write their own manifest, `name.manifest.1of4.json` for offset 1 and
+/// stride 4, so that renders of the other strides into the same folder don't overwrite it
+pub fn manifest_path(output_path: &Path, (stride, offset): (usize, usize)) -> PathBuf {
+    if stride > 1 {
+        sidecar_path(
+            output_path,
+            &format!("manifest.{}of{}", offset, stride),
+            "json",
+        )
+    } else {
+        sidecar_path(output_path, "manifest", "json")
+    }
+}
+
+/// Writes the manifest of the frames of the animation, `frame_path` gives the file of each frame
+///
+/// the output only depends on its inputs so rendering the same animation twice gives the same file
+pub fn write_manifest(
+    path: &Path,
+    animation: &Animation,
+    render_settings: RenderSettings,
+    fps: f32,
+    (stride, offset): (usize, usize),
+    frame_path: impl Fn(i32) -> PathBuf,
+) -> io::Result<()> {
+    let mut json = String::new();
+
+    json.push_str("{\n");
+    let _ = writeln!(json, "  \"frame_count\": {},", animation.n_frames());
+    let _ = writeln!(json, "  \"fps\": {},", fps);
+    let _ = writeln!(json, "  \"stride\": {},", stride);
+    let _ = writeln!(json, "  \"offset\": {},", offset);
+    let _ = writeln!(
+        json,
+        "  \"render_settings\": {},",
+        render_settings_json(render_settings)
+    );
+    json.push_str("  \"frames\": [");
+
+    for (i, frame) in animation.frames().iter().enumerate() {
+        let scene = &frame.1;
+        let file = frame_path(frame.0);
+        let file_name = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+
+        if i > 0 {
+            json.push(',');
+        }
+        let _ = write!(
+            json,
+            "\n    {{\"file\": {}, \"frame\": {}, \"metric\": {}, \"trajectory\": {}, \"initial_radius\": {}, \"energy\": {}, \"outward\": {}, \"time\": {}, \"radius\": {}, \"mass\": {}, \"lensing\": {}, \"aberration\": {}, \"magnification\": {}, \"spin\": {}, \"charge\": {}, \"exposure\": {}, \"companion\": {}, \"neutron_star\": {}, \"binary\": {}, \"transparent_star_radius\": {}, \"jet\": {}, \"camera\": {}}}",
+            json_string(file_name),
+            frame.0,
+            json_string(&scene.spacetime.to_string()),
+            json_string(&scene.diver.trajectory.to_string()),
+            scene.diver.initial_radius(),
+            scene.diver.energy(),
+            scene.diver.outward,
+            scene.diver.time(),
+            scene.diver_radius(),
+            scene.mass,
+            scene.lensing,
+            scene.aberration,
+            scene.magnification,
+            scene.spin,
+            scene.charge,
+            scene.exposure,
+            scene
+                .companion
+                .as_ref()
+                .map_or("null".to_owned(), companion_json),
+            scene
+                .neutron_star
+                .as_ref()
+                .map_or("null".to_owned(), neutron_star_json),
+            scene.binary.as_ref().map_or("null".to_owned(), binary_json),
+            scene
+                .transparent_star
+                .map_or("null".to_owned(), |star| star.radius.to_string()),
+            scene.jet.as_ref().map_or("null".to_owned(), jet_json),
+            camera_json(&scene.camera),
+        );
+    }
+
+    json.push_str("\n  ]\n}\n");
+
+    fs::write(path, json)
+}
+
+fn render_settings_json(render_settings: RenderSettings) -> String {
+    format!(
+        "{{\"projection\": {}, \"resolution\": [{}, {}], \"super_sampling\": {}, \"exposure\": {}, \"white_balance\": [{}, {}, {}], \"dither\": {}, \"sixteen_bit\": {}, \"render_mode\": {}, \"sampling\": {}, \"mipmaps\": {}, \"crop\": {}, \"stereo\": {}, \"denoise\": {}}}",
+        json_string(&render_settings.projection.to_string()),
+        render_settings.resolution.x,
+        render_settings.resolution.y,
+        render_settings
+            .super_sampling
+            .map_or("null".to_owned(), |factor| factor.to_string()),
+        render_settings.exposure,
+        render_settings.white_balance[0],
+        render_settings.white_balance[1],
+        render_settings.white_balance[2],
+        render_settings.dither,
+        render_settings.sixteen_bit,
+        json_string(&render_settings.render_mode.to_string()),
+        json_string(&render_settings.sampling.to_string()),
+        render_settings.mipmaps,
+        crop_json(render_settings),
+        render_settings
+            .eye_separation()
+            .map_or("null".to_owned(), |eye_separation| eye_separation.to_string()),
+        render_settings
+            .denoise
+            .map_or("null".to_owned(), |strength| strength.to_string()),
+    )
+}
+
+/// returns the top left pixel and size of the crop as `[x, y, width, height]`, null without one
+fn crop_json(render_settings: RenderSettings) -> String {
+    match render_settings.crop {
+        Some(_) => {
+            let (offset, size) = render_settings.region();
+            format!("[{}, {}, {}, {}]", offset.x, offset.y, size.x, size.y)
+        }
+        None => "null".to_owned(),
+    }
+}
+
+fn companion_json(companion: &Companion) -> String {
+    let position = companion.position;
+
+    format!(
+        "{{\"position\": [{}, {}, {}], \"radius\": {}}}",
+        position.x, position.y, position.z, companion.radius,
+    )
+}
+
+fn neutron_star_json(neutron_star: &NeutronStar) -> String {
+    format!("{{\"radius\": {}}}", neutron_star.radius)
+}
+
+fn binary_json(binary: &Binary) -> String {
+    format!(
+        "{{\"separation\": {}, \"mass_ratio\": {}, \"phase_degrees\": {}}}",
+        binary.separation,
+        binary.mass_ratio,
+        binary.phase.to_degrees(),
+    )
+}
+
+fn jet_json(jet: &Jet) -> String {
+    let axis = jet.axis;
+
+    format!(
+        "{{\"axis\": [{}, {}, {}], \"opening_angle_degrees\": {}, \"base_radius\": {}, \"length\": {}, \"falloff\": {}, \"lorentz_factor\": {}, \"brightness\": {}}}",
+        axis.x,
+        axis.y,
+        axis.z,
+        jet.opening_angle.to_degrees(),
+        jet.base_radius,
+        jet.length,
+        jet.falloff,
+        jet.lorentz_factor,
+        jet.brightness,
+    )
+}
+
+fn camera_json(camera: &Camera) -> String {
+    let vector = |v: nalgebra::Vector3<f64>| format!("[{}, {}, {}]", v.x, v.y, v.z);
+
+    format!(
+        "{{\"fov_degrees\": {}, \"observer\": {}, \"right\": {}, \"up\": {}, \"facing\": {}}}",
+        camera.fov.to_degrees(),
+        json_string(&camera.observer.to_string()),
+        vector(camera.right()),
+        vector(camera.up()),
+        vector(camera.facing()),
+    )
+}
+
+/// Returns the string as a quoted JSON string
+fn json_string(string: &str) -> String {
+    let mut json = String::from('"');
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
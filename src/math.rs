@@ -1,5 +1,6 @@
-// Contains the math for calculating conversions between rain angles and map angles in the vicinity of a Schwarzschild black hole
+// Contains the math for calculating conversions between rain angles and map angles in the vicinity of a Schwarzschild or Kerr black hole
 
+use crate::metric::Metric;
 use num_traits::{AsPrimitive, Float};
 use quadrature::integrate;
 use std::f64::consts::PI;
@@ -35,7 +36,7 @@ where
 }
 
 /// Returns the critical rain angle for this radius
-fn critical_rain_angle<T: Float + 'static>(r: T) -> T
+pub fn critical_rain_angle<T: Float + 'static>(r: T) -> T
 where
     i32: AsPrimitive<T>,
 {
@@ -45,69 +46,177 @@ where
     .acos()
 }
 
-/// Returns the map angle not garanteed to be normalized to any range
+/// Returns the angular radius of the shadow of the black hole around the direction towards it seen
+/// from this radius, by the diver falling from rest at infinity with the rain or by a static
+/// observer, None for static observers inside the horizon where they can't exist
+pub fn shadow_angular_radius(r: f64, static_observer: bool) -> Option<f64> {
+    if !static_observer {
+        return Some(critical_rain_angle(r));
+    }
+    if r <= 2_f64 {
+        return None;
+    }
+
+    // sin(alpha) = 3 sqrt(3) sqrt(1 - 2/r) / r, inside the photon sphere the shadow covers more
+    // than half the sky
+    let angle = (3_f64 * 3_f64.sqrt() * (1_f64 - 2_f64 / r).sqrt() / r)
+        .min(1_f64)
+        .asin();
+    Some(if r >= 3_f64 { angle } else { PI - angle })
+}
+
+/// Acceptable error in phi angle
+const PHI_ERROR: f64 = 1e-6;
+
+/// Acceptable error in the total deflection, which is only a few times 1e-4 at the largest impact
+/// parameters shown
+const DEFLECTION_ERROR: f64 = 1e-12;
+
+/// Inverse radius of the photon sphere, where the orbit integrand peaks sharply for photons
+/// with impact parameters close to the critical value
+const PHOTON_SPHERE_U: f64 = 1_f64 / 3_f64;
+
+/// Integrates the orbit equation dphi/du = b / sqrt(1 - b^2 u^2 (1 - 2u)) between the inverse
+/// radii u1 < u2
+///
+/// Intervals containing the photon sphere are split there so that the peak of the integrand
+/// lands on an endpoint, where the quadrature is most accurate. This keeps renders close to the
+/// horizon from showing numerical noise.
+fn integrate_orbit(b: f64, u1: f64, u2: f64) -> f64 {
+    let integrand = |u: f64| b / (1_f64 - b.powi(2) * u.powi(2) * (1_f64 - 2_f64 * u)).sqrt();
+
+    if u1 < PHOTON_SPHERE_U && PHOTON_SPHERE_U < u2 {
+        integrate(integrand, u1, PHOTON_SPHERE_U, PHI_ERROR).integral
+            + integrate(integrand, PHOTON_SPHERE_U, u2, PHI_ERROR).integral
+    } else {
+        integrate(integrand, u1, u2, PHI_ERROR).integral
+    }
+}
+
+/// Returns the angle the photon sweeps around the black hole on its way from infinity to the
+/// observer, not garanteed to be normalized to any range
 fn map_angle_from_impact_parameter<T: Float + 'static>(theta_rain: T, b: T, r: T) -> T
 where
     i32: AsPrimitive<T>,
     f64: AsPrimitive<T>,
     T: AsPrimitive<f64>,
 {
-    // Acceptable error in phi angle
-    const PHI_ERROR: f64 = 1e-6;
-
-    // We transform the usual integrand in order to numerically integrate from and infinite radius
-    // This also changes the bounds of integration below
-    fn integrand(x: f64, b: f64) -> f64 {
-        b / ((1_f64 / (x - 1_f64).powi(4)
-            - (b.powi(2) * (1_f64 + 2_f64 * (x - 1_f64)) / (x - 1_f64).powi(2)))
-        .sqrt()
-            * (x - 1_f64).powi(2))
-    }
+    // We integrate over the inverse radius u = 1/r so that the photon coming from an infinite
+    // radius starts at u = 0 and the integrand stays finite all the way down to the horizon
+    let u: f64 = 1_f64 / r.as_();
 
     if photon_is_incoming(theta_rain, r) {
-        integrate(
-            |x| integrand(x, b.as_()),
-            1_f64,
-            (r.as_() - 1_f64) / r.as_(),
-            PHI_ERROR,
-        )
-        .integral
-        .as_()
+        (-integrate_orbit(b.as_(), 0_f64, u)).as_()
     } else {
-        let rtp = turning_point(b);
+        let u_tp: f64 = 1_f64 / turning_point(b).as_();
 
-        (integrate(
-            |x| integrand(x, b.as_()),
-            1_f64,
-            (rtp.as_() - 1_f64) / rtp.as_(),
-            PHI_ERROR,
-        )
-        .integral
-            - integrate(
-                |x| integrand(x, b.as_()),
-                (rtp.as_() - 1_f64) / rtp.as_(),
-                (r.as_() - 1_f64) / r.as_(),
-                PHI_ERROR,
-            )
-            .integral)
-            .as_()
+        // integrate in to the turning point and back out to the observer
+        (-(integrate_orbit(b.as_(), 0_f64, u_tp) + integrate_orbit(b.as_(), u, u_tp))).as_()
+    }
+}
+
+/// Returns the total deflection angle of a photon passing the black hole from infinity back out to
+/// infinity with impact parameter b, or None if the photon is captured
+///
+/// at large impact parameters the deflection is a tiny difference between the orbit integral and
+/// pi, so the integral is taken over t where u = u_tp (1 - t^2), in which the square root
+/// vanishing at the turning point cancels out and the integrand is smooth
+pub fn total_deflection(b: f64) -> Option<f64> {
+    if b.abs() <= 27_f64.sqrt() {
+        return None;
     }
+
+    let u_tp = 1_f64 / turning_point(b);
+
+    // 1 - b^2 u^2 (1 - 2u) factored by its root at the turning point is b^2 u_tp t^2 times this
+    let remainder = |u: f64| u + u_tp - 2_f64 * (u.powi(2) + u * u_tp + u_tp.powi(2));
+    let integrand = |t: f64| {
+        let u = u_tp * (1_f64 - t.powi(2));
+        2_f64 * (u_tp / remainder(u)).sqrt()
+    };
+
+    Some(2_f64 * integrate(integrand, 0_f64, 1_f64, DEFLECTION_ERROR).integral - PI)
+}
+
+/// Returns the weak field limit 4M/b of the deflection angle
+pub fn weak_field_deflection(b: f64) -> f64 {
+    4_f64 / b.abs()
 }
 
 /// Returns true if the photon at this rain angle hits the black hole
+///
+/// inside the horizon every photon falls inward, so the ones reaching the diver came straight in
+/// from outside unless they have negative energy or couldn't have passed the photon sphere. The
+/// critical rain angle is the same edge but this keeps the view exact down to the singularity.
 pub fn hits_black_hole<T: Float + 'static>(theta_rain: T, r: T) -> bool
 where
     i32: AsPrimitive<T>,
 {
+    if r < 2.as_() {
+        return theta_rain.cos() >= (r / 2.as_()).sqrt()
+            || impact_parameter(theta_rain, r).powi(2) >= 27.as_();
+    }
+
     theta_rain < critical_rain_angle(r)
 }
 
-/// Returns the spherical map angle from the rain angle
+/// Returns the order of the image formed by photons sweeping through this angle around the black
+/// hole, 0 for the primary image, 1 for the secondary image and so on
+///
+/// this is the number of times the photon has wound past the far side of the black hole
+pub fn image_order(swept_angle: f64) -> u32 {
+    (swept_angle.max(0_f64) / PI).floor() as u32
+}
+
+/// Returns the angle the photon arriving at this rain angle swept around the black hole on its way
+/// from infinity, None if it came from the black hole
+pub fn swept_angle(theta_rain: f64, r: f64) -> Option<f64> {
+    if hits_black_hole(theta_rain, r) {
+        return None;
+    }
+    if r <= 0_f64 {
+        return Some(0_f64);
+    }
+
+    Some(map_angle_from_impact_parameter(
+        theta_rain,
+        impact_parameter(theta_rain, r),
+        r,
+    ))
+}
+
+/// Returns the rain angle of the ring of photons that swept through this angle around the black
+/// hole before reaching the diver at the radius
+///
+/// the swept angle grows from zero looking straight away from the black hole to infinity at the
+/// edge of the shadow, so the ring is found by bisection between them
+pub fn ring_rain_angle(swept: f64, r: f64) -> f64 {
+    let (mut low, mut high) = (0_f64, PI);
+    for _ in 0..64 {
+        let mid = (low + high) / 2_f64;
+        // rays from the black hole are further in than the ring
+        if swept_angle(mid, r)
+            .filter(|mid_swept| *mid_swept <= swept)
+            .is_none()
+        {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2_f64
+}
+
+/// Returns the spherical map angle from the rain angle and the order of the image it is part of
+///
+/// photons that loop around the black hole more than `max_half_orbits` half orbits are treated
+/// as hitting it
 pub fn rain_angle_to_map_angle<T: Float + 'static>(
     theta_rain: T,
     phi_rain: T,
     r: T,
-) -> Option<(T, T)>
+    max_half_orbits: u32,
+) -> Option<(T, T, u32)>
 where
     i32: AsPrimitive<T>,
     f64: AsPrimitive<T>,
@@ -117,9 +226,22 @@ where
         return None;
     }
 
+    // at the singularity the photons reaching the diver have all come straight down from the
+    // point of the sky behind it
+    if r <= 0.as_() {
+        return Some((PI.as_(), phi_rain, 0));
+    }
+
     let b = impact_parameter(theta_rain, r);
 
-    let theta_map = PI.as_() - map_angle_from_impact_parameter(theta_rain, b, r);
+    let swept_angle = map_angle_from_impact_parameter(theta_rain, b, r);
+
+    // each half orbit sweeps the photon through pi
+    if swept_angle > (max_half_orbits as f64 * PI).as_() {
+        return None;
+    }
+
+    let theta_map = PI.as_() - swept_angle;
 
     // set theta_map back to range 0->pi
     let theta_map_normalized = theta_map.cos().acos();
@@ -131,7 +253,411 @@ where
         phi_map = phi_map + PI.as_();
     }
 
-    Some((theta_map_normalized, phi_map))
+    Some((
+        theta_map_normalized,
+        phi_map,
+        image_order(swept_angle.as_()),
+    ))
+}
+
+/// Returns the rain angle the diver at the radius sees the point of the sky at this map angle in,
+/// as part of the image of this order, None if no photon from there reaches them in that image
+///
+/// this inverts `rain_angle_to_map_angle`: images of odd order sweep through theta_map beyond a
+/// whole number of half orbits with phi flipped and images of even order through pi - theta_map
+pub fn map_angle_to_rain_angle(
+    theta_map: f64,
+    phi_map: f64,
+    r: f64,
+    order: u32,
+) -> Option<(f64, f64)> {
+    // every photon reaching the singularity comes from the same point of the sky
+    if r <= 0_f64 {
+        return None;
+    }
+
+    let theta_map = theta_map.clamp(0_f64, PI);
+    let (swept, phi_rain) = if order % 2 == 1 {
+        (order as f64 * PI + theta_map, phi_map + PI)
+    } else {
+        (order as f64 * PI + PI - theta_map, phi_map)
+    };
+
+    let theta_rain = ring_rain_angle(swept, r);
+
+    // the bisection ends at the edge of the shadow when no photon sweeps through the angle
+    swept_angle(theta_rain, r)
+        .filter(|found| (found - swept).abs() < 1e3 * PHI_ERROR)
+        .map(|_| (theta_rain, n_mod_m(phi_rain, 2_f64 * PI)))
+}
+
+// Kerr black holes
+//
+// The diver falls in along the spin axis from rest at infinity, so every photon reaching them has
+// no angular momentum about the axis. Its path only depends on K = Q + a^2, where Q is the Carter
+// constant, and the image is still symmetric about the black hole apart from a twist in phi from
+// frame dragging. Radial integrals are over the inverse radius u = 1/r like in the Schwarzschild
+// case and phi is the azimuth of ingoing Kerr coordinates so that it is finite at the horizon.
+
+/// Returns the radii of the outer and inner horizons of a black hole with spin a
+pub fn kerr_horizons(a: f64) -> (f64, f64) {
+    let root = (1_f64 - a.powi(2)).max(0_f64).sqrt();
+    (1_f64 + root, 1_f64 - root)
+}
+
+/// Returns the angle the frame of the rain is turned about the spin axis relative to a zero angular
+/// momentum observer at the radius, outside the outer horizon of a black hole with spin a
+///
+/// the azimuth of ingoing Kerr coordinates is the Boyer-Lindquist one plus the integral of a / Delta
+/// over the radius with Delta = r^2 - 2r + a^2, so the two frames agree far away and the rain frame
+/// winds around without end approaching the horizon
+pub fn kerr_frame_twist(r: f64, a: f64) -> f64 {
+    let (outer, inner) = kerr_horizons(a);
+    if a == 0_f64 || r <= outer {
+        return 0_f64;
+    }
+
+    if outer - inner > 1e-12 {
+        a / (outer - inner) * ((r - inner) / (r - outer)).ln()
+    } else {
+        // extremal black holes have a double root
+        a / (r - outer)
+    }
+}
+
+/// Returns the radius of the spherical photon orbit that passes over the poles
+fn kerr_polar_photon_orbit(a: f64) -> f64 {
+    let a2 = a.powi(2);
+    let x = 1_f64 - a2 / 3_f64;
+    1_f64 + 2_f64 * x.sqrt() * (((1_f64 - a2) / x.powf(1.5)).acos() / 3_f64).cos()
+}
+
+/// Returns K of photons on the polar photon orbit, photons with a larger K are turned back by it
+fn kerr_critical_k(a: f64) -> f64 {
+    let r = kerr_polar_photon_orbit(a);
+    (r.powi(2) + a.powi(2)).powi(2) / (r.powi(2) - 2_f64 * r + a.powi(2))
+}
+
+/// Radial potential R divided by r^4 as a function of the inverse radius
+fn kerr_radial_potential(u: f64, k: f64, a: f64) -> f64 {
+    let a2 = a.powi(2);
+    (1_f64 + a2 * u.powi(2)).powi(2) - k * u.powi(2) * (1_f64 - 2_f64 * u + a2 * u.powi(2))
+}
+
+/// Returns K and whether the photon at this rain angle is moving away from the black hole, None if
+/// it has a negative energy at infinity and so never came from the sky
+fn kerr_photon_constants(theta_rain: f64, r: f64, a: f64) -> Option<(f64, bool)> {
+    let sigma = r.powi(2) + a.powi(2);
+    // infall speed of the diver
+    let beta = (2_f64 * r / sigma).sqrt();
+
+    // energy at infinity per unit of energy measured by the diver
+    let energy = 1_f64 - beta * theta_rain.cos();
+    if energy <= 0_f64 {
+        return None;
+    }
+
+    Some((
+        sigma * theta_rain.sin().powi(2) / energy.powi(2),
+        theta_rain.cos() > beta,
+    ))
+}
+
+/// Returns true if the photon at this rain angle does not come from the sky around a black hole
+/// with spin a
+pub fn kerr_hits_black_hole(theta_rain: f64, r: f64, a: f64) -> bool {
+    // past the inner horizon the rain frame breaks down
+    if r <= kerr_horizons(a).1 {
+        return true;
+    }
+
+    let Some((k, outgoing)) = kerr_photon_constants(theta_rain, r, a) else {
+        return true;
+    };
+
+    if k > kerr_critical_k(a) {
+        // turned back by the photon orbit, from the sky only if the diver is outside of it
+        r < kerr_polar_photon_orbit(a)
+    } else {
+        // nothing turns it back so it came from the horizon if it is moving outwards
+        outgoing
+    }
+}
+
+/// Returns the critical rain angle of the shadow of a black hole with spin a
+pub fn kerr_critical_rain_angle(r: f64, a: f64) -> f64 {
+    let (mut low, mut high) = (0_f64, PI);
+    for _ in 0..64 {
+        let mid = (low + high) / 2_f64;
+        if kerr_hits_black_hole(mid, r, a) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2_f64
+}
+
+/// Returns the inverse radius of the turning point between the inverse radius of the diver and the
+/// photon orbit
+fn kerr_turning_point(k: f64, a: f64, u: f64, u_orbit: f64) -> f64 {
+    let (mut low, mut high) = (u, u_orbit);
+    for _ in 0..64 {
+        let mid = (low + high) / 2_f64;
+        if kerr_radial_potential(mid, k, a) > 0_f64 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// Integrates between the inverse radii u1 < u2 splitting the interval at the photon orbit like
+/// `integrate_orbit`, also used for other spherically symmetric black holes
+fn integrate_kerr(integrand: impl Fn(f64) -> f64, u1: f64, u2: f64, u_orbit: f64) -> f64 {
+    if u1 < u_orbit && u_orbit < u2 {
+        integrate(&integrand, u1, u_orbit, PHI_ERROR).integral
+            + integrate(&integrand, u_orbit, u2, PHI_ERROR).integral
+    } else {
+        integrate(&integrand, u1, u2, PHI_ERROR).integral
+    }
+}
+
+/// Returns the Jacobi amplitude am(x | m) for 0 <= m < 1 with the arithmetic-geometric mean
+fn jacobi_amplitude(x: f64, m: f64) -> f64 {
+    let mut a = vec![1_f64];
+    let mut c = vec![m.sqrt()];
+    let mut b = (1_f64 - m).sqrt();
+
+    while c[c.len() - 1].abs() > 1e-15 && a.len() < 32 {
+        let (a_last, b_last) = (a[a.len() - 1], b);
+        a.push((a_last + b_last) / 2_f64);
+        c.push((a_last - b_last) / 2_f64);
+        b = (a_last * b_last).sqrt();
+    }
+
+    let n = a.len() - 1;
+    let mut phi = 2_f64.powi(n as i32) * a[n] * x;
+    for i in (1..=n).rev() {
+        phi = (phi + (c[i] / a[i] * phi.sin()).asin()) / 2_f64;
+    }
+    phi
+}
+
+/// Returns the angle swept around the black hole in the time it takes to travel the Mino time
+/// from the diver, found by solving dtheta/dtime = sqrt(K - a^2 sin^2 theta)
+fn kerr_swept_angle(k: f64, a: f64, mino_time: f64) -> f64 {
+    if k <= 0_f64 {
+        return 0_f64;
+    }
+
+    let m = a.powi(2) / k;
+    if m < 1_f64 - 1e-12 {
+        jacobi_amplitude(k.sqrt() * mino_time, m)
+    } else if m > 1_f64 + 1e-12 {
+        // photons that never reach the equator oscillate about the axis
+        (k.sqrt() / a * jacobi_amplitude(a * mino_time, 1_f64 / m).sin()).asin()
+    } else {
+        // photons approaching the equator asymptotically
+        2_f64 * (k.sqrt() * mino_time).exp().atan() - PI / 2_f64
+    }
+}
+
+/// Returns the spherical map angle from the rain angle around a black hole with spin a and the
+/// order of the image it is part of
+///
+/// photons that loop around the black hole more than `max_half_orbits` half orbits are treated
+/// as hitting it
+pub fn kerr_rain_angle_to_map_angle(
+    theta_rain: f64,
+    phi_rain: f64,
+    r: f64,
+    a: f64,
+    max_half_orbits: u32,
+) -> Option<(f64, f64, u32)> {
+    if kerr_hits_black_hole(theta_rain, r, a) {
+        return None;
+    }
+
+    let (k, outgoing) = kerr_photon_constants(theta_rain, r, a)?;
+    let u = 1_f64 / r;
+    let u_orbit = 1_f64 / kerr_polar_photon_orbit(a);
+    let a2 = a.powi(2);
+
+    let mino_integrand = |u: f64| 1_f64 / kerr_radial_potential(u, k, a).sqrt();
+    // change in phi per inverse radius moving inwards and outwards
+    let twist_in = |u: f64| {
+        let root = kerr_radial_potential(u, k, a).sqrt();
+        a * (k * u.powi(2) - 1_f64 - 2_f64 * u - a2 * u.powi(2)) / ((2_f64 * u + root) * root)
+    };
+    let twist_out = |u: f64| {
+        let root = kerr_radial_potential(u, k, a).sqrt();
+        a * (2_f64 * u + root) / ((1_f64 - 2_f64 * u + a2 * u.powi(2)) * root)
+    };
+
+    let (mino_time, twist) = if outgoing {
+        // integrate in to the turning point and back out to the observer
+        let u_tp = kerr_turning_point(k, a, u, u_orbit);
+        (
+            integrate_kerr(mino_integrand, 0_f64, u_tp, u_orbit)
+                + integrate_kerr(mino_integrand, u, u_tp, u_orbit),
+            integrate_kerr(twist_in, 0_f64, u_tp, u_orbit)
+                + integrate_kerr(twist_out, u, u_tp, u_orbit),
+        )
+    } else {
+        (
+            integrate_kerr(mino_integrand, 0_f64, u, u_orbit),
+            integrate_kerr(twist_in, 0_f64, u, u_orbit),
+        )
+    };
+
+    let swept_angle = kerr_swept_angle(k, a, mino_time);
+
+    // each half orbit sweeps the photon through pi
+    if swept_angle > max_half_orbits as f64 * PI {
+        return None;
+    }
+
+    let theta_map = PI - swept_angle;
+
+    let mut phi_map = phi_rain + twist;
+
+    // flip phi if needed
+    if theta_map.sin() < 0_f64 {
+        phi_map += PI;
+    }
+
+    // set theta_map back to range 0->pi
+    Some((theta_map.cos().acos(), phi_map, image_order(swept_angle)))
+}
+
+// Spherically symmetric black holes
+//
+// Any static spherically symmetric metric is described by its metric factor f(r), see metric.rs,
+// like 1 - 2/r + q^2/r^2 for a black hole with charge q per unit mass. The diver still falls from
+// rest at infinity, so the rain falls at sqrt(1 - f) through static observers. Photons follow the
+// Schwarzschild orbit equation with the potential V(u) = u^2 f(1/u), so everything else carries
+// over apart from the photon sphere and turning points having no closed form in general.
+
+/// Returns the impact parameter of photons on the photon sphere, photons with a larger one are
+/// turned back by it
+fn metric_critical_impact_parameter(metric: &dyn Metric) -> f64 {
+    1_f64
+        / metric
+            .effective_potential(1_f64 / metric.photon_sphere_radius())
+            .sqrt()
+}
+
+/// Returns the impact parameter and whether the photon at this rain angle is moving away from the
+/// black hole, None if it has a negative energy at infinity and so never came from the sky
+pub(crate) fn metric_photon_constants(
+    theta_rain: f64,
+    r: f64,
+    metric: &dyn Metric,
+) -> Option<(f64, bool)> {
+    let beta = metric.rain_speed(r);
+
+    // energy at infinity per unit of energy measured by the diver
+    let energy = 1_f64 - beta * theta_rain.cos();
+    if energy <= 0_f64 {
+        return None;
+    }
+
+    Some((r * theta_rain.sin() / energy, theta_rain.cos() > beta))
+}
+
+/// Returns true if the photon at this rain angle does not come from the sky around the black hole
+/// with this metric
+pub fn metric_hits_black_hole(theta_rain: f64, r: f64, metric: &dyn Metric) -> bool {
+    // past the inner horizon the rain frame breaks down
+    if r <= metric.inner_horizon_radius() {
+        return true;
+    }
+
+    let Some((b, outgoing)) = metric_photon_constants(theta_rain, r, metric) else {
+        return true;
+    };
+
+    if b > metric_critical_impact_parameter(metric) {
+        // turned back by the photon sphere, from the sky only if the diver is outside of it
+        r < metric.photon_sphere_radius()
+    } else {
+        // nothing turns it back so it came from the horizon if it is moving outwards
+        outgoing
+    }
+}
+
+/// Returns the critical rain angle of the shadow of the black hole with this metric
+pub fn metric_critical_rain_angle(r: f64, metric: &dyn Metric) -> f64 {
+    let (mut low, mut high) = (0_f64, PI);
+    for _ in 0..64 {
+        let mid = (low + high) / 2_f64;
+        if metric_hits_black_hole(mid, r, metric) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2_f64
+}
+
+/// Returns the spherical map angle from the rain angle around the black hole with this metric and
+/// the order of the image it is part of
+///
+/// photons that loop around the black hole more than `max_half_orbits` half orbits are treated
+/// as hitting it
+pub fn metric_rain_angle_to_map_angle(
+    theta_rain: f64,
+    phi_rain: f64,
+    r: f64,
+    metric: &dyn Metric,
+    max_half_orbits: u32,
+) -> Option<(f64, f64, u32)> {
+    if metric_hits_black_hole(theta_rain, r, metric) {
+        return None;
+    }
+
+    let (b, outgoing) = metric_photon_constants(theta_rain, r, metric)?;
+    let u = 1_f64 / r;
+    let u_orbit = 1_f64 / metric.photon_sphere_radius();
+
+    let integrand = |u: f64| b / (1_f64 - b.powi(2) * metric.effective_potential(u)).sqrt();
+
+    let swept_angle = if outgoing {
+        // the turning point lies between the diver and the photon sphere
+        let (mut low, mut high) = (u, u_orbit);
+        for _ in 0..64 {
+            let mid = (low + high) / 2_f64;
+            if b.powi(2) * metric.effective_potential(mid) < 1_f64 {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        // integrate in to the turning point and back out to the observer
+        integrate_kerr(integrand, 0_f64, low, u_orbit) + integrate_kerr(integrand, u, low, u_orbit)
+    } else {
+        integrate_kerr(integrand, 0_f64, u, u_orbit)
+    };
+
+    // each half orbit sweeps the photon through pi
+    if swept_angle > max_half_orbits as f64 * PI {
+        return None;
+    }
+
+    let theta_map = PI - swept_angle;
+
+    let mut phi_map = phi_rain;
+
+    // flip phi if needed
+    if theta_map.sin() < 0_f64 {
+        phi_map += PI;
+    }
+
+    // set theta_map back to range 0->pi
+    Some((theta_map.cos().acos(), phi_map, image_order(swept_angle)))
 }
 
 /// Returns true if the photon at this rain angle hits the "event horizon" of the black hole
@@ -145,3 +671,350 @@ where
         || (theta_rain.tan() < 2.as_() / (r.powi(2) - 4.as_()).sqrt()
             && theta_rain <= (PI / 2_f64).as_())
 }
+
+/// Returns the proper time for a diver falling radially inward with the energy per unit mass to
+/// reach the singularity from the radius
+///
+/// energies below one are bound and were dropped from rest at 2 / (1 - E^2) (drip), one falls
+/// from rest at infinity (rain) and above one was thrown inward from infinity (hail)
+pub fn radial_fall_time(r: f64, energy: f64) -> f64 {
+    if energy == 1_f64 {
+        return 2_f64.sqrt() * r.powf(1.5) / 3_f64;
+    }
+
+    if energy < 1_f64 {
+        // cycloid parametrisation from the radius of rest
+        let r_max = 2_f64 / (1_f64 - energy.powi(2));
+        let eta = (2_f64 * r.min(r_max) / r_max - 1_f64).acos();
+        (r_max / 2_f64).powf(1.5) * (PI - eta - eta.sin())
+    } else {
+        let a = 1_f64 / (energy.powi(2) - 1_f64);
+        let eta = (1_f64 + r / a).acosh();
+        a.powf(1.5) * (eta.sinh() - eta)
+    }
+}
+
+/// Returns the radius a radially falling diver with the energy per unit mass reaches the
+/// singularity from after the proper time, the inverse of `radial_fall_time`
+pub fn radial_fall_radius(time: f64, energy: f64) -> f64 {
+    if time <= 0_f64 {
+        return 0_f64;
+    }
+    if energy == 1_f64 {
+        return (3_f64 * time / 2_f64.sqrt()).powf(2_f64 / 3_f64);
+    }
+
+    // the fall time increases with the radius so bisect for it
+    let mut high = if energy < 1_f64 {
+        2_f64 / (1_f64 - energy.powi(2))
+    } else {
+        1_f64
+    };
+    while energy >= 1_f64 && radial_fall_time(high, energy) < time {
+        high *= 2_f64;
+    }
+    let mut low = 0_f64;
+    for _ in 0..64 {
+        let mid = (low + high) / 2_f64;
+        if radial_fall_time(mid, energy) < time {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2_f64
+}
+
+/// Returns the tortoise coordinate r* = r + 2 ln|r/2 - 1|
+pub fn tortoise(r: f64) -> f64 {
+    r + 2_f64 * (r / 2_f64 - 1_f64).abs().ln()
+}
+
+/// Returns the velocity through the rain frame of a diver at the radius with the energy and
+/// angular momentum per unit mass and the radial component of its four velocity, as the
+/// components along the orbit and inward
+pub fn rain_frame_velocity(
+    r: f64,
+    energy: f64,
+    angular_momentum: f64,
+    radial_velocity: f64,
+) -> (f64, f64) {
+    let beta = (2_f64 / r).sqrt();
+    let tangential = angular_momentum / r;
+
+    // components of the four velocity in the frame of the rain observer, written so that they
+    // stay finite through the horizon for infalling divers and when moving outward with the
+    // energy of the rain
+    let (gamma, outward) = if radial_velocity > 0_f64 {
+        let static_factor = 1_f64 - beta.powi(2);
+        (
+            (energy + beta * radial_velocity) / static_factor,
+            (beta * energy + radial_velocity) / static_factor,
+        )
+    } else {
+        (
+            (energy.powi(2) + beta.powi(2) * (1_f64 + tangential.powi(2)))
+                / (energy - beta * radial_velocity),
+            (1_f64 + tangential.powi(2) - energy.powi(2)) / (beta * energy - radial_velocity),
+        )
+    };
+
+    (tangential / gamma, -outward / gamma)
+}
+
+// Ellis wormholes
+//
+// The metric is ds^2 = -dt^2 + dl^2 + (l^2 + b0^2) dOmega^2 where l is the proper distance from
+// the throat of radius b0. There is no gravity so static observers are inertial and see no shift
+// in frequency. Photons with impact parameters below b0 pass through the throat to the other side
+// and the rest turn around outside of it. The orbit integrals are written in terms of an angle
+// psi that removes the singularities at the turning point and at infinity.
+
+/// Returns the view angle from the direction of the throat of its edge for a static observer at
+/// the proper distance l from the throat
+pub fn wormhole_throat_angle(l: f64, b0: f64) -> f64 {
+    if l <= 0_f64 {
+        // every photon arriving from the front has come through the throat
+        return PI / 2_f64;
+    }
+    (b0 / (l.powi(2) + b0.powi(2)).sqrt()).asin()
+}
+
+/// Returns the map angle of the photon arriving at a static observer at the proper distance l
+/// from the throat of the wormhole from the view angle, the order of the image it is part of and
+/// whether it came from the other side of the wormhole
+///
+/// photons that loop around the throat more than `max_half_orbits` half orbits are treated as
+/// lost
+pub fn wormhole_view_angle_to_map_angle(
+    theta_view: f64,
+    phi_view: f64,
+    l: f64,
+    b0: f64,
+    max_half_orbits: u32,
+) -> Option<(f64, f64, u32, bool)> {
+    let l = l.max(0_f64);
+    let b = (l.powi(2) + b0.powi(2)).sqrt() * theta_view.sin();
+    let towards_throat = theta_view < PI / 2_f64;
+    let through_throat = towards_throat && b < b0;
+
+    let swept_angle = if b < b0 {
+        // passes l = 0 with l = q tan(psi)
+        let q = (b0.powi(2) - b.powi(2)).sqrt();
+        let integrand =
+            |psi: f64| b / (q.powi(2) * psi.sin().powi(2) + b0.powi(2) * psi.cos().powi(2)).sqrt();
+        let psi = (l / q).atan();
+
+        if through_throat {
+            integrate(integrand, -PI / 2_f64, psi, PHI_ERROR).integral
+        } else {
+            integrate(integrand, psi, PI / 2_f64, PHI_ERROR).integral
+        }
+    } else {
+        // turns around at l_tp with l = l_tp / cos(psi)
+        let l_tp = (b.powi(2) - b0.powi(2)).sqrt();
+        let integrand = |psi: f64| b / (l_tp.powi(2) + b0.powi(2) * psi.cos().powi(2)).sqrt();
+        let psi = (l_tp / l).min(1_f64).acos();
+
+        let outward = integrate(integrand, psi, PI / 2_f64, PHI_ERROR).integral;
+        if towards_throat {
+            // in to the turning point and back out past the observer
+            outward + 2_f64 * integrate(integrand, 0_f64, psi, PHI_ERROR).integral
+        } else {
+            outward
+        }
+    };
+
+    // each half orbit sweeps the photon through pi
+    if swept_angle > max_half_orbits as f64 * PI {
+        return None;
+    }
+
+    let theta_map = PI - swept_angle;
+
+    let mut phi_map = phi_view;
+
+    // flip phi if needed
+    if theta_map.sin() < 0_f64 {
+        phi_map += PI;
+    }
+
+    Some((
+        theta_map.cos().acos(),
+        phi_map,
+        image_order(swept_angle),
+        through_throat,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// integrates the orbit equation between the inverse radii with the midpoint rule in t where
+    /// u = u2 - t^2, which removes the square root singularity at a turning point u2
+    fn reference_orbit(b: f64, u1: f64, u2: f64) -> f64 {
+        let steps = 200_000;
+        let t_max = (u2 - u1).sqrt();
+        let dt = t_max / steps as f64;
+        (0..steps)
+            .map(|i| {
+                let t = (i as f64 + 0.5_f64) * dt;
+                let u = u2 - t.powi(2);
+                2_f64 * t * b / (1_f64 - b.powi(2) * u.powi(2) * (1_f64 - 2_f64 * u)).sqrt() * dt
+            })
+            .sum()
+    }
+
+    /// swept angle like `map_angle_from_impact_parameter` integrated with `reference_orbit`
+    fn reference_swept_angle(theta_rain: f64, r: f64) -> f64 {
+        let b = impact_parameter(theta_rain, r);
+        let u = 1_f64 / r;
+        if photon_is_incoming(theta_rain, r) {
+            -reference_orbit(b, 0_f64, u)
+        } else {
+            let u_tp = 1_f64 / turning_point(b);
+            -(reference_orbit(b, 0_f64, u_tp) + reference_orbit(b, u, u_tp))
+        }
+    }
+
+    #[test]
+    fn orbit_matches_reference_near_horizon() {
+        for r in [2.1_f64, 2.01, 2.001, 2.0001] {
+            let critical = critical_rain_angle(r);
+            // from looking away from the black hole down to the third order image at its shadow
+            for theta_rain in [
+                PI - 1e-3,
+                critical + 1_f64,
+                critical + 1e-2,
+                critical + 1e-4,
+            ] {
+                let swept = swept_angle(theta_rain, r).unwrap();
+                let reference = reference_swept_angle(theta_rain, r);
+                assert!(
+                    (swept - reference).abs() < 10_f64 * PHI_ERROR,
+                    "r {r} rain angle {theta_rain}: {swept} != {reference}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn orbit_converges_at_horizon() {
+        for theta_rain in [0.8_f64, 1.5, 2.5] {
+            let at_horizon = swept_angle(theta_rain, 2_f64).unwrap();
+            let mut previous_error = f64::INFINITY;
+            // the swept angle is smooth in r so each step ten times closer to the horizon should
+            // bring it about ten times closer to its value there
+            for r in [2.1_f64, 2.01, 2.001, 2.0001] {
+                let error = (swept_angle(theta_rain, r).unwrap() - at_horizon).abs();
+                assert!(
+                    error < previous_error / 5_f64,
+                    "rain angle {theta_rain} at r {r}: {error} after {previous_error}"
+                );
+                previous_error = error;
+            }
+        }
+    }
+
+    #[test]
+    fn shadow_approaches_critical_impact_parameter() {
+        // far away both observers see the critical impact parameter 3 sqrt(3) M as sin(alpha) r,
+        // up to the aberration of the rain falling at sqrt(2/r)
+        let r = 1e6_f64;
+        for static_observer in [false, true] {
+            let angle = shadow_angular_radius(r, static_observer).unwrap();
+            let impact_parameter = angle.sin() * r;
+            let critical = 3_f64 * 3_f64.sqrt();
+            assert!(
+                (impact_parameter - critical).abs() < critical * 2_f64 * (2_f64 / r).sqrt(),
+                "static {static_observer}: {impact_parameter}"
+            );
+        }
+
+        // static observers see half the sky dark at the photon sphere and can't exist inside the
+        // horizon, while the rain sees the critical rain angle
+        let photon_sphere = shadow_angular_radius(3_f64, true).unwrap();
+        assert!((photon_sphere - PI / 2_f64).abs() < 1e-6);
+        assert_eq!(shadow_angular_radius(1_f64, true), None);
+        assert_eq!(
+            shadow_angular_radius(1_f64, false),
+            Some(critical_rain_angle(1_f64))
+        );
+    }
+
+    #[test]
+    fn max_half_orbits_clips_rings() {
+        let r = 10_f64;
+        // photons sweeping through half of the first and of the second half orbit
+        let primary = ring_rain_angle(0.5 * PI, r);
+        let secondary = ring_rain_angle(1.5 * PI, r);
+
+        assert_eq!(rain_angle_to_map_angle(primary, 0_f64, r, 1).unwrap().2, 0);
+        assert!(rain_angle_to_map_angle(secondary, 0_f64, r, 1).is_none());
+        assert_eq!(
+            rain_angle_to_map_angle(secondary, 0_f64, r, 2).unwrap().2,
+            1
+        );
+        assert!(rain_angle_to_map_angle(primary, 0_f64, r, 0).is_none());
+    }
+
+    #[test]
+    fn map_angle_inverts_rain_angle() {
+        for r in [2.5_f64, 10_f64, 100_f64] {
+            let critical = critical_rain_angle(r);
+            // images of the first three orders on both sides of the black hole
+            for theta_rain in [
+                PI - 0.1,
+                0.5 * (PI + critical),
+                critical + 1e-2,
+                critical + 1e-4,
+            ] {
+                for phi_rain in [0.3_f64, 4_f64] {
+                    let (theta_map, phi_map, order) =
+                        rain_angle_to_map_angle(theta_rain, phi_rain, r, 8).unwrap();
+                    let (theta, phi) =
+                        map_angle_to_rain_angle(theta_map, n_mod_m(phi_map, 2_f64 * PI), r, order)
+                            .unwrap();
+                    assert!(
+                        (theta - theta_rain).abs() < 1e-6 && (phi - phi_rain).abs() < 1e-9,
+                        "r {r} rain angle ({theta_rain}, {phi_rain}): ({theta}, {phi})"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn kerr_matches_schwarzschild_without_spin() {
+        for r in [10_f64, 4_f64, 2.5] {
+            let critical = critical_rain_angle(r);
+            for k in 1..10 {
+                let theta_rain = critical + (PI - critical) * k as f64 / 10_f64;
+                let phi_rain = 0.3 * k as f64;
+                let (theta, phi, order) =
+                    rain_angle_to_map_angle(theta_rain, phi_rain, r, u32::MAX).unwrap();
+                let (kerr_theta, kerr_phi, kerr_order) =
+                    kerr_rain_angle_to_map_angle(theta_rain, phi_rain, r, 1e-6, u32::MAX).unwrap();
+
+                let phi_difference = n_mod_m(kerr_phi - phi + PI, 2_f64 * PI) - PI;
+                assert!(
+                    (kerr_theta - theta).abs() < 1e-5 && phi_difference.abs() < 1e-5,
+                    "r {r} rain angle {theta_rain}: ({kerr_theta}, {kerr_phi}) != ({theta}, {phi})"
+                );
+                assert_eq!(kerr_order, order);
+            }
+        }
+    }
+
+    #[test]
+    fn weak_field_deflection_limit() {
+        // the next order of the deflection is 15 pi / 4b^2, so the ratio approaches 1 like 1/b
+        for k in 0..=8 {
+            let b = 10_f64.powf(2_f64 + k as f64 / 4_f64);
+            let ratio = total_deflection(b).unwrap() / weak_field_deflection(b);
+            assert!((ratio - 1_f64).abs() < 4_f64 / b, "b {b}: ratio {ratio}");
+            assert!(ratio > 1_f64, "b {b}: ratio {ratio}");
+        }
+    }
+}
@@ -0,0 +1,17 @@
+// Exports a matte of the pixels light reaches the diver through so renders can be composited over
+// other backgrounds
+//
+// Encoding: an 8-bit grayscale PNG the size of the render, or of its crop, that is white where the
+// rays reach the sky or an object and black where they fall into the black hole, to be used as the
+// alpha of the render. The pass recorder counts the samples of each pixel the render traces, so the
+// edge of the shadow is antialiased like the render. The glow of a jet in front of the shadow isn't part of
+// the matte. Stereo renders stack the matte of each eye like the render.
+
+use crate::render::sidecar_path;
+use std::path::{Path, PathBuf};
+
+/// Returns the path of the matte pass written alongside a render, `name.png` becomes
+/// `name.matte.png`
+pub fn matte_pass_path(output_path: &Path) -> PathBuf {
+    sidecar_path(output_path, "matte", "png")
+}
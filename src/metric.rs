@@ -0,0 +1,204 @@
+// Static spherically symmetric spacetimes that the lensing can be calculated in
+//
+// The metric is ds^2 = -f dt^2 + dr^2 / f + r^2 dOmega^2 in units of the mass of the black hole.
+// Everything the lensing needs follows from the metric factor f(r): photons obey the orbit
+// equation (du/dphi)^2 = 1/b^2 - V(u) with the effective potential V(u) = u^2 f(1/u), and the
+// rain falls through static observers at sqrt(1 - f).
+//
+// Inside matter g_rr is no longer 1 / f. With the stretch S = sqrt(-g_tt g_rr) the orbit equation
+// becomes (du/dphi)^2 = (1/b^2 - V(u)) / S^2, so photons turn at the same radii but sweep S times
+// more angle on the way. The rain still falls through static observers at sqrt(1 - f).
+
+use std::sync::Arc;
+
+/// Spacetime around a black hole, implement this to lens with other spherically symmetric metrics
+pub trait Metric: Send + Sync {
+    /// returns the metric factor f(r) = -g_tt = 1 / g_rr, negative between the horizons
+    fn metric_factor(&self, r: f64) -> f64;
+
+    /// returns the radius of the outer event horizon, zero if there is none
+    fn horizon_radius(&self) -> f64;
+
+    /// returns the stretch sqrt(-g_tt g_rr) of the angle swept by photons, 1 in vacuum
+    fn radial_stretch(&self, _r: f64) -> f64 {
+        1_f64
+    }
+
+    /// returns the radius of the inner horizon, past which the rain frame breaks down
+    fn inner_horizon_radius(&self) -> f64 {
+        0_f64
+    }
+
+    /// returns the effective potential V(u) of the orbit equation at the inverse radius
+    fn effective_potential(&self, u: f64) -> f64 {
+        if u <= 0_f64 {
+            return 0_f64;
+        }
+        u.powi(2) * self.metric_factor(1_f64 / u)
+    }
+
+    /// returns the derivative of the effective potential with respect to the inverse radius
+    fn effective_potential_slope(&self, u: f64) -> f64 {
+        let h = 1e-6 * u.max(1e-3);
+        (self.effective_potential(u + h) - self.effective_potential(u - h)) / (2_f64 * h)
+    }
+
+    /// returns the factor that light from infinity is blueshifted by for static observers, None
+    /// inside the horizon where they can't exist
+    fn redshift_factor(&self, r: f64) -> Option<f64> {
+        let f = self.metric_factor(r);
+        (f > 0_f64).then(|| 1_f64 / f.sqrt())
+    }
+
+    /// returns the radius of the photon sphere, the outermost peak of the effective potential
+    ///
+    /// the default searches between infinity and the horizon assuming there is a single peak
+    fn photon_sphere_radius(&self) -> f64 {
+        // golden section search for the peak
+        let ratio = (5_f64.sqrt() - 1_f64) / 2_f64;
+        let (mut low, mut high) = (0_f64, 1_f64 / self.horizon_radius());
+        for _ in 0..100 {
+            let left = high - ratio * (high - low);
+            let right = low + ratio * (high - low);
+            if self.effective_potential(left) < self.effective_potential(right) {
+                low = left;
+            } else {
+                high = right;
+            }
+        }
+        2_f64 / (low + high)
+    }
+
+    /// returns the infall speed of the rain through static observers, zero where it comes to rest
+    fn rain_speed(&self, r: f64) -> f64 {
+        (1_f64 - self.metric_factor(r)).max(0_f64).sqrt()
+    }
+}
+
+/// Black hole with the charge q per unit mass, 0 to 1
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ReissnerNordstrom {
+    pub charge: f64,
+}
+
+impl ReissnerNordstrom {
+    pub fn new(charge: f64) -> Self {
+        Self { charge }
+    }
+
+    fn horizons(&self) -> (f64, f64) {
+        let root = (1_f64 - self.charge.powi(2)).max(0_f64).sqrt();
+        (1_f64 + root, 1_f64 - root)
+    }
+}
+
+impl Metric for ReissnerNordstrom {
+    fn metric_factor(&self, r: f64) -> f64 {
+        1_f64 - 2_f64 / r + self.charge.powi(2) / r.powi(2)
+    }
+
+    fn horizon_radius(&self) -> f64 {
+        self.horizons().0
+    }
+
+    fn inner_horizon_radius(&self) -> f64 {
+        self.horizons().1
+    }
+
+    fn effective_potential_slope(&self, u: f64) -> f64 {
+        2_f64 * u - 6_f64 * u.powi(2) + 4_f64 * self.charge.powi(2) * u.powi(3)
+    }
+
+    fn photon_sphere_radius(&self) -> f64 {
+        (3_f64 + (9_f64 - 8_f64 * self.charge.powi(2)).sqrt()) / 2_f64
+    }
+}
+
+/// Smallest radius of a constant density star in units of its mass, at 9/4 the pressure at the
+/// center becomes infinite
+pub const MIN_STAR_RADIUS: f64 = 2.3;
+
+/// Transparent star of constant density with the interior Schwarzschild solution inside of it
+///
+/// inside the star f = (3/2 sqrt(1 - 2/R) - 1/2 sqrt(1 - 2r^2/R^3))^2 and
+/// g_rr = 1 / (1 - 2r^2/R^3), outside it is the Schwarzschild metric. Light passes through the
+/// star, so every ray comes back out and there is no horizon or shadow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InteriorSchwarzschild {
+    // radius of the star in units of its mass
+    pub radius: f64,
+}
+
+impl Default for InteriorSchwarzschild {
+    fn default() -> Self {
+        Self { radius: 4_f64 }
+    }
+}
+
+impl InteriorSchwarzschild {
+    pub fn new(radius: f64) -> Self {
+        Self {
+            radius: radius.max(MIN_STAR_RADIUS),
+        }
+    }
+
+    /// returns 1 / g_rr inside the star
+    fn interior_factor(&self, r: f64) -> f64 {
+        1_f64 - 2_f64 * r.powi(2) / self.radius.powi(3)
+    }
+}
+
+impl Metric for InteriorSchwarzschild {
+    fn metric_factor(&self, r: f64) -> f64 {
+        if r >= self.radius {
+            return 1_f64 - 2_f64 / r;
+        }
+
+        let surface = (1_f64 - 2_f64 / self.radius).sqrt();
+        (1.5 * surface - 0.5 * self.interior_factor(r).sqrt()).powi(2)
+    }
+
+    fn horizon_radius(&self) -> f64 {
+        0_f64
+    }
+
+    fn radial_stretch(&self, r: f64) -> f64 {
+        if r >= self.radius {
+            return 1_f64;
+        }
+        (self.metric_factor(r) / self.interior_factor(r)).sqrt()
+    }
+
+    fn photon_sphere_radius(&self) -> f64 {
+        // outside 3M the potential rises all the way into the star without a peak
+        if self.radius < 3_f64 {
+            3_f64
+        } else {
+            self.radius
+        }
+    }
+}
+
+/// Metric shared between scenes, compared by instance like `SharedEnvironment`
+#[derive(Clone)]
+pub struct SharedMetric(Arc<dyn Metric>);
+
+impl SharedMetric {
+    pub fn new(metric: impl Metric + 'static) -> Self {
+        Self(Arc::new(metric))
+    }
+}
+
+impl std::ops::Deref for SharedMetric {
+    type Target = dyn Metric;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl PartialEq for SharedMetric {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::as_ptr(&self.0) as *const () == Arc::as_ptr(&other.0) as *const ()
+    }
+}
@@ -0,0 +1,97 @@
+// A compact star such as a neutron star with a visible surface in place of the event horizon
+//
+// Outside a static spherical star spacetime is the same as around a black hole of the same mass,
+// so rays are followed back through it in the same way until they reach the surface. The bending
+// lets the diver see more than half of the surface, and all of it once the surface is inside the
+// photon sphere at 3M. The surface shines by itself with its texture wrapped around the center.
+
+use crate::{
+    companion::sphere_intersection,
+    environment::SharedEnvironment,
+    ray_path::RayPath,
+    spherical_angle::{MapAngle, SphericalAngle},
+    traits::Interpolate,
+    units::Units,
+};
+use image::Rgb;
+use nalgebra::Vector3;
+
+/// Smallest radius of the surface in units of the mass, a star any smaller would be a black hole
+pub const MIN_SURFACE_RADIUS: f64 = 2.05;
+
+#[derive(Clone, PartialEq)]
+pub struct NeutronStar {
+    // radius of the surface in units of the mass of the black hole
+    pub radius: f64,
+    // texture wrapped around the surface
+    pub surface: SharedEnvironment,
+}
+
+impl Default for NeutronStar {
+    fn default() -> Self {
+        Self {
+            // typical of neutron stars and inside the photon sphere
+            radius: 2.9,
+            surface: SharedEnvironment::default_grid(),
+        }
+    }
+}
+
+impl Interpolate for NeutronStar {
+    fn interpolate(&self, other: &Self, factor: f32) -> Self {
+        Self {
+            radius: self.radius.interpolate(&other.radius, factor),
+            surface: self.surface.clone(),
+        }
+    }
+}
+
+impl NeutronStar {
+    /// returns the first point where the path around a star with the mass reaches the surface and
+    /// the angle the ray swept around the star before it, the diver sees only the surface once it
+    /// is inside the star
+    pub fn hit(&self, path: &RayPath, mass: f64) -> Option<(Vector3<f64>, f64)> {
+        let radius = mass * self.radius.max(MIN_SURFACE_RADIUS);
+
+        let &(start, _) = path.points.first()?;
+        if start.norm() <= radius {
+            return Some((start, 0_f64));
+        }
+
+        path.segments(radius).find_map(|(start, end, swept)| {
+            sphere_intersection(Vector3::zeros(), radius, start, end - start)
+                .filter(|t| *t <= 1_f64)
+                .map(|t| (start + t * (end - start), swept))
+        })
+    }
+
+    /// samples the texture at the point on the surface
+    pub fn get_pixel(&self, point: Vector3<f64>) -> Rgb<u8> {
+        self.surface.get_pixel(MapAngle::from_vector(point))
+    }
+
+    /// shows the settings of the star around a black hole with this mass
+    pub fn show(&mut self, ui: &mut egui::Ui, units: &Units, mass: f64) {
+        egui::Grid::new("neutron_star_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Surface radius");
+                ui.add(
+                    egui::DragValue::new(&mut self.radius)
+                        .clamp_range(MIN_SURFACE_RADIUS..=f64::MAX)
+                        .speed(0.01)
+                        .suffix(" M"),
+                )
+                .on_hover_text(
+                    "Radius of the surface in units of the mass, inside 3M the whole surface can \
+                    be seen at once",
+                );
+                ui.end_row();
+
+                ui.label("Surface");
+                ui.label(units.describe_length(mass * self.radius))
+                    .on_hover_text("Radius of the surface in the units of the scene");
+                ui.end_row();
+            });
+    }
+}
@@ -0,0 +1,98 @@
+// Errors of the render threads shown over the corner of the window until they are dismissed
+//
+// Render threads can't show anything themselves, so they send a message for every file they fail
+// to write through a channel and the UI collects them each frame. The same message repeated, like
+// a side file that fails to update on every frame of an animation, is only shown once.
+
+use eframe::egui;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Returns a reporter for render threads and the notifications of the errors it reports
+pub fn channel() -> (ErrorReporter, Notifications) {
+    let (sender, receiver) = mpsc::channel();
+    (
+        ErrorReporter { sender },
+        Notifications {
+            receiver,
+            messages: Vec::new(),
+        },
+    )
+}
+
+/// Sending end of the notifications that can be moved to render threads
+#[derive(Debug, Clone)]
+pub struct ErrorReporter {
+    sender: Sender<String>,
+}
+
+impl ErrorReporter {
+    pub fn report(&self, message: String) {
+        // nothing is listening if the app is closing
+        let _ = self.sender.send(message);
+    }
+
+    /// reports the error of the result after a description of what failed, returns the value if
+    /// the result was ok
+    pub fn ok<T, E: ToString>(
+        &self,
+        result: Result<T, E>,
+        what: impl FnOnce() -> String,
+    ) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.report(format!("{}: {}", what(), error.to_string()));
+                None
+            }
+        }
+    }
+
+    /// reports the error of the result like `ok`, returns whether the result was ok
+    pub fn check<T, E: ToString>(
+        &self,
+        result: Result<T, E>,
+        what: impl FnOnce() -> String,
+    ) -> bool {
+        self.ok(result, what).is_some()
+    }
+}
+
+/// Errors reported by render threads that haven't been dismissed
+pub struct Notifications {
+    receiver: Receiver<String>,
+    messages: Vec<String>,
+}
+
+impl Notifications {
+    /// shows the errors over the bottom right corner of the window
+    pub fn show(&mut self, ctx: &egui::Context) {
+        for message in self.receiver.try_iter() {
+            if !self.messages.contains(&message) {
+                self.messages.push(message);
+            }
+        }
+        if self.messages.is_empty() {
+            return;
+        }
+
+        let mut dismissed = None;
+        egui::Area::new("render error notifications")
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8_f32, -8_f32))
+            .show(ctx, |ui| {
+                for (i, message) in self.messages.iter().enumerate() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.colored_label(ui.visuals().error_fg_color, message);
+                            if ui.small_button("✖").on_hover_text("Dismiss").clicked() {
+                                dismissed = Some(i);
+                            }
+                        });
+                    });
+                }
+            });
+
+        if let Some(i) = dismissed {
+            self.messages.remove(i);
+        }
+    }
+}
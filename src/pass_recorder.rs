@@ -0,0 +1,243 @@
+// Collects the passes written alongside a render from the rays its samples trace, so the passes
+// don't trace the scene a second time
+//
+// The recorder covers the region of the render, with the eyes of stereo renders stacked like the
+// render. Every pixel keeps sums of what the rays of its samples found for the passes to average,
+// so motion blurred frames average their shutter samples. Diagnostic renders don't trace the rays
+// the passes need, so their passes are empty.
+
+use crate::{
+    render::RenderSettings, scene::RaySample, stereo::looks_behind, telemetry::FrameTelemetry,
+};
+use image::{GrayImage, Luma, Rgb, Rgb32FImage};
+use nalgebra::Vector2;
+use std::sync::{Arc, Mutex};
+
+/// What the rays of the samples of a pixel found, summed over them
+#[derive(Debug, Default, Clone, Copy)]
+struct PixelRays {
+    // samples of the pixel and how many of them light reaches the diver along
+    samples: u32,
+    lit: u32,
+    // samples nearest the center of the pixel
+    centers: u32,
+    // sum of the frequency ratios of the center samples that light reaches the diver along
+    frequency_ratio: f64,
+    // center samples whose rays reach the sky
+    escaped: u32,
+    // sum of the deflections of the escaping center samples
+    deflection: f64,
+    // smallest and largest frequency ratio of the escaping center samples
+    escaped_ratio_range: Option<(f64, f64)>,
+}
+
+impl PixelRays {
+    /// adds a sample of the pixel
+    fn add_sample(&mut self, ray: RaySample) {
+        self.samples += 1;
+        self.lit += ray.frequency_ratio.is_some() as u32;
+    }
+
+    /// adds the sample nearest the center of the pixel
+    fn add_center(&mut self, ray: RaySample) {
+        self.centers += 1;
+        self.frequency_ratio += ray.frequency_ratio.unwrap_or(0_f64);
+        if let Some(deflection) = ray.deflection {
+            let ratio = ray.frequency_ratio.unwrap_or(1_f64);
+            self.escaped += 1;
+            self.deflection += deflection;
+            self.escaped_ratio_range = Some(match self.escaped_ratio_range {
+                Some((min, max)) => (min.min(ratio), max.max(ratio)),
+                None => (ratio, ratio),
+            });
+        }
+    }
+}
+
+/// Passes of a render being collected as it renders, clones record into the same passes
+#[derive(Clone)]
+pub struct PassRecorder {
+    pixels: Arc<Mutex<Vec<PixelRays>>>,
+    // size of the recorded output, with both eyes stacked for stereo renders
+    size: Vector2<u32>,
+    // pixel of the render at the top left of the output and the size of the region of the render
+    // each eye covers
+    offset: Vector2<u32>,
+    region_size: Vector2<u32>,
+    // row of the output the eye being recorded starts at
+    eye_row: u32,
+    // resolution of the render, stereo renders show the left eye behind the camera to both eyes
+    resolution: Vector2<u32>,
+}
+
+impl PassRecorder {
+    /// returns an empty recorder for the output of a render with the settings
+    pub fn new(render_settings: RenderSettings) -> Self {
+        let (offset, region_size) = render_settings.region();
+        let size = render_settings.output_size();
+        Self {
+            pixels: Arc::new(Mutex::new(vec![
+                PixelRays::default();
+                (size.x * size.y) as usize
+            ])),
+            size,
+            offset,
+            region_size,
+            eye_row: 0,
+            resolution: render_settings.resolution,
+        }
+    }
+
+    /// returns the recorder for the view of an eye of a stereo render, 0 for the left eye on top
+    /// and 1 for the right eye below it
+    pub fn for_eye(&self, eye: u32) -> Self {
+        Self {
+            eye_row: eye * self.region_size.y,
+            ..self.clone()
+        }
+    }
+
+    /// adds the rays of the samples of the rectangle of the render with its top left pixel at
+    /// `offset` and the given `size`, the samples are in rows and each pixel has `super_sampling`
+    /// by `super_sampling` of them
+    pub fn record(
+        &self,
+        offset: Vector2<u32>,
+        size: Vector2<u32>,
+        super_sampling: u32,
+        rays: &[RaySample],
+    ) {
+        let row_length = size.x * super_sampling;
+        let center = super_sampling / 2;
+
+        let sample = |x: u32, y: u32| rays[(y * row_length + x) as usize];
+
+        let mut pixels = self.pixels.lock().unwrap();
+        for y in 0..size.y {
+            for x in 0..size.x {
+                // margins rendered around a region for denoising fall outside of it
+                let pixel = Vector2::new(x, y) + offset;
+                let (Some(px), Some(py)) = (
+                    pixel.x.checked_sub(self.offset.x),
+                    pixel.y.checked_sub(self.offset.y),
+                ) else {
+                    continue;
+                };
+                if px >= self.region_size.x || py >= self.region_size.y {
+                    continue;
+                }
+
+                // behind the camera the left eye of a stereo render is recorded for both eyes
+                let behind =
+                    self.size.y > self.region_size.y && looks_behind(pixel.x, self.resolution);
+                if behind && self.eye_row > 0 {
+                    continue;
+                }
+                let rows = [
+                    Some(py + self.eye_row),
+                    behind.then(|| py + self.region_size.y),
+                ];
+
+                for py in rows.into_iter().flatten() {
+                    let sums = &mut pixels[(py * self.size.x + px) as usize];
+                    for dy in 0..super_sampling {
+                        for dx in 0..super_sampling {
+                            sums.add_sample(sample(
+                                x * super_sampling + dx,
+                                y * super_sampling + dy,
+                            ));
+                        }
+                    }
+                    sums.add_center(sample(
+                        x * super_sampling + center,
+                        y * super_sampling + center,
+                    ));
+                }
+            }
+        }
+    }
+
+    /// returns the redshift pass, the frequency ratio the center sample of every pixel sees and
+    /// 0 where no light reaches the diver
+    pub fn redshift_pass(&self) -> Rgb32FImage {
+        let pixels = self.pixels.lock().unwrap();
+        Rgb32FImage::from_fn(self.size.x, self.size.y, |x, y| {
+            let sums = pixels[(y * self.size.x + x) as usize];
+            let ratio = sums.frequency_ratio / sums.centers.max(1) as f64;
+            Rgb([ratio as f32; 3])
+        })
+    }
+
+    /// returns the matte pass, the fraction of the samples of every pixel that light reaches the
+    /// diver along
+    pub fn matte_pass(&self) -> GrayImage {
+        let pixels = self.pixels.lock().unwrap();
+        GrayImage::from_fn(self.size.x, self.size.y, |x, y| {
+            let sums = pixels[(y * self.size.x + x) as usize];
+            let coverage = sums.lit as f32 / sums.samples.max(1) as f32;
+            Luma([(coverage * 255_f32).round() as u8])
+        })
+    }
+
+    /// returns the statistics of the center samples of the pixels for the telemetry of the frame
+    pub fn telemetry(&self, frame: i32) -> FrameTelemetry {
+        let pixels = self.pixels.lock().unwrap();
+        let centers: u32 = pixels.iter().map(|sums| sums.centers).sum();
+        let escaped: u32 = pixels.iter().map(|sums| sums.escaped).sum();
+        let deflection: f64 = pixels.iter().map(|sums| sums.deflection).sum();
+
+        // the redshift z = 1 / ratio - 1 falls as the ratio rises
+        let ratio_range = pixels
+            .iter()
+            .filter_map(|sums| sums.escaped_ratio_range)
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)));
+
+        FrameTelemetry {
+            frame,
+            captured_fraction: 1_f64 - escaped as f64 / centers.max(1) as f64,
+            mean_deflection: (escaped > 0).then(|| deflection / escaped as f64),
+            redshift_range: ratio_range
+                .map(|(min, max)| (1_f64 / max - 1_f64, 1_f64 / min - 1_f64)),
+        }
+    }
+}
+
+impl PartialEq for PassRecorder {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.pixels, &other.pixels) && self.eye_row == other.eye_row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Projection;
+
+    #[test]
+    fn passes_of_super_sampled_pixels() {
+        let render_settings =
+            RenderSettings::new(Projection::Perspective, Vector2::new(2, 1), Some(2));
+        let recorder = PassRecorder::new(render_settings);
+
+        // only the center sample of the left pixel sees the sky
+        let mut rays = [RaySample::default(); 8];
+        rays[5] = RaySample {
+            frequency_ratio: Some(2_f64),
+            deflection: Some(0.5_f64),
+        };
+        recorder.record(Vector2::zeros(), Vector2::new(2, 1), 2, &rays);
+
+        let matte = recorder.matte_pass();
+        assert_eq!((matte[(0, 0)].0, matte[(1, 0)].0), ([64], [0]));
+        let redshift = recorder.redshift_pass();
+        assert_eq!(
+            (redshift[(0, 0)].0, redshift[(1, 0)].0),
+            ([2_f32; 3], [0_f32; 3])
+        );
+
+        let telemetry = recorder.telemetry(0);
+        assert_eq!(telemetry.captured_fraction, 0.5_f64);
+        assert_eq!(telemetry.mean_deflection, Some(0.5_f64));
+        assert_eq!(telemetry.redshift_range, Some((-0.5_f64, -0.5_f64)));
+    }
+}
@@ -1,24 +1,44 @@
+use crate::deflection_lut::LutCache;
+use crate::progress::RenderProgress;
 use crate::render::RenderSettings;
 use crate::scene::Scene;
 use image::RgbImage;
-use nalgebra::Vector2;
 use std::ops::Deref;
-use std::sync::{Arc, Mutex};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
 use std::thread;
 use std::time::{Duration, Instant};
 
 pub struct PreviewManager {
-    working: Arc<Mutex<bool>>,
+    // number of renders still running, cancelled ones stop early
+    working: Arc<AtomicUsize>,
     previous_render: Arc<Mutex<Option<(RgbImage, Duration)>>>,
-    previous_scene_resolution: Option<(Scene, Vector2<u32>)>,
+    // number of renders that replaced the previous render, so a new one can be told apart
+    generation: Arc<AtomicUsize>,
+    // the previous scene rendered in flat space without lensing to compare against
+    flat_render: Arc<Mutex<Option<RgbImage>>>,
+    previous_scene_settings: Option<(Scene, RenderSettings, bool)>,
+    // progress of the draft render of the latest scene, cancelled when a newer one arrives
+    draft: Option<RenderProgress>,
+    // progress of the higher quality render of the previous scene once it has settled
+    refinement: Option<RenderProgress>,
+    // tables of the lensing of recent scenes, reused while only the camera turns
+    lut_cache: LutCache,
 }
 
 impl Default for PreviewManager {
     fn default() -> Self {
         Self {
-            working: Arc::new(Mutex::new(false)),
+            working: Arc::new(AtomicUsize::new(0)),
             previous_render: Arc::new(Mutex::new(None)),
-            previous_scene_resolution: None,
+            generation: Arc::new(AtomicUsize::new(0)),
+            flat_render: Arc::new(Mutex::new(None)),
+            previous_scene_settings: None,
+            draft: None,
+            refinement: None,
+            lut_cache: LutCache::new(),
         }
     }
 }
@@ -50,40 +70,154 @@ impl PreviewManager {
         }
     }
 
+    /// returns a number that changes whenever a new render replaces the previous render
+    pub fn render_generation(&self) -> usize {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// returns the render with the columns left of `split` taken from the flat space render, None
+    /// if the flat space render isn't available at the same resolution
+    pub fn split_with_flat(&self, render: &RgbImage, split: u32) -> Option<RgbImage> {
+        let flat_render = self.flat_render.lock().unwrap();
+        let flat = flat_render
+            .as_ref()
+            .filter(|flat| flat.dimensions() == render.dimensions())?;
+
+        Some(RgbImage::from_fn(
+            render.width(),
+            render.height(),
+            |x, y| {
+                if x < split {
+                    *flat.get_pixel(x, y)
+                } else {
+                    *render.get_pixel(x, y)
+                }
+            },
+        ))
+    }
+
     pub fn is_working(&self) -> bool {
-        *self.working.lock().unwrap()
+        self.working.load(Ordering::Relaxed) > 0
+    }
+
+    /// returns the progress of the refinement render if one is running
+    pub fn refinement_progress(&self) -> Option<f32> {
+        self.refinement
+            .as_ref()
+            .filter(|refinement| !refinement.is_finished() && !refinement.is_cancelled())
+            .map(|refinement| refinement.fraction())
     }
 
-    pub fn new_render(&mut self, scene: Scene, resolution: Vector2<u32>) {
+    /// renders the scene if it changed, once the scene has stopped changing it is rendered again
+    /// with the refinement settings if they are given
+    ///
+    /// a render of an older scene still running is cancelled so the preview always catches up
+    /// with the latest scene
+    ///
+    /// the scene is also rendered in flat space to compare against if `compare_flat` is true
+    pub fn new_render(
+        &mut self,
+        scene: Scene,
+        render_settings: RenderSettings,
+        refine_settings: Option<RenderSettings>,
+        compare_flat: bool,
+    ) {
         // if the scene is the same as the last scene then don't re-render it
-        if let Some((previous_scene, previous_resolution)) = &self.previous_scene_resolution {
-            if scene == *previous_scene && resolution == *previous_resolution {
+        if let Some((previous_scene, previous_settings, previous_compare_flat)) =
+            &self.previous_scene_settings
+        {
+            if scene == *previous_scene
+                && render_settings == *previous_settings
+                && compare_flat == *previous_compare_flat
+            {
+                // the scene has settled so refine it once its draft is done
+                if let (Some(refine_settings), None, false) =
+                    (refine_settings, &self.refinement, self.is_working())
+                {
+                    let progress = RenderProgress::new();
+                    self.spawn_render(scene, refine_settings, progress.clone(), compare_flat);
+                    self.refinement = Some(progress);
+                }
                 return;
             }
         }
 
-        let mut working = self.working.lock().unwrap();
+        // any change abandons the renders of the older scene and starts again from a draft
+        for progress in [self.draft.take(), self.refinement.take()].iter().flatten() {
+            progress.cancel();
+        }
 
-        if !*working {
-            *working = true;
-            drop(working);
+        let progress = RenderProgress::new();
+        self.spawn_render(
+            scene.clone(),
+            render_settings,
+            progress.clone(),
+            compare_flat,
+        );
+        self.draft = Some(progress);
+        self.previous_scene_settings = Some((scene, render_settings, compare_flat));
+    }
 
-            self.previous_scene_resolution = Some((scene.clone(), resolution));
+    /// renders on a new thread, cancelling the progress stops it early without keeping the result
+    fn spawn_render(
+        &self,
+        scene: Scene,
+        render_settings: RenderSettings,
+        progress: RenderProgress,
+        compare_flat: bool,
+    ) {
+        self.working.fetch_add(1, Ordering::Relaxed);
 
-            let working = self.working.clone();
-            let previous_render = self.previous_render.clone();
+        let working = self.working.clone();
+        let previous_render = self.previous_render.clone();
+        let generation = self.generation.clone();
+        let flat_render = self.flat_render.clone();
+        let lut_cache = self.lut_cache.clone();
 
-            // render on a new thread
-            thread::spawn(move || {
-                // rendering logic
-                let start = Instant::now();
-                let render = scene.render(RenderSettings::preview(resolution));
+        // render on a new thread
+        thread::spawn(move || {
+            // rendering logic
+            let start = Instant::now();
 
-                // save render
-                *previous_render.lock().unwrap() = Some((render, Instant::now() - start));
-                // update working to false
-                *working.lock().unwrap() = false;
-            });
-        }
+            let with_lut = |scene: Scene| {
+                scene
+                    .with_cached_deflection_lut(render_settings, &lut_cache)
+                    .unwrap_or(scene)
+            };
+
+            // the same view without lensing renders alongside, each pass is a step of the progress
+            let pass = |scene: Scene| {
+                let pass_progress = progress.subtask();
+                let render = with_lut(scene).render_with_progress(render_settings, &pass_progress);
+                progress.advance(1);
+                render
+            };
+            progress.add_total(if compare_flat { 2 } else { 1 });
+            let ((render, duration), flat) = rayon::join(
+                || (pass(scene.clone()), Instant::now() - start),
+                || {
+                    compare_flat
+                        .then(|| {
+                            pass(Scene {
+                                lensing: false,
+                                ..scene.clone()
+                            })
+                        })
+                        .flatten()
+                },
+            );
+
+            // save render unless it was cancelled, checked while holding the lock so a render of
+            // an older scene can't replace the render of the newer one that cancelled it
+            let mut previous_render = previous_render.lock().unwrap();
+            if let Some(render) = render.filter(|_| !progress.is_cancelled()) {
+                *previous_render = Some((render, duration));
+                *flat_render.lock().unwrap() = flat;
+                generation.fetch_add(1, Ordering::Relaxed);
+            }
+            drop(previous_render);
+
+            working.fetch_sub(1, Ordering::Relaxed);
+        });
     }
 }
@@ -0,0 +1,128 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Shared handle for following the progress of a render and cancelling it from another thread
+#[derive(Debug, Clone, Default)]
+pub struct RenderProgress {
+    cancelled: Arc<AtomicBool>,
+    done: Arc<AtomicUsize>,
+    total: Arc<AtomicUsize>,
+    // subtasks that are still running, counted in the fraction for the part of their step they did
+    subtasks: Arc<Mutex<Vec<RenderProgress>>>,
+}
+
+impl RenderProgress {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// returns a progress with its own amount of work that is cancelled along with this one
+    ///
+    /// the subtask is expected to make up one step of this progress that is marked as done once
+    /// the subtask is finished
+    pub fn subtask(&self) -> Self {
+        let subtask = Self {
+            cancelled: self.cancelled.clone(),
+            ..Default::default()
+        };
+        self.subtasks.lock().unwrap().push(subtask.clone());
+        subtask
+    }
+
+    /// asks the render to stop as soon as possible
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// adds steps to the total amount of work
+    pub fn add_total(&self, steps: usize) {
+        self.total.fetch_add(steps, Ordering::Relaxed);
+    }
+
+    /// marks steps of work as done
+    pub fn advance(&self, steps: usize) {
+        self.done.fetch_add(steps, Ordering::Relaxed);
+    }
+
+    /// returns the fraction of the work that is done including the running subtasks
+    pub fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0_f32;
+        }
+
+        // finished subtasks are already counted once their step is marked as done, subtasks
+        // that haven't added any work yet are kept until they have
+        let mut subtasks = self.subtasks.lock().unwrap();
+        subtasks
+            .retain(|subtask| subtask.total.load(Ordering::Relaxed) == 0 || !subtask.is_finished());
+        let running: f32 = subtasks.iter().map(|subtask| subtask.fraction()).sum();
+
+        ((self.done.load(Ordering::Relaxed) as f32 + running) / total as f32).min(1_f32)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.done.load(Ordering::Relaxed) >= self.total.load(Ordering::Relaxed)
+    }
+}
+
+/// Estimates the time left of a render from how fast its progress went over the last seconds
+#[derive(Debug, Default)]
+pub struct Eta {
+    // recent fractions done and when they were seen, oldest first
+    history: VecDeque<(Instant, f32)>,
+}
+
+impl Eta {
+    // how far back the rate of progress is measured over
+    const WINDOW: Duration = Duration::from_secs(10);
+
+    /// forgets the progress of the previous render
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    /// records the fraction done now and returns the estimated time left, None until the progress
+    /// has moved
+    pub fn update(&mut self, fraction: f32) -> Option<Duration> {
+        let now = Instant::now();
+        if self.history.back().map(|&(_, last)| last) != Some(fraction) {
+            self.history.push_back((now, fraction));
+        }
+        // keep one sample older than the window so the rate spans all of it
+        while self.history.len() > 2 && now - self.history[1].0 > Self::WINDOW {
+            self.history.pop_front();
+        }
+
+        let &(start, start_fraction) = self.history.front()?;
+        let progressed = fraction - start_fraction;
+        if progressed <= 0_f32 {
+            return None;
+        }
+        let seconds = (now - start).as_secs_f32() * (1_f32 - fraction) / progressed;
+        Some(Duration::from_secs_f32(seconds))
+    }
+}
+
+/// Formats a duration roughly, like `1h 5m`, `3m 20s` or `12s`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
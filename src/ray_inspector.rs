@@ -0,0 +1,246 @@
+// Shows how the ray seen at a ctrl-clicked pixel of the preview travels around the black hole
+//
+// The ray is followed back from the diver like the renderer does for the companion and the jet.
+// Its deflection is the angle it sweeps around the black hole beyond the pi - theta a straight
+// line leaving the diver at theta from the black hole would sweep.
+
+use crate::{
+    app::BHDiver,
+    math::image_order,
+    ray_path::RayPath,
+    scene::{Scene, Spacetime},
+    spherical_angle::{RainAngle, SphericalAngle},
+    units::Units,
+};
+use nalgebra::Vector3;
+use std::f64::consts::PI;
+
+/// Summary of the path of a ray around the black hole
+struct RayReport {
+    // smallest distance from the black hole along the path
+    closest_approach: f64,
+    // angle swept around the black hole until the ray escapes or ends
+    swept: f64,
+    // bending compared to a straight line, None if the ray doesn't escape
+    deflection: Option<f64>,
+}
+
+impl RayReport {
+    fn new(path: &RayPath) -> Option<Self> {
+        let &(start, _) = path.points.first()?;
+        let &(last, last_swept) = path.points.last()?;
+
+        let closest_approach = path
+            .points
+            .iter()
+            .map(|(point, _)| point.norm())
+            .fold(f64::INFINITY, f64::min);
+
+        // the escaping ray sweeps the rest of the angle to its asymptote
+        let swept = match path.escape {
+            Some(escape) => last_swept + angle_between(last, escape),
+            None => last_swept,
+        };
+
+        // the direction the ray leaves the diver in going back along it
+        let direction = path
+            .points
+            .get(1)
+            .map(|&(point, _)| point - start)
+            .or(path.escape);
+        let deflection = path.escape.zip(direction).map(|(_, direction)| {
+            let from_black_hole = angle_between(-start, direction);
+            swept - (PI - from_black_hole)
+        });
+
+        Some(Self {
+            closest_approach,
+            swept,
+            deflection,
+        })
+    }
+}
+
+/// Returns the angle between the vectors
+fn angle_between(a: Vector3<f64>, b: Vector3<f64>) -> f64 {
+    (a.normalize().dot(&b.normalize()))
+        .clamp(-1_f64, 1_f64)
+        .acos()
+}
+
+#[derive(Default)]
+pub struct RayInspector {
+    // view angle of the inspected ray, which stays fixed in space when the camera turns
+    view_angle: Option<RainAngle>,
+}
+
+impl RayInspector {
+    /// inspects the ray seen at the view angle
+    pub fn inspect(&mut self, view_angle: RainAngle) {
+        self.view_angle = Some(view_angle);
+    }
+
+    pub fn build(ui: &mut egui::Ui, app: &mut BHDiver) {
+        let Some(view_angle) = app.ray_inspector.view_angle else {
+            ui.label("Ctrl-click the preview to inspect the ray seen at that pixel");
+            return;
+        };
+
+        let scene = app.timeline.get_current_scene();
+        let render_settings = app.renderer.render_settings();
+        let units = &app.settings.units;
+        let path = scene.ray_path(view_angle, render_settings);
+        let report = path.as_ref().and_then(RayReport::new);
+
+        egui::Grid::new("ray_inspector_grid")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                let (rain_angle, _) = scene.view_to_rain_angle(view_angle);
+                ui.label("Rain angle");
+                ui.label(format!(
+                    "θ {:.3}°, φ {:.3}°",
+                    rain_angle.theta().to_degrees(),
+                    rain_angle.phi().to_degrees()
+                ))
+                .on_hover_text("Direction the ray arrives from, θ is measured from the black hole");
+                ui.end_row();
+
+                ui.label("Outcome");
+                match scene.trace_to_side(view_angle, render_settings) {
+                    Some((map_angle, order, other_side)) => {
+                        ui.label(match (scene.spacetime, other_side) {
+                            (Spacetime::Wormhole, true) => "Through the throat",
+                            _ => "Escapes",
+                        });
+                        ui.end_row();
+
+                        ui.label("Map angle");
+                        ui.label(format!(
+                            "θ {:.3}°, φ {:.3}°",
+                            map_angle.theta.to_degrees(),
+                            map_angle.phi.to_degrees()
+                        ))
+                        .on_hover_text("Direction on the sky the ray comes from");
+                        ui.end_row();
+
+                        ui.label("Image order");
+                        ui.label(order.to_string());
+                        ui.end_row();
+                    }
+                    None => {
+                        ui.label("Falls into the black hole");
+                        ui.end_row();
+                    }
+                }
+
+                if let Some(report) = &report {
+                    ui.label("Closest approach");
+                    ui.label(units.describe_length(report.closest_approach));
+                    ui.end_row();
+
+                    ui.label("Swept angle");
+                    ui.label(format!(
+                        "{:.3}° = {:.3} turns",
+                        report.swept.to_degrees(),
+                        report.swept / (2_f64 * PI)
+                    ))
+                    .on_hover_text("Angle the ray winds around the black hole");
+                    ui.end_row();
+
+                    if let Some(deflection) = report.deflection {
+                        ui.label("Deflection");
+                        ui.label(format!("{:.3}°", deflection.to_degrees()))
+                            .on_hover_text(format!(
+                                "Bending compared to a straight line, image order {}",
+                                image_order(report.swept)
+                            ));
+                        ui.end_row();
+                    }
+                }
+            });
+
+        match &path {
+            Some(path) => {
+                ui.separator();
+                Self::show_path(ui, &scene, path, units);
+            }
+            None => {
+                ui.weak("Rays can't be followed around spinning black holes or through wormholes");
+            }
+        }
+    }
+
+    /// plots the path in its plane seen from above, with the diver below the black hole
+    fn show_path(ui: &mut egui::Ui, scene: &Scene, path: &RayPath, units: &Units) {
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), ui.available_height().max(200_f32)),
+            egui::Sense::hover(),
+        );
+        let rect = response.rect;
+        let painter = painter.with_clip_rect(rect);
+        painter.rect_filled(rect, egui::Rounding::none(), ui.visuals().extreme_bg_color);
+
+        // the path stays in a plane so the distance and swept angle place it
+        let plane_point = |radius: f64, swept: f64| (radius * swept.sin(), -radius * swept.cos());
+
+        let start = path.points.first().map_or(0_f64, |(point, _)| point.norm());
+        let extent = 1.5 * start.max(3_f64 * scene.mass);
+        let scale = (rect.width().min(rect.height()) as f64 / 2_f64) / extent;
+        let to_screen = |(x, y): (f64, f64)| {
+            rect.center() + egui::vec2((x * scale) as f32, -(y * scale) as f32)
+        };
+
+        let weak = ui.visuals().weak_text_color();
+        if scene.spacetime == Spacetime::BlackHole {
+            painter.circle_filled(
+                rect.center(),
+                (2_f64 * scene.mass * scale) as f32,
+                egui::Color32::BLACK,
+            );
+            painter.circle_stroke(
+                rect.center(),
+                (2_f64 * scene.mass * scale) as f32,
+                egui::Stroke::new(1_f32, weak),
+            );
+            painter.circle_stroke(
+                rect.center(),
+                (3_f64 * scene.mass * scale) as f32,
+                egui::Stroke::new(0.5, weak),
+            );
+        }
+
+        let mut points: Vec<_> = path
+            .points
+            .iter()
+            .map(|&(point, swept)| to_screen(plane_point(point.norm(), swept)))
+            .collect();
+        if let (Some(escape), Some(&(last, last_swept))) = (path.escape, path.points.last()) {
+            // continue to the edge of the plot along the asymptote
+            let swept = last_swept + angle_between(last, escape);
+            let (x, y) = plane_point(last.norm(), last_swept);
+            let (dx, dy) = plane_point(1_f64, swept);
+            let reach = 2_f64 * extent;
+            points.push(to_screen((x + dx * reach, y + dy * reach)));
+        }
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.5_f32, ui.visuals().strong_text_color()),
+        ));
+
+        // the diver
+        painter.circle_filled(
+            to_screen(plane_point(start, 0_f64)),
+            4_f32,
+            ui.visuals().selection.bg_fill,
+        );
+
+        painter.text(
+            rect.left_bottom(),
+            egui::Align2::LEFT_BOTTOM,
+            format!("Plot radius {}", units.describe_length(extent)),
+            egui::TextStyle::Small.resolve(ui.style()),
+            weak,
+        );
+    }
+}
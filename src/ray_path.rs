@@ -0,0 +1,126 @@
+// Paths of rays followed back from the diver through the space around the black hole, for finding
+// the objects they pass through before reaching the sky
+//
+// The delay of the light along a path is measured in the ingoing Eddington-Finkelstein time
+// v = t + r*, which stays finite through the horizon. Light satisfies
+// f dv^2 - 2 dr dv - r^2 dphi^2 = 0 with f = 1 - 2M/r, so going inward it takes
+// dv = r^2 dphi^2 / (sqrt(dr^2 + f r^2 dphi^2) - dr) and going outward dv = (dr + sqrt(...)) / f,
+// with dr the change in radius forward in time. Light falling straight in takes no time at all.
+
+use crate::companion::sphere_intersection;
+use nalgebra::Vector3;
+
+/// Most steps taken along a single segment of a ray when measuring its delay
+const MAX_DELAY_STEPS: usize = 1000;
+
+/// Ray followed back from the diver as straight segments in the frame of the map
+#[derive(Debug, Clone, PartialEq)]
+pub struct RayPath {
+    // points along the ray and the angle swept around the black hole at each
+    pub points: Vec<(Vector3<f64>, f64)>,
+    // direction the ray continues in a straight line after the last point, None if it ends there
+    pub escape: Option<Vector3<f64>>,
+}
+
+impl RayPath {
+    /// returns the start, end and swept angle at the start of each segment of the path, the
+    /// escaping ray is cut off once it is at least `reach` from the black hole
+    pub fn segments(
+        &self,
+        reach: f64,
+    ) -> impl Iterator<Item = (Vector3<f64>, Vector3<f64>, f64)> + '_ {
+        let escape = self
+            .escape
+            .zip(self.points.last())
+            .map(|(direction, last)| {
+                let (start, swept) = *last;
+                let end = start + direction.normalize() * (reach + start.norm());
+                (start, end, swept)
+            });
+
+        self.points
+            .windows(2)
+            .map(|pair| (pair[0].0, pair[1].0, pair[0].1))
+            .chain(escape)
+    }
+
+    /// returns the path ending at the point on it, where the ray reaches something opaque
+    pub fn up_to(&self, point: Vector3<f64>) -> RayPath {
+        let on_segment = |start: Vector3<f64>, end: Vector3<f64>| {
+            let step = end - start;
+            let t = if step.norm_squared() > 0_f64 {
+                ((point - start).dot(&step) / step.norm_squared()).clamp(0_f64, 1_f64)
+            } else {
+                0_f64
+            };
+            (start + t * step - point).norm() <= 1e-9 * point.norm().max(1_f64)
+        };
+
+        // the first segment the point is on, a ray that passed the point before would have hit
+        // the object there
+        let mut points = Vec::new();
+        for pair in self.points.windows(2) {
+            points.push(pair[0]);
+            if on_segment(pair[0].0, pair[1].0) {
+                points.push((point, pair[0].1));
+                return RayPath {
+                    points,
+                    escape: None,
+                };
+            }
+        }
+
+        // otherwise the point is on the ray escaping from the last point
+        points.extend(self.points.last());
+        if let Some(&(_, swept)) = self.points.last() {
+            points.push((point, swept));
+        }
+        RayPath {
+            points,
+            escape: None,
+        }
+    }
+
+    /// returns the advanced time the light takes to reach the diver from where the path is at the
+    /// radius `reach`, or from where it ends, around a black hole with the mass
+    pub fn delay(&self, mass: f64, reach: f64) -> f64 {
+        let mut delay = 0_f64;
+
+        for (start, end, _) in self.segments(reach) {
+            // stop where the ray leaves the sphere it comes from
+            let crossing = sphere_intersection(Vector3::zeros(), reach, start, end - start)
+                .filter(|t| start.norm() <= reach && *t <= 1_f64);
+            let end = crossing.map_or(end, |t| start + t * (end - start));
+
+            // the bending changes little within a step of a tenth of the distance from the hole
+            let length = (end - start).norm();
+            let spacing = 0.1 * start.norm().min(end.norm()).max(mass);
+            let steps = ((length / spacing).ceil() as usize).clamp(1, MAX_DELAY_STEPS);
+
+            for i in 0..steps {
+                // the ray is followed back so the light travels from the later to the earlier point
+                let near = start + (end - start) * (i as f64 / steps as f64);
+                let far = start + (end - start) * ((i + 1) as f64 / steps as f64);
+                let dr = near.norm() - far.norm();
+                let tangential_squared = ((far - near).norm_squared() - dr.powi(2)).max(0_f64);
+                let f = 1_f64 - 2_f64 * mass / ((near.norm() + far.norm()) / 2_f64);
+                let root = (dr.powi(2) + f * tangential_squared).max(0_f64).sqrt();
+
+                // nothing moves outward inside the horizon
+                delay += if dr > 0_f64 && f > 0_f64 {
+                    (dr + root) / f
+                } else if root - dr > 0_f64 {
+                    tangential_squared / (root - dr)
+                } else {
+                    0_f64
+                };
+            }
+
+            if crossing.is_some() {
+                break;
+            }
+        }
+
+        delay
+    }
+}
@@ -0,0 +1,188 @@
+// Shifts the colors of the environment by the ratio of the frequency the diver sees to the
+// frequency the light had far from the black hole
+//
+// Each channel is treated as a sample of the spectrum at a representative wavelength. The spectrum
+// is interpolated linearly between them and is flat outside of them, so the observed channel
+// samples it at the emitted wavelength. The specific intensity scales with the cube of the ratio.
+//
+// In the blackbody mode each pixel is instead treated as a blackbody source. Its temperature is
+// fitted from the ratio of the blue to the red channel and the shift turns it into a blackbody at
+// the temperature multiplied by the ratio, which already includes the change in intensity. The
+// part of the color the blackbody doesn't explain is scaled by how much the blackbody brightens, so
+// pixels that aren't blackbodies are unchanged when there is no shift.
+//
+// Colors are linear and never clipped, blueshifted light brighter than white is kept for HDR
+// renders and only clipped when the render is saved with 8 bits.
+
+/// Wavelengths in nanometres that the red, green and blue channels sample the spectrum at
+const CHANNEL_WAVELENGTHS: [f64; 3] = [610_f64, 550_f64, 465_f64];
+
+/// Second radiation constant hc/k in nanometre kelvin
+const RADIATION_CONSTANT: f64 = 1.4388e7;
+
+/// Range of temperatures in kelvin that pixels are fitted with
+const MIN_TEMPERATURE: f64 = 1000_f64;
+const MAX_TEMPERATURE: f64 = 100_000_f64;
+
+/// How the colors of the environment are shifted
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorShiftMode {
+    // the channels sample a spectrum interpolated between them
+    #[default]
+    Channels,
+    // the pixels are blackbodies whose temperature is shifted
+    Blackbody,
+}
+
+impl ToString for ColorShiftMode {
+    fn to_string(&self) -> String {
+        match self {
+            ColorShiftMode::Channels => "Channels".to_owned(),
+            ColorShiftMode::Blackbody => "Blackbody".to_owned(),
+        }
+    }
+}
+
+/// How the brightness of the environment is scaled by the Doppler factor without shifting colors
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Beaming {
+    #[default]
+    Off,
+    // specific intensity of light at a fixed frequency in the sky
+    Cubic,
+    // intensity integrated over all frequencies
+    Quartic,
+}
+
+impl ToString for Beaming {
+    fn to_string(&self) -> String {
+        match self {
+            Beaming::Off => "Off".to_owned(),
+            Beaming::Cubic => "D³".to_owned(),
+            Beaming::Quartic => "D⁴".to_owned(),
+        }
+    }
+}
+
+impl Beaming {
+    pub const ALL: [Beaming; 3] = [Beaming::Off, Beaming::Cubic, Beaming::Quartic];
+
+    /// returns the power of the Doppler factor the intensity scales with, None when off
+    pub fn exponent(&self) -> Option<i32> {
+        match self {
+            Beaming::Off => None,
+            Beaming::Cubic => Some(3),
+            Beaming::Quartic => Some(4),
+        }
+    }
+}
+
+/// Returns the ratio of the observed to the emitted frequency of light arriving from the rain angle
+///
+/// combines the gravitational blueshift seen by a static observer with lensing and the Doppler
+/// shift of the diver falling from rest at infinity with aberration, for the falling diver the two
+/// together are 1 / (1 - v cos theta_rain)
+pub fn frequency_ratio(theta_rain: f64, speed: f64, lensing: bool, aberration: bool) -> f64 {
+    let lorentz_factor = 1_f64 / (1_f64 - speed.powi(2)).max(0_f64).sqrt();
+
+    match (lensing, aberration) {
+        // the Lorentz factors cancel, which keeps the ratio finite inside the horizon
+        (true, true) => 1_f64 / (1_f64 - speed * theta_rain.cos()),
+        (true, false) => lorentz_factor,
+        (false, true) => 1_f64 / (lorentz_factor * (1_f64 - speed * theta_rain.cos())),
+        (false, false) => 1_f64,
+    }
+}
+
+/// Returns the Doppler factor of light arriving from the rain angle, the part of the frequency ratio
+/// from the motion of the rain through the static observers without the gravitational blueshift,
+/// None inside the horizon where there are no static observers
+pub fn doppler_factor(theta_rain: f64, speed: f64, aberration: bool) -> Option<f64> {
+    if speed >= 1_f64 {
+        return None;
+    }
+    let lorentz_factor = 1_f64 / (1_f64 - speed.powi(2)).sqrt();
+
+    Some(if aberration {
+        1_f64 / (lorentz_factor * (1_f64 - speed * theta_rain.cos()))
+    } else {
+        1_f64
+    })
+}
+
+/// Returns the linear intensity of the spectrum at the wavelength
+fn sample_spectrum(linear: [f64; 3], wavelength: f64) -> f64 {
+    // wavelengths decrease from red to blue
+    if wavelength >= CHANNEL_WAVELENGTHS[0] {
+        return linear[0];
+    }
+    if wavelength <= CHANNEL_WAVELENGTHS[2] {
+        return linear[2];
+    }
+
+    let i = if wavelength >= CHANNEL_WAVELENGTHS[1] {
+        0
+    } else {
+        1
+    };
+    let factor = (CHANNEL_WAVELENGTHS[i] - wavelength)
+        / (CHANNEL_WAVELENGTHS[i] - CHANNEL_WAVELENGTHS[i + 1]);
+    linear[i] + (linear[i + 1] - linear[i]) * factor
+}
+
+/// Returns the intensity of a blackbody at the temperature and wavelength up to a constant factor
+fn planck(temperature: f64, wavelength: f64) -> f64 {
+    wavelength.powi(-5) / (RADIATION_CONSTANT / (wavelength * temperature)).exp_m1()
+}
+
+/// Returns the temperature of the blackbody with the same ratio of blue to red as the color,
+/// clamped to the range that is fitted
+fn blackbody_temperature(linear: [f64; 3]) -> f64 {
+    let ratio = linear[2] / linear[0];
+    let blue_to_red = |temperature: f64| {
+        planck(temperature, CHANNEL_WAVELENGTHS[2]) / planck(temperature, CHANNEL_WAVELENGTHS[0])
+    };
+
+    // the ratio rises with the temperature, bisect in log space
+    let (mut low, mut high) = (MIN_TEMPERATURE.ln(), MAX_TEMPERATURE.ln());
+    for _ in 0..50 {
+        let mid = (low + high) / 2_f64;
+        if blue_to_red(mid.exp()) < ratio {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    ((low + high) / 2_f64).exp()
+}
+
+/// Returns the linear color seen when light of the linear color is shifted by the frequency ratio,
+/// unclipped so that blueshifted light can be brighter than white
+pub fn shift_color(linear: [f64; 3], ratio: f64) -> [f64; 3] {
+    let intensity = ratio.powi(3);
+
+    CHANNEL_WAVELENGTHS.map(|wavelength| sample_spectrum(linear, wavelength * ratio) * intensity)
+}
+
+/// Returns the linear color seen when light of the linear color is shifted by the frequency ratio
+/// treating it as a blackbody, unclipped so that blueshifted light can be brighter than white
+pub fn shift_color_blackbody(linear: [f64; 3], ratio: f64) -> [f64; 3] {
+    if linear == [0_f64; 3] {
+        return linear;
+    }
+
+    // least squares fit of a blackbody to the color
+    let temperature = blackbody_temperature(linear);
+    let emitted = CHANNEL_WAVELENGTHS.map(|wavelength| planck(temperature, wavelength));
+    let scale = (0..3).map(|i| linear[i] * emitted[i]).sum::<f64>()
+        / emitted.iter().map(|b| b.powi(2)).sum::<f64>();
+    let observed = CHANNEL_WAVELENGTHS.map(|wavelength| planck(temperature * ratio, wavelength));
+
+    // what the blackbody doesn't explain is scaled by how much the blackbody brightens overall
+    let gain = observed.iter().sum::<f64>() / emitted.iter().sum::<f64>();
+
+    [0, 1, 2].map(|i| {
+        let residual = linear[i] - scale * emitted[i];
+        (scale * observed[i] + residual * gain).max(0_f64)
+    })
+}
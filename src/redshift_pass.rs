@@ -0,0 +1,21 @@
+// Exports the frequency shift of the light at every pixel so compositors can grade it themselves
+//
+// Encoding: a 32-bit float RGB OpenEXR image the size of the render, or of its crop, with the ratio
+// of the frequency the diver sees to the frequency the light had leaving the sky in all three
+// channels, so 1 is unshifted, above 1 is blueshifted and below 1 is redshifted. Rays that fall
+// into the black hole are 0 since no light reaches the diver from there. Only floats can hold the
+// unbounded ratio, so the pass is always written as OpenEXR whatever the format of the render.
+// Stereo renders stack the pass of each eye like the render.
+//
+// The pass is recorded from the rays the render traces, taking the ratio of the sample nearest the
+// center of each pixel, the ratio jumps across the edge of the shadow so averaging the samples
+// would give values no light has.
+
+use crate::render::sidecar_path;
+use std::path::{Path, PathBuf};
+
+/// Returns the path of the redshift pass written alongside a render, `name.png` becomes
+/// `name.redshift.exr`
+pub fn redshift_pass_path(output_path: &Path) -> PathBuf {
+    sidecar_path(output_path, "redshift", "exr")
+}
@@ -0,0 +1,171 @@
+use image::{imageops::FilterType, Rgb, RgbImage};
+use std::ops::Range;
+
+/// How the live preview is compared against the pinned reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonMode {
+    // amplified absolute difference of the two
+    Difference,
+    // reference to the left of the wipe position and live preview to the right
+    Wipe,
+}
+
+/// Preview pinned for comparing edits against
+pub struct Reference {
+    image: Option<RgbImage>,
+    mode: ComparisonMode,
+    // factor the difference is multiplied by
+    gain: f32,
+    // fraction of the width at which the wipe splits the images
+    wipe: f32,
+    // number of times an image was pinned or cleared, so a new pin can be told from the old one
+    revision: usize,
+}
+
+impl Default for Reference {
+    fn default() -> Self {
+        Self {
+            image: None,
+            mode: ComparisonMode::Difference,
+            gain: 8_f32,
+            wipe: 0.5,
+            revision: 0,
+        }
+    }
+}
+
+impl Reference {
+    pub fn pin(&mut self, image: &RgbImage) {
+        self.image = Some(image.clone());
+        self.revision += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.image = None;
+        self.revision += 1;
+    }
+
+    /// returns everything the composite depends on apart from the live preview, the composite of
+    /// the same preview stays the same while this does
+    pub fn state(&self) -> (usize, ComparisonMode, f32, f32) {
+        (self.revision, self.mode, self.gain, self.wipe)
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.image.is_some()
+    }
+
+    /// returns the live preview compared against the reference, None if nothing is pinned
+    ///
+    /// the wipe is positioned within the `visible` columns of the image
+    pub fn composite(&self, live: &RgbImage, visible: Range<u32>) -> Option<RgbImage> {
+        let reference = self.image.as_ref()?;
+
+        // the preview resolution may have changed since the reference was pinned
+        let resized;
+        let reference = if reference.dimensions() == live.dimensions() {
+            reference
+        } else {
+            resized = image::imageops::resize(
+                reference,
+                live.width(),
+                live.height(),
+                FilterType::Triangle,
+            );
+            &resized
+        };
+
+        let split = visible.start + (self.wipe * visible.len() as f32) as u32;
+
+        Some(RgbImage::from_fn(live.width(), live.height(), |x, y| {
+            let live_pixel = live.get_pixel(x, y);
+            let reference_pixel = reference.get_pixel(x, y);
+
+            match self.mode {
+                ComparisonMode::Difference => Rgb(std::array::from_fn(|i| {
+                    (live_pixel[i].abs_diff(reference_pixel[i]) as f32 * self.gain).min(255_f32)
+                        as u8
+                })),
+                ComparisonMode::Wipe if x < split => *reference_pixel,
+                ComparisonMode::Wipe => *live_pixel,
+            }
+        }))
+    }
+
+    /// labels the comparison drawn over the preview in `rect`
+    pub fn paint_labels(&self, ui: &egui::Ui, rect: egui::Rect) {
+        if !self.is_pinned() {
+            return;
+        }
+
+        let painter = ui.painter();
+        let font = egui::TextStyle::Body.resolve(ui.style());
+        let color = ui.visuals().strong_text_color();
+        let margin = egui::vec2(4_f32, 4_f32);
+
+        match self.mode {
+            ComparisonMode::Difference => {
+                painter.text(
+                    rect.left_top() + margin,
+                    egui::Align2::LEFT_TOP,
+                    format!("Difference from reference ×{}", self.gain),
+                    font,
+                    color,
+                );
+            }
+            ComparisonMode::Wipe => {
+                let x = rect.left() + self.wipe * rect.width();
+                painter.vline(x, rect.y_range(), egui::Stroke::new(1_f32, color));
+                painter.text(
+                    egui::pos2(x - margin.x, rect.top() + margin.y),
+                    egui::Align2::RIGHT_TOP,
+                    "Reference",
+                    font.clone(),
+                    color,
+                );
+                painter.text(
+                    egui::pos2(x + margin.x, rect.top() + margin.y),
+                    egui::Align2::LEFT_TOP,
+                    "Live",
+                    font,
+                    color,
+                );
+            }
+        }
+    }
+
+    /// shows the reference controls, returns whether the current preview should be pinned
+    pub fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        let pin = ui
+            .button("📌 Pin reference")
+            .on_hover_text("Keep the current preview to compare edits against")
+            .clicked();
+
+        if self.is_pinned() {
+            if ui.button("Clear reference").clicked() {
+                self.clear();
+            }
+
+            ui.separator();
+
+            ui.selectable_value(&mut self.mode, ComparisonMode::Difference, "Difference");
+            ui.selectable_value(&mut self.mode, ComparisonMode::Wipe, "Wipe");
+
+            match self.mode {
+                ComparisonMode::Difference => {
+                    ui.label("Gain");
+                    ui.add(
+                        egui::DragValue::new(&mut self.gain)
+                            .clamp_range(1_f32..=100_f32)
+                            .speed(0.1),
+                    );
+                }
+                ComparisonMode::Wipe => {
+                    ui.add(egui::Slider::new(&mut self.wipe, 0_f32..=1_f32).show_value(false));
+                }
+            }
+        }
+
+        pin
+    }
+}
@@ -1,20 +1,231 @@
-use crate::{animation::Animation, camera::Projection, scene::Scene, timeline::Timeline};
-use egui::mutex::Mutex;
+use crate::{
+    animation::{Animation, Frame},
+    camera::{Camera, Projection},
+    deflection_lut::{LutCache, DEFAULT_LUT_SIZE, MIN_LUT_SIZE},
+    denoise::DEFAULT_STRENGTH,
+    environment::SamplingMode,
+    filename_template::{FilenameTemplate, TemplateError, TemplateValues, DEFAULT_TEMPLATE},
+    geodesic::LensingBackend,
+    gpu_preview::GpuPreview,
+    light_curve::{light_curve_path, LightCurve},
+    manifest::{manifest_path, write_manifest},
+    matte_pass::matte_pass_path,
+    notifications::{self, ErrorReporter, Notifications},
+    pass_recorder::PassRecorder,
+    progress::{format_duration, Eta, RenderProgress},
+    redshift_pass::redshift_pass_path,
+    scene::{Scene, Spacetime},
+    stereo::DEFAULT_EYE_SEPARATION,
+    telemetry::{telemetry_path, Telemetry},
+    tiff_output::{is_tiff_path, render_to_tiff},
+    tiles::{render_tiled, LiveRender},
+    timeline::Timeline,
+    units::Units,
+    uv_map::{render_uv_map, uv_map_path},
+    video_output::{is_video_path, VideoEncoder},
+};
+use egui::{
+    epaint::ecolor::{gamma_from_linear, linear_f32_from_gamma_u8, linear_from_gamma},
+    mutex::Mutex,
+};
+use image::{ImageBuffer, ImageResult, Pixel, Rgb, Rgb32FImage, RgbImage};
 use nalgebra::Vector2;
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use std::{
-    ffi::OsStr,
-    path::PathBuf,
-    sync::{
-        mpsc::{channel, Sender},
-        Arc,
-    },
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread::JoinHandle,
 };
 
-#[derive(Debug, Clone, Copy)]
+/// linear luminance that the exposure eyedropper maps the picked pixel to
+const MIDDLE_GREY: f32 = 0.18;
+
+/// half orbit limit high enough that no photon reaches it at double precision
+const DEFAULT_MAX_HALF_ORBITS: u32 = 64;
+
+/// Tints multiplying the secondary, tertiary and higher order images when they are highlighted
+const IMAGE_ORDER_TINTS: [[f32; 3]; 3] = [
+    [1_f32, 0.35, 0.35],
+    [0.35, 1_f32, 0.35],
+    [0.35, 0.35, 1_f32],
+];
+
+/// colors of the heat map of the diagnostic render modes from low to high values
+const HEAT_MAP: [[f32; 3]; 5] = [
+    [0_f32, 0_f32, 0.1],
+    [0.35, 0.05, 0.55],
+    [0.85, 0.2, 0.3],
+    [1_f32, 0.65, 0.1],
+    [1_f32, 1_f32, 0.85],
+];
+
+/// Image with 16 bits per channel
+pub type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
+
+/// 4×4 ordered dithering threshold map
+const BAYER_MATRIX: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// What the rendered image shows
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    // the lensed environment
+    #[default]
+    Beauty,
+    // heat map of the angle the rays are bent by
+    Deflection,
+    // white where rays reach the sky and black where they fall into the black hole
+    HitMask,
+    // red for redshifted and blue for blueshifted light
+    Redshift,
+    // heat map of the time taken to trace each ray
+    IntegrationCost,
+}
+
+impl ToString for RenderMode {
+    fn to_string(&self) -> String {
+        match self {
+            RenderMode::Beauty => "Beauty".to_owned(),
+            RenderMode::Deflection => "Deflection angle".to_owned(),
+            RenderMode::HitMask => "Hit mask".to_owned(),
+            RenderMode::Redshift => "Redshift factor".to_owned(),
+            RenderMode::IntegrationCost => "Integration cost".to_owned(),
+        }
+    }
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 5] = [
+        RenderMode::Beauty,
+        RenderMode::Deflection,
+        RenderMode::HitMask,
+        RenderMode::Redshift,
+        RenderMode::IntegrationCost,
+    ];
+
+    /// returns whether the mode shows false colors instead of the environment
+    pub fn is_diagnostic(&self) -> bool {
+        *self != RenderMode::Beauty
+    }
+}
+
+/// Returns the color of the heat map at the value between 0 and 1
+pub fn heat_color(value: f64) -> Rgb<u8> {
+    let position = value.clamp(0_f64, 1_f64) as f32 * (HEAT_MAP.len() - 1) as f32;
+    let i = (position as usize).min(HEAT_MAP.len() - 2);
+    let factor = position - i as f32;
+    Rgb([0, 1, 2].map(|channel| {
+        let linear =
+            HEAT_MAP[i][channel] + (HEAT_MAP[i + 1][channel] - HEAT_MAP[i][channel]) * factor;
+        (linear * 255_f32).round() as u8
+    }))
+}
+
+/// Returns a color fading from red at -1 through white at 0 to blue at 1
+pub fn diverging_color(value: f64) -> Rgb<u8> {
+    let value = value.clamp(-1_f64, 1_f64) as f32;
+    let fade = ((1_f32 - value.abs()) * 255_f32).round() as u8;
+    if value < 0_f32 {
+        Rgb([255, fade, fade])
+    } else {
+        Rgb([fade, fade, 255])
+    }
+}
+
+/// Processor still frames are rendered on
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenderBackend {
+    #[default]
+    Cpu,
+    // the shader of the GPU preview, only for still frames of scenes it supports
+    Gpu,
+}
+
+impl ToString for RenderBackend {
+    fn to_string(&self) -> String {
+        match self {
+            RenderBackend::Cpu => "CPU".to_owned(),
+            RenderBackend::Gpu => "GPU".to_owned(),
+        }
+    }
+}
+
+impl RenderBackend {
+    pub const ALL: [RenderBackend; 2] = [RenderBackend::Cpu, RenderBackend::Gpu];
+}
+
+/// Rectangle of the image to render, in fractions of its width and height so that it stays over
+/// the same part of the image when the resolution changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crop {
+    // top left and bottom right corners
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+}
+
+impl Crop {
+    /// returns the crop between two opposite corners given in any order, limited to the image
+    pub fn from_corners(a: Vector2<f32>, b: Vector2<f32>) -> Self {
+        let clamp = |corner: Vector2<f32>| corner.map(|value| value.clamp(0_f32, 1_f32));
+        Self {
+            min: clamp(a.inf(&b)),
+            max: clamp(a.sup(&b)),
+        }
+    }
+
+    /// returns the top left pixel and the size of the crop at the resolution, at least one pixel
+    pub fn pixels(&self, resolution: Vector2<u32>) -> (Vector2<u32>, Vector2<u32>) {
+        let resolution_f = resolution.cast::<f32>();
+        let start = self
+            .min
+            .component_mul(&resolution_f)
+            .map(|value| value.floor() as u32)
+            .inf(&resolution.map(|value| value.saturating_sub(1)));
+        let end = self
+            .max
+            .component_mul(&resolution_f)
+            .map(|value| value.ceil() as u32)
+            .inf(&resolution)
+            .sup(&start.add_scalar(1));
+        (start, end - start)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct RenderSettings {
     pub projection: Projection,
     pub resolution: Vector2<u32>,
     pub super_sampling: Option<usize>,
+    // exposure adjustment in stops
+    pub exposure: f32,
+    // per channel gain applied in linear light
+    pub white_balance: [f32; 3],
+    // whether to dither when quantizing to 8 bits
+    pub dither: bool,
+    // whether to save formats that support it with 16 bits per channel
+    pub sixteen_bit: bool,
+    // photons looping around the black hole more half orbits than this are rendered black
+    pub max_half_orbits: u32,
+    // tint the higher order images to show the structure of the photon rings
+    pub tint_image_orders: bool,
+    // method the lensing is calculated with
+    pub lensing_backend: LensingBackend,
+    // whether to render the environment or a diagnostic image
+    pub render_mode: RenderMode,
+    // processor still frames are rendered on
+    pub backend: RenderBackend,
+    // number of entries of the table the lensing is interpolated from, None to lens every ray
+    pub deflection_lut: Option<usize>,
+    // how the environment is filtered between its pixels
+    pub sampling: SamplingMode,
+    // whether to average the environment over the patch of sky each pixel covers
+    pub mipmaps: bool,
+    // part of the image still frames and animations are limited to, None renders all of it
+    pub crop: Option<Crop>,
+    // distance between the eyes of a top-bottom stereo 360 image, None renders one view
+    pub stereo: Option<f64>,
+    // how far apart colors the denoiser smooths together can be, None leaves the noise
+    pub denoise: Option<f32>,
 }
 
 impl Default for RenderSettings {
@@ -33,6 +244,21 @@ impl RenderSettings {
             projection,
             resolution,
             super_sampling,
+            exposure: 0_f32,
+            white_balance: [1_f32; 3],
+            dither: false,
+            sixteen_bit: false,
+            max_half_orbits: DEFAULT_MAX_HALF_ORBITS,
+            tint_image_orders: false,
+            lensing_backend: LensingBackend::Quadrature,
+            render_mode: RenderMode::Beauty,
+            backend: RenderBackend::Cpu,
+            deflection_lut: None,
+            sampling: SamplingMode::Nearest,
+            mipmaps: false,
+            crop: None,
+            stereo: None,
+            denoise: None,
         }
     }
 
@@ -40,27 +266,190 @@ impl RenderSettings {
         Self::new(Projection::Perspective, resolution, None)
     }
 
+    /// returns the top left pixel and the size of the part of the image that is rendered
+    pub fn region(&self) -> (Vector2<u32>, Vector2<u32>) {
+        self.crop
+            .map_or((Vector2::zeros(), self.resolution), |crop| {
+                crop.pixels(self.resolution)
+            })
+    }
+
+    /// returns the distance between the eyes if both are rendered, only 360° images are stereo
+    pub fn eye_separation(&self) -> Option<f64> {
+        self.stereo
+            .filter(|_| self.projection == Projection::Equirectangular)
+    }
+
+    /// returns the settings to render the view of one eye of a stereo image with
+    pub fn one_eye(&self) -> Self {
+        Self {
+            stereo: None,
+            ..*self
+        }
+    }
+
+    /// returns the size of the saved image, the region of both eyes stacked for stereo renders
+    pub fn output_size(&self) -> Vector2<u32> {
+        let (_, size) = self.region();
+        match self.eye_separation() {
+            Some(_) => Vector2::new(size.x, size.y * 2),
+            None => size,
+        }
+    }
+
+    /// tints the pixel by the order of the image it is part of if enabled, the primary image is
+    /// left as is
+    pub fn tint_image_order(&self, linear: [f64; 3], order: u32) -> [f64; 3] {
+        if !self.tint_image_orders || order == 0 {
+            return linear;
+        }
+
+        // the tints are gamma encoded
+        let tint = IMAGE_ORDER_TINTS[(order as usize - 1).min(IMAGE_ORDER_TINTS.len() - 1)];
+        [0, 1, 2].map(|i| linear[i] * linear_from_gamma(tint[i]) as f64)
+    }
+
+    /// returns whether exposure or white balance change the rendered pixels, diagnostic images
+    /// are never graded
+    pub fn is_graded(&self) -> bool {
+        !self.render_mode.is_diagnostic()
+            && (self.exposure != 0_f32 || self.white_balance != [1_f32; 3])
+    }
+
+    /// applies the exposure and white balance to a gamma encoded pixel
+    pub fn grade(&self, pixel: Rgb<f32>) -> Rgb<f32> {
+        let linear = self.grade_linear(pixel.map(linear_from_gamma));
+        linear.map(gamma_from_linear)
+    }
+
+    /// applies the exposure and white balance to a linear pixel
+    pub fn grade_linear(&self, pixel: Rgb<f32>) -> Rgb<f32> {
+        let gain = 2_f32.powf(self.exposure);
+        Rgb(std::array::from_fn(|i| {
+            pixel[i] * gain * self.white_balance[i]
+        }))
+    }
+
+    /// clips a linear image to white and converts it to 8 bits like the pixels of an 8 bit render
+    pub fn clip_hdr(&self, image: &Rgb32FImage) -> RgbImage {
+        let encoded = Rgb32FImage::from_fn(image.width(), image.height(), |x, y| {
+            image
+                .get_pixel(x, y)
+                .map(|channel| gamma_from_linear(channel.min(1_f32)))
+        });
+        self.quantize(&encoded)
+    }
+
+    /// converts a floating point image to 8 bits, dithering if enabled
+    pub fn quantize(&self, image: &Rgb32FImage) -> RgbImage {
+        RgbImage::from_fn(image.width(), image.height(), |x, y| {
+            let offset = if self.dither {
+                (BAYER_MATRIX[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16_f32 - 0.5
+            } else {
+                0_f32
+            };
+
+            Rgb(image
+                .get_pixel(x, y)
+                .0
+                .map(|channel| (channel * 255_f32 + offset).round().clamp(0_f32, 255_f32) as u8))
+        })
+    }
+
+    /// converts a floating point image to 16 bits
+    pub fn quantize_16(&self, image: &Rgb32FImage) -> Rgb16Image {
+        Rgb16Image::from_fn(image.width(), image.height(), |x, y| {
+            Rgb(image.get_pixel(x, y).0.map(|channel| {
+                (channel * u16::MAX as f32)
+                    .round()
+                    .clamp(0_f32, u16::MAX as f32) as u16
+            }))
+        })
+    }
+
+    /// adjusts the white balance so that an already graded pixel becomes neutral
+    pub fn pick_white_balance(&mut self, graded_pixel: Rgb<u8>) {
+        let linear = graded_pixel.0.map(linear_f32_from_gamma_u8);
+
+        // a channel with no signal can't be balanced
+        if linear.contains(&0_f32) {
+            return;
+        }
+
+        let mean = linear.iter().sum::<f32>() / 3_f32;
+        for (gain, channel) in self.white_balance.iter_mut().zip(linear) {
+            *gain *= mean / channel;
+        }
+    }
+
+    /// adjusts the exposure so that an already graded pixel becomes middle grey
+    pub fn pick_exposure(&mut self, graded_pixel: Rgb<u8>) {
+        let [r, g, b] = graded_pixel.0.map(linear_f32_from_gamma_u8);
+        let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+
+        if luminance > 0_f32 {
+            self.exposure += (MIDDLE_GREY / luminance).log2();
+        }
+    }
+
+    pub fn reset_grading(&mut self) {
+        self.exposure = 0_f32;
+        self.white_balance = [1_f32; 3];
+    }
+
     pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Render mode");
+            egui::ComboBox::from_id_source("render mode combo box")
+                .selected_text(self.render_mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in RenderMode::ALL {
+                        ui.selectable_value(&mut self.render_mode, mode, mode.to_string());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Render false color diagnostic images of how the rays are traced instead of the environment",
+                );
+        });
         ui.horizontal(|ui| {
             ui.label("Projection");
             egui::ComboBox::from_id_source("projection combo box")
                 .selected_text(self.projection.to_string())
                 .show_ui(ui, |ui| {
-                    ui.selectable_value(
-                        &mut self.projection,
-                        Projection::Perspective,
-                        Projection::Perspective.to_string(),
-                    );
-                    ui.selectable_value(
-                        &mut self.projection,
-                        Projection::Equirectangular,
-                        Projection::Equirectangular.to_string(),
-                    );
-                });
+                    for projection in Projection::ALL {
+                        ui.selectable_value(
+                            &mut self.projection,
+                            projection,
+                            projection.to_string(),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "360° renders the whole sky around the diver, a cube map renders it onto the six faces of a cube side by side for game engine skyboxes and a fisheye renders a dome master for planetariums with the field of view of the camera as its aperture",
+                );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Sampling");
+            egui::ComboBox::from_id_source("sampling combo box")
+                .selected_text(self.sampling.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in SamplingMode::ALL {
+                        ui.selectable_value(&mut self.sampling, mode, mode.to_string());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "How image environments are filtered between their pixels, bilinear and bicubic avoid blocky skies where the lensing magnifies them",
+                );
+            ui.checkbox(&mut self.mipmaps, "Mipmaps").on_hover_text(
+                "Average image environments over the patch of sky each pixel sees, removing the sparkling around the photon ring where the sky is squeezed together. The GPU doesn't filter with mipmaps so previews and renders with them run on the CPU",
+            );
         });
 
-        if self.projection == Projection::Equirectangular {
-            self.resolution.x = self.resolution.y * 2;
+        if let Some(aspect_ratio) = self.projection.aspect_ratio() {
+            self.resolution.x = self.resolution.y * aspect_ratio;
         }
         ui.horizontal(|ui| {
             ui.label("Resolution");
@@ -68,9 +457,9 @@ impl RenderSettings {
                 .add(egui::DragValue::new(&mut self.resolution.x).suffix(" px"))
                 .changed()
             {
-                if self.projection == Projection::Equirectangular {
-                    self.resolution.y = self.resolution.x / 2;
-                    self.resolution.x = self.resolution.y * 2;
+                if let Some(aspect_ratio) = self.projection.aspect_ratio() {
+                    self.resolution.y = self.resolution.x / aspect_ratio;
+                    self.resolution.x = self.resolution.y * aspect_ratio;
                 }
             }
             ui.label("×");
@@ -78,8 +467,8 @@ impl RenderSettings {
                 .add(egui::DragValue::new(&mut self.resolution.y).suffix(" px"))
                 .changed()
             {
-                if self.projection == Projection::Equirectangular {
-                    self.resolution.x = self.resolution.y * 2;
+                if let Some(aspect_ratio) = self.projection.aspect_ratio() {
+                    self.resolution.x = self.resolution.y * aspect_ratio;
                 }
             }
         });
@@ -98,39 +487,557 @@ impl RenderSettings {
                 self.super_sampling = None;
             }
         });
+        ui.horizontal(|ui| {
+            // each image order is another half orbit around the black hole
+            ui.label("Max image order");
+            let mut max_image_order = self.max_half_orbits - 1;
+            ui.add(
+                egui::DragValue::new(&mut max_image_order)
+                    .clamp_range(0_u32..=DEFAULT_MAX_HALF_ORBITS - 1),
+            )
+            .on_hover_text(
+                "Images of higher order than this are rendered black, 0 is the primary image, 1 the secondary image inside the Einstein ring and so on",
+            );
+            self.max_half_orbits = max_image_order + 1;
+        });
+        ui.horizontal(|ui| {
+            ui.label("Lensing backend");
+            egui::ComboBox::from_id_source("lensing backend combo box")
+                .selected_text(self.lensing_backend.to_string())
+                .show_ui(ui, |ui| {
+                    for backend in [LensingBackend::Quadrature, LensingBackend::Geodesic] {
+                        ui.selectable_value(
+                            &mut self.lensing_backend,
+                            backend,
+                            backend.to_string(),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Integrate each ray step by step instead of evaluating the closed form integrals by quadrature. Spinning black holes always use quadrature",
+                );
+        });
+        ui.horizontal(|ui| {
+            let mut denoise = self.denoise.is_some();
+            if ui
+                .checkbox(&mut denoise, "Denoise")
+                .on_hover_text(
+                    "Smooth the noise of renders with few samples while keeping edges sharp, OpenEXR renders are left raw",
+                )
+                .changed()
+            {
+                self.denoise = denoise.then_some(DEFAULT_STRENGTH);
+            }
+            if let Some(strength) = &mut self.denoise {
+                ui.add(
+                    egui::DragValue::new(strength)
+                        .clamp_range(0.01_f32..=1_f32)
+                        .speed(0.005),
+                )
+                .on_hover_text("How different colors can be and still be smoothed together");
+            }
+        });
+        ui.horizontal(|ui| {
+            let mut use_lut = self.deflection_lut.is_some();
+            if ui
+                .checkbox(&mut use_lut, "Deflection table")
+                .on_hover_text(
+                    "Lens a table of rays at the radius of the diver once per radius and interpolate every pixel from it, so frames that only turn the camera reuse it, much faster but less accurate at the edge of the shadow. Binaries are always lensed ray by ray",
+                )
+                .changed()
+            {
+                self.deflection_lut = use_lut.then_some(DEFAULT_LUT_SIZE);
+            }
+            if let Some(size) = &mut self.deflection_lut {
+                ui.add(
+                    egui::DragValue::new(size)
+                        .clamp_range(MIN_LUT_SIZE..=usize::MAX)
+                        .suffix(" entries"),
+                );
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Render stills on");
+            egui::ComboBox::from_id_source("render backend combo box")
+                .selected_text(self.backend.to_string())
+                .show_ui(ui, |ui| {
+                    for backend in RenderBackend::ALL {
+                        ui.selectable_value(&mut self.backend, backend, backend.to_string());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Still frames only: render them with the shader of the GPU preview from a lookup table of the deflection, much faster but the dithering is dropped. Animations always render on the CPU, as do super sampled, OpenEXR and 16 bit still frames and those of scenes and modes the GPU preview doesn't support",
+                );
+        });
+        ui.checkbox(&mut self.tint_image_orders, "Tint image orders")
+            .on_hover_text(
+                "Tint the secondary, tertiary and higher order images red, green and blue to show the photon rings",
+            );
+        ui.checkbox(&mut self.dither, "Dither").on_hover_text(
+            "Trades a little noise for smoother gradients when quantizing to 8 bits",
+        );
+        ui.checkbox(&mut self.sixteen_bit, "16 bit").on_hover_text(
+            "Save PNG and TIFF renders with 16 bits per channel, keeping the detail of the faint gradients near the shadow",
+        );
+        ui.horizontal(|ui| {
+            ui.label("Exposure");
+            ui.add(
+                egui::DragValue::new(&mut self.exposure)
+                    .speed(0.05)
+                    .suffix(" EV"),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("White balance");
+            for gain in self.white_balance.iter_mut() {
+                ui.add(
+                    egui::DragValue::new(gain)
+                        .clamp_range(0_f32..=f32::MAX)
+                        .speed(0.01),
+                );
+            }
+        });
+    }
+}
+
+/// Returns whether the path has an OpenEXR extension, renders saved there keep the light
+/// brighter than white in linear floating point
+fn is_exr_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("exr"))
+}
+
+/// Returns whether the path has the extension of a format that can hold 16 bits per channel
+fn supports_sixteen_bit(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            ["png", "tif", "tiff"]
+                .iter()
+                .any(|supported| extension.eq_ignore_ascii_case(supported))
+        })
+}
+
+/// Returns whether renders saved to the path have 16 bits per channel, if enabled and the format
+/// supports it
+fn saves_sixteen_bit(render_settings: RenderSettings, path: &Path) -> bool {
+    render_settings.sixteen_bit && supports_sixteen_bit(path)
+}
+
+/// Saves the gamma encoded floating point render with 16 bits per channel if enabled and the format
+/// supports it, otherwise with 8 bits
+fn save_quantized(
+    image: &Rgb32FImage,
+    render_settings: RenderSettings,
+    path: &Path,
+) -> ImageResult<()> {
+    if saves_sixteen_bit(render_settings, path) {
+        render_settings.quantize_16(image).save(path)
+    } else {
+        render_settings.quantize(image).save(path)
+    }
+}
+
+/// Returns the name of the file at the path for messages
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Describes a failed save of the file at the path for `ErrorReporter::check`
+fn saving(path: &Path) -> impl FnOnce() -> String + '_ {
+    move || format!("Couldn't save {}", file_name(path))
+}
+
+/// Returns the output path moved into the first version folder next to it that doesn't exist yet,
+/// `renders/shot.png` becomes `renders/v001/shot.png`
+fn versioned_path(output_path: &Path) -> PathBuf {
+    let parent = output_path.parent().unwrap_or(Path::new(""));
+    let file_name = output_path.file_name().unwrap_or_default();
+    (1..)
+        .map(|version| parent.join(format!("v{:03}", version)))
+        .find(|folder| !folder.exists())
+        .unwrap()
+        .join(file_name)
+}
+
+/// Returns the path of a file written alongside the output, `name.png` becomes `name.suffix.ext`
+pub fn sidecar_path(output_path: &Path, suffix: &str, ext: &str) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    output_path.with_file_name(format!("{}.{}.{}", stem, suffix, ext))
+}
+
+/// Images saved next to rendered frames besides the frame itself
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Passes {
+    // whether to write the frequency shift of each pixel
+    pub redshift: bool,
+    // whether to write a matte of the pixels outside the shadow
+    pub matte: bool,
+}
+
+/// Frame of an animation that has rendered, waiting for the video, light curve and passes to take
+/// it in order
+struct RenderedFrame {
+    image: RgbImage,
+    // linear pixels before they were clipped, only kept for the light curve
+    hdr_image: Option<Rgb32FImage>,
+    // passes recorded while the frame rendered
+    passes: Option<PassRecorder>,
+    saved: bool,
+}
+
+/// How the frames of an animation job are named and written
+struct AnimationOutput {
+    // pattern the files of the frames are named with and the values of its tokens, worked out
+    // once so the date doesn't change part way through
+    template: FilenameTemplate,
+    values: TemplateValues,
+    // files the frames are written to by frame number, the overwrite check looks at the same files
+    frame_paths: BTreeMap<i32, PathBuf>,
+    // the frames of the job are the ones whose number is offset modulo stride
+    stride: usize,
+    offset: usize,
+    // whether to skip the frames already saved to continue an interrupted render
+    skip_existing: bool,
+    // whether to write statistics of the rays and the brightness of every frame next to the frames
+    telemetry: bool,
+    light_curve: bool,
+    // number of frames rendered at the same time
+    frames_in_flight: usize,
+}
+
+/// What a render job renders
+enum RenderJobKind {
+    Frame(Box<Scene>),
+    // the frames, the frame rate of the timeline and how the frames are written
+    Animation(Animation, f32, AnimationOutput),
+}
+
+/// Still frame or animation waiting in the render queue with the settings and options of the
+/// render panel it was queued with, so editing the panel only changes the jobs queued after
+struct RenderJob {
+    kind: RenderJobKind,
+    render_settings: RenderSettings,
+    output_path: PathBuf,
+    passes: Passes,
+}
+
+impl RenderJob {
+    /// returns whether the job saves a file for every frame rather than one file
+    fn writes_frames(&self) -> bool {
+        matches!(self.kind, RenderJobKind::Animation(..)) && !is_video_path(&self.output_path)
+    }
+
+    /// moves the output of the job to the path, naming the frames of animations next to it
+    fn set_output_path(&mut self, output_path: PathBuf) {
+        if let RenderJobKind::Animation(animation, _, output) = &mut self.kind {
+            output.frame_paths = animation
+                .frames()
+                .iter()
+                .map(|frame| {
+                    let path = output
+                        .template
+                        .frame_path(&output_path, &output.values, frame.0);
+                    (frame.0, path)
+                })
+                .collect();
+        }
+        self.output_path = output_path;
+    }
+
+    /// returns the files the job writes alongside its output, the passes of every frame and the
+    /// manifest, light curve and telemetry of animations
+    fn sidecar_paths(&self) -> Vec<PathBuf> {
+        let passes = |path: &Path| {
+            [
+                self.passes.redshift.then(|| redshift_pass_path(path)),
+                self.passes.matte.then(|| matte_pass_path(path)),
+            ]
+            .into_iter()
+            .flatten()
+        };
+
+        match &self.kind {
+            RenderJobKind::Frame(_) => passes(&self.output_path).collect(),
+            RenderJobKind::Animation(_, _, output) => [
+                Some(manifest_path(
+                    &self.output_path,
+                    (output.stride, output.offset),
+                )),
+                output
+                    .light_curve
+                    .then(|| light_curve_path(&self.output_path)),
+                output.telemetry.then(|| telemetry_path(&self.output_path)),
+            ]
+            .into_iter()
+            .flatten()
+            .chain(output.frame_paths.values().flat_map(|path| passes(path)))
+            .collect(),
+        }
+    }
+
+    /// returns the number of files the job would write over
+    fn existing_files(&self) -> usize {
+        let outputs = match &self.kind {
+            RenderJobKind::Animation(_, _, output) if self.writes_frames() => {
+                // resumed renders keep the frames that were saved and add to their files
+                if output.skip_existing {
+                    return 0;
+                }
+                output
+                    .frame_paths
+                    .values()
+                    .filter(|path| path.exists())
+                    .count()
+            }
+            _ => usize::from(self.output_path.exists()),
+        };
+        let sidecars = self
+            .sidecar_paths()
+            .iter()
+            .filter(|path| path.exists())
+            .count();
+        outputs + sidecars
+    }
+
+    /// returns a short description of the job for the render panel
+    fn describe(&self) -> String {
+        let what = match &self.kind {
+            RenderJobKind::Frame(_) => "Frame".to_owned(),
+            RenderJobKind::Animation(animation, _, _) => {
+                format!("{} frames", animation.n_frames())
+            }
+        };
+        let size = self.render_settings.output_size();
+        let file_name = self
+            .output_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        format!(
+            "{}, {}×{} {} to {}",
+            what,
+            size.x,
+            size.y,
+            self.render_settings.projection.to_string(),
+            file_name
+        )
     }
 }
 
 pub struct Renderer {
     render_settings: RenderSettings,
     output_path: String,
+    // images written next to rendered frames
+    passes: Passes,
+    // whether to write statistics of the rays of every frame next to rendered animations
+    telemetry: bool,
+    // whether to write the brightness of every frame as a light curve next to rendered animations
+    light_curve: bool,
+    // whether animations skip the frames already saved to continue an interrupted render
+    skip_existing: bool,
+    // pattern the files of animation frames are named with
+    filename_template: String,
+    // only frames whose number is offset modulo stride are rendered
+    stride: usize,
+    offset: usize,
+    // number of animation frames rendered at the same time, so small frames keep every core busy
+    frames_in_flight: usize,
+    // still frame waiting for the app to render it on the GPU with its settings, passes and output
+    // path
+    gpu_frame: Option<(Scene, RenderSettings, Passes, PathBuf)>,
+    // jobs rendered one after another once the current render finishes
+    queue: Vec<RenderJob>,
+    // description of the job being rendered
+    running_job: Option<String>,
+    // errors of the render threads and the notifications they are shown in
+    errors: ErrorReporter,
+    notifications: Notifications,
+    // job waiting for the user to decide whether to write over the files it would replace, with
+    // the files described
+    pending_overwrite: Option<(RenderJob, String)>,
+    // whether clicking the preview picks the grading
+    eyedropper: bool,
+    // whether dragging over the preview selects the crop instead of turning the camera
+    crop_tool: bool,
+    // named cameras that can be used instead of the scene camera when rendering the current frame
+    camera_bookmarks: Vec<(String, Camera)>,
+    camera_override: Option<usize>,
+    // fraction of each frame the shutter is open for in degrees, 0 renders without motion blur
+    shutter_angle: f32,
+    // number of moments rendered and averaged while the shutter is open
+    motion_blur_samples: usize,
+    // initial radii and number of frames of the radius turntable
+    turntable_radii: (f64, f64),
+    turntable_frames: usize,
     rendering: Arc<Mutex<bool>>,
-    progress: Arc<Mutex<Option<f32>>>,
-    cancel_sender: Option<Sender<()>>,
+    // progress of the still frame or animation being rendered
+    progress: Option<RenderProgress>,
+    // estimate of the time left of the render
+    eta: Eta,
+    // still frame being rendered on the CPU, filled in a tile at a time
+    live_render: LiveRender,
+    animation_thread: Option<JoinHandle<()>>,
 }
 
 impl Default for Renderer {
     fn default() -> Self {
+        let (errors, notifications) = notifications::channel();
         Self {
             render_settings: Default::default(),
             output_path: Default::default(),
+            passes: Passes::default(),
+            telemetry: false,
+            light_curve: false,
+            skip_existing: false,
+            filename_template: DEFAULT_TEMPLATE.to_owned(),
+            stride: 1,
+            offset: 0,
+            frames_in_flight: 1,
+            gpu_frame: None,
+            queue: Vec::new(),
+            running_job: None,
+            pending_overwrite: None,
+            errors,
+            notifications,
+            eyedropper: false,
+            crop_tool: false,
+            camera_bookmarks: Vec::new(),
+            camera_override: None,
+            shutter_angle: 0_f32,
+            motion_blur_samples: 8,
+            turntable_radii: (20_f64, 3_f64),
+            turntable_frames: 120,
             rendering: Arc::new(Mutex::new(false)),
-            progress: Arc::new(Mutex::new(None)),
-            cancel_sender: None,
+            progress: None,
+            eta: Eta::default(),
+            live_render: LiveRender::new(),
+            animation_thread: None,
         }
     }
 }
 
 impl Renderer {
     pub fn cancel_render(&mut self) {
-        if let Some(sender) = &self.cancel_sender {
-            let result = sender.send(());
+        if let Some(progress) = &self.progress {
+            progress.cancel();
+        }
 
-            // if there was an error then the rendering thread crashed so set rendering to false
-            if result.is_err() {
-                *self.rendering.lock() = false;
-            }
+        // if the rendering thread has already stopped then it crashed so set rendering to false
+        if self
+            .animation_thread
+            .as_ref()
+            .is_some_and(|thread| thread.is_finished())
+        {
+            *self.rendering.lock() = false;
+        }
+    }
+
+    pub fn render_settings(&self) -> RenderSettings {
+        self.render_settings
+    }
+
+    /// returns the width over the height of the rendered images
+    pub fn aspect_ratio(&self) -> f32 {
+        self.render_settings.resolution.x as f32 / self.render_settings.resolution.y as f32
+    }
+
+    /// returns the settings to render the preview with at this resolution
+    pub fn preview_settings(&self, resolution: Vector2<u32>) -> RenderSettings {
+        RenderSettings {
+            exposure: self.render_settings.exposure,
+            white_balance: self.render_settings.white_balance,
+            max_half_orbits: self.render_settings.max_half_orbits,
+            tint_image_orders: self.render_settings.tint_image_orders,
+            lensing_backend: self.render_settings.lensing_backend,
+            render_mode: self.render_settings.render_mode,
+            deflection_lut: self.render_settings.deflection_lut,
+            sampling: self.render_settings.sampling,
+            mipmaps: self.render_settings.mipmaps,
+            denoise: self.render_settings.denoise,
+            ..RenderSettings::preview(resolution)
+        }
+    }
+
+    pub fn eyedropper_active(&self) -> bool {
+        self.eyedropper
+    }
+
+    pub fn crop_tool_active(&self) -> bool {
+        self.crop_tool
+    }
+
+    pub fn set_crop(&mut self, crop: Option<Crop>) {
+        self.render_settings.crop = crop;
+    }
+
+    /// picks the white balance, or the exposure if `exposure` is true, from a graded preview pixel
+    pub fn pick_grading(&mut self, graded_pixel: Rgb<u8>, exposure: bool) {
+        if exposure {
+            self.render_settings.pick_exposure(graded_pixel);
+        } else {
+            self.render_settings.pick_white_balance(graded_pixel);
+        }
+    }
+
+    pub fn bookmark_camera(&mut self, camera: Camera) {
+        let name = format!("Camera {}", self.camera_bookmarks.len() + 1);
+        self.camera_bookmarks.push((name, camera));
+    }
+
+    /// returns the scene with its camera replaced by the selected bookmark, if any
+    pub fn apply_camera_override(&self, mut scene: Scene) -> Scene {
+        if let Some((_, camera)) = self
+            .camera_override
+            .and_then(|i| self.camera_bookmarks.get(i))
+        {
+            scene.camera = *camera;
         }
+        scene
+    }
+
+    fn show_camera_override(&mut self, timeline: &Timeline, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Frame camera");
+            let selected_text = match self.camera_override {
+                Some(i) => self.camera_bookmarks[i].0.clone(),
+                None => "Scene camera".to_owned(),
+            };
+            egui::ComboBox::from_id_source("camera override combo box")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.camera_override, None, "Scene camera");
+                    for (i, (name, _)) in self.camera_bookmarks.iter().enumerate() {
+                        ui.selectable_value(&mut self.camera_override, Some(i), name);
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Camera used when rendering the current frame, the preview and timeline are unchanged",
+                );
+            if ui
+                .button("🔖")
+                .on_hover_text("Bookmark the current camera")
+                .clicked()
+            {
+                self.bookmark_camera(timeline.get_current_scene().camera);
+            }
+            if let Some(i) = self.camera_override {
+                if ui.button("🗑").on_hover_text("Delete bookmark").clicked() {
+                    self.camera_bookmarks.remove(i);
+                    self.camera_override = None;
+                }
+            }
+        });
     }
 
     pub fn is_rendering(&self) -> bool {
@@ -140,86 +1047,684 @@ impl Renderer {
     pub fn get_output_path(&self) -> Option<PathBuf> {
         let path = self.output_path.parse::<PathBuf>().ok()?;
 
-        if image::ImageFormat::from_extension(path.extension()?).is_some() {
+        if image::ImageFormat::from_extension(path.extension()?).is_some() || is_video_path(&path) {
             return Some(path);
         } else {
             return None;
         }
     }
 
+    /// returns the fraction of the render that is done if one is running
+    pub fn progress_fraction(&self) -> Option<f32> {
+        self.progress
+            .as_ref()
+            .filter(|progress| {
+                self.is_rendering() && !progress.is_finished() && !progress.is_cancelled()
+            })
+            .map(|progress| progress.fraction())
+    }
+
+    /// returns the still frame being rendered to show while its tiles finish
+    pub fn live_render(&self) -> LiveRender {
+        self.live_render.clone()
+    }
+
     /// returns whether to allow rendering
     pub fn can_render(&self) -> bool {
-        self.get_output_path().is_some() && !self.is_rendering()
+        self.get_output_path().is_some() && !self.is_rendering() && self.pending_overwrite.is_none()
     }
 
-    pub fn render_frame(&mut self, scene: Scene) {
+    /// returns whether to allow rendering a single image to the output path, only animations can
+    /// be encoded to videos
+    pub fn can_render_still(&self) -> bool {
+        self.can_render()
+            && !self
+                .get_output_path()
+                .is_some_and(|path| is_video_path(&path))
+    }
+
+    /// returns the job rendering the scene or animation with the current settings to the output
+    /// path, None if the output path isn't valid
+    fn job(&self, kind: RenderJobKind) -> Option<RenderJob> {
+        let mut job = RenderJob {
+            kind,
+            render_settings: self.render_settings,
+            output_path: PathBuf::new(),
+            passes: self.passes,
+        };
+        job.set_output_path(self.get_output_path()?);
+        Some(job)
+    }
+
+    /// starts rendering the job, first asking what to do if it would replace files that exist
+    fn start_job(&mut self, job: RenderJob) {
+        let existing = job.existing_files();
+        if existing == 0 {
+            return self.run_job(job);
+        }
+        let message = if job.writes_frames() || existing > usize::from(job.output_path.exists()) {
+            format!("{} files the render writes already exist.", existing)
+        } else {
+            format!(
+                "{} already exists.",
+                job.output_path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+            )
+        };
+        self.pending_overwrite = Some((job, message));
+    }
+
+    /// asks whether to write over the files of the job waiting to start, render it into a new
+    /// version folder instead or not render it
+    fn show_overwrite_dialog(&mut self, ctx: &egui::Context) {
+        let Some((job, message)) = &self.pending_overwrite else {
+            return;
+        };
+        let version_path = versioned_path(&job.output_path);
+
+        let (mut overwrite, mut new_version, mut cancel) = (false, false, false);
+        egui::Window::new("Output exists")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(message.as_str());
+                ui.horizontal(|ui| {
+                    overwrite = ui.button("Overwrite").clicked();
+                    new_version = ui
+                        .button("New version")
+                        .on_hover_text(format!("Render to {} instead", version_path.display()))
+                        .clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if overwrite || new_version || cancel {
+            let Some((mut job, _)) = self.pending_overwrite.take() else {
+                return;
+            };
+            if new_version {
+                // the folder has to exist for the files to be saved in it
+                if let Some(folder) = version_path.parent() {
+                    self.errors.check(std::fs::create_dir_all(folder), || {
+                        format!("Couldn't create {}", folder.display())
+                    });
+                }
+                job.set_output_path(version_path);
+            }
+            if !cancel {
+                self.run_job(job);
+            }
+        }
+    }
+
+    /// starts rendering the job straight away
+    fn run_job(&mut self, job: RenderJob) {
+        self.running_job = Some(job.describe());
+        let RenderJob {
+            kind,
+            render_settings,
+            output_path,
+            passes,
+        } = job;
+        match kind {
+            RenderJobKind::Frame(scene) => {
+                self.render_frame(*scene, render_settings, passes, output_path)
+            }
+            RenderJobKind::Animation(animation, fps, output) => {
+                self.render_animation(animation, fps, output, render_settings, passes, output_path)
+            }
+        }
+    }
+
+    /// returns the kind of job rendering the frames of the animation in the stride with the
+    /// current frame names and options, None if the frames can't be named
+    fn animation_job_kind(&self, animation: Animation, fps: f32) -> Option<RenderJobKind> {
+        let spacetime = animation
+            .frames()
+            .first()
+            .map_or(Spacetime::default(), |frame| frame.1.spacetime);
+        let output = AnimationOutput {
+            template: self.filename_template().ok()?,
+            values: TemplateValues::new(&self.get_output_path()?, spacetime),
+            // named once the job has its output path
+            frame_paths: BTreeMap::new(),
+            stride: self.stride,
+            offset: self.offset,
+            skip_existing: self.skip_existing,
+            telemetry: self.telemetry,
+            light_curve: self.light_curve,
+            frames_in_flight: self.frames_in_flight.max(1),
+        };
+        Some(RenderJobKind::Animation(
+            animation.stride(output.stride, output.offset),
+            fps,
+            output,
+        ))
+    }
+
+    /// returns the job rendering the animation of the timeline, None if it can't be named
+    fn timeline_job(&self, timeline: &Timeline) -> Option<RenderJob> {
+        let animation =
+            timeline.to_animation_with_motion_blur(self.shutter_angle, self.motion_blur_samples);
+        self.job(self.animation_job_kind(animation, timeline.fps)?)
+    }
+
+    /// starts the next job of the queue once the last render has finished
+    fn start_queued_job(&mut self) {
+        if !self.is_rendering() && self.pending_overwrite.is_none() && !self.queue.is_empty() {
+            let job = self.queue.remove(0);
+            self.start_job(job);
+        }
+    }
+
+    fn render_frame(
+        &mut self,
+        scene: Scene,
+        render_settings: RenderSettings,
+        passes: Passes,
+        output_path: PathBuf,
+    ) {
         // set rendering to true
         *self.rendering.lock() = true;
 
-        let render_settings = self.render_settings.clone();
-        let output_path = self.get_output_path();
+        // the GPU can only be drawn with from the UI thread, so the app picks the frame up there,
+        // it always draws the whole image from one eye with one sample per pixel and without
+        // denoising so crops, stereo, super sampled and denoised frames are rendered on the CPU, as
+        // are frames with passes recorded from the rays of the CPU render, and it only draws 8 bits
+        // so OpenEXR and 16 bit frames are too
+        if render_settings.backend == RenderBackend::Gpu
+            && render_settings.super_sampling.is_none()
+            && !is_exr_path(&output_path)
+            && !saves_sixteen_bit(render_settings, &output_path)
+            && !passes.redshift
+            && !passes.matte
+            && !render_settings.render_mode.is_diagnostic()
+            && !render_settings.tint_image_orders
+            && render_settings.crop.is_none()
+            && render_settings.eye_separation().is_none()
+            && GpuPreview::supports(&scene, render_settings)
+        {
+            self.gpu_frame = Some((scene, render_settings, passes, output_path));
+        } else {
+            self.save_frame(scene, None, render_settings, passes, output_path);
+        }
+    }
+
+    /// takes the frame waiting to be rendered on the GPU with its settings, passes and output path
+    pub fn take_gpu_frame(&mut self) -> Option<(Scene, RenderSettings, Passes, PathBuf)> {
+        self.gpu_frame.take()
+    }
+
+    /// saves the frame on another thread, rendering it on the CPU unless the image is given
+    pub fn save_frame(
+        &mut self,
+        scene: Scene,
+        image: Option<RgbImage>,
+        render_settings: RenderSettings,
+        passes: Passes,
+        output_path: PathBuf,
+    ) {
         let rendering = self.rendering.clone();
+        let progress = RenderProgress::new();
+        self.progress = Some(progress.clone());
+        self.eta.reset();
+        let live_render = self.live_render.clone();
+        let errors = self.errors.clone();
         std::thread::spawn(move || {
-            // render the image
-            let image = scene.render(render_settings);
+            // the passes are recorded as the frame renders
+            let recorder = (image.is_none() && (passes.redshift || passes.matte))
+                .then(|| PassRecorder::new(render_settings));
+            let scene = Scene {
+                pass_recorder: recorder.clone(),
+                ..scene
+            };
 
-            // save the image and ignore the result for now
-            let _ = image.save(output_path.unwrap());
+            if let Some(image) = image {
+                errors.check(image.save(&output_path), saving(&output_path));
+            } else if render_settings.eye_separation().is_some() {
+                // the eyes are stacked in memory so they aren't streamed or tiled
+                let frame = Frame(0, scene.clone(), Vec::new());
+                if is_exr_path(&output_path) {
+                    if let Some(image) = frame.render_hdr(render_settings, &progress) {
+                        errors.check(image.save(&output_path), saving(&output_path));
+                    }
+                } else if let Some(image) = frame.render_float(render_settings, &progress) {
+                    errors.check(
+                        save_quantized(&image, render_settings, &output_path),
+                        saving(&output_path),
+                    );
+                }
+            } else if is_tiff_path(&output_path) {
+                // stream tiffs to disk so large renders don't have to fit in memory
+                errors.check(
+                    render_to_tiff(&scene, render_settings, &output_path, &progress),
+                    saving(&output_path),
+                );
+            } else if is_exr_path(&output_path) {
+                if let Some(image) = scene.render_hdr_with_progress(render_settings, &progress) {
+                    errors.check(image.save(&output_path), saving(&output_path));
+                }
+            } else if let Some(image) =
+                render_tiled(&scene, render_settings, &progress, &live_render)
+            {
+                errors.check(
+                    save_quantized(&image, render_settings, &output_path),
+                    saving(&output_path),
+                );
+            }
+            if let Some(recorder) = recorder.filter(|_| !progress.is_cancelled()) {
+                if passes.redshift {
+                    let path = redshift_pass_path(&output_path);
+                    errors.check(recorder.redshift_pass().save(&path), saving(&path));
+                }
+                if passes.matte {
+                    let path = matte_pass_path(&output_path);
+                    errors.check(recorder.matte_pass().save(&path), saving(&path));
+                }
+            }
 
             // set rendering to false
             *rendering.lock() = false;
         });
     }
 
-    pub fn render_animation(&mut self, animation: Animation) {
+    /// renders the test pattern scene with the predicted shadow edge drawn in red
+    pub fn render_test_pattern(&mut self) {
         // set rendering to true
         *self.rendering.lock() = true;
 
-        let (sender, receiver) = channel::<()>();
+        // the shadow outline is drawn over the whole image
+        let render_settings = RenderSettings {
+            crop: None,
+            ..self.render_settings
+        };
+        let output_path = self.get_output_path();
+        let rendering = self.rendering.clone();
+        let progress = RenderProgress::new();
+        self.progress = Some(progress.clone());
+        self.eta.reset();
+        self.running_job = Some("Test pattern".to_owned());
+        let errors = self.errors.clone();
+        std::thread::spawn(move || {
+            let scene = Scene::test_pattern();
+
+            // render the image and overlay the theoretical shadow
+            if let Some(mut image) = scene.render_with_progress(render_settings, &progress) {
+                scene.draw_shadow_outline(&mut image, render_settings, Rgb([255, 0, 0]));
+
+                let output_path = output_path.unwrap();
+                errors.check(image.save(&output_path), saving(&output_path));
+            }
+
+            // set rendering to false
+            *rendering.lock() = false;
+        });
+    }
 
-        self.cancel_sender = Some(sender);
+    /// exports the lensing of the scene as a UV remap texture next to the output path
+    pub fn render_uv_map(&mut self, scene: Scene) {
+        // set rendering to true
+        *self.rendering.lock() = true;
 
-        let render_settings = self.render_settings.clone();
+        let render_settings = self.render_settings;
         let output_path = self.get_output_path();
         let rendering = self.rendering.clone();
-        let progress = self.progress.clone();
+        let errors = self.errors.clone();
         std::thread::spawn(move || {
-            let output_path = output_path.unwrap();
-            let base_path_name = output_path.file_stem().unwrap().to_str().unwrap();
-            let n_frames = animation.n_frames();
+            let uv_map = render_uv_map(&scene, render_settings);
+
+            let path = uv_map_path(&output_path.unwrap());
+            errors.check(uv_map.save(&path), saving(&path));
+
+            // set rendering to false
+            *rendering.lock() = false;
+        });
+    }
+
+    /// returns the template animation frames are named with, or why it is invalid
+    fn filename_template(&self) -> Result<FilenameTemplate, TemplateError> {
+        FilenameTemplate::parse(&self.filename_template)
+    }
+
+    /// returns whether to allow rendering an animation, which also needs valid frame names
+    fn can_render_animation(&self) -> bool {
+        self.can_render() && self.filename_template().is_ok()
+    }
+
+    /// renders the frames of the animation on another thread with the options of its job
+    fn render_animation(
+        &mut self,
+        animation: Animation,
+        fps: f32,
+        output: AnimationOutput,
+        render_settings: RenderSettings,
+        passes: Passes,
+        output_path: PathBuf,
+    ) {
+        let AnimationOutput {
+            frame_paths,
+            stride,
+            offset,
+            skip_existing,
+            telemetry,
+            light_curve,
+            frames_in_flight,
+            ..
+        } = output;
+        // the job names every one of its frames
+        let frame_path = move |frame: i32| frame_paths[&frame].clone();
+
+        // set rendering to true
+        *self.rendering.lock() = true;
+
+        let progress = RenderProgress::new();
+        self.progress = Some(progress.clone());
+        self.eta.reset();
+
+        let rendering = self.rendering.clone();
+        let errors = self.errors.clone();
+        self.animation_thread = Some(std::thread::spawn(move || {
+            // index the frames before rendering so interrupted renders are still described
+            let manifest_path = manifest_path(&output_path, (stride, offset));
+            errors.check(
+                write_manifest(
+                    &manifest_path,
+                    &animation,
+                    render_settings,
+                    fps,
+                    (stride, offset),
+                    &frame_path,
+                ),
+                saving(&manifest_path),
+            );
+
+            // continue an interrupted render from the frames that weren't saved, a video is always
+            // encoded from the start
+            let resume = skip_existing && !is_video_path(&output_path);
+            let animation = if resume {
+                animation.filter(|Frame(frame, _, _)| !frame_path(*frame).exists())
+            } else {
+                animation
+            };
+            progress.add_total(animation.n_frames());
+
+            // encode videos as the frames render, strided frames play slower to keep the duration
+            let mut video = None;
+            if is_video_path(&output_path) {
+                match VideoEncoder::spawn(
+                    &output_path,
+                    render_settings.output_size(),
+                    fps / stride as f32,
+                ) {
+                    Ok(encoder) => video = Some(encoder),
+                    // ffmpeg isn't available so there is nowhere to save the frames
+                    Err(error) => {
+                        errors.report(format!("Couldn't start ffmpeg: {}", error));
+                        *rendering.lock() = false;
+                        return;
+                    }
+                }
+            }
+
+            // write the brightness of each frame as it renders, resumed renders add to the light
+            // curve of the frames already saved
+            let light_curve_path = light_curve_path(&output_path);
+            let mut light_curve = light_curve
+                .then(|| {
+                    let light_curve = if resume {
+                        LightCurve::append(&light_curve_path)
+                    } else {
+                        LightCurve::create(&light_curve_path)
+                    };
+                    errors.ok(light_curve, saving(&light_curve_path))
+                })
+                .flatten();
+            let mut telemetry = telemetry.then(|| Telemetry::new(telemetry_path(&output_path)));
+
+            // frames at the same radius share the table of their lensing
+            let lut_cache = LutCache::new();
 
             // render the animation
-            for (i, (_frame, image)) in animation.render_frames(render_settings).enumerate() {
-                // if a cancel message was sent then stop rendering
-                if receiver.try_recv().is_ok() {
+            let encoding = video.is_some();
+            'frames: for frames in animation.frames().chunks(frames_in_flight) {
+                // if the render was cancelled then stop rendering
+                if progress.is_cancelled() {
                     break;
                 }
 
-                let mut frame_name = base_path_name.to_owned();
-                frame_name.push_str(&format!(".{:0>5}", i + 1));
-                frame_name.push_str(&format!(
-                    ".{}",
-                    output_path.extension().unwrap().to_str().unwrap()
-                ));
+                // render the frames in flight together, saving each to its own file as it
+                // finishes, None if the render was cancelled part way through the frame
+                let rendered: Vec<Option<RenderedFrame>> = frames
+                    .par_iter()
+                    .map(|frame_samples| {
+                        let frame_path = frame_path(frame_samples.0);
+                        let recorder = (passes.redshift || passes.matte || telemetry.is_some())
+                            .then(|| PassRecorder::new(render_settings));
+                        let frame_samples = frame_samples
+                            .with_cached_deflection_luts(render_settings, &lut_cache)
+                            .with_pass_recorder(recorder.clone());
 
-                let result = image.save(output_path.with_file_name(OsStr::new(&frame_name)));
+                        // the frame stops part way through when the render is cancelled
+                        let frame_progress = progress.subtask();
 
-                // problem saving a frame so stop rendering
-                if result.is_err() {
-                    break;
+                        // keep the light brighter than white in OpenEXR frames
+                        if is_exr_path(&output_path) {
+                            let hdr_image =
+                                frame_samples.render_hdr(render_settings, &frame_progress)?;
+                            let saved =
+                                errors.check(hdr_image.save(&frame_path), saving(&frame_path));
+                            Some(RenderedFrame {
+                                image: render_settings.clip_hdr(&hdr_image),
+                                hdr_image: Some(hdr_image),
+                                passes: recorder,
+                                saved,
+                            })
+                        } else {
+                            let (float_image, hdr_image) = if light_curve.is_some() {
+                                let (float_image, hdr_image) = frame_samples
+                                    .render_float_and_hdr(render_settings, &frame_progress)?;
+                                (float_image, Some(hdr_image))
+                            } else {
+                                let float_image =
+                                    frame_samples.render_float(render_settings, &frame_progress)?;
+                                (float_image, None)
+                            };
+                            // video frames are encoded in order below
+                            let saved = encoding
+                                || errors.check(
+                                    save_quantized(&float_image, render_settings, &frame_path),
+                                    saving(&frame_path),
+                                );
+                            Some(RenderedFrame {
+                                image: render_settings.quantize(&float_image),
+                                hdr_image,
+                                passes: recorder,
+                                saved,
+                            })
+                        }
+                    })
+                    .collect();
+
+                // the video, light curve and telemetry take the frames in order
+                for (Frame(frame, ..), rendered) in frames.iter().zip(rendered) {
+                    let Some(RenderedFrame {
+                        image,
+                        hdr_image,
+                        passes: recorder,
+                        mut saved,
+                    }) = rendered
+                    else {
+                        break 'frames;
+                    };
+                    let frame_path = frame_path(*frame);
+
+                    if let Some(video) = &mut video {
+                        saved = errors.check(video.add_frame(&image), || {
+                            format!("Couldn't encode frame {}", frame)
+                        });
+                    }
+
+                    // problem saving a frame so stop rendering, before the light curve takes it so
+                    // that resuming the render doesn't add the frame a second time
+                    if !saved {
+                        break 'frames;
+                    }
+                    if let (Some(light_curve), Some(hdr_image)) = (&mut light_curve, &hdr_image) {
+                        errors.check(
+                            light_curve.add_frame(*frame, hdr_image),
+                            saving(&light_curve_path),
+                        );
+                    }
+                    if let Some(recorder) = recorder.as_ref().filter(|_| passes.redshift) {
+                        let path = redshift_pass_path(&frame_path);
+                        errors.check(recorder.redshift_pass().save(&path), saving(&path));
+                    }
+                    if let Some(recorder) = recorder.as_ref().filter(|_| passes.matte) {
+                        let path = matte_pass_path(&frame_path);
+                        errors.check(recorder.matte_pass().save(&path), saving(&path));
+                    }
+                    if let (Some(telemetry), Some(recorder)) = (&mut telemetry, &recorder) {
+                        errors.check(
+                            telemetry.add_frame(recorder.telemetry(*frame)),
+                            saving(&telemetry_path(&output_path)),
+                        );
+                    }
+                    progress.advance(1);
                 }
-                *progress.lock() = Some(i as f32 / n_frames as f32)
             }
 
-            // remove progress
-            *progress.lock() = None;
+            // finish the video with the frames rendered so far
+            if let Some(video) = video {
+                errors.check(video.finish(), saving(&output_path));
+            }
+
             // set rendering to false
             *rendering.lock() = false;
+        }));
+    }
+
+    fn show_turntable(&mut self, timeline: &Timeline, units: &Units, ui: &mut egui::Ui) {
+        ui.collapsing("Radius turntable", |ui| {
+            egui::Grid::new("turntable_grid").num_columns(2).show(ui, |ui| {
+                ui.label("Start distance");
+                ui.add(
+                    units
+                        .length_drag_value(&mut self.turntable_radii.0, 0.1)
+                        .clamp_range(0_f64..=f64::MAX),
+                );
+                ui.end_row();
+
+                ui.label("End distance");
+                ui.add(
+                    units
+                        .length_drag_value(&mut self.turntable_radii.1, 0.1)
+                        .clamp_range(0_f64..=f64::MAX),
+                );
+                ui.end_row();
+
+                ui.label("Frames");
+                ui.add(
+                    egui::DragValue::new(&mut self.turntable_frames)
+                        .clamp_range(1_usize..=usize::MAX),
+                );
+                ui.end_row();
+            });
+
+            ui.vertical_centered_justified(|ui| {
+                if ui
+                    .add_enabled(
+                        self.can_render_animation(),
+                        egui::Button::new("Render Turntable"),
+                    )
+                    .on_hover_text(
+                        "Render an animation of the current scene sweeping the initial distance of the diver",
+                    )
+                    .clicked()
+                {
+                    let (start_radius, end_radius) = self.turntable_radii;
+                    let animation = Animation::from_scene_radii(
+                        timeline.get_current_scene(),
+                        start_radius,
+                        end_radius,
+                        self.turntable_frames,
+                    );
+                    if let Some(job) = self
+                        .animation_job_kind(animation, timeline.fps)
+                        .and_then(|kind| self.job(kind))
+                    {
+                        self.start_job(job);
+                    }
+                }
+            });
         });
     }
 
-    pub fn show(&mut self, timeline: &Timeline, ui: &mut egui::Ui) {
+    pub fn show(&mut self, timeline: &Timeline, units: &Units, ui: &mut egui::Ui) {
+        self.notifications.show(ui.ctx());
+        self.show_overwrite_dialog(ui.ctx());
+        self.start_queued_job();
+
         self.render_settings.show(ui);
+        if self.render_settings.projection == Projection::Equirectangular {
+            ui.horizontal(|ui| {
+                let mut stereo = self.render_settings.stereo.is_some();
+                if ui
+                    .checkbox(&mut stereo, "Stereo")
+                    .on_hover_text(
+                        "Render the view of each eye for VR headsets, stacked with the left eye on top. Only the half of the sky in front of the camera is in stereo, the depth fades out towards the sides and behind the camera both eyes see the same view",
+                    )
+                    .changed()
+                {
+                    self.render_settings.stereo = stereo.then_some(DEFAULT_EYE_SEPARATION);
+                }
+                if let Some(eye_separation) = &mut self.render_settings.stereo {
+                    ui.label("Eye separation");
+                    ui.add(
+                        units
+                            .length_drag_value(eye_separation, 0.01)
+                            .clamp_range(0_f64..=f64::MAX),
+                    )
+                    .on_hover_text(
+                        "Distance between the eyes, larger separations exaggerate the depth of the black hole against the sky",
+                    );
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut self.eyedropper, "🖊 Eyedropper")
+                .on_hover_text(
+                    "Click the preview to pick a neutral white, shift click to pick the exposure",
+                );
+            if ui.button("Reset").on_hover_text("Reset grading").clicked() {
+                self.render_settings.reset_grading();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut self.crop_tool, "⛶ Crop").on_hover_text(
+                "Drag over the preview to render only that region at full quality, the preview aspect ratio has to be locked",
+            );
+            if ui
+                .add_enabled(
+                    self.render_settings.crop.is_some(),
+                    egui::Button::new("Clear"),
+                )
+                .on_hover_text("Render the whole image again")
+                .clicked()
+            {
+                self.render_settings.crop = None;
+            }
+            if self.render_settings.crop.is_some() {
+                let (offset, size) = self.render_settings.region();
+                ui.label(format!(
+                    "{}×{} at ({}, {})",
+                    size.x, size.y, offset.x, offset.y
+                ));
+            }
+        });
 
         // output path
 
@@ -233,31 +1738,267 @@ impl Renderer {
                 }
             }
             ui.add(egui::TextEdit::singleline(&mut self.output_path).desired_width(f32::INFINITY))
+                .on_hover_text(
+                    "The format is picked from the extension, .exr saves 32 bit float linear images that keep light brighter than white for compositing, .mp4 and .webm encode animations to a video with ffmpeg",
+                );
+        });
+        self.show_filename_template(timeline, ui);
+
+        ui.checkbox(&mut self.passes.redshift, "Redshift pass")
+            .on_hover_text(
+                "Also save the frequency shift factor of each pixel as a float OpenEXR image next to rendered frames, for grading in a compositor. The pass is recorded from the rays of the render, so frames with it render on the CPU",
+            );
+
+        ui.checkbox(&mut self.passes.matte, "Matte pass")
+            .on_hover_text(
+                "Also save a grayscale matte next to rendered frames that is black where rays fall into the black hole, to use as the alpha when compositing over other backgrounds. The pass is recorded from the rays of the render, so frames with it render on the CPU",
+            );
+
+        ui.checkbox(&mut self.telemetry, "Telemetry")
+            .on_hover_text(
+                "Also save the captured fraction of the pixels, mean deflection and redshift range of every frame of rendered animations as JSON",
+            );
+
+        ui.checkbox(&mut self.light_curve, "Light curve")
+            .on_hover_text(
+                "Also save the mean brightness of every frame of rendered animations as a CSV light curve, to measure the amplification of the lensing",
+            );
+
+        self.show_camera_override(timeline, ui);
+
+        self.show_turntable(timeline, units, ui);
+
+        ui.horizontal(|ui| {
+            ui.label("Shutter angle");
+            ui.add(
+                egui::DragValue::new(&mut self.shutter_angle)
+                    .clamp_range(0_f32..=360_f32)
+                    .suffix("°"),
+            )
+            .on_hover_text(
+                "Blur the motion of animations over this fraction of each frame, 180° is typical for film and 0° turns motion blur off",
+            );
+            ui.label("Samples");
+            ui.add_enabled(
+                self.shutter_angle > 0_f32,
+                egui::DragValue::new(&mut self.motion_blur_samples).clamp_range(2_usize..=256),
+            )
+            .on_hover_text("Moments rendered and averaged while the shutter is open");
+        });
+
+        // frame subset for distributed rendering
+        ui.horizontal(|ui| {
+            ui.label("Frame stride");
+            ui.add(egui::DragValue::new(&mut self.stride).clamp_range(1_usize..=usize::MAX))
+                .on_hover_text("Only render frames whose number is the offset modulo the stride");
+            ui.label("Offset");
+            ui.add(egui::DragValue::new(&mut self.offset).clamp_range(0..=self.stride - 1));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Frames in flight");
+            ui.add(egui::DragValue::new(&mut self.frames_in_flight).clamp_range(1_usize..=64))
+                .on_hover_text(
+                    "Animation frames rendered at the same time, more keeps every core busy on small frames but holds more frames in memory",
+                );
         });
+        ui.checkbox(&mut self.skip_existing, "Skip existing frames")
+            .on_hover_text(
+                "Only render the frames of animations that aren't saved yet, to continue a render that crashed or was cancelled. The telemetry only covers the frames rendered this time",
+            );
 
         ui.vertical_centered_justified(|ui| {
             if ui
-                .add_enabled(self.can_render(), egui::Button::new("Render Current Frame"))
+                .add_enabled(self.can_render_still(), egui::Button::new("Render Current Frame"))
+                .on_disabled_hover_text("Single frames can't be saved as a video")
                 .clicked()
             {
-                self.render_frame(timeline.get_current_scene());
+                let scene = self.apply_camera_override(timeline.get_current_scene());
+                if let Some(job) = self.job(RenderJobKind::Frame(Box::new(scene))) {
+                    self.start_job(job);
+                }
             }
             if ui
-                .add_enabled(self.can_render(), egui::Button::new("Render Animation"))
+                .add_enabled(
+                    self.can_render_animation(),
+                    egui::Button::new("Render Animation"),
+                )
                 .clicked()
             {
-                self.render_animation(timeline.to_animation());
+                if let Some(job) = self.timeline_job(timeline) {
+                    self.start_job(job);
+                }
+            }
+            if ui
+                .add_enabled(self.can_render(), egui::Button::new("Export UV Map"))
+                .on_hover_text(
+                    "Save the map coordinates each pixel of the current frame comes from as a 16-bit texture for remapping a skybox",
+                )
+                .clicked()
+            {
+                self.render_uv_map(self.apply_camera_override(timeline.get_current_scene()));
+            }
+            if ui
+                .add_enabled(self.can_render_still(), egui::Button::new("Render Test Pattern"))
+                .on_hover_text(
+                    "Render a grid environment from a canonical scene with the predicted shadow edge drawn in red",
+                )
+                .clicked()
+            {
+                self.render_test_pattern();
             }
             if ui
                 .add_enabled(self.is_rendering(), egui::Button::new("Cancel Render"))
+                .on_hover_text("Stop the current render, the queued renders carry on after it")
                 .clicked()
             {
                 self.cancel_render();
             }
 
-            if let Some(progress) = *self.progress.lock() {
-                ui.add(egui::ProgressBar::new(progress).show_percentage());
+            // the progress of a finished or cancelled render is no longer shown
+            if let Some(fraction) = self.progress_fraction() {
+                if let Some(job) = &self.running_job {
+                    ui.label(job);
+                }
+                ui.add(egui::ProgressBar::new(fraction).show_percentage());
+                match self.eta.update(fraction) {
+                    Some(remaining) => {
+                        ui.label(format!("About {} left", format_duration(remaining)))
+                    }
+                    None => ui.label("Estimating time left..."),
+                };
+            }
+        });
+
+        self.show_queue(timeline, ui);
+    }
+
+    fn show_filename_template(&mut self, timeline: &Timeline, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Frame names");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.filename_template)
+                    .desired_width(f32::INFINITY),
+            )
+            .on_hover_text(
+                "Names of the files of animation frames, {name} is the name of the output, {frame} the frame number and {frame:05} the frame number padded with zeros to 5 digits, {scene} is black-hole or wormhole and {date} the day the render was started or queued. The extension of the output is added to the end",
+            );
+        });
+
+        // show the name of the current frame or why the frames can't be named
+        match self.filename_template() {
+            Ok(template) => {
+                if let Some(output_path) = self.get_output_path() {
+                    let scene = timeline.get_current_scene();
+                    let values = TemplateValues::new(&output_path, scene.spacetime);
+                    let example =
+                        template.frame_path(&output_path, &values, timeline.current_frame);
+                    ui.weak(format!(
+                        "e.g. {}",
+                        example.file_name().unwrap_or_default().to_string_lossy()
+                    ));
+                }
+            }
+            Err(error) => {
+                ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+            }
+        }
+    }
+
+    fn show_queue(&mut self, timeline: &Timeline, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let can_queue = self.get_output_path().is_some();
+            let can_queue_still = can_queue
+                && !self
+                    .get_output_path()
+                    .is_some_and(|path| is_video_path(&path));
+            if ui
+                .add_enabled(can_queue_still, egui::Button::new("Queue Frame"))
+                .on_hover_text(
+                    "Render the current frame with the current settings and output once the renders before it finish",
+                )
+                .clicked()
+            {
+                let scene = self.apply_camera_override(timeline.get_current_scene());
+                self.queue.extend(self.job(RenderJobKind::Frame(Box::new(scene))));
+            }
+            if ui
+                .add_enabled(
+                    can_queue && self.filename_template().is_ok(),
+                    egui::Button::new("Queue Animation"),
+                )
+                .on_hover_text(
+                    "Render the animation with the current settings and output once the renders before it finish",
+                )
+                .clicked()
+            {
+                self.queue.extend(self.timeline_job(timeline));
+            }
+        });
+
+        if self.queue.is_empty() {
+            return;
+        }
+        ui.collapsing(format!("Queue ({})", self.queue.len()), |ui| {
+            let mut move_up = None;
+            let mut remove = None;
+            for (i, job) in self.queue.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(i > 0, egui::Button::new("⏶"))
+                        .on_hover_text("Render earlier")
+                        .clicked()
+                    {
+                        move_up = Some(i);
+                    }
+                    if ui
+                        .add_enabled(i + 1 < self.queue.len(), egui::Button::new("⏷"))
+                        .on_hover_text("Render later")
+                        .clicked()
+                    {
+                        move_up = Some(i + 1);
+                    }
+                    if ui
+                        .button("🗑")
+                        .on_hover_text("Remove from the queue")
+                        .clicked()
+                    {
+                        remove = Some(i);
+                    }
+                    ui.label(job.describe());
+                });
+            }
+
+            if let Some(i) = move_up {
+                self.queue.swap(i - 1, i);
+            }
+            if let Some(i) = remove {
+                self.queue.remove(i);
+            }
+            if ui.button("Clear Queue").clicked() {
+                self.queue.clear();
             }
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dithering_preserves_mean() {
+        let mut render_settings =
+            RenderSettings::new(Projection::default(), Vector2::new(16, 16), None);
+        render_settings.dither = true;
+
+        // a flat grey three tenths of the way between two output levels
+        let level = 127.3_f32;
+        let image = Rgb32FImage::from_pixel(16, 16, Rgb([level / 255_f32; 3]));
+        let quantized = render_settings.quantize(&image);
+
+        let values: Vec<u8> = quantized.pixels().map(|pixel| pixel[0]).collect();
+        let mean = values.iter().map(|&value| value as f32).sum::<f32>() / values.len() as f32;
+        assert!((mean - level).abs() < 0.1, "mean {mean}");
+        assert!(values.iter().any(|&value| value != values[0]));
+    }
+}
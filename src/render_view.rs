@@ -0,0 +1,48 @@
+// Shows the still frame being rendered as its tiles finish
+
+use crate::app::BHDiver;
+use egui::ColorImage;
+
+pub struct RenderView;
+
+impl RenderView {
+    pub fn build(ui: &mut egui::Ui, app: &mut BHDiver) {
+        let live_render = app.renderer.live_render();
+        let version = live_render.version();
+
+        // upload the image again only when tiles were added to it
+        let id = ui.id().with("render view texture");
+        let cached: Option<(usize, egui::TextureHandle)> = ui.data_mut(|data| data.get_temp(id));
+        let texture = match cached.filter(|(cached_version, _)| *cached_version == version) {
+            Some((_, texture)) => Some(texture),
+            None => live_render.with_image(|image| {
+                let texture = ui.ctx().load_texture(
+                    "render view texture",
+                    ColorImage::from_rgb(
+                        [image.width() as usize, image.height() as usize],
+                        image.as_flat_samples().as_slice(),
+                    ),
+                    Default::default(),
+                );
+                ui.data_mut(|data| data.insert_temp(id, (version, texture.clone())));
+                texture
+            }),
+        };
+
+        let Some(texture) = texture else {
+            ui.label("Render a still frame to watch its tiles fill in here");
+            return;
+        };
+
+        if let Some(fraction) = app.renderer.progress_fraction() {
+            ui.add(egui::ProgressBar::new(fraction).show_percentage());
+        }
+
+        // fit the image to the window keeping its aspect ratio
+        let size = texture.size_vec2();
+        let scale = (ui.available_width() / size.x)
+            .min(ui.available_height().max(200_f32) / size.y)
+            .min(1_f32);
+        ui.vertical_centered(|ui| ui.image(&texture, size * scale));
+    }
+}
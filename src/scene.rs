@@ -1,116 +1,1802 @@
 use crate::{
-    camera::Camera, diver::Diver, environment::Environment, render::RenderSettings,
+    binary::Binary,
+    camera::{Camera, Projection},
+    companion::{sphere_intersection, Companion},
+    deflection_lut::{DeflectionLut, LutCache},
+    denoise,
+    diver::{Diver, Trajectory},
+    environment::{GridEnvironment, SharedEnvironment},
+    geodesic::{geodesic_path, LensingBackend},
+    jet::Jet,
+    math::{
+        critical_rain_angle, image_order, kerr_critical_rain_angle, metric_critical_rain_angle,
+        ring_rain_angle, shadow_angular_radius, tortoise, wormhole_throat_angle,
+    },
+    metric::{InteriorSchwarzschild, Metric, ReissnerNordstrom, SharedMetric, MIN_STAR_RADIUS},
+    neutron_star::{NeutronStar, MIN_SURFACE_RADIUS},
+    pass_recorder::PassRecorder,
+    progress::RenderProgress,
+    ray_path::RayPath,
+    redshift::{
+        doppler_factor, frequency_ratio, shift_color, shift_color_blackbody, Beaming,
+        ColorShiftMode,
+    },
+    render::{diverging_color, heat_color, RenderMode, RenderSettings},
+    spherical_angle::{MapAngle, RainAngle, SphericalAngle},
     traits::Interpolate,
+    units::Units,
 };
-use image::{ImageBuffer, Pixel, Rgb, RgbImage};
-use nalgebra::Vector2;
-use rayon::prelude::{ParallelBridge, ParallelIterator};
+use egui::epaint::ecolor::{gamma_from_linear, linear_f32_from_gamma_u8};
+use image::{GenericImageView, Rgb, Rgb32FImage, RgbImage};
+use nalgebra::{Rotation3, Vector2, Vector3};
+use rayon::prelude::{IntoParallelIterator, ParallelBridge, ParallelIterator};
+use std::{f64::consts::PI, sync::Arc, time::Instant};
+
+/// log10 of the shortest and longest trace times in nanoseconds of the integration cost heat map
+const COST_RANGE: (f64, f64) = (2_f64, 6_f64);
+
+/// radius in units of the mass of the black hole the delay of light from environments at
+/// infinity is measured from
+const LIGHT_DELAY_RADIUS: f64 = 1000_f64;
+
+/// log2 of the frequency ratio shown fully red or blue in the redshift factor image
+const REDSHIFT_RANGE: f64 = 2_f64;
+
+/// Spacetime the diver falls through
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Spacetime {
+    // Schwarzschild or Kerr black hole
+    #[default]
+    BlackHole,
+    // Ellis wormhole leading to the sky of another universe
+    Wormhole,
+}
+
+impl ToString for Spacetime {
+    fn to_string(&self) -> String {
+        match self {
+            Spacetime::BlackHole => "Black hole".to_owned(),
+            Spacetime::Wormhole => "Wormhole".to_owned(),
+        }
+    }
+}
 
 #[derive(Clone, PartialEq)]
 pub struct Scene {
     pub camera: Camera,
-    pub env: Environment,
+    pub env: SharedEnvironment,
+    // sky on the other side of the wormhole
+    pub other_env: SharedEnvironment,
     pub diver: Diver,
-    pub gr: bool,
+    pub spacetime: Spacetime,
+    // radius of the throat of the wormhole, the diver hovers at its initial distance from it
+    pub throat_radius: f64,
+    // bending of light by the curved spacetime
+    pub lensing: bool,
+    // special relativistic aberration from the infall of the diver
+    pub aberration: bool,
+    // brighten the environment by the magnification of the lensing
+    pub magnification: bool,
+    // dimensionless spin a of the black hole about the axis the diver falls along, 0 to 1
+    pub spin: f64,
+    // charge q per unit mass of the black hole, 0 to 1
+    pub charge: f64,
+    // metric of a black hole that isn't built in, replaces the spin and charge when set
+    pub custom_metric: Option<SharedMetric>,
+    // shift the colors of the environment by the blueshift seen by the diver
+    pub color_shift: bool,
+    // exponent of the frequency ratio, 0 is no shift and 1 is the physical shift
+    pub color_shift_strength: f64,
+    // whether the channels or blackbody temperatures of the environment are shifted
+    pub color_shift_mode: ColorShiftMode,
+    // brighten the sky ahead of the diver and dim it behind without shifting colors
+    pub beaming: Beaming,
+    // exposure adjustment in stops on top of the render settings, animated with the scene
+    pub exposure: f64,
+    // textured sphere next to the black hole that gets lensed like the sky
+    pub companion: Option<Companion>,
+    // relativistic jet glowing on top of the sky and the companion
+    pub jet: Option<Jet>,
+    // surface of a star in place of the event horizon
+    pub neutron_star: Option<NeutronStar>,
+    // pair of black holes sharing the mass that lens the sky together
+    pub binary: Option<Binary>,
+    // constant density star that light passes through in place of the black hole
+    pub transparent_star: Option<InteriorSchwarzschild>,
+    // mass of the black hole in units of the mass the lengths and times of the scene are given in
+    pub mass: f64,
+    // sample animated environments at the time the light seen left them
+    pub light_delay: bool,
+    // warp the view by how the tides would stretch an observer of `observer_size`
+    pub tidal_stretch: bool,
+    pub observer_size: f64,
+    // table of the lensing at the radius of the diver that rays are interpolated from, only set
+    // on the copy of the scene being rendered
+    pub deflection_lut: Option<Arc<DeflectionLut>>,
+    // rotation from the frame the view of an eye of a stereo render is lensed in back to the frame
+    // of the diver, only set on the copies of the scene rendered for each eye
+    pub eye_rotation: Option<Rotation3<f64>>,
+    // advanced time of the diver that the light delay is taken from, only set on the copy of the
+    // scene being rendered so it isn't integrated for every ray
+    pub diver_advanced_time: Option<f64>,
+    // collects the passes written alongside the render from the rays of its samples, only set on
+    // the copy of the scene being rendered
+    pub pass_recorder: Option<PassRecorder>,
+}
+
+/// What the ray of a sample found besides its color, for the passes written alongside a render
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RaySample {
+    // ratio of the frequency the diver sees to the emitted frequency, None if no light reaches the
+    // diver along the ray
+    pub frequency_ratio: Option<f64>,
+    // angle the ray is bent through on its way to the sky, None if it doesn't reach the sky
+    pub deflection: Option<f64>,
+}
+
+/// Ray of a sample traced ahead of its color
+#[derive(Debug, Clone, Copy)]
+struct TracedSample {
+    // map angle, image order and side of the wormhole like `Scene::trace_to_side`
+    traced: Option<(MapAngle, u32, bool)>,
+    // largest angle of the sky between the ray and the rays of the next samples across and down
+    footprint: f64,
 }
 
+/// lowest scene exposure in stops, scenes at the minimum render black
+pub const MIN_EXPOSURE: f64 = -16_f64;
+
+/// lightest black hole relative to the unit of mass of the scene
+pub const MIN_MASS: f64 = 1e-3;
+
+/// largest radial stretch and transverse squeeze of the tidal warp, reached near the singularity
+const MAX_TIDAL_STRETCH: f64 = 10_f64;
+const MAX_TIDAL_SQUEEZE: f64 = 0.9;
+
 impl Scene {
-    pub fn new(camera: Camera, env: Environment, diver: Diver, gr: bool) -> Scene {
+    pub fn new(
+        camera: Camera,
+        env: SharedEnvironment,
+        diver: Diver,
+        lensing: bool,
+        aberration: bool,
+    ) -> Scene {
         Self {
             camera,
             env,
+            other_env: SharedEnvironment::default_grid(),
             diver,
-            gr,
+            spacetime: Spacetime::BlackHole,
+            throat_radius: 2_f64,
+            lensing,
+            aberration,
+            magnification: false,
+            spin: 0_f64,
+            charge: 0_f64,
+            custom_metric: None,
+            color_shift: false,
+            color_shift_strength: 1_f64,
+            color_shift_mode: ColorShiftMode::Channels,
+            beaming: Beaming::Off,
+            exposure: 0_f64,
+            companion: None,
+            jet: None,
+            neutron_star: None,
+            binary: None,
+            transparent_star: None,
+            mass: 1_f64,
+            light_delay: false,
+            tidal_stretch: false,
+            observer_size: 1_f64,
+            deflection_lut: None,
+            eye_rotation: None,
+            diver_advanced_time: None,
+            pass_recorder: None,
         }
     }
 
-    pub fn render(&self, render_settings: RenderSettings) -> RgbImage {
-        let super_sampling_bool = render_settings.super_sampling.is_some();
-        let super_sampling = render_settings.super_sampling.unwrap_or(1);
+    /// returns the rain angle of the edge of the shadow of the black hole with lensing, or of the
+    /// throat of the wormhole
+    pub fn critical_rain_angle(&self) -> f64 {
+        if self.spacetime == Spacetime::Wormhole {
+            return wormhole_throat_angle(self.wormhole_distance(), self.throat_radius);
+        }
+        // light passes through the star so it casts no shadow
+        if self.active_transparent_star().is_some() {
+            return 0_f64;
+        }
 
-        let resolution = Vector2::new(
-            render_settings.resolution.x * super_sampling as u32,
-            render_settings.resolution.y * super_sampling as u32,
-        );
+        let critical_angle = if self.is_spherical_lensing() {
+            self.with_spherical_metric(|metric| {
+                metric_critical_rain_angle(self.scaled_diver().position(), metric)
+            })
+        } else if self.active_spin() == 0_f64 {
+            critical_rain_angle(self.scaled_diver().position())
+        } else {
+            kerr_critical_rain_angle(self.scaled_diver().position(), self.active_spin())
+        };
 
-        // Create the image buffer
-        let mut buf: RgbImage = ImageBuffer::new(resolution.x, resolution.y);
+        if self.has_aberration() {
+            critical_angle
+        } else {
+            // where a static observer sees the edge
+            RainAngle::new(critical_angle, 0_f64)
+                .aberrate(-self.diver_speed())
+                .theta()
+        }
+    }
 
-        // Calculate pixels in parallel
-        if self.gr {
-            buf.enumerate_pixels_mut()
-                .par_bridge()
-                .for_each(|(x, y, pixel)| {
-                    let rain_angle = self.camera.pixel_to_rain_angle(
-                        render_settings.projection,
-                        Vector2::new(x, y),
-                        resolution,
-                    );
-
-                    if let Some(map_angle) = rain_angle.to_map_angle(self.diver.position()) {
-                        // Successful map angle
-                        *pixel = self.env.get_pixel(map_angle)
-                    } else {
-                        // Ray went into black hole
-                        *pixel = *Rgb::from_slice(&[0, 0, 0])
-                    }
-                });
+    /// returns view angles around the edge of the shadow of the black hole or of the throat of the
+    /// wormhole, aberrated along with the view, None for a lensing binary whose shadow isn't round
+    pub fn shadow_outline(&self, n_points: usize) -> Option<Vec<RainAngle>> {
+        if self.lensing && self.binary.is_some() {
+            return None;
+        }
+        Some(self.ring_outline(self.critical_rain_angle(), n_points))
+    }
+
+    /// returns the rain angle of the ring of light that swept through the angle around the black
+    /// hole on its way to the diver, None unless the black hole is lensed without spin, charge or
+    /// a custom metric
+    pub fn deflection_ring_angle(&self, swept: f64) -> Option<f64> {
+        if self.spacetime != Spacetime::BlackHole
+            || !self.lensing
+            || self.binary.is_some()
+            || self.is_spherical_lensing()
+            || self.active_spin() != 0_f64
+        {
+            return None;
+        }
+
+        let ring_angle = ring_rain_angle(swept, self.scaled_diver().position());
+        Some(if self.has_aberration() {
+            ring_angle
         } else {
-            // Calculate pixels in parallel
-            buf.enumerate_pixels_mut()
-                .par_bridge()
-                .for_each(|(x, y, pixel)| {
-                    let rain_angle = self.camera.pixel_to_rain_angle(
-                        render_settings.projection,
-                        Vector2::new(x, y),
-                        resolution,
-                    );
-
-                    if let Some(map_angle) =
-                        rain_angle.try_to_map_angle_no_gr(self.diver.position())
-                    {
-                        // Successful map angle
-                        *pixel = self.env.get_pixel(map_angle)
-                    } else {
-                        // Ray went into black hole
-                        *pixel = *Rgb::from_slice(&[0, 0, 0])
-                    }
-                });
+            // where a static observer sees the ring
+            RainAngle::new(ring_angle, 0_f64)
+                .aberrate(-self.diver_speed())
+                .theta()
+        })
+    }
+
+    /// returns view angles around the ring at the rain angle from the black hole, aberrated along
+    /// with the view
+    pub fn ring_outline(&self, rain_angle: f64, n_points: usize) -> Vec<RainAngle> {
+        let velocity = self.view_velocity();
+        let twist = self.view_twist();
+
+        (0..n_points)
+            .map(|i| {
+                let edge = RainAngle::new(rain_angle, 2_f64 * PI * i as f64 / n_points as f64);
+                let edge = if velocity == Vector3::zeros() {
+                    edge
+                } else {
+                    // boosting back undoes the aberration of view_to_rain_angle
+                    edge.from_moving_frame(-velocity).0
+                };
+                Camera::untwist(edge, -twist)
+            })
+            .collect()
+    }
+
+    /// returns the spin used for the lensing, only divers falling along the spin axis with the
+    /// rain see it
+    pub fn active_spin(&self) -> f64 {
+        if self.diver.falls_with_rain()
+            && self.custom_metric.is_none()
+            && self.transparent_star.is_none()
+        {
+            self.spin
+        } else {
+            0_f64
+        }
+    }
+
+    /// returns the charge used for the lensing, only divers falling with the rain see it and it is
+    /// ignored while the black hole spins
+    pub fn active_charge(&self) -> f64 {
+        if self.diver.falls_with_rain() && self.active_spin() == 0_f64 {
+            self.charge
+        } else {
+            0_f64
+        }
+    }
+
+    /// returns the custom metric used for the lensing, only divers falling with the rain see it
+    pub fn active_custom_metric(&self) -> Option<&SharedMetric> {
+        self.custom_metric
+            .as_ref()
+            .filter(|_| self.diver.falls_with_rain())
+    }
+
+    /// returns the transparent star used for the lensing, only divers falling with the rain see it
+    pub fn active_transparent_star(&self) -> Option<InteriorSchwarzschild> {
+        self.transparent_star
+            .filter(|_| self.spacetime == Spacetime::BlackHole && self.diver.falls_with_rain())
+    }
+
+    /// returns true if the lensing needs the general spherically symmetric formulas instead of the
+    /// closed forms for Schwarzschild and Kerr black holes
+    fn is_spherical_lensing(&self) -> bool {
+        self.active_transparent_star().is_some()
+            || self.active_custom_metric().is_some()
+            || self.active_charge() != 0_f64
+    }
+
+    /// calls f with the metric of the black hole when it isn't spinning
+    fn with_spherical_metric<T>(&self, f: impl FnOnce(&dyn Metric) -> T) -> T {
+        if let Some(star) = self.active_transparent_star() {
+            return f(&star);
+        }
+        match self.active_custom_metric() {
+            Some(custom_metric) => f(&**custom_metric),
+            None => f(&ReissnerNordstrom::new(self.active_charge())),
+        }
+    }
+
+    /// returns the diver in units of the mass of the black hole, which the lensing is calculated in
+    pub fn scaled_diver(&self) -> Diver {
+        self.diver.rescaled(self.mass)
+    }
+
+    /// returns the distance of the diver from the black hole
+    pub fn diver_radius(&self) -> f64 {
+        self.mass * self.scaled_diver().position()
+    }
+
+    /// sets the proper time of the diver, no later than when it hits the singularity
+    pub fn set_diver_time(&mut self, time: f64) {
+        self.diver.set_time_for_mass(time, self.mass);
+    }
+
+    /// sets the time to when the diver is at the radius like `Diver::set_position`
+    pub fn set_diver_radius(&mut self, radius: f64) {
+        self.diver.set_position_for_mass(radius, self.mass);
+    }
+
+    /// returns the proper time the diver has left before it hits the singularity
+    pub fn remaining_proper_time(&self) -> f64 {
+        self.mass * self.scaled_diver().remaining_time()
+    }
+
+    /// returns the speed of the diver relative to static observers at its position, None inside
+    /// the horizon where they can't exist
+    pub fn diver_static_speed(&self) -> Option<f64> {
+        let rain_speed = self.diver_speed();
+        if rain_speed >= 1_f64 {
+            return None;
+        }
+
+        // both velocities are through the rain frame, static observers move outward through it
+        let diver_velocity = self.scaled_diver().velocity();
+        let static_velocity = Vector3::new(0_f64, 0_f64, -rain_speed);
+        let lorentz_factor = (1_f64 - diver_velocity.dot(&static_velocity))
+            / ((1_f64 - diver_velocity.norm_squared()) * (1_f64 - rain_speed.powi(2))).sqrt();
+        Some((1_f64 - lorentz_factor.powi(-2)).max(0_f64).sqrt())
+    }
+
+    /// returns the proper distance of the diver from the throat of the wormhole
+    pub fn wormhole_distance(&self) -> f64 {
+        self.diver.initial_radius()
+    }
+
+    /// returns whether the view has aberration from the infall of the diver
+    ///
+    /// static observers only exist outside the horizon so the aberration can't be removed inside
+    pub fn has_aberration(&self) -> bool {
+        self.aberration || self.diver_speed() >= 1_f64
+    }
+
+    /// returns a canonical scene with a grid environment for checking the rendering pipeline
+    pub fn test_pattern() -> Self {
+        Self::new(
+            Camera::default(),
+            SharedEnvironment::new(GridEnvironment::default()),
+            Diver::default(),
+            true,
+            true,
+        )
+    }
+
+    /// returns the angular radius of the shadow seen by the diver, which a hovering diver sees
+    /// from the frame of static observers around black holes without spin or charge, otherwise
+    /// the critical rain angle, None for a lensing binary whose shadow isn't round
+    pub fn shadow_angular_radius(&self) -> Option<f64> {
+        if self.lensing && self.binary.is_some() {
+            return None;
+        }
+        let static_shadow = (self.diver.trajectory == Trajectory::Hover
+            && self.spacetime == Spacetime::BlackHole
+            && self.active_spin() == 0_f64
+            && !self.is_spherical_lensing())
+        .then(|| shadow_angular_radius(self.scaled_diver().position(), true))
+        .flatten();
+        Some(static_shadow.unwrap_or_else(|| self.critical_rain_angle()))
+    }
+
+    /// draws the edge of the shadow predicted by the critical rain angle onto a render
+    pub fn draw_shadow_outline(
+        &self,
+        image: &mut RgbImage,
+        render_settings: RenderSettings,
+        color: Rgb<u8>,
+    ) {
+        let resolution = render_settings.resolution;
+        let critical_angle = self.critical_rain_angle();
+
+        // whether each pixel looks into the shadow
+        let in_shadow: Vec<bool> = (0..resolution.x * resolution.y)
+            .into_par_iter()
+            .map(|i| {
+                let view_angle = self.pixel_to_view_angle(
+                    render_settings.projection,
+                    Vector2::new(i % resolution.x, i / resolution.x),
+                    resolution,
+                );
+                self.view_to_rain_angle(view_angle).0.theta() < critical_angle
+            })
+            .collect();
+
+        let index = |x: u32, y: u32| (y * resolution.x + x) as usize;
+
+        // mark pixels whose right or bottom neighbour is on the other side of the edge
+        for y in 0..resolution.y {
+            for x in 0..resolution.x {
+                let edge = (x + 1 < resolution.x
+                    && in_shadow[index(x, y)] != in_shadow[index(x + 1, y)])
+                    || (y + 1 < resolution.y
+                        && in_shadow[index(x, y)] != in_shadow[index(x, y + 1)]);
+
+                if edge {
+                    image.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// returns the infall speed of the diver through the rain frame, `Diver::speed` when the
+    /// black hole isn't spinning, charged or custom
+    pub fn diver_speed(&self) -> f64 {
+        let r = self.scaled_diver().position();
+        if self.is_spherical_lensing() {
+            return self.with_spherical_metric(|metric| metric.rain_speed(r));
+        }
+        (2_f64 * r / (r.powi(2) + self.active_spin().powi(2))).sqrt()
+    }
+
+    /// returns the velocity of the observer of the camera through the rain frame that the view is
+    /// aberrated by
+    pub fn view_velocity(&self) -> Vector3<f64> {
+        // wormholes have no gravity so the hovering diver is at rest
+        if self.spacetime == Spacetime::BlackHole && self.has_aberration() {
+            self.camera
+                .observer_velocity(self.diver_speed(), self.scaled_diver().velocity())
+        } else {
+            Vector3::zeros()
+        }
+    }
+
+    /// returns the angle the frame of the observer of the camera is turned about the spin axis
+    /// relative to the rain frame
+    pub fn view_twist(&self) -> f64 {
+        if self.spacetime == Spacetime::BlackHole && self.has_aberration() {
+            self.camera.frame_twist(
+                self.diver_speed(),
+                self.scaled_diver().position(),
+                self.active_spin(),
+            )
+        } else {
+            0_f64
+        }
+    }
+
+    /// returns the radial and transverse strain of an observer of `observer_size` across, the
+    /// tidal tensor 2M/r^3 and -M/r^3 times the size squared, zero when the tidal stretch is off
+    fn tidal_strain(&self) -> (f64, f64) {
+        if !self.tidal_stretch || self.spacetime == Spacetime::Wormhole {
+            return (0_f64, 0_f64);
+        }
+
+        let tide = self.mass * self.observer_size.powi(2) / self.diver_radius().powi(3);
+        (
+            (2_f64 * tide).min(MAX_TIDAL_STRETCH),
+            (-tide).max(-MAX_TIDAL_SQUEEZE),
+        )
+    }
+
+    /// returns the view angle of the diver seen at the pixel, stretched towards the black hole by
+    /// the tidal warp
+    pub fn pixel_to_view_angle(
+        &self,
+        projection: Projection,
+        pixel: Vector2<u32>,
+        resolution: Vector2<u32>,
+    ) -> RainAngle {
+        let view_angle = self
+            .camera
+            .pixel_to_rain_angle(projection, pixel, resolution);
+        let (radial, transverse) = self.tidal_strain();
+        if (radial, transverse) == (0_f64, 0_f64) {
+            return view_angle;
+        }
+
+        // squeezing the directions towards the plane across the black hole spreads what is seen
+        // there out along it
+        let vector = view_angle.to_vector();
+        RainAngle::from_vector(Vector3::new(
+            vector.x / (1_f64 + transverse),
+            vector.y / (1_f64 + transverse),
+            vector.z / (1_f64 + radial),
+        ))
+    }
+
+    /// returns the position of the view angle on the image like the pixels of
+    /// `pixel_to_view_angle`, None if it is behind a perspective camera
+    pub fn view_angle_to_pixel(
+        &self,
+        projection: Projection,
+        view_angle: RainAngle,
+        resolution: Vector2<u32>,
+    ) -> Option<Vector2<f64>> {
+        let (radial, transverse) = self.tidal_strain();
+        let vector = view_angle.to_vector();
+        let warped = RainAngle::from_vector(Vector3::new(
+            vector.x * (1_f64 + transverse),
+            vector.y * (1_f64 + transverse),
+            vector.z * (1_f64 + radial),
+        ));
+        self.camera
+            .rain_angle_to_pixel(projection, warped, resolution)
+    }
+
+    /// returns the rain angle of the view angle of the diver and the ratio of the frequency the
+    /// diver sees to the frequency in the rain frame, the same angle for a diver falling with the
+    /// rain
+    pub fn view_to_rain_angle(&self, view_angle: RainAngle) -> (RainAngle, f64) {
+        let view_angle = Camera::untwist(view_angle, self.view_twist());
+        let velocity = self.view_velocity();
+        if velocity == Vector3::zeros() {
+            (view_angle, 1_f64)
+        } else {
+            view_angle.from_moving_frame(velocity)
+        }
+    }
+
+    /// returns the ratio of the frequency the diver sees at the view angle to the emitted
+    /// frequency, light through a wormhole isn't shifted
+    pub fn view_frequency_ratio(&self, view_angle: RainAngle) -> f64 {
+        if self.spacetime == Spacetime::Wormhole {
+            return 1_f64;
+        }
+
+        let (rain_angle, doppler) = self.view_to_rain_angle(view_angle);
+        frequency_ratio(
+            rain_angle.theta(),
+            self.diver_speed(),
+            self.lensing,
+            self.has_aberration(),
+        ) * doppler
+    }
+
+    /// returns the Doppler factor of the motion of the diver at the view angle relative to the static
+    /// observers, None inside the horizon
+    fn view_doppler_factor(&self, view_angle: RainAngle) -> Option<f64> {
+        let (rain_angle, doppler) = self.view_to_rain_angle(view_angle);
+        doppler_factor(
+            rain_angle.theta(),
+            self.diver_speed(),
+            self.has_aberration(),
+        )
+        .map(|factor| factor * doppler)
+    }
+
+    /// shifts the linear color of the environment seen at the view angle if the color shift is
+    /// on, or only scales its brightness if the beaming is on instead
+    fn shift_color(&self, view_angle: RainAngle, linear: [f64; 3]) -> [f64; 3] {
+        if self.spacetime == Spacetime::Wormhole {
+            return linear;
+        }
+        if !self.color_shift {
+            return match (
+                self.beaming.exponent(),
+                self.view_doppler_factor(view_angle),
+            ) {
+                (Some(exponent), Some(doppler)) => {
+                    let factor = doppler.powi(exponent);
+                    linear.map(|channel| channel * factor)
+                }
+                _ => linear,
+            };
+        }
+
+        let ratio = self
+            .view_frequency_ratio(view_angle)
+            .powf(self.color_shift_strength);
+        match self.color_shift_mode {
+            ColorShiftMode::Channels => shift_color(linear, ratio),
+            ColorShiftMode::Blackbody => shift_color_blackbody(linear, ratio),
+        }
+    }
+
+    /// returns the factor the environment seen at the view angle is magnified by, the ratio of the
+    /// solid angle of a small patch of the view to the solid angle of the sky it shows, given the
+    /// map angle the view angle traces to
+    ///
+    /// found from the rays through the corners of a small triangle around the view angle, None if
+    /// they don't all reach the sky
+    pub fn magnification(
+        &self,
+        view_angle: RainAngle,
+        map_angle: &MapAngle,
+        render_settings: RenderSettings,
+    ) -> Option<f64> {
+        // large enough for the error of the lensing to stay small
+        const STEP: f64 = 1e-3;
+
+        let view = view_angle.to_vector();
+        let axis = if view.x.abs() < 0.9 {
+            Vector3::x()
+        } else {
+            Vector3::y()
+        };
+        let first = view.cross(&axis).normalize() * STEP;
+        let second = view.cross(&first);
+
+        let trace = |offset: Vector3<f64>| {
+            self.trace(RainAngle::from_vector(view + offset), render_settings)
+                .map(|map_angle| map_angle.to_vector())
+        };
+        let center = map_angle.to_vector();
+        let sky_area = (trace(first)? - center)
+            .cross(&(trace(second)? - center))
+            .norm();
+
+        // the triangle in the view has the area STEP^2 / 2 like the sky area is doubled
+        Some(STEP.powi(2) / sky_area)
+    }
+
+    /// returns the map angle that the ray arriving from the view angle of the diver comes from,
+    /// None if it comes from the black hole or loops around it more than the max half orbits of
+    /// the render settings
+    pub fn trace(
+        &self,
+        view_angle: RainAngle,
+        render_settings: RenderSettings,
+    ) -> Option<MapAngle> {
+        self.trace_with_order(view_angle, render_settings)
+            .map(|(map_angle, _)| map_angle)
+    }
+
+    /// returns the map angle like `trace` along with the order of the image the ray is part of,
+    /// without lensing every ray is part of the primary image
+    pub fn trace_with_order(
+        &self,
+        view_angle: RainAngle,
+        render_settings: RenderSettings,
+    ) -> Option<(MapAngle, u32)> {
+        self.trace_to_side(view_angle, render_settings)
+            .map(|(map_angle, order, _)| (map_angle, order))
+    }
+
+    /// returns the map angle and image order like `trace_with_order` along with whether the ray
+    /// comes from the other side of the wormhole
+    pub fn trace_to_side(
+        &self,
+        view_angle: RainAngle,
+        render_settings: RenderSettings,
+    ) -> Option<(MapAngle, u32, bool)> {
+        if self.spacetime == Spacetime::Wormhole {
+            let l = self.wormhole_distance();
+            let traced = if self.lensing {
+                view_angle.to_map_angle_wormhole(
+                    l,
+                    self.throat_radius,
+                    render_settings.max_half_orbits,
+                )
+            } else {
+                // without lensing the throat looks like a window to the other side
+                let through_throat = view_angle.theta() < self.critical_rain_angle();
+                Some((
+                    MapAngle::new(view_angle.theta(), view_angle.phi()),
+                    0,
+                    through_throat,
+                ))
+            };
+            return traced
+                .map(|(map_angle, order, side)| (self.eye_to_diver_frame(map_angle), order, side));
+        }
+
+        let (rain_angle, _) = self.view_to_rain_angle(view_angle);
+        let (map_angle, order) = self.lens(rain_angle, render_settings)?;
+        let map_angle = self.eye_to_diver_frame(map_angle);
+
+        // the map turns with the diver as it goes around the black hole in the xz plane
+        let orbit_angle = self.scaled_diver().orbit_angle();
+        if orbit_angle == 0_f64 {
+            return Some((map_angle, order, false));
+        }
+
+        let fixed = self.to_fixed_frame(map_angle.to_vector());
+        Some((MapAngle::from_vector(fixed), order, false))
+    }
+
+    /// returns the false color of the diagnostic render mode for the ray arriving from the view
+    /// angle, rays falling into the black hole are black apart from in the hit mask and the
+    /// integration cost
+    fn diagnostic_color(&self, view_angle: RainAngle, render_settings: RenderSettings) -> Rgb<u8> {
+        let start = Instant::now();
+        let traced = self.trace_to_side(view_angle, render_settings);
+        let nanos = start.elapsed().as_nanos() as f64;
+
+        match (render_settings.render_mode, traced) {
+            (RenderMode::IntegrationCost, _) => heat_color(
+                (nanos.max(1_f64).log10() - COST_RANGE.0) / (COST_RANGE.1 - COST_RANGE.0),
+            ),
+            (RenderMode::HitMask, Some((_, _, true))) => Rgb([128, 128, 128]),
+            (RenderMode::HitMask, Some(_)) => Rgb([255, 255, 255]),
+            (_, None) | (RenderMode::Beauty, _) => Rgb([0, 0, 0]),
+            (RenderMode::Deflection, Some((map_angle, _, _))) => {
+                heat_color(self.deflection(view_angle, &map_angle) / PI)
+            }
+            (RenderMode::Redshift, Some(_)) => {
+                // redshifted light has a ratio below one
+                diverging_color(self.view_frequency_ratio(view_angle).log2() / REDSHIFT_RANGE)
+            }
+        }
+    }
+
+    /// returns the angle between the map angle the ray arriving from the view angle came from and
+    /// the direction it would come from without the black hole, 0 to pi
+    pub fn deflection(&self, view_angle: RainAngle, map_angle: &MapAngle) -> f64 {
+        let (rain_angle, _) = self.view_to_rain_angle(view_angle);
+        let straight = if self.spacetime == Spacetime::BlackHole {
+            self.to_fixed_frame(rain_angle.to_vector())
+        } else {
+            view_angle.to_vector()
+        };
+        map_angle
+            .to_vector()
+            .dot(&straight)
+            .clamp(-1_f64, 1_f64)
+            .acos()
+    }
+
+    /// returns the map angle of the point where the path first crosses the sphere the environment
+    /// is painted on, None if the environment is at infinity or the path never reaches it
+    fn environment_hit(&self, path: Option<&RayPath>) -> Option<MapAngle> {
+        let (radius, path) = self.env.radius.zip(path)?;
+
+        path.segments(radius)
+            .find_map(|(start, end, _)| {
+                sphere_intersection(Vector3::zeros(), radius, start, end - start)
+                    .filter(|t| *t <= 1_f64)
+                    .map(|t| start + t * (end - start))
+            })
+            .map(MapAngle::from_vector)
+    }
+
+    /// returns the time the environment is sampled at for the ray along the path, the proper time
+    /// of the diver, or if the light delay is on the coordinate time the light left the
+    /// environment at, which is zero where the trajectory of the diver starts
+    ///
+    /// rays are followed through space like `ray_path`, so the delay is only found around black
+    /// holes that aren't spinning
+    fn environment_time(&self, path: Option<&RayPath>) -> f64 {
+        match path.filter(|_| self.light_delay) {
+            Some(path) => {
+                let reach = self.env.radius.unwrap_or(LIGHT_DELAY_RADIUS * self.mass);
+                let advanced_time = self
+                    .diver_advanced_time
+                    .unwrap_or_else(|| self.mass * self.scaled_diver().advanced_time());
+
+                // the advanced time v = t + r* falls by the delay along the ray, without lensing
+                // the light crosses flat space where r* = r
+                if self.lensing {
+                    advanced_time
+                        - path.delay(self.mass, reach)
+                        - self.mass * tortoise(reach / self.mass)
+                } else {
+                    advanced_time - path.delay(0_f64, reach) - reach
+                }
+            }
+            None => self.diver.time(),
+        }
+    }
+
+    /// returns the map angle lensed for the view of an eye of a stereo render as seen by the diver,
+    /// the same angle for scenes that aren't an eye
+    fn eye_to_diver_frame(&self, map_angle: MapAngle) -> MapAngle {
+        match self.eye_rotation {
+            Some(rotation) => MapAngle::from_vector(rotation * map_angle.to_vector()),
+            None => map_angle,
+        }
+    }
+
+    /// returns the vector relative to the position of the diver in the frame of the map, which
+    /// turns with the diver as it goes around the black hole in the xz plane
+    pub fn to_fixed_frame(&self, local: Vector3<f64>) -> Vector3<f64> {
+        let (sin, cos) = self.scaled_diver().orbit_angle().sin_cos();
+        Vector3::new(
+            local.x * cos - local.z * sin,
+            local.y,
+            local.x * sin + local.z * cos,
+        )
+    }
+
+    /// returns the path of the ray arriving at the view angle followed back through the space
+    /// around the black hole, None for wormholes, spinning black holes and binaries that rays
+    /// can't be followed through yet
+    pub fn ray_path(
+        &self,
+        view_angle: RainAngle,
+        render_settings: RenderSettings,
+    ) -> Option<RayPath> {
+        if self.spacetime != Spacetime::BlackHole
+            || self.active_spin() != 0_f64
+            || (self.lensing && self.binary.is_some())
+        {
+            return None;
+        }
+
+        let (rain_angle, _) = self.view_to_rain_angle(view_angle);
+        let ray_angle = self.ray_angle(rain_angle)?;
+        let r = self.scaled_diver().position();
+
+        // the ray stays in the plane of the black hole, the diver and the direction it arrives from,
+        // the radius is scaled back from units of the mass of the black hole to those of the scene
+        let across = Vector3::new(ray_angle.phi().cos(), ray_angle.phi().sin(), 0_f64);
+        let point = |radius: f64, swept: f64| {
+            self.to_fixed_frame(
+                self.mass * radius * (across * swept.sin() - Vector3::z() * swept.cos()),
+            )
+        };
+        let start = point(r, 0_f64);
+
+        if !self.lensing {
+            let direction = self.to_fixed_frame(ray_angle.to_vector());
+
+            // without gravity the ray goes straight until it hits the horizon
+            return Some(
+                match sphere_intersection(Vector3::zeros(), 2_f64 * self.mass, start, direction) {
+                    Some(t) if r > 2_f64 => RayPath {
+                        points: vec![(start, 0_f64), (start + t * direction, 0_f64)],
+                        escape: None,
+                    },
+                    _ => RayPath {
+                        points: vec![(start, 0_f64)],
+                        escape: (r > 2_f64).then_some(direction),
+                    },
+                },
+            );
+        }
+
+        // rays falling in are followed down to the surface of the neutron star
+        let min_radius = self
+            .neutron_star
+            .as_ref()
+            .map_or(f64::INFINITY, |star| star.radius.max(MIN_SURFACE_RADIUS));
+        let path = self.with_spherical_metric(|metric| {
+            geodesic_path(
+                ray_angle.theta(),
+                r,
+                metric,
+                render_settings.max_half_orbits,
+                min_radius,
+            )
+        });
+
+        // escaping rays end at an infinite radius and continue in a straight line
+        let escape = path
+            .last()
+            .filter(|(radius, _)| radius.is_infinite())
+            .map(|(_, swept)| point(1_f64, *swept));
+        let points = path
+            .into_iter()
+            .filter(|(radius, _)| radius.is_finite())
+            .map(|(radius, swept)| (point(radius, swept), swept))
+            .collect();
+
+        Some(RayPath { points, escape })
+    }
+
+    /// returns the color of the companion seen at the view angle and the order of its image, None
+    /// if the ray doesn't hit it
+    ///
+    /// the ray is followed through space like `ray_path`, so the companion is only seen around
+    /// black holes that aren't spinning
+    pub fn trace_companion(
+        &self,
+        view_angle: RainAngle,
+        render_settings: RenderSettings,
+    ) -> Option<(Rgb<u8>, u32)> {
+        self.companion_on_path(&self.ray_path(view_angle, render_settings)?)
+    }
+
+    /// returns the color of the companion where the path hits it and the order of its image
+    fn companion_on_path(&self, path: &RayPath) -> Option<(Rgb<u8>, u32)> {
+        let companion = self.companion.as_ref()?;
+        let (hit, swept) = companion.hit(path)?;
+        Some((companion.get_pixel(hit), image_order(swept)))
+    }
+
+    /// returns the path of the ray arriving from the view angle, only followed through space when
+    /// there is something there to hit
+    fn traced_path(
+        &self,
+        view_angle: RainAngle,
+        render_settings: RenderSettings,
+    ) -> Option<RayPath> {
+        if self.companion.is_some()
+            || self.jet.is_some()
+            || self.neutron_star.is_some()
+            || self.env.radius.is_some()
+            || (self.light_delay && self.env.is_animated())
+        {
+            self.ray_path(view_angle, render_settings)
+        } else {
+            None
+        }
+    }
+
+    /// returns the color of the companion or the surface of the neutron star, whichever the path
+    /// reaches first, the point it is reached at and the angle the ray swept before it
+    fn first_hit(&self, path: &RayPath) -> Option<(Rgb<u8>, Vector3<f64>, f64)> {
+        let surface_hit = self
+            .neutron_star
+            .as_ref()
+            .filter(|_| self.spacetime == Spacetime::BlackHole)
+            .and_then(|star| {
+                let (hit, swept) = star.hit(path, self.mass)?;
+                Some((star.get_pixel(hit), hit, swept))
+            });
+        let companion_hit = self.companion.as_ref().and_then(|companion| {
+            let (hit, swept) = companion.hit(path)?;
+            Some((companion.get_pixel(hit), hit, swept))
+        });
+
+        // the swept angle only grows along the path
+        match (surface_hit, companion_hit) {
+            (Some(surface), Some(companion)) if companion.2 < surface.2 => Some(companion),
+            (Some(surface), _) => Some(surface),
+            (None, companion) => companion,
+        }
+    }
+
+    /// returns the angle that the ray arriving from the rain angle is followed back from, in the
+    /// rain frame with lensing and the static frame without it, None if there are no static
+    /// observers to see it
+    fn ray_angle(&self, rain_angle: RainAngle) -> Option<RainAngle> {
+        let speed = self.diver_speed();
+
+        // the lensing is computed in the rain frame and the view without it in the static frame,
+        // so add or remove the aberration to match the view
+        match (self.lensing, self.has_aberration()) {
+            (true, true) | (false, false) => Some(rain_angle),
+            (true, false) => Some(rain_angle.aberrate(speed)),
+            (false, true) => (speed < 1_f64).then(|| rain_angle.aberrate(-speed)),
+        }
+    }
+
+    /// returns the map angle relative to the position of the diver and the image order of the
+    /// ray arriving from the rain angle like `trace_with_order`
+    ///
+    /// this only depends on the theta of the rain angle apart from rotating phi, apart from around
+    /// a binary
+    pub fn lens(
+        &self,
+        rain_angle: RainAngle,
+        render_settings: RenderSettings,
+    ) -> Option<(MapAngle, u32)> {
+        if let Some(lut) = &self.deflection_lut {
+            return lut.lookup(rain_angle);
         }
 
-        // downscale the image if needed
-        if super_sampling_bool {
-            return image::imageops::resize(
+        let r = self.scaled_diver().position();
+        if let (Some(binary), true) = (&self.binary, self.lensing) {
+            // the binary is integrated from the view of a static observer
+            let static_angle = if self.has_aberration() {
+                let speed = self.diver_speed();
+                (speed < 1_f64).then(|| rain_angle.aberrate(-speed))?
+            } else {
+                rain_angle
+            };
+            let (sky, swept) =
+                binary.trace(static_angle.to_vector(), r, render_settings.max_half_orbits)?;
+            return Some((MapAngle::from_vector(sky), image_order(swept)));
+        }
+
+        let spin = self.active_spin();
+        let spherical = self.is_spherical_lensing();
+        let max_half_orbits = render_settings.max_half_orbits;
+        let lensed = |rain_angle: RainAngle| {
+            // the geodesic integrator only handles black holes that aren't spinning, and only it
+            // follows rays through the star
+            if (render_settings.lensing_backend == LensingBackend::Geodesic
+                || self.active_transparent_star().is_some())
+                && spin == 0_f64
+            {
+                self.with_spherical_metric(|metric| {
+                    rain_angle.to_map_angle_geodesic(r, metric, max_half_orbits)
+                })
+            } else if spherical {
+                self.with_spherical_metric(|metric| {
+                    rain_angle.to_map_angle_metric(r, metric, max_half_orbits)
+                })
+            } else {
+                rain_angle.to_map_angle_kerr(r, spin, max_half_orbits)
+            }
+        };
+
+        let ray_angle = self.ray_angle(rain_angle)?;
+        if self.lensing {
+            lensed(ray_angle)
+        } else if self.active_transparent_star().is_some() {
+            // nothing blocks the view through the star
+            Some((MapAngle::new(ray_angle.theta(), ray_angle.phi()), 0))
+        } else {
+            ray_angle
+                .try_to_map_angle_no_gr(r)
+                .map(|map_angle| (map_angle, 0))
+        }
+    }
+
+    /// returns a copy of the scene with a table of its lensing to interpolate the rays from if the
+    /// render settings ask for one, None if it already has one or its lensing can't be tabulated
+    pub fn with_deflection_lut(&self, render_settings: RenderSettings) -> Option<Scene> {
+        let size = render_settings.deflection_lut?;
+        if self.deflection_lut.is_some() || !DeflectionLut::supports(self) {
+            return None;
+        }
+
+        Some(Scene {
+            deflection_lut: Some(Arc::new(DeflectionLut::build(self, render_settings, size))),
+            ..self.clone()
+        })
+    }
+
+    /// returns a copy of the scene with a table of its lensing like `with_deflection_lut`, taken
+    /// from the cache if a scene with the same lensing was rendered recently
+    pub fn with_cached_deflection_lut(
+        &self,
+        render_settings: RenderSettings,
+        cache: &LutCache,
+    ) -> Option<Scene> {
+        let size = render_settings.deflection_lut?;
+        if self.deflection_lut.is_some() || !DeflectionLut::supports(self) {
+            return None;
+        }
+
+        Some(Scene {
+            deflection_lut: Some(cache.get(self, render_settings, size)),
+            ..self.clone()
+        })
+    }
+
+    pub fn render(&self, render_settings: RenderSettings) -> RgbImage {
+        // nothing else holds the progress so the render can't be cancelled
+        self.render_with_progress(render_settings, &RenderProgress::new())
+            .unwrap()
+    }
+
+    /// renders the image, or only its crop if it has one, reporting progress, returns None if the
+    /// render was cancelled
+    pub fn render_with_progress(
+        &self,
+        render_settings: RenderSettings,
+        progress: &RenderProgress,
+    ) -> Option<RgbImage> {
+        let (offset, size) = render_settings.region();
+        self.render_region(render_settings, offset, size, progress)
+    }
+
+    /// renders the image like `render_with_progress` in linear floating point without clipping
+    /// light brighter than white, returns None if the render was cancelled
+    pub fn render_hdr_with_progress(
+        &self,
+        render_settings: RenderSettings,
+        progress: &RenderProgress,
+    ) -> Option<Rgb32FImage> {
+        let (offset, size) = render_settings.region();
+        self.render_region_hdr(render_settings, offset, size, progress)
+    }
+
+    /// renders the rectangle of the image with its top left pixel at `offset` and the given `size`
+    ///
+    /// each sample adds a step to the progress, returns None if the render was cancelled
+    pub fn render_region(
+        &self,
+        render_settings: RenderSettings,
+        offset: Vector2<u32>,
+        size: Vector2<u32>,
+        progress: &RenderProgress,
+    ) -> Option<RgbImage> {
+        self.render_region_float(render_settings, offset, size, progress)
+            .map(|image| render_settings.quantize(&image))
+    }
+
+    /// renders the rectangle of the image like `render_region` but leaves the gamma encoded pixels
+    /// in floating point so they can be quantized to 8 or 16 bits
+    pub fn render_region_float(
+        &self,
+        render_settings: RenderSettings,
+        offset: Vector2<u32>,
+        size: Vector2<u32>,
+        progress: &RenderProgress,
+    ) -> Option<Rgb32FImage> {
+        self.render_region_float_and_hdr(render_settings, offset, size, progress)
+            .map(|(image, _)| image)
+    }
+
+    /// renders the rectangle of the image like `render_region_float`, along with the linear pixels
+    /// `render_region_hdr` averages from the same samples before they are clipped
+    pub fn render_region_float_and_hdr(
+        &self,
+        render_settings: RenderSettings,
+        offset: Vector2<u32>,
+        size: Vector2<u32>,
+        progress: &RenderProgress,
+    ) -> Option<(Rgb32FImage, Rgb32FImage)> {
+        if let Some(scene) = self.with_deflection_lut(render_settings) {
+            return scene.render_region_float_and_hdr(render_settings, offset, size, progress);
+        }
+
+        if let Some(strength) = render_settings.denoise {
+            // render a margin around the region so it is filtered across its edges
+            let start = offset.map(|value| value.saturating_sub(denoise::RADIUS));
+            let end = (offset + size)
+                .add_scalar(denoise::RADIUS)
+                .inf(&render_settings.resolution);
+            let (noisy, hdr) = self.render_region_float_and_hdr(
+                RenderSettings {
+                    denoise: None,
+                    ..render_settings
+                },
+                start,
+                end - start,
+                progress,
+            )?;
+
+            let margin = offset - start;
+            let denoised = denoise::denoise(&noisy, strength);
+            return Some((
+                denoised.view(margin.x, margin.y, size.x, size.y).to_image(),
+                hdr.view(margin.x, margin.y, size.x, size.y).to_image(),
+            ));
+        }
+
+        let render_settings = self.graded_settings(render_settings);
+        let mut buf = self.render_samples(render_settings, offset, size, progress)?;
+        let hdr = Self::downsample_linear(&buf, size, render_settings);
+
+        // clip to white and encode
+        buf.pixels_mut().par_bridge().for_each(|pixel| {
+            *pixel = Rgb(pixel.0.map(|channel| gamma_from_linear(channel.min(1_f32))));
+        });
+
+        // downscale and grade in floating point to avoid banding
+        if render_settings.super_sampling.is_some() {
+            buf = image::imageops::resize(
                 &buf,
-                render_settings.resolution.x,
-                render_settings.resolution.y,
+                size.x,
+                size.y,
                 image::imageops::FilterType::Lanczos3,
             );
+        }
+
+        if render_settings.is_graded() {
+            buf.pixels_mut()
+                .par_bridge()
+                .for_each(|pixel| *pixel = render_settings.grade(*pixel));
+        }
+
+        Some((buf, hdr))
+    }
+
+    /// renders the rectangle of the image like `render_region` but in linear floating point
+    /// without clipping or quantizing, for saving as OpenEXR
+    pub fn render_region_hdr(
+        &self,
+        render_settings: RenderSettings,
+        offset: Vector2<u32>,
+        size: Vector2<u32>,
+        progress: &RenderProgress,
+    ) -> Option<Rgb32FImage> {
+        if let Some(scene) = self.with_deflection_lut(render_settings) {
+            return scene.render_region_hdr(render_settings, offset, size, progress);
+        }
+
+        let render_settings = self.graded_settings(render_settings);
+        let samples = self.render_samples(render_settings, offset, size, progress)?;
+        Some(Self::downsample_linear(&samples, size, render_settings))
+    }
+
+    /// returns the render settings graded with the exposure of the scene as well, fading to black
+    /// at the minimum
+    fn graded_settings(&self, render_settings: RenderSettings) -> RenderSettings {
+        let scene_exposure = if self.exposure <= MIN_EXPOSURE {
+            f32::NEG_INFINITY
         } else {
-            return buf;
+            self.exposure as f32
+        };
+        RenderSettings {
+            exposure: render_settings.exposure + scene_exposure,
+            ..render_settings
         }
     }
 
-    pub fn show(&mut self, ui: &mut egui::Ui) {
+    /// returns the graded linear pixels of the rectangle of the given size from its samples,
+    /// resizing with the image crate would clip them to white
+    fn downsample_linear(
+        samples: &Rgb32FImage,
+        size: Vector2<u32>,
+        render_settings: RenderSettings,
+    ) -> Rgb32FImage {
+        let super_sampling = render_settings.super_sampling.unwrap_or(1) as u32;
+        let mut buf = Rgb32FImage::new(size.x, size.y);
+        buf.enumerate_pixels_mut()
+            .par_bridge()
+            .for_each(|(x, y, pixel)| {
+                let mut sum = [0_f32; 3];
+                for dy in 0..super_sampling {
+                    for dx in 0..super_sampling {
+                        let sample =
+                            samples.get_pixel(x * super_sampling + dx, y * super_sampling + dy);
+                        (0..3).for_each(|i| sum[i] += sample[i]);
+                    }
+                }
+                let average = sum.map(|channel| channel / super_sampling.pow(2) as f32);
+                *pixel = render_settings.grade_linear(Rgb(average));
+            });
+        buf
+    }
+
+    /// renders the linear colors of every sample of the rectangle of the image, the image is
+    /// larger than the rectangle by the super sampling
+    ///
+    /// each sample adds a step to the progress, returns None if the render was cancelled
+    fn render_samples(
+        &self,
+        render_settings: RenderSettings,
+        offset: Vector2<u32>,
+        size: Vector2<u32>,
+        progress: &RenderProgress,
+    ) -> Option<Rgb32FImage> {
+        // every ray reaches the diver at the same advanced time
+        if self.light_delay && self.env.is_animated() && self.diver_advanced_time.is_none() {
+            let scene = Scene {
+                diver_advanced_time: Some(self.mass * self.scaled_diver().advanced_time()),
+                ..self.clone()
+            };
+            return scene.render_samples(render_settings, offset, size, progress);
+        }
+
+        let super_sampling = render_settings.super_sampling.unwrap_or(1) as u32;
+
+        let resolution = render_settings.resolution * super_sampling;
+        let offset = offset * super_sampling;
+
+        let samples = size * super_sampling;
+        progress.add_total((samples.x * samples.y) as usize);
+
+        let view_angle = |pixel: Vector2<u32>| {
+            self.camera
+                .sees_pixel(render_settings.projection, pixel, resolution)
+                .then(|| self.pixel_to_view_angle(render_settings.projection, pixel, resolution))
+        };
+
+        // with mipmaps the rays are traced first, with an extra column and row for the neighbours
+        // of the last samples, so each sample finds the patch of sky it covers from the rays of its
+        // neighbours
+        let grid = samples.add_scalar(1);
+        let traced: Option<Vec<Option<(MapAngle, u32, bool)>>> =
+            render_settings.mipmaps.then(|| {
+                progress.add_total((grid.x * grid.y) as usize);
+                (0..grid.x * grid.y)
+                    .into_par_iter()
+                    .map(|i| {
+                        if progress.is_cancelled() {
+                            return None;
+                        }
+                        let traced = view_angle(Vector2::new(i % grid.x, i / grid.x) + offset)
+                            .and_then(|view_angle| self.trace_to_side(view_angle, render_settings));
+                        progress.advance(1);
+                        traced
+                    })
+                    .collect()
+            });
+        let traced_sample = |pixel: Vector2<u32>| {
+            let traced = traced.as_ref()?;
+            let ray = |pixel: Vector2<u32>| traced[(pixel.y * grid.x + pixel.x) as usize];
+            let center = ray(pixel);
+            let footprint = center.map_or(0_f64, |(map_angle, _, _)| {
+                [pixel + Vector2::x(), pixel + Vector2::y()]
+                    .into_iter()
+                    .filter_map(ray)
+                    .map(|(neighbour, _, _)| neighbour.to_vector().angle(&map_angle.to_vector()))
+                    .fold(0_f64, f64::max)
+            });
+            Some(TracedSample {
+                traced: center,
+                footprint,
+            })
+        };
+
+        // Calculate pixels in parallel
+        let colors: Vec<([f64; 3], RaySample)> = (0..samples.x * samples.y)
+            .into_par_iter()
+            .map(|i| {
+                if progress.is_cancelled() {
+                    return Default::default();
+                }
+
+                let sample = Vector2::new(i % samples.x, i / samples.x);
+                let Some(rain_angle) = view_angle(sample + offset) else {
+                    progress.advance(1);
+                    return Default::default();
+                };
+
+                let sample = self.linear_color(rain_angle, traced_sample(sample), render_settings);
+                progress.advance(1);
+                sample
+            })
+            .collect();
+
+        if progress.is_cancelled() {
+            return None;
+        }
+
+        if let Some(recorder) = &self.pass_recorder {
+            let rays: Vec<RaySample> = colors.iter().map(|(_, ray)| *ray).collect();
+            recorder.record(offset / super_sampling, size, super_sampling, &rays);
+        }
+        Some(Rgb32FImage::from_fn(samples.x, samples.y, |x, y| {
+            let (color, _) = colors[(y * samples.x + x) as usize];
+            Rgb(color.map(|channel| channel as f32))
+        }))
+    }
+
+    /// returns the linear color seen at the view angle, light brighter than white isn't clipped,
+    /// and what the ray found for the passes if they are being recorded
+    ///
+    /// the ray is traced here unless it was traced ahead, and the environment is filtered over the
+    /// footprint of rays traced ahead
+    fn linear_color(
+        &self,
+        rain_angle: RainAngle,
+        traced_ahead: Option<TracedSample>,
+        render_settings: RenderSettings,
+    ) -> ([f64; 3], RaySample) {
+        let linear = |color: Rgb<u8>| {
+            color
+                .0
+                .map(|channel| linear_f32_from_gamma_u8(channel) as f64)
+        };
+
+        if render_settings.render_mode.is_diagnostic() {
+            return (
+                linear(self.diagnostic_color(rain_angle, render_settings)),
+                RaySample::default(),
+            );
+        }
+
+        let path = self.traced_path(rain_angle, render_settings);
+        let hit = path.as_ref().and_then(|path| self.first_hit(path));
+        let traced = hit
+            .is_none()
+            .then(|| match traced_ahead {
+                Some(sample) => sample.traced,
+                None => self.trace_to_side(rain_angle, render_settings),
+            })
+            .flatten();
+
+        // light reaches the diver from objects and the sky
+        let ray = match &self.pass_recorder {
+            Some(_) => RaySample {
+                frequency_ratio: (hit.is_some() || traced.is_some())
+                    .then(|| self.view_frequency_ratio(rain_angle)),
+                deflection: traced
+                    .as_ref()
+                    .map(|(map_angle, _, _)| self.deflection(rain_angle, map_angle)),
+            },
+            None => RaySample::default(),
+        };
+
+        let color = if let Some((color, _, swept)) = hit {
+            let color = self.shift_color(rain_angle, linear(color));
+            render_settings.tint_image_order(color, image_order(swept))
+        } else if let Some((map_angle, order, other_side)) = traced {
+            // Successful map angle
+            let (env, env_hit) = if other_side {
+                (&self.other_env, None)
+            } else {
+                (&self.env, self.environment_hit(path.as_ref()))
+            };
+            let magnification = if self.magnification {
+                self.magnification(rain_angle, &map_angle, render_settings)
+            } else {
+                None
+            };
+            let env_time = self.environment_time(path.as_ref());
+            let footprint = traced_ahead.map_or(0_f64, |sample| sample.footprint);
+            let mut color = self.shift_color(
+                rain_angle,
+                linear(env.sample_at(
+                    env_hit.unwrap_or(map_angle),
+                    env_time,
+                    render_settings.sampling,
+                    footprint,
+                )),
+            );
+            if let Some(factor) = magnification {
+                color = color.map(|channel| channel * factor);
+            }
+            render_settings.tint_image_order(color, order)
+        } else {
+            // Ray went into black hole
+            [0_f64; 3]
+        };
+
+        // the jet only shines in front of what the ray hits
+        let color = match (&self.jet, &path, hit) {
+            (Some(jet), Some(path), Some((_, point, _))) => jet.shine(color, &path.up_to(point)),
+            (Some(jet), Some(path), None) => jet.shine(color, path),
+            _ => color,
+        };
+        (color, ray)
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, units: &Units) {
         ui.collapsing("Camera", |ui| {
             self.camera.show(ui);
         });
         ui.collapsing("Diver", |ui| {
-            self.diver.show(ui);
+            self.diver.show(ui, units, self.mass);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Exposure");
+            ui.add(
+                egui::DragValue::new(&mut self.exposure)
+                    .clamp_range(MIN_EXPOSURE..=16_f64)
+                    .speed(0.05)
+                    .suffix(" EV"),
+            )
+            .on_hover_text(
+                "Animatable exposure on top of the render settings, the minimum is black",
+            );
+        });
+        ui.collapsing("Environment", |ui| {
+            self.env.show_sequence_loader(ui, units);
+            self.env.orientation.show(ui);
+            if self.spacetime == Spacetime::BlackHole {
+                self.env.show_radius(ui, units);
+                ui.add_enabled(
+                    self.env.is_animated(),
+                    egui::Checkbox::new(&mut self.light_delay, "Light travel delay"),
+                )
+                .on_hover_text(
+                    "Show each part of an animated environment as it was when the light seen \
+                    left it, following every ray through space which is slow and only done \
+                    around black holes that aren't spinning",
+                )
+                .on_disabled_hover_text("The environment isn't animated, load an image sequence");
+            }
+            if self.spacetime == Spacetime::Wormhole {
+                ui.label("Other side");
+                ui.push_id("other side orientation", |ui| {
+                    self.other_env.orientation.show(ui);
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.color_shift, "Color shift")
+                    .on_hover_text(
+                    "Shift the colors and brightness of the sky by the blueshift seen by the diver",
+                );
+                ui.add_enabled(
+                    self.color_shift,
+                    egui::Slider::new(&mut self.color_shift_strength, 0_f64..=1_f64)
+                        .text("Strength"),
+                );
+            });
+            ui.add_enabled_ui(self.color_shift, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Shift mode");
+                    egui::ComboBox::from_id_source("color shift mode combo box")
+                        .selected_text(self.color_shift_mode.to_string())
+                        .show_ui(ui, |ui| {
+                            for mode in [ColorShiftMode::Channels, ColorShiftMode::Blackbody] {
+                                ui.selectable_value(
+                                    &mut self.color_shift_mode,
+                                    mode,
+                                    mode.to_string(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Channels: shift the spectrum interpolated between the color \
+                            channels\nBlackbody: treat each pixel as a blackbody whose \
+                            temperature is shifted",
+                        );
+                });
+            });
+            ui.add_enabled_ui(!self.color_shift, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Beaming");
+                    egui::ComboBox::from_id_source("beaming combo box")
+                        .selected_text(self.beaming.to_string())
+                        .show_ui(ui, |ui| {
+                            for beaming in Beaming::ALL {
+                                ui.selectable_value(
+                                    &mut self.beaming,
+                                    beaming,
+                                    beaming.to_string(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Brighten the sky ahead of the diver and dim it behind by a power of \
+                            the Doppler factor D of its motion through the static observers, \
+                            without shifting its colors or the gravitational blueshift. There are \
+                            no static observers inside the horizon so the sky isn't beamed there\n\
+                            D³: intensity at a fixed frequency\nD⁴: intensity over all frequencies",
+                        )
+                        .on_disabled_hover_text("The color shift already changes the brightness");
+                });
+            });
+        });
+        ui.collapsing("Companion", |ui| {
+            let mut enabled = self.companion.is_some();
+            if ui
+                .checkbox(&mut enabled, "Show companion")
+                .on_hover_text(
+                    "Add a textured sphere next to the black hole, it is lensed like the sky but \
+                    not around a spinning black hole",
+                )
+                .changed()
+            {
+                self.companion = enabled.then(Companion::default);
+            }
+            if let Some(companion) = &mut self.companion {
+                companion.show(ui, units);
+            }
+        });
+        if self.spacetime == Spacetime::BlackHole {
+            ui.collapsing("Neutron star", |ui| {
+                let mut enabled = self.neutron_star.is_some();
+                if ui
+                    .checkbox(&mut enabled, "Surface instead of horizon")
+                    .on_hover_text(
+                        "Replace the black hole with a star of the same mass whose textured surface \
+                        is lensed like the sky, but not around a spinning black hole",
+                    )
+                    .changed()
+                {
+                    self.neutron_star = enabled.then(NeutronStar::default);
+                }
+                if let Some(star) = &mut self.neutron_star {
+                    star.show(ui, units, self.mass);
+                }
+            });
+            ui.collapsing("Transparent star", |ui| {
+                let mut enabled = self.transparent_star.is_some();
+                if ui
+                    .checkbox(&mut enabled, "Constant density star")
+                    .on_hover_text(
+                        "Replace the black hole with a star of the same mass that light passes \
+                        through, lensed by the interior Schwarzschild solution inside it. The diver \
+                        still falls as it would into the black hole and only sees the star while \
+                        falling with the rain",
+                    )
+                    .changed()
+                {
+                    self.transparent_star = enabled.then(InteriorSchwarzschild::default);
+                }
+                if let Some(star) = &mut self.transparent_star {
+                    ui.horizontal(|ui| {
+                        ui.label("Radius");
+                        ui.add(
+                            egui::DragValue::new(&mut star.radius)
+                                .clamp_range(MIN_STAR_RADIUS..=f64::MAX)
+                                .speed(0.01)
+                                .suffix(" M"),
+                        )
+                        .on_hover_text(
+                            "Radius of the star in units of its mass, stars smaller than 3M have a \
+                            photon sphere outside of them",
+                        );
+                        ui.label(units.describe_length(self.mass * star.radius));
+                    });
+                }
+            });
+            ui.collapsing("Binary (experimental)", |ui| {
+                let mut enabled = self.binary.is_some();
+                if ui
+                    .checkbox(&mut enabled, "Two black holes")
+                    .on_hover_text(
+                        "Split the mass between two black holes orbiting in front of the diver, \
+                        rays are integrated through the sum of their bending which is only a \
+                        rough illustration and ignores spin, charge and objects around them",
+                    )
+                    .changed()
+                {
+                    self.binary = enabled.then(Binary::default);
+                }
+                if let Some(binary) = &mut self.binary {
+                    binary.show(ui);
+                }
+            });
+        }
+        ui.collapsing("Jet", |ui| {
+            let mut enabled = self.jet.is_some();
+            if ui
+                .checkbox(&mut enabled, "Show jet")
+                .on_hover_text(
+                    "Add a glowing bipolar jet around the black hole, it is lensed like the sky but \
+                    not around a spinning black hole",
+                )
+                .changed()
+            {
+                self.jet = enabled.then(Jet::default);
+            }
+            if let Some(jet) = &mut self.jet {
+                jet.show(ui, units);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Metric");
+            egui::ComboBox::from_id_source("metric combo box")
+                .selected_text(self.spacetime.to_string())
+                .show_ui(ui, |ui| {
+                    for spacetime in [Spacetime::BlackHole, Spacetime::Wormhole] {
+                        ui.selectable_value(&mut self.spacetime, spacetime, spacetime.to_string());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "A wormhole has no gravity, the diver hovers at its initial distance from the \
+                    throat and sees the sky of the other side through it",
+                );
+        });
+        if self.spacetime == Spacetime::Wormhole {
+            ui.horizontal(|ui| {
+                ui.label("Throat radius");
+                ui.add(
+                    units
+                        .length_drag_value(&mut self.throat_radius, 0.1)
+                        .clamp_range(0.01..=f64::MAX),
+                );
+            });
+        }
+        ui.checkbox(&mut self.lensing, "Lensing")
+            .on_hover_text("Bend light through the curved spacetime around the black hole");
+        ui.checkbox(&mut self.aberration, "Aberration")
+            .on_hover_text(
+                "Show the view of the falling diver instead of a stationary observer at the same \
+            radius, inside the horizon nothing can stay still so the view always has aberration",
+            );
+        let built_in = self.spacetime == Spacetime::BlackHole && self.custom_metric.is_none();
+        ui.checkbox(&mut self.magnification, "Magnification")
+            .on_hover_text(
+                "Brighten the environment by how much the lensing magnifies it, so the Einstein \
+                ring and the images near the shadow glow",
+            );
+        ui.add_enabled_ui(self.spacetime == Spacetime::BlackHole, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.tidal_stretch, "Tidal stretch")
+                    .on_hover_text(
+                    "Stretch the view towards the black hole and squeeze it across by how much \
+                        the tides would stretch an observer of this size, which grows without \
+                        bound towards the singularity",
+                );
+                ui.add_enabled(
+                    self.tidal_stretch,
+                    units
+                        .length_drag_value(&mut self.observer_size, 0.01)
+                        .clamp_range(0_f64..=f64::MAX),
+                )
+                .on_hover_text("Size of the observer being stretched");
+            });
+        });
+        ui.add_enabled_ui(self.spacetime == Spacetime::BlackHole, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Mass");
+                ui.add(
+                    egui::DragValue::new(&mut self.mass)
+                        .clamp_range(MIN_MASS..=f64::MAX)
+                        .speed(0.005),
+                )
+                .on_hover_text(
+                    "Mass of the black hole relative to the unit of mass of the scene, the diver \
+                    keeps its radius and time in the units of the scene so animating it grows or \
+                    shrinks the black hole around the diver",
+                );
+            });
+        });
+        ui.add_enabled_ui(self.lensing && built_in, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Spin");
+                ui.add(
+                    egui::DragValue::new(&mut self.spin)
+                        .clamp_range(0_f64..=1_f64)
+                        .speed(0.005),
+                )
+                .on_hover_text(
+                    "Spin a of the black hole about the axis the diver falls along, 0 is not rotating",
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Charge");
+                ui.add(
+                    egui::DragValue::new(&mut self.charge)
+                        .clamp_range(0_f64..=1_f64)
+                        .speed(0.005),
+                )
+                .on_hover_text(
+                    "Charge Q per unit mass of the black hole, 0 is uncharged and it is ignored \
+                    while the black hole spins",
+                );
+            });
         });
-        ui.checkbox(&mut self.gr, "General Relativity");
     }
 }
 
 impl Interpolate for Scene {
     fn interpolate(&self, other: &Scene, factor: f32) -> Scene {
         let camera = self.camera.interpolate(&other.camera, factor);
-        Scene::new(
-            camera,
-            self.env.clone(),
-            self.diver.interpolate(&other.diver, factor),
-            self.gr,
-        )
+        Scene {
+            other_env: self.other_env.clone(),
+            spacetime: self.spacetime,
+            magnification: self.magnification,
+            throat_radius: self.throat_radius.interpolate(&other.throat_radius, factor),
+            spin: self.spin.interpolate(&other.spin, factor),
+            charge: self.charge.interpolate(&other.charge, factor),
+            custom_metric: self.custom_metric.clone(),
+            color_shift: self.color_shift,
+            color_shift_strength: self
+                .color_shift_strength
+                .interpolate(&other.color_shift_strength, factor),
+            color_shift_mode: self.color_shift_mode,
+            beaming: self.beaming,
+            exposure: self.exposure.interpolate(&other.exposure, factor),
+            mass: self.mass.interpolate(&other.mass, factor),
+            light_delay: self.light_delay,
+            tidal_stretch: self.tidal_stretch,
+            observer_size: self.observer_size.interpolate(&other.observer_size, factor),
+            deflection_lut: None,
+            eye_rotation: None,
+            diver_advanced_time: None,
+            pass_recorder: None,
+            companion: match (&self.companion, &other.companion) {
+                (Some(companion), Some(other)) => Some(companion.interpolate(other, factor)),
+                _ => self.companion.clone(),
+            },
+            jet: match (&self.jet, &other.jet) {
+                (Some(jet), Some(other)) => Some(jet.interpolate(other, factor)),
+                _ => self.jet,
+            },
+            transparent_star: match (&self.transparent_star, &other.transparent_star) {
+                (Some(star), Some(other)) => Some(InteriorSchwarzschild::new(
+                    star.radius.interpolate(&other.radius, factor),
+                )),
+                _ => self.transparent_star,
+            },
+            binary: match (&self.binary, &other.binary) {
+                (Some(binary), Some(other)) => Some(binary.interpolate(other, factor)),
+                _ => self.binary,
+            },
+            neutron_star: match (&self.neutron_star, &other.neutron_star) {
+                (Some(star), Some(other)) => Some(star.interpolate(other, factor)),
+                _ => self.neutron_star.clone(),
+            },
+            ..Scene::new(
+                camera,
+                self.env.clone(),
+                self.diver.interpolate(&other.diver, factor),
+                self.lensing,
+                self.aberration,
+            )
+        }
     }
 }
 
@@ -119,8 +1805,175 @@ impl Default for Scene {
         Self {
             camera: Default::default(),
             env: Default::default(),
+            other_env: SharedEnvironment::default_grid(),
             diver: Default::default(),
-            gr: true,
+            spacetime: Spacetime::BlackHole,
+            throat_radius: 2_f64,
+            lensing: true,
+            aberration: true,
+            magnification: false,
+            spin: 0_f64,
+            charge: 0_f64,
+            custom_metric: None,
+            color_shift: false,
+            color_shift_strength: 1_f64,
+            color_shift_mode: ColorShiftMode::Channels,
+            beaming: Beaming::Off,
+            exposure: 0_f64,
+            companion: None,
+            jet: None,
+            neutron_star: None,
+            binary: None,
+            transparent_star: None,
+            mass: 1_f64,
+            light_delay: false,
+            tidal_stretch: false,
+            observer_size: 1_f64,
+            deflection_lut: None,
+            eye_rotation: None,
+            diver_advanced_time: None,
+            pass_recorder: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{camera::Projection, timeline::Timeline};
+
+    /// timeline fading from the exposure at frame 0 to black at frame 10
+    fn fade_out(exposure: f64) -> Timeline {
+        let scene = |exposure| Scene {
+            exposure,
+            ..Scene::test_pattern()
+        };
+        let mut timeline = Timeline::new(0, 10, 30_f32, scene(exposure));
+        timeline.set_scene(10, scene(MIN_EXPOSURE));
+        timeline
+    }
+
+    #[test]
+    fn exposure_interpolates_between_keyframes() {
+        let timeline = fade_out(2_f64);
+        for frame in 0..=10 {
+            let expected = 2_f64 + (MIN_EXPOSURE - 2_f64) * frame as f64 / 10_f64;
+            let exposure = timeline.get_scene(frame).exposure;
+            assert!(
+                (exposure - expected).abs() < 1e-5,
+                "frame {frame}: {exposure} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn fade_to_black_ends_black() {
+        let timeline = fade_out(0_f64);
+        let render_settings = RenderSettings::new(Projection::default(), Vector2::new(16, 8), None);
+
+        let start = timeline.get_scene(0).render(render_settings);
+        assert!(start.pixels().any(|pixel| pixel.0 != [0; 3]));
+
+        let end = timeline.get_scene(10).render(render_settings);
+        assert!(end.pixels().all(|pixel| pixel.0 == [0; 3]));
+        let (offset, size) = render_settings.region();
+        let end_float = timeline
+            .get_scene(10)
+            .render_region_hdr(render_settings, offset, size, &RenderProgress::new())
+            .unwrap();
+        assert!(end_float.pixels().all(|pixel| pixel.0 == [0_f32; 3]));
+    }
+
+    #[test]
+    fn jet_hidden_behind_companion() {
+        // the jet lobe reaching out along the x axis, with the companion between it and the diver
+        let jet = Jet {
+            axis: Vector3::x(),
+            opening_angle: 10_f64.to_radians(),
+            ..Jet::default()
+        };
+        let companion = Companion {
+            position: Vector3::new(12_f64, 0_f64, -4_f64),
+            ..Companion::default()
+        };
+        let scene = |jet: Option<Jet>, companion: Option<Companion>| Scene {
+            jet,
+            companion,
+            ..Scene::test_pattern()
+        };
+        let render_settings = RenderSettings::default();
+
+        let with_companion = scene(Some(jet), Some(companion.clone()));
+        let without_jet = scene(None, Some(companion));
+        let without_companion = scene(Some(jet), None);
+
+        let mut jet_behind = false;
+        for i in 0..100 {
+            for j in 0..200 {
+                let view_angle = RainAngle {
+                    theta: PI * (i as f64 + 0.5) / 100_f64,
+                    phi: 2_f64 * PI * j as f64 / 200_f64,
+                };
+                if with_companion
+                    .trace_companion(view_angle, render_settings)
+                    .is_none()
+                {
+                    continue;
+                }
+
+                assert_eq!(
+                    with_companion
+                        .linear_color(view_angle, None, render_settings)
+                        .0,
+                    without_jet
+                        .linear_color(view_angle, None, render_settings)
+                        .0,
+                );
+                let (color, _) = without_companion.linear_color(view_angle, None, render_settings);
+                let (background, _) =
+                    scene(None, None).linear_color(view_angle, None, render_settings);
+                jet_behind |= color[0] > background[0];
+            }
+        }
+        assert!(jet_behind);
+    }
+
+    #[test]
+    fn light_delay_of_radial_ray() {
+        // light falling straight in keeps its advanced time, so it left the environment at
+        // t = v - r* at its radius
+        let scene = |diver: Diver| {
+            let mut scene = Scene {
+                diver,
+                light_delay: true,
+                ..Scene::test_pattern()
+            };
+            scene.env.radius = Some(100_f64);
+            scene
+        };
+        let outward = RainAngle {
+            theta: PI,
+            phi: 0_f64,
+        };
+        let render_settings = RenderSettings::default();
+        let environment_time = |scene: &Scene| {
+            let path = scene.ray_path(outward, render_settings);
+            assert!(path.is_some());
+            scene.environment_time(path.as_ref())
+        };
+
+        // at the start of the fall the diver is at t = 0
+        let falling = scene(Diver::new(10_f64, 0_f64));
+        let expected = tortoise(10_f64) - tortoise(100_f64);
+        let time = environment_time(&falling);
+        assert!((time - expected).abs() < 1e-6, "{time} != {expected}");
+
+        // hovering divers see the environment age at the rate of the coordinate time
+        let mut diver = Diver::new(10_f64, 5_f64);
+        diver.trajectory = Trajectory::Hover;
+        let hovering = scene(diver);
+        let expected = tortoise(10_f64) + 5_f64 / 0.8_f64.sqrt() - tortoise(100_f64);
+        let time = environment_time(&hovering);
+        assert!((time - expected).abs() < 1e-6, "{time} != {expected}");
+    }
+}
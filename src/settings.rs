@@ -1,17 +1,49 @@
-use crate::app::BHDiver;
+use crate::{app::BHDiver, keymap::Keymap, scene::Scene, units::Units};
 
 pub struct Settings {
     pub resolution_scale: f32,
+    // only render the preview at the resolution scale while the view is being dragged or zoomed,
+    // and at full resolution once it stops
+    pub adaptive_resolution: bool,
+    // render the preview at the aspect ratio and projection of the render settings
+    pub lock_preview_aspect: bool,
+    // render the preview again at full quality once the scene stops changing
+    pub refine_preview: bool,
+    // draw the preview with a shader instead of the CPU renderer
+    pub gpu_preview: bool,
+    // draw the radius, speed, remaining time and shadow size of the diver over the preview
+    pub physics_overlay: bool,
+    // draw the edge of the shadow where theory puts it over the preview
+    pub shadow_outline: bool,
+    // split the preview into flat space on the left and general relativity on the right
+    pub flat_comparison: bool,
+    // fraction of the width of the preview at which it is split
+    pub flat_comparison_split: f32,
     pub mouse_sensitivity: f64,
     pub zoom_sensitivity: f64,
+    pub units: Units,
+    // scene of the first keyframe of new projects
+    pub default_scene: Scene,
+    pub keymap: Keymap,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             resolution_scale: 0.5,
+            adaptive_resolution: true,
+            lock_preview_aspect: false,
+            refine_preview: false,
+            gpu_preview: true,
+            physics_overlay: false,
+            shadow_outline: false,
+            flat_comparison: false,
+            flat_comparison_split: 0.5,
             mouse_sensitivity: 10_f64,
             zoom_sensitivity: 10_f64,
+            units: Default::default(),
+            default_scene: Default::default(),
+            keymap: Default::default(),
         }
     }
 }
@@ -27,10 +59,58 @@ impl Settings {
                     .clamp_range(0_f32..=2_f32)
                     .speed(0.1),
             );
+            ui.end_row();
+
+            ui.label("Adaptive resolution");
+            ui.checkbox(&mut app.settings.adaptive_resolution, "")
+                .on_hover_text(
+                    "Only lower the preview to the resolution scale while dragging or zooming, and render it at full resolution once the input stops",
+                );
+            ui.end_row();
         });
 
         ui.separator();
 
+        ui.heading("Preview");
+
+        ui.checkbox(
+            &mut app.settings.lock_preview_aspect,
+            "Match render aspect ratio",
+        )
+        .on_hover_text("Letterbox the preview to the aspect ratio and projection of the render");
+
+        let gpu_available = app.is_gpu_preview_available();
+        ui.add_enabled(
+            gpu_available,
+            egui::Checkbox::new(&mut app.settings.gpu_preview, "GPU preview"),
+        )
+        .on_hover_text(
+            "Draw the preview at full resolution with a shader that samples the environment bilinearly, scenes and render settings the shader can't draw, like mipmaps and denoising, and the eyedropper fall back to the CPU preview",
+        )
+        .on_disabled_hover_text("The graphics context doesn't support the preview shader");
+
+        ui.checkbox(&mut app.settings.refine_preview, "Refine preview")
+            .on_hover_text(
+                "Once the scene stops changing, render the preview again at full resolution with super sampling",
+            );
+
+        ui.checkbox(&mut app.settings.physics_overlay, "Physics overlay")
+            .on_hover_text(
+                "Show the radius, speed, remaining proper time and hovering acceleration of the diver and the size of the shadow over the preview",
+            );
+
+        ui.checkbox(&mut app.settings.shadow_outline, "Shadow outline")
+            .on_hover_text(
+                "Outline where the edge of the shadow is expected analytically to check the lensing of the preview against. Binaries have no analytic shadow so it isn't outlined for them",
+            );
+
+        ui.checkbox(&mut app.settings.flat_comparison, "Compare with flat space")
+            .on_hover_text(
+                "Split the preview to show the same view without lensing on the left, drag the split to move it",
+            );
+
+        ui.separator();
+
         ui.heading("Timeline");
 
         egui::Grid::new("timeline_settings_grid").show(ui, |ui| {
@@ -40,6 +120,33 @@ impl Settings {
 
         ui.separator();
 
+        ui.heading("Units");
+
+        app.settings.units.show(ui);
+
+        ui.separator();
+
+        ui.heading("Default Scene");
+
+        ui.collapsing("Scene for new projects", |ui| {
+            app.settings.default_scene.show(ui, &app.settings.units);
+        });
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("Use current scene")
+                .on_hover_text("Start new projects from the scene at the current frame")
+                .clicked()
+            {
+                app.settings.default_scene = app.timeline.get_current_scene();
+            }
+            if ui.button("Reset").clicked() {
+                app.settings.default_scene = Scene::default();
+            }
+        });
+
+        ui.separator();
+
         ui.heading("Sensitivity");
 
         egui::Grid::new("sensitivity_grid").show(ui, |ui| {
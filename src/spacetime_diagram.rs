@@ -0,0 +1,269 @@
+// Plots the worldline of the diver on a Kruskal-Szekeres or Penrose diagram of the black hole
+//
+// The worldline is followed in the ingoing Eddington-Finkelstein time v = t + r*, which stays
+// finite across the horizon. Along a geodesic with energy E and angular momentum L per unit mass it
+// advances at dv/dtau = (1 + L^2/r^2) / (E - dr/dtau). The Kruskal coordinates are then
+// V = e^(v/4) and U = -(r/2 - 1) e^(r/2) / V, with T = (V + U) / 2 and X = (V - U) / 2, and the
+// Penrose diagram squeezes U and V into a finite range with arctan.
+
+use crate::{app::BHDiver, diver::Diver, math::tortoise, scene::Spacetime};
+use std::f64::consts::PI;
+
+/// Steps the worldline is sampled at
+const SAMPLES: usize = 1000;
+
+/// Proper time the worldline is followed for past the current time when the diver never falls in
+const ENDLESS_DURATION: f64 = 200_f64;
+
+const MARKER_SIZE: f32 = 4_f32;
+
+/// Coordinates the diagram is drawn in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DiagramKind {
+    // compactified so that infinity and the whole worldline fit
+    #[default]
+    Penrose,
+    // Kruskal-Szekeres coordinates where light moves at 45°
+    Kruskal,
+}
+
+impl ToString for DiagramKind {
+    fn to_string(&self) -> String {
+        match self {
+            DiagramKind::Penrose => "Penrose".to_owned(),
+            DiagramKind::Kruskal => "Kruskal".to_owned(),
+        }
+    }
+}
+
+impl DiagramKind {
+    /// returns the space and time coordinates of the diagram at the Kruskal coordinates U and V
+    fn coordinates(&self, u: f64, v: f64) -> (f64, f64) {
+        match self {
+            DiagramKind::Penrose => (v.atan() - u.atan(), v.atan() + u.atan()),
+            DiagramKind::Kruskal => ((v - u) / 2_f64, (v + u) / 2_f64),
+        }
+    }
+}
+
+/// Returns the proper time and the Kruskal coordinates U and V along the worldline of the diver
+/// from the start, or from its time if that is earlier, and the index of the sample at its time
+///
+/// the coordinate time is zero at the current time so the diver is near the middle of the diagram
+fn worldline(diver: &Diver) -> (Vec<(f64, f64, f64)>, usize) {
+    let start = diver.time().min(0_f64);
+    let end = if diver.final_time().is_finite() {
+        diver.final_time()
+    } else {
+        diver.time().max(0_f64) + ENDLESS_DURATION
+    };
+
+    let mut times: Vec<f64> = (0..=SAMPLES)
+        .map(|i| start + (end - start) * i as f64 / SAMPLES as f64)
+        .filter(|time| *time != diver.time())
+        .collect();
+    let current = times.partition_point(|time| *time < diver.time());
+    times.insert(current, diver.time());
+
+    let mut sample = *diver;
+    let mut last_rate = 0_f64;
+    let samples: Vec<(f64, f64, f64)> = times
+        .into_iter()
+        .map(|time| {
+            sample.set_time(time);
+            let r = sample.position();
+            let rate = sample.advanced_time_rate();
+
+            // the rate is infinite at the singularity where the worldline ends anyway
+            if rate.is_finite() {
+                last_rate = rate;
+            }
+            (time, r, last_rate)
+        })
+        .collect();
+
+    // integrate the advanced time with the trapezoid rule
+    let mut advanced_times = vec![0_f64; samples.len()];
+    for i in 1..samples.len() {
+        advanced_times[i] = advanced_times[i - 1]
+            + (samples[i].0 - samples[i - 1].0) * (samples[i].2 + samples[i - 1].2) / 2_f64;
+    }
+
+    // t = v - r* is zero at the current time
+    let offset = tortoise(samples[current].1) - advanced_times[current];
+    let offset = if offset.is_finite() { offset } else { 0_f64 };
+
+    let points = samples
+        .into_iter()
+        .zip(advanced_times)
+        .map(|((time, r, _), advanced_time)| {
+            let v = ((advanced_time + offset) / 4_f64).exp();
+            let u = -(r / 2_f64 - 1_f64) * (r / 2_f64).exp() / v;
+            (time, u, v)
+        })
+        .collect();
+    (points, current)
+}
+
+pub struct SpacetimeDiagram;
+
+impl SpacetimeDiagram {
+    pub fn build(ui: &mut egui::Ui, app: &mut BHDiver) {
+        // the kind of diagram is kept in temporary ui memory
+        let kind_id = egui::Id::new("spacetime diagram kind");
+        let mut kind: DiagramKind =
+            ui.data_mut(|reader| reader.get_temp(kind_id).unwrap_or_default());
+
+        ui.horizontal(|ui| {
+            for option in [DiagramKind::Penrose, DiagramKind::Kruskal] {
+                ui.selectable_value(&mut kind, option, option.to_string());
+            }
+        });
+
+        ui.data_mut(|reader| reader.insert_temp(kind_id, kind));
+
+        let scene = app.timeline.get_current_scene();
+        if scene.spacetime == Spacetime::Wormhole {
+            ui.label(
+                "The diver hovers outside the wormhole, the diagram is only drawn for black holes",
+            );
+            return;
+        }
+        if scene.active_spin() != 0_f64 || scene.active_charge() != 0_f64 {
+            ui.weak("Drawn for a black hole without spin or charge");
+        }
+
+        Self::show_diagram(ui, kind, &scene.scaled_diver());
+    }
+
+    fn show_diagram(ui: &mut egui::Ui, kind: DiagramKind, diver: &Diver) {
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), ui.available_height().max(200_f32)),
+            egui::Sense::hover(),
+        );
+        let rect = response.rect;
+        let painter = painter.with_clip_rect(rect);
+        painter.rect_filled(rect, egui::Rounding::none(), ui.visuals().extreme_bg_color);
+
+        let (worldline, current) = worldline(diver);
+        let worldline: Vec<_> = worldline
+            .into_iter()
+            .map(|(_, u, v)| kind.coordinates(u, v))
+            .collect();
+        let marker = worldline[current];
+
+        // half the width and height of the diagram in its coordinates
+        let (half_width, half_height) = match kind {
+            DiagramKind::Penrose => (PI, PI / 2_f64),
+            DiagramKind::Kruskal => {
+                // around the diver, the worldline runs off exponentially far
+                let extent = (1.5 * marker.0.abs().max(marker.1.abs())).max(2_f64);
+                (extent, extent)
+            }
+        };
+
+        // keep the coordinates square so that light moves at 45°
+        let scale = (rect.width() as f64 / (2_f64 * half_width))
+            .min(rect.height() as f64 / (2_f64 * half_height));
+        let to_screen =
+            |x: f64, t: f64| rect.center() + egui::vec2((x * scale) as f32, -(t * scale) as f32);
+
+        let font = egui::TextStyle::Small.resolve(ui.style());
+        let guide = egui::Stroke::new(1_f32, ui.visuals().weak_text_color());
+        let singularity = egui::Stroke::new(2_f32, egui::Color32::from_rgb(200, 60, 60));
+
+        // horizons
+        let horizon = match kind {
+            DiagramKind::Penrose => PI / 2_f64,
+            DiagramKind::Kruskal => half_width.min(half_height),
+        };
+        painter.line_segment(
+            [to_screen(-horizon, -horizon), to_screen(horizon, horizon)],
+            guide,
+        );
+        painter.line_segment(
+            [to_screen(-horizon, horizon), to_screen(horizon, -horizon)],
+            guide,
+        );
+        painter.text(
+            to_screen(horizon / 2_f64, horizon / 2_f64),
+            egui::Align2::LEFT_TOP,
+            "r = 2M",
+            font.clone(),
+            ui.visuals().weak_text_color(),
+        );
+
+        match kind {
+            DiagramKind::Penrose => {
+                // future and past singularities
+                for t in [PI / 2_f64, -PI / 2_f64] {
+                    painter.line_segment(
+                        [to_screen(-PI / 2_f64, t), to_screen(PI / 2_f64, t)],
+                        singularity,
+                    );
+                }
+
+                // null infinities bounding the outside of the black hole and the other universe
+                for side in [1_f64, -1_f64] {
+                    let corners = [
+                        to_screen(side * PI / 2_f64, PI / 2_f64),
+                        to_screen(side * PI, 0_f64),
+                        to_screen(side * PI / 2_f64, -PI / 2_f64),
+                    ];
+                    painter.line_segment([corners[0], corners[1]], guide);
+                    painter.line_segment([corners[1], corners[2]], guide);
+                }
+            }
+            DiagramKind::Kruskal => {
+                // the singularities are the hyperbolas T^2 - X^2 = 1
+                let hyperbola = |sign: f64| {
+                    (0..=100)
+                        .map(|i| {
+                            let x = half_width * (2_f64 * i as f64 / 100_f64 - 1_f64);
+                            let t = sign * (1_f64 + x.powi(2)).sqrt();
+                            to_screen(x, t.clamp(-half_height, half_height))
+                        })
+                        .collect()
+                };
+                painter.add(egui::Shape::line(hyperbola(1_f64), singularity));
+                painter.add(egui::Shape::line(hyperbola(-1_f64), singularity));
+            }
+        }
+        painter.text(
+            to_screen(
+                0_f64,
+                match kind {
+                    DiagramKind::Penrose => PI / 2_f64,
+                    DiagramKind::Kruskal => 1_f64,
+                },
+            ),
+            egui::Align2::CENTER_BOTTOM,
+            "r = 0",
+            font,
+            singularity.color,
+        );
+
+        // the worldline is dimmed after the current time
+        let points = |samples: &[(f64, f64)]| {
+            samples
+                .iter()
+                .filter(|(x, t)| x.is_finite() && t.is_finite())
+                .map(|&(x, t)| to_screen(x, t))
+                .collect::<Vec<_>>()
+        };
+        painter.add(egui::Shape::line(
+            points(&worldline[current..]),
+            egui::Stroke::new(1.5_f32, ui.visuals().weak_text_color()),
+        ));
+        painter.add(egui::Shape::line(
+            points(&worldline[..=current]),
+            egui::Stroke::new(2_f32, ui.visuals().strong_text_color()),
+        ));
+
+        painter.circle_filled(
+            to_screen(marker.0, marker.1),
+            MARKER_SIZE,
+            ui.visuals().selection.bg_fill,
+        );
+    }
+}
@@ -1,4 +1,11 @@
-use crate::math::{hits_black_hole_no_gr, n_mod_m, rain_angle_to_map_angle};
+use crate::{
+    geodesic::geodesic_rain_angle_to_map_angle,
+    math::{
+        hits_black_hole_no_gr, kerr_rain_angle_to_map_angle, metric_rain_angle_to_map_angle,
+        n_mod_m, rain_angle_to_map_angle, wormhole_view_angle_to_map_angle,
+    },
+    metric::Metric,
+};
 use nalgebra::Vector3;
 use std::f64::consts::PI;
 
@@ -48,10 +55,99 @@ impl SphericalAngle for RainAngle {
 
 impl RainAngle {
     pub fn to_map_angle(self, r: f64) -> Option<MapAngle> {
-        let angle = rain_angle_to_map_angle(self.theta, self.phi, r)?;
+        self.to_map_angle_with_orbit_limit(r, u32::MAX)
+    }
+
+    /// returns the map angle, or None if the photon hits the black hole or loops around it more
+    /// than `max_half_orbits` half orbits
+    pub fn to_map_angle_with_orbit_limit(self, r: f64, max_half_orbits: u32) -> Option<MapAngle> {
+        let angle = rain_angle_to_map_angle(self.theta, self.phi, r, max_half_orbits)?;
         Some(MapAngle::new(angle.0, angle.1))
     }
 
+    /// returns the angle seen by an observer moving towards theta = 0 at the speed relative to the
+    /// observer seeing this angle, a negative speed undoes the aberration
+    pub fn aberrate(self, speed: f64) -> RainAngle {
+        let cos_theta = (self.theta.cos() + speed) / (1_f64 + speed * self.theta.cos());
+        RainAngle::new(cos_theta.clamp(-1_f64, 1_f64).acos(), self.phi)
+    }
+
+    /// returns the rain angle of this view angle of an observer moving through the rain frame at
+    /// the velocity and the ratio of the frequency the observer sees to the one in the rain frame
+    pub fn from_moving_frame(self, velocity: Vector3<f64>) -> (RainAngle, f64) {
+        let speed = velocity.norm();
+        let gamma = 1_f64 / (1_f64 - speed.powi(2)).sqrt();
+        let direction = velocity / speed;
+
+        // boost the momentum of the photon, which travels opposite to the view
+        let view = self.to_vector();
+        let momentum =
+            -view - (gamma - 1_f64) * view.dot(&direction) * direction + gamma * velocity;
+        let energy = gamma * (1_f64 - velocity.dot(&view));
+
+        let rain_view = -momentum / energy;
+        (
+            RainAngle::from_vector(rain_view),
+            gamma * (1_f64 + velocity.dot(&rain_view)),
+        )
+    }
+
+    /// returns the map angle around a black hole with the spin a and the order of the image it is
+    /// part of, or None like `to_map_angle_with_orbit_limit`
+    pub fn to_map_angle_kerr(
+        self,
+        r: f64,
+        a: f64,
+        max_half_orbits: u32,
+    ) -> Option<(MapAngle, u32)> {
+        let angle = if a == 0_f64 {
+            rain_angle_to_map_angle(self.theta, self.phi, r, max_half_orbits)?
+        } else {
+            kerr_rain_angle_to_map_angle(self.theta, self.phi, r, a, max_half_orbits)?
+        };
+        Some((MapAngle::new(angle.0, angle.1), angle.2))
+    }
+
+    /// returns the map angle around the black hole with this metric and the order of the image
+    /// it is part of, or None if the photon hits the black hole or loops around it more than
+    /// `max_half_orbits` half orbits
+    pub fn to_map_angle_metric(
+        self,
+        r: f64,
+        metric: &dyn Metric,
+        max_half_orbits: u32,
+    ) -> Option<(MapAngle, u32)> {
+        let angle =
+            metric_rain_angle_to_map_angle(self.theta, self.phi, r, metric, max_half_orbits)?;
+        Some((MapAngle::new(angle.0, angle.1), angle.2))
+    }
+
+    /// returns the map angle around the black hole with this metric and the order of the image
+    /// like `to_map_angle_metric` by integrating the geodesic numerically
+    pub fn to_map_angle_geodesic(
+        self,
+        r: f64,
+        metric: &dyn Metric,
+        max_half_orbits: u32,
+    ) -> Option<(MapAngle, u32)> {
+        let angle =
+            geodesic_rain_angle_to_map_angle(self.theta, self.phi, r, metric, max_half_orbits)?;
+        Some((MapAngle::new(angle.0, angle.1), angle.2))
+    }
+
+    /// returns the map angle seen at this view angle by a static observer at the proper distance
+    /// l from the throat of a wormhole with the radius b0, the order of the image and whether it
+    /// is on the other side of the wormhole
+    pub fn to_map_angle_wormhole(
+        self,
+        l: f64,
+        b0: f64,
+        max_half_orbits: u32,
+    ) -> Option<(MapAngle, u32, bool)> {
+        let angle = wormhole_view_angle_to_map_angle(self.theta, self.phi, l, b0, max_half_orbits)?;
+        Some((MapAngle::new(angle.0, angle.1), angle.2, angle.3))
+    }
+
     pub fn try_to_map_angle_no_gr(self, r: f64) -> Option<MapAngle> {
         if hits_black_hole_no_gr(self.theta, r) {
             return None;
@@ -61,7 +157,7 @@ impl RainAngle {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct MapAngle {
     pub theta: f64,
     pub phi: f64,
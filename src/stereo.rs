@@ -0,0 +1,97 @@
+// Renders stereo 360 images for VR headsets, the view of each eye stacked with the left eye on top
+//
+// Each eye sits half the eye separation to the side of the diver along the right of the camera. The
+// scene is lensed as if the black hole were straight ahead of the diver, so each eye is rendered
+// by turning the scene until the black hole is straight ahead of the eye, which shifts the black
+// hole across the sky of the two eyes by the parallax between them, and turning the sky it lenses
+// back so that the distant stars stay put. Moving the eye also changes its distance from the black
+// hole, but only by the square of the separation over the distance, which is left out, and each
+// eye falls straight at the black hole from where it is instead of alongside the diver.
+//
+// Omni-directional stereo would offset the eyes across the direction of every column, which needs
+// a scene lensed from different eyes for every column. Instead the eyes are offset along the right
+// of the camera for the whole panorama, so the depth is only right in the middle of the image
+// where the camera faces. It fades out towards the sides, where the eyes are one behind the other,
+// and behind the camera the eyes would be swapped and the depth inside out. So stereo only covers
+// the front hemisphere, behind the camera both eyes see the view of the left eye without depth.
+
+use crate::{
+    render::RenderSettings,
+    scene::{Scene, Spacetime},
+};
+use image::{GenericImage, ImageBuffer, Pixel};
+use nalgebra::{Rotation3, Vector2, Vector3};
+use std::f64::consts::PI;
+
+/// Eye separation new stereo renders start with, in the length unit of the scene
+pub const DEFAULT_EYE_SEPARATION: f64 = 0.5;
+
+/// Returns the scenes seen by the left and right eye of the diver `eye_separation` apart
+pub fn eye_scenes(scene: &Scene, eye_separation: f64) -> [Scene; 2] {
+    [(0, -0.5_f64), (1, 0.5)].map(|(eye, side)| {
+        let mut eye_scene = eye_scene(scene, side * eye_separation);
+        // the passes stack the eyes like the render
+        eye_scene.pass_recorder = scene
+            .pass_recorder
+            .as_ref()
+            .map(|recorder| recorder.for_eye(eye));
+        eye_scene
+    })
+}
+
+/// Returns the scene seen from `offset` to the right of the diver
+fn eye_scene(scene: &Scene, offset: f64) -> Scene {
+    let distance = match scene.spacetime {
+        Spacetime::BlackHole => scene.diver_radius(),
+        Spacetime::Wormhole => scene.wormhole_distance(),
+    };
+
+    // the black hole is straight ahead along z from the diver
+    let to_black_hole = Vector3::z() * distance - scene.camera.right() * offset;
+    let rotation = Rotation3::rotation_between(&to_black_hole, &Vector3::z())
+        .unwrap_or_else(Rotation3::identity);
+
+    let mut eye = scene.clone();
+    eye.camera.rotate(rotation);
+    eye.eye_rotation = Some(rotation.inverse());
+    eye
+}
+
+/// Returns whether the column of a 360° image with the resolution looks behind the camera, where
+/// the eyes are swapped
+pub fn looks_behind(x: u32, resolution: Vector2<u32>) -> bool {
+    // the camera faces the middle column
+    (PI * x as f64 / resolution.y as f64).cos() > 0_f64
+}
+
+/// Returns the images of the two eyes of the region of the render stacked with the first on top,
+/// with the view of the first in the columns that look behind the camera of both
+pub fn stack_eyes<P: Pixel>(
+    [top, mut bottom]: [ImageBuffer<P, Vec<P::Subpixel>>; 2],
+    render_settings: RenderSettings,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (offset, _) = render_settings.region();
+    for x in 0..top.width() {
+        if looks_behind(offset.x + x, render_settings.resolution) {
+            for y in 0..top.height() {
+                bottom.put_pixel(x, y, *top.get_pixel(x, y));
+            }
+        }
+    }
+
+    let mut stacked = ImageBuffer::new(top.width(), top.height() + bottom.height());
+    // the images of both eyes are the same size so they fit
+    stacked.copy_from(&top, 0, 0).unwrap();
+    stacked.copy_from(&bottom, 0, top.height()).unwrap();
+    stacked
+}
+
+/// Renders the image of each eye with `render` and stacks them, None if either render is None
+pub fn render_eyes<T, P: Pixel>(
+    eyes: [T; 2],
+    render_settings: RenderSettings,
+    render: impl Fn(T) -> Option<ImageBuffer<P, Vec<P::Subpixel>>>,
+) -> Option<ImageBuffer<P, Vec<P::Subpixel>>> {
+    let [left, right] = eyes;
+    Some(stack_eyes([render(left)?, render(right)?], render_settings))
+}
@@ -0,0 +1,88 @@
+// Records how the black hole affects every frame of an animation render for analysing it later
+//
+// The pass recorder measures the frames from the rays the render traces through the center of each
+// pixel of its region. The captured fraction counts the pixels whose rays don't reach the sky, they
+// fall into the black hole, loop around it more than the max half orbits or hit an object in front
+// of it. The deflection is averaged over the escaping rays like the deflection diagnostic, and the
+// redshift z = 1 / ratio - 1 of their frequency ratio is positive for redshifted light and negative
+// for blueshifted light. Values that need escaping rays are null when none escape.
+//
+// The JSON file is rewritten after every frame so interrupted renders keep what they have.
+
+use crate::render::sidecar_path;
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Returns the path of the telemetry written alongside a frame sequence, `name.png` becomes
+/// `name.telemetry.json`
+pub fn telemetry_path(output_path: &Path) -> PathBuf {
+    sidecar_path(output_path, "telemetry", "json")
+}
+
+/// Statistics of the rays of a single frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameTelemetry {
+    pub frame: i32,
+    // fraction of the pixels whose rays don't reach the sky
+    pub captured_fraction: f64,
+    // mean deflection of the escaping rays in radians
+    pub mean_deflection: Option<f64>,
+    // smallest and largest redshift of the escaping rays
+    pub redshift_range: Option<(f64, f64)>,
+}
+
+impl FrameTelemetry {
+    fn json(&self) -> String {
+        let number =
+            |value: Option<f64>| value.map_or("null".to_owned(), |value| value.to_string());
+        format!(
+            "{{\"frame\": {}, \"captured_fraction\": {}, \"mean_deflection\": {}, \"min_redshift\": {}, \"max_redshift\": {}}}",
+            self.frame,
+            self.captured_fraction,
+            number(self.mean_deflection),
+            number(self.redshift_range.map(|(min, _)| min)),
+            number(self.redshift_range.map(|(_, max)| max)),
+        )
+    }
+}
+
+/// Telemetry being written while the frames render
+pub struct Telemetry {
+    path: PathBuf,
+    frames: Vec<FrameTelemetry>,
+}
+
+impl Telemetry {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            frames: Vec::new(),
+        }
+    }
+
+    /// adds the measured frame and rewrites the file with it
+    pub fn add_frame(&mut self, frame: FrameTelemetry) -> io::Result<()> {
+        self.frames.push(frame);
+        self.write()
+    }
+
+    fn write(&self) -> io::Result<()> {
+        let mut json = String::new();
+
+        json.push_str("{\n");
+        let _ = writeln!(json, "  \"deflection_unit\": \"radians\",");
+        json.push_str("  \"frames\": [");
+        for (i, frame) in self.frames.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, "\n    {}", frame.json());
+        }
+        json.push_str("\n  ]\n}\n");
+
+        fs::write(&self.path, json)
+    }
+}
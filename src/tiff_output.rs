@@ -0,0 +1,161 @@
+// Streams renders to TIFF files a strip at a time so very large images never have to fit in memory
+
+use crate::{progress::RenderProgress, render::RenderSettings, scene::Scene};
+use image::Rgb32FImage;
+use nalgebra::Vector2;
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+};
+use tiff::{
+    encoder::{
+        colortype::{ColorType, RGB16, RGB8},
+        TiffEncoder, TiffValue,
+    },
+    TiffResult,
+};
+
+/// Number of image rows rendered and written at a time
+const STRIP_ROWS: u32 = 64;
+
+/// Extra rows rendered on either side of a super sampled strip so that downscaling it gives the
+/// same pixels as downscaling the whole image
+pub const SUPER_SAMPLING_MARGIN: u32 = 4;
+
+/// Returns whether the path has a TIFF extension
+pub fn is_tiff_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("tif") || extension.eq_ignore_ascii_case("tiff")
+        })
+}
+
+/// Renders the scene straight to a TIFF file one strip of rows at a time, with 16 bits per
+/// channel if the render settings ask for it
+///
+/// the whole image is never held in memory, peak memory is bounded by one strip of `STRIP_ROWS`
+/// rows in floating point, times the super sampling squared with its margins, and the environment
+/// whatever the height of the image
+///
+/// each strip adds a step to the progress, cancelling it stops the render and deletes the
+/// unfinished file
+pub fn render_to_tiff(
+    scene: &Scene,
+    render_settings: RenderSettings,
+    path: &Path,
+    progress: &RenderProgress,
+) -> TiffResult<()> {
+    let tiff = TiffEncoder::new(BufWriter::new(File::create(path)?))?;
+    let written = if render_settings.sixteen_bit {
+        write_strips::<RGB16>(tiff, scene, render_settings, progress, |strip| {
+            render_settings.quantize_16(strip).into_raw()
+        })
+    } else {
+        write_strips::<RGB8>(tiff, scene, render_settings, progress, |strip| {
+            render_settings.quantize(strip).into_raw()
+        })
+    };
+
+    // the strips written before the render was cancelled aren't a readable TIFF
+    if progress.is_cancelled() {
+        fs::remove_file(path)?;
+    }
+    written
+}
+
+/// renders the strips of the image and writes them with the color type after quantizing them
+fn write_strips<C: ColorType>(
+    mut tiff: TiffEncoder<BufWriter<File>>,
+    scene: &Scene,
+    render_settings: RenderSettings,
+    progress: &RenderProgress,
+    quantize: impl Fn(&Rgb32FImage) -> Vec<C::Inner>,
+) -> TiffResult<()>
+where
+    [C::Inner]: TiffValue,
+{
+    // build the table of the lensing once instead of for every strip
+    let lut_scene = scene.with_deflection_lut(render_settings);
+    let scene = lut_scene.as_ref().unwrap_or(scene);
+
+    let resolution = render_settings.resolution;
+    let (offset, size) = render_settings.region();
+    let margin = if render_settings.super_sampling.is_some() {
+        SUPER_SAMPLING_MARGIN
+    } else {
+        0
+    };
+
+    let mut image = tiff.new_image::<C>(size.x, size.y)?;
+    image.rows_per_strip(STRIP_ROWS)?;
+
+    let strips = (offset.y..offset.y + size.y).step_by(STRIP_ROWS as usize);
+    progress.add_total(strips.len());
+
+    for strip_start in strips {
+        let strip_end = (strip_start + STRIP_ROWS).min(offset.y + size.y);
+
+        // render the strip with its margins and crop them off again
+        let render_start = strip_start.saturating_sub(margin);
+        let render_end = (strip_end + margin).min(resolution.y);
+        let Some(strip) = scene.render_region_float(
+            render_settings,
+            Vector2::new(offset.x, render_start),
+            Vector2::new(size.x, render_end - render_start),
+            &progress.subtask(),
+        ) else {
+            // cancelled, the caller deletes the file
+            return Ok(());
+        };
+        let strip = image::imageops::crop_imm(
+            &strip,
+            0,
+            strip_start - render_start,
+            size.x,
+            strip_end - strip_start,
+        )
+        .to_image();
+
+        image.write_strip(&quantize(&strip))?;
+        progress.advance(1);
+    }
+
+    image.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_read_back_exactly() {
+        let scene = Scene::test_pattern();
+        // three strips, the last one short
+        let render_settings = RenderSettings::new(
+            Default::default(),
+            Vector2::new(48, 2 * STRIP_ROWS + 22),
+            None,
+        );
+
+        let path = std::env::temp_dir().join("bh_diver_strips_test.tif");
+        render_to_tiff(&scene, render_settings, &path, &RenderProgress::new()).unwrap();
+        let written = image::open(&path).unwrap().into_rgb8();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(written, scene.render(render_settings));
+    }
+
+    #[test]
+    fn cancelled_render_leaves_no_file() {
+        let render_settings =
+            RenderSettings::new(Default::default(), Vector2::new(16, STRIP_ROWS + 1), None);
+        let progress = RenderProgress::new();
+        progress.cancel();
+
+        let path = std::env::temp_dir().join("bh_diver_cancelled_test.tif");
+        render_to_tiff(&Scene::test_pattern(), render_settings, &path, &progress).unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,132 @@
+// Renders still frames a tile at a time so the finished tiles can be shown while the rest render
+//
+// Tiles are handed to the thread pool in scanline order, top left to bottom right, so the image
+// fills in from the top. Super sampled tiles are rendered with a margin of extra pixels on every
+// side and cropped after downscaling, so the tiles join up like the rows of a TIFF render.
+
+use crate::{
+    progress::RenderProgress, render::RenderSettings, scene::Scene,
+    tiff_output::SUPER_SAMPLING_MARGIN,
+};
+use image::{GenericImage, Rgb32FImage, RgbImage};
+use nalgebra::Vector2;
+use rayon::prelude::{ParallelBridge, ParallelIterator};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+/// Width and height of a tile in pixels
+const TILE_SIZE: u32 = 64;
+
+/// Image that the tiles of a render are copied into as they finish, shared with the UI
+#[derive(Clone, Default)]
+pub struct LiveRender {
+    image: Arc<Mutex<Option<RgbImage>>>,
+    // counts the tiles copied in so the UI only uploads the image again when it changed
+    version: Arc<AtomicUsize>,
+}
+
+impl LiveRender {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// clears the image to black at the resolution
+    fn start(&self, resolution: Vector2<u32>) {
+        *self.image.lock().unwrap() = Some(RgbImage::new(resolution.x, resolution.y));
+        self.version.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// copies the finished tile into the image with its top left pixel at `offset`
+    fn add_tile(&self, offset: Vector2<u32>, tile: &RgbImage) {
+        if let Some(image) = self.image.lock().unwrap().as_mut() {
+            let _ = image.copy_from(tile, offset.x, offset.y);
+        }
+        self.version.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// returns the number of changes made to the image so far
+    pub fn version(&self) -> usize {
+        self.version.load(Ordering::Relaxed)
+    }
+
+    /// calls f with the image if a render has started
+    pub fn with_image<T>(&self, f: impl FnOnce(&RgbImage) -> T) -> Option<T> {
+        self.image.lock().unwrap().as_ref().map(f)
+    }
+}
+
+/// Renders the scene tile by tile copying each into the live render as it finishes
+///
+/// only the tiles of the crop are rendered if the settings have one, they are shown in place in the
+/// live render and the crop is returned. Returns the gamma encoded pixels in floating point like
+/// `Scene::render_region_float`, each tile adds a step to the progress, returns None if the render
+/// was cancelled
+pub fn render_tiled(
+    scene: &Scene,
+    render_settings: RenderSettings,
+    progress: &RenderProgress,
+    live_render: &LiveRender,
+) -> Option<Rgb32FImage> {
+    // build the table of the lensing once instead of for every tile
+    let lut_scene = scene.with_deflection_lut(render_settings);
+    let scene = lut_scene.as_ref().unwrap_or(scene);
+
+    let resolution = render_settings.resolution;
+    let (offset, size) = render_settings.region();
+    let region_end = offset + size;
+    let margin = if render_settings.super_sampling.is_some() {
+        SUPER_SAMPLING_MARGIN
+    } else {
+        0
+    };
+
+    let tiles: Vec<Vector2<u32>> = (offset.y..region_end.y)
+        .step_by(TILE_SIZE as usize)
+        .flat_map(|y| {
+            (offset.x..region_end.x)
+                .step_by(TILE_SIZE as usize)
+                .map(move |x| Vector2::new(x, y))
+        })
+        .collect();
+
+    live_render.start(resolution);
+    progress.add_total(tiles.len());
+    let image = Mutex::new(Rgb32FImage::new(size.x, size.y));
+
+    tiles.into_iter().par_bridge().for_each(|start| {
+        if progress.is_cancelled() {
+            return;
+        }
+
+        // render the tile with its margins and crop them off again
+        let end = (start + Vector2::repeat(TILE_SIZE)).inf(&region_end);
+        let render_start = start.map(|value| value.saturating_sub(margin));
+        let render_end = (end + Vector2::repeat(margin)).inf(&resolution);
+        let Some(tile) = scene.render_region_float(
+            render_settings,
+            render_start,
+            render_end - render_start,
+            &progress.subtask(),
+        ) else {
+            return;
+        };
+        let size = end - start;
+        let inset = start - render_start;
+        let tile = image::imageops::crop_imm(&tile, inset.x, inset.y, size.x, size.y).to_image();
+
+        let _ = image
+            .lock()
+            .unwrap()
+            .copy_from(&tile, start.x - offset.x, start.y - offset.y);
+        live_render.add_tile(start, &render_settings.quantize(&tile));
+        progress.advance(1);
+    });
+
+    if progress.is_cancelled() {
+        return None;
+    }
+
+    Some(image.into_inner().unwrap())
+}
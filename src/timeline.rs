@@ -1,54 +1,99 @@
 use crate::{
     animation::{Animation, Frame},
+    keyframe::{segment_value, AnimatedField, DiverDrive, Keyframe},
+    keymap::{Action, Keymap},
     math_utils::first_digit,
     scene::Scene,
     traits::Interpolate,
 };
 use std::collections::BTreeMap;
 
+/// How the frames of the timeline are spaced along the fall of the diver
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSpacing {
+    // the diver follows the drive and handles of every keyframe
+    #[default]
+    Keyframes,
+    // frames are equal intervals of the proper time of the diver from the first keyframe to the
+    // last, the keyframes in between still animate everything but the diver
+    ProperTime,
+}
+
+impl FrameSpacing {
+    pub const ALL: [FrameSpacing; 2] = [FrameSpacing::Keyframes, FrameSpacing::ProperTime];
+}
+
+impl ToString for FrameSpacing {
+    fn to_string(&self) -> String {
+        match self {
+            FrameSpacing::Keyframes => "Keyframes".to_owned(),
+            FrameSpacing::ProperTime => "Proper time".to_owned(),
+        }
+    }
+}
+
 pub struct Timeline {
     pub start_frame: i32,
     pub end_frame: i32,
     pub fps: f32,
     pub current_frame: i32,
+    pub frame_spacing: FrameSpacing,
     // frame and time on which the preview was started
     preview_start: Option<(i32, f64)>,
-    keyframes: BTreeMap<i32, Scene>,
+    keyframes: BTreeMap<i32, Keyframe>,
+    // frame range, frame spacing and keyframes when the project was last saved or created
+    saved: (i32, i32, FrameSpacing, BTreeMap<i32, Keyframe>),
 }
 
 impl Default for Timeline {
     fn default() -> Self {
-        let mut keyframes = BTreeMap::new();
-        keyframes.insert(1, Scene::default());
-
-        Self {
-            start_frame: 1,
-            end_frame: 120,
-            fps: 30_f32,
-            current_frame: 1,
-            preview_start: None,
-            keyframes,
-        }
+        Self::new(1, 120, 30_f32, Scene::default())
     }
 }
 
 impl Timeline {
-    pub fn new(start_frame: i32, end_frame: i32, fps: f32) -> Self {
+    pub fn new(start_frame: i32, end_frame: i32, fps: f32, scene: Scene) -> Self {
         let mut keyframes = BTreeMap::new();
 
-        // add default scene as current keyframe
-        keyframes.insert(start_frame, Scene::default());
+        // add the scene as the current keyframe
+        keyframes.insert(start_frame, Keyframe::new(scene));
 
         Self {
             start_frame,
             end_frame,
             fps,
             current_frame: start_frame,
+            frame_spacing: FrameSpacing::default(),
             preview_start: None,
+            saved: (
+                start_frame,
+                end_frame,
+                FrameSpacing::default(),
+                keyframes.clone(),
+            ),
             keyframes,
         }
     }
 
+    /// returns whether the frame range, frame spacing or keyframes changed since the project was
+    /// last saved
+    pub fn has_unsaved_changes(&self) -> bool {
+        let (start_frame, end_frame, frame_spacing, keyframes) = &self.saved;
+        *start_frame != self.start_frame
+            || *end_frame != self.end_frame
+            || *frame_spacing != self.frame_spacing
+            || *keyframes != self.keyframes
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = (
+            self.start_frame,
+            self.end_frame,
+            self.frame_spacing,
+            self.keyframes.clone(),
+        );
+    }
+
     pub fn start_preview(&mut self, ui: &egui::Ui) {
         self.current_frame = self.current_frame.clamp(self.start_frame, self.end_frame);
         if self.current_frame == self.end_frame {
@@ -63,30 +108,94 @@ impl Timeline {
         self.current_frame = self.current_frame.clamp(self.start_frame, self.end_frame);
     }
 
-    pub fn next_keyframe(&self, frame: i32) -> Option<(&i32, &Scene)> {
+    pub fn next_keyframe(&self, frame: i32) -> Option<(&i32, &Keyframe)> {
         self.keyframes.range(frame + 1..).next()
     }
 
-    pub fn previous_keyframe(&self, frame: i32) -> Option<(&i32, &Scene)> {
+    pub fn previous_keyframe(&self, frame: i32) -> Option<(&i32, &Keyframe)> {
         self.keyframes.range(..frame).last()
     }
 
+    pub fn keyframes(&self) -> &BTreeMap<i32, Keyframe> {
+        &self.keyframes
+    }
+
+    pub fn keyframe_mut(&mut self, frame: i32) -> Option<&mut Keyframe> {
+        self.keyframes.get_mut(&frame)
+    }
+
     pub fn get_scene(&self, frame: i32) -> Scene {
-        if let Some(scene) = self.keyframes.get(&frame) {
-            return Clone::clone(scene);
+        if let Some(keyframe) = self.keyframes.get(&frame) {
+            let mut scene = Clone::clone(&keyframe.scene);
+            self.space_frame(frame as f32, &mut scene);
+            return scene;
         }
 
-        let left = self.previous_keyframe(frame);
-        let right = self.next_keyframe(frame);
+        self.get_scene_at(frame as f32)
+    }
 
-        match (left, right) {
-            (Some(left), Some(right)) => left
-                .1
-                .interpolate(right.1, (frame - left.0) as f32 / (right.0 - left.0) as f32),
-            (None, Some(right)) => Clone::clone(right.1),
-            (Some(left), None) => Clone::clone(left.1),
-            (None, None) => unreachable!(),
+    /// moves the diver of the scene at the frame to its proper time when frames are spaced by
+    /// proper time, interpolated linearly from the first keyframe to the last
+    fn space_frame(&self, frame: f32, scene: &mut Scene) {
+        if self.frame_spacing != FrameSpacing::ProperTime {
+            return;
         }
+
+        let (&first_frame, first) = self.keyframes.first_key_value().unwrap();
+        let (&last_frame, last) = self.keyframes.last_key_value().unwrap();
+        if first_frame == last_frame {
+            return;
+        }
+
+        // frames outside of the keyframes hold the time of the nearest one
+        let t = ((frame as f64 - first_frame as f64) / (last_frame - first_frame) as f64)
+            .clamp(0_f64, 1_f64);
+        let first_time = AnimatedField::Time.get(&first.scene);
+        let last_time = AnimatedField::Time.get(&last.scene);
+        AnimatedField::Time.set(scene, first_time + (last_time - first_time) * t);
+    }
+
+    /// returns the scene at a possibly fractional frame
+    pub fn get_scene_at(&self, frame: f32) -> Scene {
+        let left = self.keyframes.range(..=frame.floor() as i32).last();
+        let right = self.keyframes.range(frame.ceil() as i32..).next();
+
+        let mut scene = match (left, right) {
+            (Some((&left_frame, left)), Some((&right_frame, _))) if left_frame == right_frame => {
+                Clone::clone(&left.scene)
+            }
+            (Some((&left_frame, left)), Some((&right_frame, right))) => {
+                let mut scene = left.scene.interpolate(
+                    &right.scene,
+                    (frame - left_frame as f32) / (right_frame - left_frame) as f32,
+                );
+
+                // follow the bezier curves of fields with handles, the radius of a diver driven by
+                // it is interpolated even without them
+                for field in AnimatedField::ALL {
+                    if !field.drives(left.diver_drive) {
+                        continue;
+                    }
+                    if left.handles(field).outgoing.is_some()
+                        || right.handles(field).incoming.is_some()
+                        || field == AnimatedField::Radius
+                    {
+                        field.set(
+                            &mut scene,
+                            segment_value(field, (left_frame, left), (right_frame, right), frame),
+                        );
+                    }
+                }
+
+                scene
+            }
+            (None, Some(right)) => Clone::clone(&right.1.scene),
+            (Some(left), None) => Clone::clone(&left.1.scene),
+            (None, None) => unreachable!(),
+        };
+
+        self.space_frame(frame, &mut scene);
+        scene
     }
 
     pub fn get_current_scene(&self) -> Scene {
@@ -101,8 +210,14 @@ impl Timeline {
         self.set_scene_if_different(self.current_frame, scene)
     }
 
+    /// sets the scene of the keyframe at this frame keeping its handles, or adds a new keyframe
     pub fn set_scene(&mut self, frame: i32, scene: Scene) {
-        self.keyframes.insert(frame, scene);
+        match self.keyframes.get_mut(&frame) {
+            Some(keyframe) => keyframe.scene = scene,
+            None => {
+                self.keyframes.insert(frame, Keyframe::new(scene));
+            }
+        }
     }
 
     pub fn set_scene_if_different(&mut self, frame: i32, scene: Scene) {
@@ -115,11 +230,58 @@ impl Timeline {
         Animation::new(
             (self.start_frame..=self.end_frame)
                 .into_iter()
-                .map(|i| Frame(i, self.get_scene(i)))
+                .map(|i| Frame(i, self.get_scene(i), Vec::new()))
+                .collect(),
+        )
+    }
+
+    /// returns the animation with the scenes of `samples` moments spread evenly over the time the
+    /// shutter is open around each frame for motion blur, the shutter angle is the fraction of the
+    /// frame it is open for in degrees
+    pub fn to_animation_with_motion_blur(&self, shutter_angle: f32, samples: usize) -> Animation {
+        if shutter_angle <= 0_f32 || samples < 2 {
+            return self.to_animation();
+        }
+
+        let open = shutter_angle / 360_f32;
+        Animation::new(
+            (self.start_frame..=self.end_frame)
+                .map(|i| {
+                    let shutter_scenes = (0..samples)
+                        .map(|sample| {
+                            let offset = ((sample as f32 + 0.5) / samples as f32 - 0.5) * open;
+                            self.get_scene_at(i as f32 + offset)
+                        })
+                        .collect();
+                    Frame(i, self.get_scene(i), shutter_scenes)
+                })
                 .collect(),
         )
     }
 
+    /// resamples the frame range at `speed` times the rate, replacing the keyframes with one
+    /// keyframe per new frame
+    ///
+    /// speeds below one give slow motion with scenes interpolated between the original frames
+    pub fn retime(&mut self, speed: f32) {
+        let length = (self.end_frame - self.start_frame) as f32;
+        let new_end_frame = self.start_frame + (length / speed).round() as i32;
+
+        self.keyframes = (self.start_frame..=new_end_frame)
+            .map(|frame| {
+                let source_frame =
+                    self.start_frame as f32 + (frame - self.start_frame) as f32 * speed;
+                (
+                    frame,
+                    Keyframe::new(self.get_scene_at(source_frame.min(self.end_frame as f32))),
+                )
+            })
+            .collect();
+
+        self.end_frame = new_end_frame;
+        self.current_frame = self.current_frame.clamp(self.start_frame, self.end_frame);
+    }
+
     pub fn delete_keyframe(&mut self, frame: i32) {
         // if this is the last keyframe then let clear keyframes handle it
         if self.keyframes.len() == 1 {
@@ -131,21 +293,21 @@ impl Timeline {
     }
 
     pub fn move_keyframe(&mut self, from_frame: i32, to_frame: i32) {
-        if let Some((_, scene)) = self.keyframes.remove_entry(&from_frame) {
-            self.keyframes.insert(to_frame, scene);
+        if let Some((_, keyframe)) = self.keyframes.remove_entry(&from_frame) {
+            self.keyframes.insert(to_frame, keyframe);
         }
     }
 
     pub fn clear_keyframes(&mut self) {
         let scene = self.get_current_scene();
         self.keyframes = BTreeMap::new();
-        self.keyframes.insert(self.start_frame, scene);
+        self.keyframes
+            .insert(self.start_frame, Keyframe::new(scene));
         self.current_frame = self.start_frame;
     }
 
     pub fn add_current_keyframe(&mut self) {
-        self.keyframes
-            .insert(self.current_frame, self.get_current_scene());
+        self.set_scene(self.current_frame, self.get_current_scene());
     }
 
     /// returns the left most frame to be drawn to the timeline
@@ -189,20 +351,26 @@ impl Timeline {
         }) * 10_i32.pow(target_step.log10().floor() as u32)
     }
 
-    fn show_timeline_controls(&mut self, ui: &mut egui::Ui) {
+    fn show_timeline_controls(&mut self, ui: &mut egui::Ui, keymap: &Keymap) {
+        let ctx = ui.ctx().clone();
+        let shortcut = |action| keymap.format(&ctx, action);
+
         ui.columns(3, |columns| {
             columns[0].horizontal(|ui| {
                 // preview buttons
                 if ui
                     .button("⏮")
-                    .on_hover_text("Jump to start (Shift + ⬅)")
+                    .on_hover_text(format!("Jump to start ({})", shortcut(Action::JumpToStart)))
                     .clicked()
                 {
                     self.current_frame = self.start_frame;
                 }
                 if ui
                     .button("⏪")
-                    .on_hover_text("Jump to previous keyframe (Ctrl + ⬅)")
+                    .on_hover_text(format!(
+                        "Jump to previous keyframe ({})",
+                        shortcut(Action::PreviousKeyframe)
+                    ))
                     .clicked()
                 {
                     if let Some((&frame, _)) = self.previous_keyframe(self.current_frame) {
@@ -210,17 +378,20 @@ impl Timeline {
                     }
                 }
                 if self.preview_start.is_some() {
-                    if ui.button("⏸").on_hover_text("Pause (Space)").clicked() {
+                    if ui.button("⏸").on_hover_text(format!("Pause ({})", shortcut(Action::TogglePlayback))).clicked() {
                         self.stop_preview();
                     }
                 } else {
-                    if ui.button("▶").on_hover_text("Play (Space)").clicked() {
+                    if ui.button("▶").on_hover_text(format!("Play ({})", shortcut(Action::TogglePlayback))).clicked() {
                         self.start_preview(&ui);
                     }
                 }
                 if ui
                     .button("⏩")
-                    .on_hover_text("Jump to next keyframe (Ctrl + ➡)")
+                    .on_hover_text(format!(
+                        "Jump to next keyframe ({})",
+                        shortcut(Action::NextKeyframe)
+                    ))
                     .clicked()
                 {
                     if let Some((&frame, _)) = self.next_keyframe(self.current_frame) {
@@ -229,7 +400,7 @@ impl Timeline {
                 }
                 if ui
                     .button("⏭")
-                    .on_hover_text("Jump to end (Shift + ➡)")
+                    .on_hover_text(format!("Jump to end ({})", shortcut(Action::JumpToEnd)))
                     .clicked()
                 {
                     self.current_frame = self.end_frame;
@@ -244,6 +415,71 @@ impl Timeline {
                 {
                     self.clear_keyframes();
                 }
+                if let Some(keyframe) = self.keyframes.get_mut(&self.current_frame) {
+                    egui::ComboBox::from_id_source("diver drive combo box")
+                        .selected_text(format!("Drive: {}", keyframe.diver_drive.to_string()))
+                        .show_ui(ui, |ui| {
+                            for diver_drive in [DiverDrive::Time, DiverDrive::Radius] {
+                                ui.selectable_value(
+                                    &mut keyframe.diver_drive,
+                                    diver_drive,
+                                    diver_drive.to_string(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Interpolate the time or the radius of the diver until the next \
+                            keyframe, driving by radius pins the radius at every keyframe and \
+                            solves for the time along the trajectory",
+                        );
+                }
+                egui::ComboBox::from_id_source("frame spacing combo box")
+                    .selected_text(format!("Spacing: {}", self.frame_spacing.to_string()))
+                    .show_ui(ui, |ui| {
+                        for frame_spacing in FrameSpacing::ALL {
+                            ui.selectable_value(
+                                &mut self.frame_spacing,
+                                frame_spacing,
+                                frame_spacing.to_string(),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "Move the diver by the drive of every keyframe, or space frames by equal \
+                        intervals of the proper time of the diver from the first keyframe to the \
+                        last so playback runs at the pace the diver experiences",
+                    );
+                ui.menu_button("Retime", |ui| {
+                    // the speed is kept in temporary ui memory
+                    let speed_id = egui::Id::new("retime speed");
+                    let mut speed: f32 =
+                        ui.data_mut(|reader| reader.get_temp(speed_id).unwrap_or(0.5));
+
+                    ui.horizontal(|ui| {
+                        ui.label("Speed");
+                        ui.add(
+                            egui::DragValue::new(&mut speed)
+                                .clamp_range(0.01..=100_f32)
+                                .speed(0.01),
+                        )
+                        .on_hover_text("Values below 1 slow the animation down");
+                    });
+
+                    if ui
+                        .button("Apply")
+                        .on_hover_text(
+                            "Resample the animation at the new rate, baking a keyframe on every frame",
+                        )
+                        .clicked()
+                    {
+                        self.retime(speed);
+                        ui.close_menu();
+                    }
+
+                    ui.data_mut(|reader| reader.insert_temp(speed_id, speed));
+                });
             });
             columns[1].vertical_centered(|ui| {
                 ui.add(egui::DragValue::new(&mut self.current_frame))
@@ -265,9 +501,9 @@ impl Timeline {
         });
     }
 
-    pub fn show(&mut self, ui: &mut egui::Ui) {
-        // preview start/stop with spacebar
-        if ui.input(|r| r.key_pressed(egui::Key::Space)) {
+    pub fn show(&mut self, ui: &mut egui::Ui, keymap: &Keymap) {
+        // preview start/stop
+        if keymap.consume(ui, Action::TogglePlayback) {
             if self.preview_start.is_some() {
                 self.stop_preview();
             } else {
@@ -275,25 +511,25 @@ impl Timeline {
             }
         }
 
-        // move current frame with arrow keys
-        if ui.input_mut(|r| r.consume_key(egui::Modifiers::SHIFT, egui::Key::ArrowRight)) {
+        // move current frame
+        if keymap.consume(ui, Action::JumpToEnd) {
             self.current_frame = self.end_frame
         }
-        if ui.input_mut(|r| r.consume_key(egui::Modifiers::SHIFT, egui::Key::ArrowLeft)) {
+        if keymap.consume(ui, Action::JumpToStart) {
             self.current_frame = self.start_frame
         }
-        if ui.input_mut(|r| r.consume_key(egui::Modifiers::NONE, egui::Key::ArrowRight)) {
+        if keymap.consume(ui, Action::NextFrame) {
             self.current_frame += 1;
         }
-        if ui.input_mut(|r| r.consume_key(egui::Modifiers::NONE, egui::Key::ArrowLeft)) {
+        if keymap.consume(ui, Action::PreviousFrame) {
             self.current_frame -= 1;
         }
-        if ui.input_mut(|r| r.consume_key(egui::Modifiers::COMMAND, egui::Key::ArrowRight)) {
+        if keymap.consume(ui, Action::NextKeyframe) {
             if let Some((&frame, _)) = self.next_keyframe(self.current_frame) {
                 self.current_frame = frame
             }
         }
-        if ui.input_mut(|r| r.consume_key(egui::Modifiers::COMMAND, egui::Key::ArrowLeft)) {
+        if keymap.consume(ui, Action::PreviousKeyframe) {
             if let Some((&frame, _)) = self.previous_keyframe(self.current_frame) {
                 self.current_frame = frame
             }
@@ -308,7 +544,7 @@ impl Timeline {
         }
 
         ui.add_space(ui.style().spacing.item_spacing.y);
-        self.show_timeline_controls(ui);
+        self.show_timeline_controls(ui, keymap);
 
         // allocate space for the timeline header
         let (timeline_rect, _) = ui.allocate_at_least(ui.available_size(), egui::Sense::hover());
@@ -353,6 +589,35 @@ impl Timeline {
             ui.visuals().faint_bg_color,
         );
 
+        // handles on the edges of the animation area for dragging the start and end frames
+        // returns the frame boundary the handle was dragged to
+        let range_handle = |ui: &mut egui::Ui, id: &str, xpos: f32| {
+            const HANDLE_WIDTH: f32 = 6_f32;
+            let handle_rect = egui::Rect::from_x_y_ranges(
+                xpos - HANDLE_WIDTH / 2_f32..=xpos + HANDLE_WIDTH / 2_f32,
+                body_rect.y_range(),
+            );
+            let handle_response = ui.interact(handle_rect, egui::Id::new(id), egui::Sense::drag());
+
+            if handle_response.hovered() || handle_response.dragged() {
+                ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeHorizontal);
+                ui.painter()
+                    .vline(xpos, body_rect.y_range(), ui.visuals().selection.stroke);
+            }
+
+            // snap to the nearest boundary between frames
+            let half_frame_width = (frame_to_xpos(1) - frame_to_xpos(0)) / 2_f32;
+            handle_response
+                .dragged()
+                .then(|| handle_response.interact_pointer_pos())
+                .flatten()
+                .map(|pos| xpos_to_frame(pos.x + half_frame_width))
+        };
+        let start_handle_frame =
+            range_handle(ui, "start frame handle", frame_to_xpos(self.start_frame));
+        let end_handle_frame =
+            range_handle(ui, "end frame handle", frame_to_xpos(self.end_frame + 1));
+
         // draw frame steps
         let frame_step = self.frame_step();
         ((self.left_most_frame() as f32 / frame_step as f32).floor() as i32 * frame_step
@@ -471,6 +736,68 @@ impl Timeline {
             self.move_keyframe(from_frame, to_frame);
         }
 
+        // move the animation range keeping start <= end
+        if let Some(frame) = start_handle_frame {
+            self.start_frame = frame.min(self.end_frame);
+        }
+        if let Some(frame) = end_handle_frame {
+            self.end_frame = (frame - 1).max(self.start_frame);
+        }
+
         ui.add_space(ui.style().spacing.item_spacing.y);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// returns the test pattern scene with the diver at the radius of the frame
+    fn scene_at(frame: i32) -> Scene {
+        let mut scene = Scene::test_pattern();
+        scene.set_diver_radius(radius_at(frame));
+        scene
+    }
+
+    fn radius_at(frame: i32) -> f64 {
+        10_f64 - 7_f64 * frame as f64 / 20_f64
+    }
+
+    /// timeline driving the diver by radius from frame 0 to frame 20
+    fn radius_driven() -> Timeline {
+        let mut timeline = Timeline::new(0, 20, 30_f32, scene_at(0));
+        timeline.set_scene(20, scene_at(20));
+        timeline.keyframe_mut(0).unwrap().diver_drive = DiverDrive::Radius;
+        timeline
+    }
+
+    #[test]
+    fn proper_time_spacing_is_even() {
+        // driving by radius spaces frames unevenly in proper time
+        let mut timeline = radius_driven();
+        timeline.frame_spacing = FrameSpacing::ProperTime;
+
+        let times: Vec<f64> = (0..=20)
+            .map(|frame| AnimatedField::Time.get(&timeline.get_scene(frame)))
+            .collect();
+        let step = (times[20] - times[0]) / 20_f64;
+        assert!(step > 0_f64);
+        for (frame, time) in times.iter().enumerate() {
+            let expected = times[0] + step * frame as f64;
+            assert!(
+                (time - expected).abs() < 1e-9,
+                "frame {frame}: {time} != {expected}"
+            );
+        }
+
+        // fractional frames follow the same spacing
+        let time = AnimatedField::Time.get(&timeline.get_scene_at(2.5));
+        assert!((time - (times[0] + step * 2.5)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn keyframe_spacing_follows_the_drive() {
+        let radius = radius_driven().get_scene(10).diver_radius();
+        assert!((radius - radius_at(10)).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,242 @@
+// Conversions between the internal G = c = M = 1 units and the units quantities are displayed in
+
+/// Gravitational radius GM/c^2 of one solar mass in kilometres
+const SOLAR_MASS_KM: f64 = 1.476625;
+
+/// Light crossing time GM/c^3 of one solar mass in milliseconds
+const SOLAR_MASS_MS: f64 = 4.925491e-3;
+
+/// Square of the speed of light in metres per second
+const SPEED_OF_LIGHT_SQUARED: f64 = 8.987552e16;
+
+/// Standard gravity in metres per second squared
+const STANDARD_GRAVITY: f64 = 9.80665;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnitConvention {
+    /// Geometric units of the black hole mass
+    #[default]
+    Mass,
+    /// Units of the Schwarzschild radius r_s = 2M
+    SchwarzschildRadius,
+    /// Kilometres and milliseconds for a black hole of a given mass
+    Physical,
+}
+
+impl ToString for UnitConvention {
+    fn to_string(&self) -> String {
+        match self {
+            UnitConvention::Mass => "M".to_owned(),
+            UnitConvention::SchwarzschildRadius => "Schwarzschild radii".to_owned(),
+            UnitConvention::Physical => "Physical".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Units {
+    pub convention: UnitConvention,
+    // black hole mass in solar masses used by the physical convention and physical readouts
+    pub solar_masses: f64,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self {
+            convention: UnitConvention::Mass,
+            solar_masses: 1_f64,
+        }
+    }
+}
+
+impl Units {
+    /// returns the number of display length units in one M
+    pub fn length_scale(&self) -> f64 {
+        match self.convention {
+            UnitConvention::Mass => 1_f64,
+            UnitConvention::SchwarzschildRadius => 0.5,
+            UnitConvention::Physical => SOLAR_MASS_KM * self.solar_masses,
+        }
+    }
+
+    /// returns the number of display time units in one M
+    pub fn time_scale(&self) -> f64 {
+        match self.convention {
+            UnitConvention::Mass => 1_f64,
+            UnitConvention::SchwarzschildRadius => 0.5,
+            UnitConvention::Physical => SOLAR_MASS_MS * self.solar_masses,
+        }
+    }
+
+    pub fn length_to_display(&self, length: f64) -> f64 {
+        length * self.length_scale()
+    }
+
+    pub fn length_from_display(&self, length: f64) -> f64 {
+        length / self.length_scale()
+    }
+
+    pub fn time_to_display(&self, time: f64) -> f64 {
+        time * self.time_scale()
+    }
+
+    pub fn time_from_display(&self, time: f64) -> f64 {
+        time / self.time_scale()
+    }
+
+    pub fn length_suffix(&self) -> &'static str {
+        match self.convention {
+            UnitConvention::Mass => " M",
+            UnitConvention::SchwarzschildRadius => " rₛ",
+            UnitConvention::Physical => " km",
+        }
+    }
+
+    pub fn time_suffix(&self) -> &'static str {
+        match self.convention {
+            UnitConvention::Mass => " M",
+            UnitConvention::SchwarzschildRadius => " rₛ/c",
+            UnitConvention::Physical => " ms",
+        }
+    }
+
+    /// returns the length in M as text in both geometric and physical units
+    pub fn describe_length(&self, length: f64) -> String {
+        format!(
+            "{:.3} M = {:.3} km",
+            length,
+            length * SOLAR_MASS_KM * self.solar_masses
+        )
+    }
+
+    /// returns the time in M as text in both geometric and physical units
+    pub fn describe_time(&self, time: f64) -> String {
+        format!(
+            "{:.3} M = {:.3} ms",
+            time,
+            time * SOLAR_MASS_MS * self.solar_masses
+        )
+    }
+
+    /// returns the acceleration in 1/M as text in both geometric units and standard gravities
+    pub fn describe_acceleration(&self, acceleration: f64) -> String {
+        let metres_per_second_squared =
+            acceleration * SPEED_OF_LIGHT_SQUARED / (SOLAR_MASS_KM * 1e3 * self.solar_masses);
+        format!(
+            "{:.3} /M = {:.3e} g",
+            acceleration,
+            metres_per_second_squared / STANDARD_GRAVITY
+        )
+    }
+
+    /// returns a drag value editing a length in M shown in display units
+    ///
+    /// `speed` is given in M per pixel
+    pub fn length_drag_value<'a>(&self, length: &'a mut f64, speed: f64) -> egui::DragValue<'a> {
+        let units = *self;
+        egui::DragValue::from_get_set(move |value| {
+            if let Some(value) = value {
+                *length = units.length_from_display(value);
+            }
+            units.length_to_display(*length)
+        })
+        .speed(speed * self.length_scale())
+        .suffix(self.length_suffix())
+    }
+
+    /// returns a drag value editing a time in M shown in display units
+    ///
+    /// `speed` is given in M per pixel
+    pub fn time_drag_value<'a>(&self, time: &'a mut f64, speed: f64) -> egui::DragValue<'a> {
+        let units = *self;
+        egui::DragValue::from_get_set(move |value| {
+            if let Some(value) = value {
+                *time = units.time_from_display(value);
+            }
+            units.time_to_display(*time)
+        })
+        .speed(speed * self.time_scale())
+        .suffix(self.time_suffix())
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("units_grid").num_columns(2).show(ui, |ui| {
+            ui.label("Convention");
+            egui::ComboBox::from_id_source("unit convention combo box")
+                .selected_text(self.convention.to_string())
+                .show_ui(ui, |ui| {
+                    for convention in [
+                        UnitConvention::Mass,
+                        UnitConvention::SchwarzschildRadius,
+                        UnitConvention::Physical,
+                    ] {
+                        ui.selectable_value(
+                            &mut self.convention,
+                            convention,
+                            convention.to_string(),
+                        );
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Black hole mass");
+            ui.add(
+                egui::DragValue::new(&mut self.solar_masses)
+                    .clamp_range(f64::MIN_POSITIVE..=f64::MAX)
+                    .speed(0.1)
+                    .suffix(" M☉"),
+            )
+            .on_hover_text(
+                "Mass the physical units are worked out for, everything scales with it so it \
+                doesn't change the view",
+            );
+            ui.end_row();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONVENTIONS: [UnitConvention; 3] = [
+        UnitConvention::Mass,
+        UnitConvention::SchwarzschildRadius,
+        UnitConvention::Physical,
+    ];
+
+    #[test]
+    fn round_trip() {
+        for convention in CONVENTIONS {
+            for solar_masses in [1_f64, 4.3e6, 0.01] {
+                let units = Units {
+                    convention,
+                    solar_masses,
+                };
+                for value in [0_f64, 2_f64, 6.5, -3_f64, 1e4] {
+                    let length = units.length_from_display(units.length_to_display(value));
+                    let time = units.time_from_display(units.time_to_display(value));
+                    assert!(
+                        (length - value).abs() <= 1e-12 * value.abs(),
+                        "{convention:?}"
+                    );
+                    assert!(
+                        (time - value).abs() <= 1e-12 * value.abs(),
+                        "{convention:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn schwarzschild_radius_is_two_mass() {
+        let units = Units {
+            convention: UnitConvention::SchwarzschildRadius,
+            ..Default::default()
+        };
+        assert_eq!(units.length_from_display(1_f64), 2_f64);
+        assert_eq!(units.length_to_display(2_f64), 1_f64);
+        assert_eq!(units.time_from_display(1_f64), 2_f64);
+    }
+}
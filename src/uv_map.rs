@@ -0,0 +1,61 @@
+// Exports the lensing as a UV remap texture so game engines can fake it by warping a skybox
+//
+// Encoding: a 16-bit two channel (luma + alpha) image at the render resolution. The first channel
+// is u = phi / 2π and the second is v = theta / π of the map angle the pixel's ray comes from,
+// i.e. the normalized coordinates of the equirectangular environment with (0, 0) at its top left.
+// Both are scaled from [0, 1] to [0, UV_MAX]. Rays that fall into the black hole are encoded as
+// (CAPTURED, CAPTURED), which no direction maps to.
+//
+// Each pixel is sampled once at its center, super sampling and grading are ignored since
+// averaging coordinates across the seam of the map would give wrong values.
+
+use crate::{
+    render::{sidecar_path, RenderSettings},
+    scene::Scene,
+    spherical_angle::SphericalAngle,
+};
+use image::{ImageBuffer, LumaA};
+use nalgebra::Vector2;
+use rayon::prelude::{ParallelBridge, ParallelIterator};
+use std::{f64::consts::PI, path::Path};
+
+/// Largest encoded coordinate, the value of u or v equal to 1
+pub const UV_MAX: u16 = u16::MAX - 1;
+
+/// Value of both channels for rays that fall into the black hole
+pub const CAPTURED: u16 = u16::MAX;
+
+pub type UvImage = ImageBuffer<LumaA<u16>, Vec<u16>>;
+
+/// Returns the path of the UV map exported alongside a render, `name.png` becomes `name.uv.png`
+pub fn uv_map_path(output_path: &Path) -> std::path::PathBuf {
+    sidecar_path(output_path, "uv", "png")
+}
+
+/// Renders the map coordinates that every pixel of the scene maps to
+pub fn render_uv_map(scene: &Scene, render_settings: RenderSettings) -> UvImage {
+    let resolution = render_settings.resolution;
+    let mut buf = UvImage::new(resolution.x, resolution.y);
+
+    let encode = |value: f64| (value.clamp(0_f64, 1_f64) * UV_MAX as f64).round() as u16;
+
+    buf.enumerate_pixels_mut()
+        .par_bridge()
+        .for_each(|(x, y, pixel)| {
+            let rain_angle = scene.pixel_to_view_angle(
+                render_settings.projection,
+                Vector2::new(x, y),
+                resolution,
+            );
+
+            *pixel = match scene.trace(rain_angle, render_settings) {
+                Some(map_angle) => LumaA([
+                    encode(map_angle.phi() / (2_f64 * PI)),
+                    encode(map_angle.theta() / PI),
+                ]),
+                None => LumaA([CAPTURED, CAPTURED]),
+            };
+        });
+
+    buf
+}
@@ -0,0 +1,77 @@
+// Encodes rendered animations straight to a video file by piping the frames to ffmpeg
+//
+// Frames are written to the standard input of an ffmpeg process as raw 8 bit RGB at the render
+// resolution. MP4 files are encoded with H.264 and WebM files with VP9, both in yuv420p so that
+// they play in browsers and media players. yuv420p needs an even width and height, so odd
+// resolutions are padded by a row or column of black. ffmpeg has to be on the PATH.
+
+use image::RgbImage;
+use nalgebra::Vector2;
+use std::{
+    io::{self, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+/// Returns whether the path has the extension of a video format animations can be encoded to
+pub fn is_video_path(path: &Path) -> bool {
+    video_codec(path).is_some()
+}
+
+/// Returns the ffmpeg encoder for the extension of the path
+fn video_codec(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?;
+    if extension.eq_ignore_ascii_case("mp4") {
+        Some("libx264")
+    } else if extension.eq_ignore_ascii_case("webm") {
+        Some("libvpx-vp9")
+    } else {
+        None
+    }
+}
+
+/// Running ffmpeg process that frames are encoded with
+pub struct VideoEncoder {
+    ffmpeg: Child,
+    stdin: ChildStdin,
+}
+
+impl VideoEncoder {
+    /// starts encoding frames of the resolution to the video at the path, overwriting it
+    pub fn spawn(path: &Path, resolution: Vector2<u32>, fps: f32) -> io::Result<Self> {
+        let codec = video_codec(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a video path"))?;
+
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{}x{}", resolution.x, resolution.y)])
+            .args(["-framerate", &fps.to_string(), "-i", "-"])
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+            .args(["-c:v", codec, "-pix_fmt", "yuv420p"])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let stdin = ffmpeg.stdin.take().unwrap();
+
+        Ok(Self { ffmpeg, stdin })
+    }
+
+    /// encodes the next frame, fails if ffmpeg has stopped
+    pub fn add_frame(&mut self, image: &RgbImage) -> io::Result<()> {
+        self.stdin.write_all(image.as_raw())
+    }
+
+    /// closes the input so ffmpeg finishes the file and waits for it
+    pub fn finish(self) -> io::Result<()> {
+        let Self { mut ffmpeg, stdin } = self;
+        drop(stdin);
+
+        let status = ffmpeg.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("ffmpeg exited with {}", status)))
+        }
+    }
+}
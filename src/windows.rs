@@ -1,4 +1,9 @@
-use crate::{app::BHDiver, settings::Settings};
+use crate::{
+    app::BHDiver, diagnostics::Diagnostics, einstein_ring::EinsteinRing,
+    embedding_view::EmbeddingView, graph_editor::GraphEditor, keymap::Keymap,
+    ray_inspector::RayInspector, render_view::RenderView, settings::Settings,
+    spacetime_diagram::SpacetimeDiagram, worldline_plot::WorldlinePlot,
+};
 
 macro_rules! unique_id {
     ($($args:tt)*) => {
@@ -6,13 +11,69 @@ macro_rules! unique_id {
     };
 }
 
-pub const ALL_WINDOWS: &[Window] = &[SETTINGS_WINDOW];
+pub const ALL_WINDOWS: &[Window] = &[
+    SETTINGS_WINDOW,
+    GRAPH_EDITOR_WINDOW,
+    DIAGNOSTICS_WINDOW,
+    SPACETIME_DIAGRAM_WINDOW,
+    WORLDLINE_PLOT_WINDOW,
+    EMBEDDING_WINDOW,
+    RAY_INSPECTOR_WINDOW,
+    EINSTEIN_RING_WINDOW,
+    RENDER_VIEW_WINDOW,
+    KEYBOARD_SHORTCUTS_WINDOW,
+];
 
 pub const SETTINGS_WINDOW: Window = Window {
     name: "Settings",
     build: Settings::build,
 };
 
+pub const GRAPH_EDITOR_WINDOW: Window = Window {
+    name: "Graph Editor",
+    build: GraphEditor::build,
+};
+
+pub const DIAGNOSTICS_WINDOW: Window = Window {
+    name: "Diagnostics",
+    build: Diagnostics::build,
+};
+
+pub const SPACETIME_DIAGRAM_WINDOW: Window = Window {
+    name: "Spacetime Diagram",
+    build: SpacetimeDiagram::build,
+};
+
+pub const WORLDLINE_PLOT_WINDOW: Window = Window {
+    name: "Worldline Plot",
+    build: WorldlinePlot::build,
+};
+
+pub const EMBEDDING_WINDOW: Window = Window {
+    name: "Embedding",
+    build: EmbeddingView::build,
+};
+
+pub const RAY_INSPECTOR_WINDOW: Window = Window {
+    name: "Ray Inspector",
+    build: RayInspector::build,
+};
+
+pub const EINSTEIN_RING_WINDOW: Window = Window {
+    name: "Einstein Ring",
+    build: EinsteinRing::build,
+};
+
+pub const RENDER_VIEW_WINDOW: Window = Window {
+    name: "Render View",
+    build: RenderView::build,
+};
+
+pub const KEYBOARD_SHORTCUTS_WINDOW: Window = Window {
+    name: "Keyboard Shortcuts",
+    build: Keymap::build,
+};
+
 #[allow(unused)]
 fn with_temp_data<F, T>(ui: &mut egui::Ui, f: F)
 where
@@ -0,0 +1,136 @@
+use crate::{app::BHDiver, scene::Spacetime, timeline::Timeline, units::Units};
+
+const POINT_SIZE: f32 = 4_f32;
+
+pub struct WorldlinePlot;
+
+impl WorldlinePlot {
+    pub fn build(ui: &mut egui::Ui, app: &mut BHDiver) {
+        ui.label("Distance of the diver from the black hole over the frames of the animation");
+        Self::show_plot(ui, &mut app.timeline, &app.settings.units);
+    }
+
+    fn show_plot(ui: &mut egui::Ui, timeline: &mut Timeline, units: &Units) {
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), ui.available_height().max(200_f32)),
+            egui::Sense::click_and_drag(),
+        );
+        let rect = response.rect;
+        painter.rect_filled(rect, egui::Rounding::none(), ui.visuals().extreme_bg_color);
+
+        let first_frame = timeline.left_most_frame();
+        let last_frame = timeline.right_most_frame();
+
+        // sample the radius and the horizon, which grows with the mass, at every frame
+        let samples: Vec<(i32, f64, Option<f64>)> = (first_frame..=last_frame)
+            .map(|frame| {
+                let scene = timeline.get_scene(frame);
+                match scene.spacetime {
+                    Spacetime::BlackHole => (frame, scene.diver_radius(), Some(2_f64 * scene.mass)),
+                    Spacetime::Wormhole => (frame, scene.wormhole_distance(), None),
+                }
+            })
+            .collect();
+
+        // radii from the singularity up to the furthest the diver gets
+        let max_value = samples
+            .iter()
+            .flat_map(|&(_, radius, horizon)| [Some(radius), horizon])
+            .flatten()
+            .filter(|value| value.is_finite())
+            .fold(0_f64, f64::max)
+            * 1.1;
+        let max_value = if max_value > 0_f64 { max_value } else { 1_f64 };
+
+        let frame_range = (last_frame - first_frame).max(1) as f32;
+
+        // transforms between (frame, radius) and egui coordinates
+        let to_screen = |frame: f32, value: f64| {
+            egui::pos2(
+                rect.left() + rect.width() * (frame - first_frame as f32) / frame_range,
+                rect.bottom() - rect.height() * (value / max_value) as f32,
+            )
+        };
+
+        // radius labels
+        let font = egui::TextStyle::Small.resolve(ui.style());
+        painter.text(
+            rect.left_top(),
+            egui::Align2::LEFT_TOP,
+            format!(
+                "{:.3}{}",
+                units.length_to_display(max_value),
+                units.length_suffix()
+            ),
+            font.clone(),
+            ui.visuals().weak_text_color(),
+        );
+        painter.text(
+            rect.left_bottom(),
+            egui::Align2::LEFT_BOTTOM,
+            format!("0{}", units.length_suffix()),
+            font.clone(),
+            ui.visuals().weak_text_color(),
+        );
+
+        // horizon
+        let horizon: Vec<_> = samples
+            .iter()
+            .filter_map(|&(frame, _, horizon)| {
+                horizon.map(|horizon| to_screen(frame as f32, horizon))
+            })
+            .collect();
+        if let Some(&last) = horizon.last() {
+            painter.text(
+                last,
+                egui::Align2::RIGHT_BOTTOM,
+                "Horizon",
+                font,
+                ui.visuals().weak_text_color(),
+            );
+            painter.add(egui::Shape::line(
+                horizon,
+                egui::Stroke::new(1_f32, ui.visuals().weak_text_color()),
+            ));
+        }
+
+        // radius
+        painter.add(egui::Shape::line(
+            samples
+                .iter()
+                .filter(|(_, radius, _)| radius.is_finite())
+                .map(|&(frame, radius, _)| to_screen(frame as f32, radius))
+                .collect(),
+            ui.visuals().widgets.active.fg_stroke,
+        ));
+
+        // keyframes
+        let selection = ui.visuals().selection;
+        for &(frame, radius, _) in samples.iter() {
+            if timeline.keyframes().contains_key(&frame) && radius.is_finite() {
+                painter.circle(
+                    to_screen(frame as f32, radius),
+                    POINT_SIZE,
+                    selection.bg_fill,
+                    selection.stroke,
+                );
+            }
+        }
+
+        // playhead
+        let mut play_head_stroke = ui.visuals().widgets.inactive.fg_stroke;
+        play_head_stroke.width = 1_f32;
+        painter.vline(
+            to_screen(timeline.current_frame as f32, 0_f64).x,
+            rect.y_range(),
+            play_head_stroke,
+        );
+
+        // clicking or dragging moves the playhead
+        if let Some(pos) = response.interact_pointer_pos() {
+            let frame = first_frame as f32 + frame_range * (pos.x - rect.left()) / rect.width();
+            timeline.current_frame =
+                (frame.round() as i32).clamp(timeline.start_frame, timeline.end_frame);
+        }
+    }
+}